@@ -0,0 +1,243 @@
+//! A small language server for trying out and testing the editor's client without a real one.
+//! It speaks the protocol over stdio and understands any text:
+//!
+//! - `TODO` and `FIXME` are reported as warnings and errors.
+//! - Hovering over a word tells how often it appears.
+//! - Completions are the words of the document.
+//! - Definitions are found after `fn`, `let`, `struct`, `enum`, `const` and `type` in any open document.
+//!
+//! `--encoding=utf-8|utf-16|utf-32` picks the position encoding instead of taking the
+//! client's first choice, and `--full-sync` asks for the whole text on every change.
+
+use std::{
+    collections::HashMap,
+    io::{BufReader, Write},
+};
+
+use hacker::text_edit::lsp::{json::Json, read_message, write_message, PositionEncoding};
+use ropey::Rope;
+
+const METHOD_NOT_FOUND: i32 = -32601;
+const DEFINITION_KEYWORDS: [&str; 7] = ["fn", "let", "mut", "struct", "enum", "const", "type"];
+
+struct Server<W: Write> {
+    output: W,
+    /// Forced by `--encoding`, or agreed on when initializing.
+    encoding: Option<PositionEncoding>,
+    full_sync: bool,
+    documents: HashMap<String, Rope>,
+    shutting_down: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, message: Json) -> std::io::Result<()> {
+        write_message(&mut self.output, &message)
+    }
+    
+    fn respond(&mut self, id: &Json, result: Json) -> std::io::Result<()> {
+        self.send(Json::object([("jsonrpc", "2.0".into()), ("id", id.clone()), ("result", result)]))
+    }
+    
+    fn notify(&mut self, method: &str, params: Json) -> std::io::Result<()> {
+        self.send(Json::object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)]))
+    }
+    
+    fn encoding(&self) -> PositionEncoding {
+        self.encoding.unwrap_or_default()
+    }
+    
+    /// Handles one message, returning the exit code once told to exit.
+    fn handle(&mut self, message: &Json) -> std::io::Result<Option<i32>> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_owned();
+        let id = message.get("id");
+        match (method, id) {
+            ("initialize", Some(id)) => {
+                let offered = params["capabilities"]["general"]["positionEncodings"].as_array().unwrap_or_default();
+                let encoding = *self.encoding.get_or_insert_with(|| {
+                    offered.iter().find_map(|name| name.as_str().and_then(PositionEncoding::from_name)).unwrap_or_default()
+                });
+                let sync = if self.full_sync { 1 } else { 2 };
+                let result = Json::object([
+                    ("capabilities", Json::object([
+                        ("positionEncoding", encoding.name().into()),
+                        ("textDocumentSync", Json::object([("openClose", true.into()), ("change", sync.into())])),
+                        ("hoverProvider", true.into()),
+                        ("completionProvider", Json::object([])),
+                        ("definitionProvider", true.into()),
+                    ])),
+                    ("serverInfo", Json::object([("name", "mock_lsp".into())])),
+                ]);
+                self.respond(id, result)?;
+            }
+            ("textDocument/didOpen", None) => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), Rope::from_str(text));
+                self.publish_diagnostics(&uri, &params["textDocument"]["version"])?;
+            }
+            ("textDocument/didChange", None) => {
+                let encoding = self.encoding();
+                let Some(rope) = self.documents.get_mut(&uri) else {
+                    return Ok(None);
+                };
+                for change in params["contentChanges"].as_array().unwrap_or_default() {
+                    let text = change["text"].as_str().unwrap_or_default();
+                    let range = &change["range"];
+                    if range.is_null() {
+                        *rope = Rope::from_str(text);
+                        continue;
+                    }
+                    let start = encoding.char_index(rope, &range["start"]).unwrap_or(0);
+                    let end = encoding.char_index(rope, &range["end"]).unwrap_or(start).max(start);
+                    rope.remove(start..end);
+                    rope.insert(start, text);
+                }
+                self.publish_diagnostics(&uri, &params["textDocument"]["version"])?;
+            }
+            ("textDocument/didClose", None) => {
+                self.documents.remove(&uri);
+                let params = Json::object([("uri", uri.into()), ("diagnostics", Json::Array(Vec::new()))]);
+                self.notify("textDocument/publishDiagnostics", params)?;
+            }
+            ("textDocument/hover", Some(id)) => {
+                let result = self.word_at(&uri, &params["position"]).map(|(rope, word, range)| {
+                    let count = words(rope).filter(|(_, other)| *other == word).count();
+                    Json::object([
+                        ("contents", Json::object([
+                            ("kind", "markdown".into()),
+                            ("value", format!("`{word}`\n\nAppears {count} times in this document.").into()),
+                        ])),
+                        ("range", range),
+                    ])
+                });
+                self.respond(id, result.into())?;
+            }
+            ("textDocument/completion", Some(id)) => {
+                let mut labels: Vec<String> = self.documents.get(&uri)
+                    .map(|rope| words(rope).map(|(_, word)| word).filter(|word| word.chars().count() >= 3).collect())
+                    .unwrap_or_default();
+                labels.sort();
+                labels.dedup();
+                let items = labels.into_iter()
+                    .map(|label| Json::object([("label", label.into()), ("kind", 1.into()), ("detail", "word".into())]))
+                    .collect::<Vec<_>>();
+                self.respond(id, Json::object([("isIncomplete", false.into()), ("items", items.into())]))?;
+            }
+            ("textDocument/definition", Some(id)) => {
+                let result = self.word_at(&uri, &params["position"]).and_then(|(_, word, _)| self.find_definition(&uri, &word));
+                self.respond(id, result.into())?;
+            }
+            ("shutdown", Some(id)) => {
+                self.shutting_down = true;
+                self.respond(id, Json::Null)?;
+            }
+            ("exit", None) => return Ok(Some(if self.shutting_down { 0 } else { 1 })),
+            (_, Some(id)) if !method.is_empty() => {
+                self.send(Json::object([
+                    ("jsonrpc", "2.0".into()),
+                    ("id", id.clone()),
+                    ("error", Json::object([("code", METHOD_NOT_FOUND.into()), ("message", format!("Unknown method `{method}`").into())])),
+                ]))?;
+            }
+            // Other notifications, and responses to requests this never makes.
+            _ => {}
+        }
+        Ok(None)
+    }
+    
+    /// The word at a position, with its range.
+    fn word_at(&self, uri: &str, position: &Json) -> Option<(&Rope, String, Json)> {
+        let rope = self.documents.get(uri)?;
+        let char_idx = self.encoding().char_index(rope, position)?;
+        let (start, word) = words(rope).find(|(start, word)| (*start..=start + word.chars().count()).contains(&char_idx))?;
+        let range = self.encoding().range(rope, start..start + word.chars().count());
+        Some((rope, word, range))
+    }
+    
+    /// A `Location` where `word` follows one of the [`DEFINITION_KEYWORDS`], looking in `uri` first.
+    fn find_definition(&self, uri: &str, word: &str) -> Option<Json> {
+        let documents = self.documents.get_key_value(uri).into_iter()
+            .chain(self.documents.iter().filter(|(other, _)| *other != uri));
+        for (uri, rope) in documents {
+            let words: Vec<(usize, String)> = words(rope).collect();
+            let found = words.windows(2)
+                .find(|pair| DEFINITION_KEYWORDS.contains(&pair[0].1.as_str()) && pair[1].1 == word)
+                .map(|pair| pair[1].0);
+            if let Some(start) = found {
+                let range = self.encoding().range(rope, start..start + word.chars().count());
+                return Some(Json::object([("uri", uri.as_str().into()), ("range", range)]));
+            }
+        }
+        None
+    }
+    
+    fn publish_diagnostics(&mut self, uri: &str, version: &Json) -> std::io::Result<()> {
+        let Some(rope) = self.documents.get(uri) else {
+            return Ok(());
+        };
+        let mut diagnostics = Vec::new();
+        for (start, word) in words(rope) {
+            let (severity, message) = match word.as_str() {
+                "FIXME" => (1, "Something here needs fixing"),
+                "TODO" => (2, "Unfinished work"),
+                _ => continue,
+            };
+            diagnostics.push(Json::object([
+                ("range", self.encoding().range(rope, start..start + word.chars().count())),
+                ("severity", severity.into()),
+                ("source", "mock_lsp".into()),
+                ("message", message.into()),
+            ]));
+        }
+        let params = Json::object([("uri", uri.into()), ("version", version.clone()), ("diagnostics", diagnostics.into())]);
+        self.notify("textDocument/publishDiagnostics", params)
+    }
+}
+
+/// Every word in the text, with the char index it starts at.
+fn words(rope: &Rope) -> impl Iterator<Item = (usize, String)> + '_ {
+    let mut chars = rope.chars().enumerate().peekable();
+    std::iter::from_fn(move || {
+        let (start, first) = chars.find(|(_, chr)| chr.is_alphanumeric() || *chr == '_')?;
+        let mut word = String::from(first);
+        while let Some((_, chr)) = chars.next_if(|(_, chr)| chr.is_alphanumeric() || *chr == '_') {
+            word.push(chr);
+        }
+        Some((start, word))
+    })
+}
+
+fn main() -> std::io::Result<()> {
+    let mut server = Server {
+        output: std::io::stdout().lock(),
+        encoding: None,
+        full_sync: false,
+        documents: HashMap::new(),
+        shutting_down: false,
+    };
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--full-sync" => server.full_sync = true,
+            _ if let Some(name) = arg.strip_prefix("--encoding=") => {
+                let encoding = PositionEncoding::from_name(name).ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Unknown encoding `{name}`"))
+                })?;
+                server.encoding = Some(encoding);
+            }
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Unknown argument `{arg}`"))),
+        }
+    }
+    let mut input = BufReader::new(std::io::stdin().lock());
+    while let Some(body) = read_message(&mut input)? {
+        // Messages that aren't JSON are skipped.
+        let Ok(message) = Json::parse(&body) else {
+            continue;
+        };
+        if let Some(code) = server.handle(&message)? {
+            std::process::exit(code);
+        }
+    }
+    // The client went away without saying exit.
+    std::process::exit(1);
+}
@@ -2,7 +2,7 @@
 use std::{cell::{Cell, RefCell}, rc::Rc, time::{Duration, Instant}};
 
 use color_eyre::eyre::Result;
-use crossterm::{event::{self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event}, execute, terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}};
+use crossterm::{event::{self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags}, execute, terminal::{disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen, LeaveAlternateScreen}};
 use ratatui::DefaultTerminal;
 use spin_sleep::sleep_until;
 use scopeguard::defer;
@@ -155,6 +155,15 @@ pub fn run<M, H: EventHandler<M>>(settings: GameSettings, mut event_handler: H)
         EnableMouseCapture,
         EnableBracketedPaste,
     ).unwrap();
+    // Terminals that support it report chords such as Ctrl+Shift+H, which are otherwise
+    // indistinguishable from Ctrl+H.
+    let keyboard_enhancement = supports_keyboard_enhancement().unwrap_or(false);
+    if keyboard_enhancement {
+        execute!(
+            terminal.backend_mut(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES),
+        ).unwrap();
+    }
     let loop_context = LoopContext::new();
     let mut next_update_time = Instant::now();
     let mut next_render_time = Instant::now();
@@ -217,6 +226,9 @@ pub fn run<M, H: EventHandler<M>>(settings: GameSettings, mut event_handler: H)
             }
         }
     };
+    if keyboard_enhancement {
+        execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags);
+    }
     execute!(
         terminal.backend_mut(),
         DisableBracketedPaste,
@@ -1,4 +1,5 @@
 pub mod context;
 pub mod game_loop;
 pub mod hashing;
-pub mod text_edit;
\ No newline at end of file
+pub mod text_edit;
+pub mod theme;
@@ -1,9 +1,9 @@
 #![allow(unused)]
 use color_eyre::Result;
-use std::{io::Write, sync::atomic::AtomicU64};
-use hacker::{game_loop::{self, GameEvent, GameSettings, LoopContext}, text_edit::{TextEdit, TextEditor}};
+use std::{io::{IsTerminal, Write}, sync::atomic::AtomicU64};
+use hacker::{game_loop::{self, GameEvent, GameSettings, LoopContext}, text_edit::{clipboard::Osc52Clipboard, command::{default_binding, EditorCommand, NAMED_COMMANDS}, completion::{Completer, CompletionEvent, CompletionItem, CompletionPopup, CompletionState}, diagnostics::InlineDiagnostics, diff::{DiffEvent, DiffState, DiffView}, emacs::{Emacs, EmacsEvent}, find_bar::{FindBar, FindBarEvent, FindBarState}, follow::Follow, go_to_line::{GoToLineBar, GoToLineEvent, GoToLineState}, gutter::Gutter, highlight::Language, lsp::{HoverPopup, LspClient, LspEvent}, macros::Macros, minimap::Minimap, palette::{find_binding, CommandPalette, CommandPaletteState, PaletteEvent, PaletteItem}, scrollbar::Scrollbars, snippet::Snippets, status_bar::StatusBar, swap::{Autosave, Recovery, RecoveryBar}, vim::{Vim, VimEvent, VimMode}, workspace::{workspace_binding, BufferId, BufferManager, TabBar, Workspace, WorkspaceCommand, WorkspaceView, NAMED_WORKSPACE_COMMANDS}, TextEdit, TextEditor, TextPosition}, theme::{ColorDepth, Theme}};
 use ratatui::{prelude::*, widgets::{self, Block, Borders, Paragraph}, DefaultTerminal};
-use crossterm::{event::{self, Event, KeyCode, KeyModifiers, MouseEventKind}, terminal::Clear};
+use crossterm::{event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseEventKind}, terminal::Clear};
 use crossterm::execute;
 use crossterm::cursor::{
     MoveTo,
@@ -32,14 +32,112 @@ fn main() -> Result<()> {
     const TEXT_BUFFER_SIZE: usize = 1024*32;
     const FRAME_TIME_MS: u64 = 16;
     const FRAME_TIME: Duration = Duration::from_millis(FRAME_TIME_MS);
-    
-    const SHORT_SCROLL: usize = 2;
-    const LONG_SCROLL: usize = 10;
     let mut last_update_time = Instant::now() - FRAME_TIME;
-    let mut text_edit = TextEditor::new();
-    let mut line = 0usize;
-    let mut col = 0usize;
-    let mut max_prev_col = col;
+    let mut keymap = Keymap::Default;
+    let mut paths = Vec::new();
+    let mut follow = false;
+    let mut themes = vec![Theme::dark(), Theme::light()];
+    let mut theme_index = 0;
+    let mut color_depth = ColorDepth::detect();
+    let mut autosave = Autosave {
+        swap_dir: Some(Autosave::default_swap_dir()),
+        ..Autosave::default()
+    };
+    let mut lsp_command = None;
+    let mut snippets = Snippets::new();
+    let mut macros_path = Some(Macros::default_path());
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--vim" => keymap = Keymap::Vim(Vim::new()),
+            "--emacs" => keymap = Keymap::Emacs(Emacs::new()),
+            "--follow" | "-f" => follow = true,
+            // A built-in theme name or the path of a theme file.
+            _ if let Some(name) = arg.strip_prefix("--theme=") => match themes.iter().position(|theme| theme.name == name) {
+                Some(index) => theme_index = index,
+                None => {
+                    themes.insert(0, Theme::load(name)?);
+                    theme_index = 0;
+                }
+            },
+            _ if let Some(depth) = arg.strip_prefix("--colors=") => {
+                color_depth = ColorDepth::from_name(depth)
+                    .ok_or_else(|| color_eyre::eyre::eyre!("Unknown color depth `{depth}`. Expected truecolor, 256, 16 or none."))?;
+            }
+            "--no-swap" => autosave.swap_dir = None,
+            // Saves files after this many seconds without an edit.
+            _ if let Some(seconds) = arg.strip_prefix("--autosave=") => {
+                let seconds = seconds.parse::<f64>().ok().filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                    .ok_or_else(|| color_eyre::eyre::eyre!("Expected a number of seconds for --autosave, not `{seconds}`."))?;
+                autosave.save_when_idle = Some(Duration::from_secs_f64(seconds));
+            }
+            // A language server to start, such as `--lsp=rust-analyzer`. Arguments are split on spaces.
+            _ if let Some(command) = arg.strip_prefix("--lsp=") => lsp_command = Some(command.to_owned()),
+            // A snippet file, such as `snippets/rust.snippets`. May be given more than once.
+            _ if let Some(path) = arg.strip_prefix("--snippets=") => snippets.extend(Snippets::load(path)?),
+            // Where recorded macros are kept between sessions.
+            _ if let Some(path) = arg.strip_prefix("--macros=") => macros_path = Some(path.into()),
+            "--no-macros" => macros_path = None,
+            _ => paths.push(arg),
+        }
+    }
+    let mut buffers = BufferManager::new();
+    buffers.set_clipboard(std::rc::Rc::new(std::cell::RefCell::new(Osc52Clipboard::stdout())));
+    buffers.set_autosave(autosave.clone());
+    buffers.set_snippets(snippets);
+    if let Some(path) = &macros_path {
+        buffers.set_macros(std::rc::Rc::new(std::cell::RefCell::new(Macros::load(path)?)));
+    }
+    // Swap files left behind by a crash are offered back one at a time.
+    let mut recoveries = match &autosave.swap_dir {
+        Some(dir) => std::collections::VecDeque::from(Recovery::find(dir)?),
+        None => std::collections::VecDeque::new(),
+    };
+    // With `--follow`, files are opened in read-only viewers that keep up with them, like `tail -f`.
+    let mut buffer_ids = paths.iter().map(|path| if follow {
+        Ok(buffers.add(TextEditor::viewer(Follow::file(path)?)))
+    } else {
+        buffers.open(path)
+    }).collect::<std::io::Result<Vec<_>>>()?;
+    // Input piped into stdin is shown as it arrives. Keys are still read from the terminal.
+    if !std::io::stdin().is_terminal() {
+        buffer_ids.insert(0, buffers.add(TextEditor::viewer(Follow::stdin()?)));
+    }
+    if buffer_ids.is_empty() {
+        let mut text_edit = TextEditor::new();
+        text_edit.set_language(Language::Rust);
+        buffer_ids.push(buffers.add(text_edit));
+    }
+    // Every file gets its own tab.
+    let mut workspace = Workspace::new(buffers, buffer_ids[0]);
+    for &buffer in &buffer_ids[1..] {
+        workspace.new_tab(buffer);
+    }
+    workspace.select_tab(0);
+    let mut lsp = match &lsp_command {
+        Some(command_line) => {
+            let mut words = command_line.split_whitespace();
+            let program = words.next().ok_or_else(|| color_eyre::eyre::eyre!("Expected a command for --lsp."))?;
+            let mut command = std::process::Command::new(program);
+            command.args(words);
+            Some(LspClient::spawn(command, &std::env::current_dir()?, None)?)
+        }
+        None => None,
+    };
+    // Shown in the status bar until the next key.
+    let mut message: Option<String> = None;
+    // Quitting with unsaved changes only warns, unless the key right after the warning quits again.
+    let mut exit_warned = false;
+    let mut exit_confirmed = false;
+    // Hover info is shown while the cursor stays where it was asked for.
+    let mut hover: Option<(BufferId, TextPosition, String)> = None;
+    let mut completer = Completer::default();
+    let mut completion: Option<CompletionState> = None;
+    let mut find_bar: Option<FindBarState> = None;
+    let mut go_to_line: Option<GoToLineState> = None;
+    let mut diff: Option<DiffState> = None;
+    let mut palette: Option<CommandPaletteState<AppCommand>> = None;
+    let mut macro_prompt: Option<MacroPrompt> = None;
+    let mut cursor_style = None;
     game_loop::run(
         GameSettings {
             render_frametime: FRAME_TIME,
@@ -52,407 +150,518 @@ fn main() -> Result<()> {
                 };
             }
             let term_size = terminal.size()?;
-            let (cx, cy) = get_cursor_pos()?;
             match event {
                 GameEvent::TermEvent(event) => {
-                    match event {
-                        Event::Key(key_event) if key_event.is_press() => match key_event.code {
-                            KeyCode::Up => {
-                                if line != 0 {
-                                    line -= 1;
-                                    let line_slice = text_edit.rope.line(line);
-                                    let mut line_len = line_slice.len_chars();
-                                    while line_len != 0 && matches!(line_slice.char(line_len - 1), '\n' | '\r') {
-                                        line_len -= 1;
-                                    }
-                                    col = col.min(line_len.min(max_prev_col)).max(max_prev_col.min(line_len));
-                                    text_edit.start_col = text_edit.start_col.min(col);
-                                    let ncx = col - text_edit.start_col;
-                                    if cy != 0 {
-                                        execute!(MoveTo(ncx as u16, cy - 1));
-                                    } else {
-                                        if text_edit.start_line != 0 {
-                                            text_edit.start_line -= 1;
-                                        }
-                                        execute!(MoveTo(ncx as u16, cy));
-                                    }
-                                } else {
-                                    col = 0;
-                                    text_edit.start_col = 0;
-                                    execute!(MoveTo(0, cy));
-                                }
-                            },
-                            KeyCode::Down => {
-                                if line + 1 != text_edit.rope.len_lines() {
-                                    line += 1;
-                                    let line_slice = text_edit.rope.line(line);
-                                    let mut line_len = line_slice.len_chars();
-                                    while line_len != 0 && matches!(line_slice.char(line_len - 1), '\n' | '\r') {
-                                        line_len -= 1;
-                                    }
-                                    col = col.min(line_len).max(line_len.min(max_prev_col));
-                                    text_edit.start_col = text_edit.start_col.min(col);
-                                    let ncx = col - text_edit.start_col;
-                                    if cy + 1 != term_size.height {
-                                        execute!(MoveTo(ncx as u16, cy + 1));
-                                    } else {
-                                        text_edit.start_line += 1;
-                                        execute!(MoveTo(ncx as u16, cy));
+                    // Tab and pane changes wait until the focused editor is no longer borrowed.
+                    let mut workspace_command = None;
+                    let mut close_pane = false;
+                    let mut click = None;
+                    let mut app_command = None;
+                    let mut lsp_request = None;
+                    let mut quit_all = None;
+                    // The recovery prompt takes every key and paste until it's answered.
+                    if !recoveries.is_empty() && let Event::Key(_) | Event::Paste(_) = &event {
+                        if let Event::Key(key_event) = &event && key_event.is_press() {
+                            match key_event.code {
+                                KeyCode::Char('r') => {
+                                    let recovery = recoveries.pop_front().expect("There is a recovery.");
+                                    match workspace.buffers.recover(recovery) {
+                                        Ok(buffer) if workspace.active_buffer() != buffer => workspace.new_tab(buffer),
+                                        Ok(_) => (),
+                                        Err(err) => message = Some(format!("Can't recover: {err}")),
                                     }
-                                } else {
-                                    let line_slice = text_edit.rope.line(line);
-                                    let mut line_len = line_slice.len_chars();
-                                    while line_len != 0 && matches!(line_slice.char(line_len - 1), '\n' | '\r') {
-                                        line_len -= 1;
-                                    }
-                                    col = line_len;
-                                    if col - text_edit.start_col >= term_size.width as usize {
-                                        text_edit.start_col = col + 1 - term_size.width as usize;
-                                    }
-                                    let ncx = col - text_edit.start_col;
-                                    execute!(MoveTo(ncx as u16, cy));
                                 }
-                            },
-                            KeyCode::Left => {
-                                if col != 0 {
-                                    col -= 1;
-                                    if cx != 0 {
-                                        execute!(MoveLeft(1));
-                                    } else {
-                                        if text_edit.start_col != 0 {
-                                            text_edit.start_col -= 1;
-                                        }
-                                    }
-                                } else {
-                                    if line != 0 {
-                                        line -= 1;
-                                        let line_slice = text_edit.rope.line(line);
-                                        let line_len = line_slice.len_chars();
-                                        col = line_len - 1;
-                                        if cy != 0 {
-                                            execute!(MoveTo(
-                                                if col <= (term_size.width + 1) as usize {
-                                                    col as u16
-                                                } else {
-                                                    term_size.width + 1
-                                                },
-                                                cy - 1
-                                            ));
-                                        } else {
-                                            if text_edit.start_line != 0 {
-                                                text_edit.start_line -= 1;
-                                                execute!(MoveTo(
-                                                    if col <= term_size.width as usize {
-                                                        col as u16
-                                                    } else {
-                                                        term_size.width + 1
-                                                    },
-                                                    cy
-                                                ));
-                                            }
-                                        }
-                                    }
+                                KeyCode::Char('d') => {
+                                    let recovery = recoveries.pop_front().expect("There is a recovery.");
+                                    recovery.discard()?;
                                 }
-                                max_prev_col = col;
-                            },
-                            KeyCode::Right => {
-                                if line < text_edit.rope.len_lines() {
-                                    let line_slice = text_edit.rope.line(line);
-                                    let mut line_len = line_slice.len_chars();
-                                    while line_len != 0 && matches!(line_slice.char(line_len - 1), '\n' | '\r') {
-                                        line_len -= 1;
-                                    }
-                                    if col < line_len {
-                                        col += 1;
-                                        if cx + 1 != term_size.width {
-                                            execute!(MoveRight(1));
-                                        } else {
-                                            text_edit.start_col += 1;
-                                        }
-                                    } else if line + 1 < text_edit.rope.len_lines() {
-                                        line += 1;
-                                        col = 0;
-                                        text_edit.start_col = 0;
-                                        if cy + 1 != term_size.height {
-                                            execute!(MoveTo(0, cy + 1));
-                                        } else {
-                                            text_edit.start_line += 1;
-                                            execute!(MoveTo(0, cy));
-                                        }
-                                    }
-                                    max_prev_col = col;
-                                }
-                            },
-                            KeyCode::Esc => context.request_exit(game_loop::ExitRequest::Success),
-                            KeyCode::Char('q') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                                context.request_exit(game_loop::ExitRequest::Success);
+                                // Skipped swap files are offered again next time.
+                                KeyCode::Char('s') | KeyCode::Esc => _ = recoveries.pop_front(),
+                                _ => (),
                             }
-                            KeyCode::Delete => {
-                                let line_start = text_edit.rope.line_to_char(line);
-                                let char_index = line_start + col;
-                                text_edit.rope.try_remove(char_index..=char_index);
+                        }
+                        return Ok(());
+                    }
+                    let text_edit = workspace.editor_mut();
+                    // Macros are saved when recording one stops, however that happens.
+                    let was_recording = text_edit.recording_macro();
+                    // Typing a word opens completions once it's long enough.
+                    let version = text_edit.version();
+                    let typed = matches!(&event, Event::Key(key_event) if key_event.is_press()
+                        && matches!(key_event.code, KeyCode::Char(_))
+                        && !key_event.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT));
+                    // Messages and hover info go away with the next key.
+                    if let Event::Key(key_event) = &event && key_event.is_press() {
+                        message = None;
+                        hover = None;
+                        exit_confirmed = std::mem::take(&mut exit_warned);
+                    }
+                    // The command palette takes every key while it's open.
+                    if let Event::Key(key_event) = &event
+                        && key_event.is_press()
+                        && let Some(state) = &mut palette {
+                        match state.handle_key(*key_event) {
+                            PaletteEvent::Handled | PaletteEvent::Ignored => return Ok(()),
+                            PaletteEvent::Close => {
+                                palette = None;
+                                return Ok(());
                             }
-                            KeyCode::Backspace => {
-                                if (col > 0 || line != 0) && line < text_edit.rope.len_lines() {
-                                    let line_start = text_edit.rope.line_to_char(line);
-                                    let char_index = line_start + col;
-                                    if char_index != 0 && char_index <= text_edit.rope.len_chars() {
-                                        if col != 0 {
-                                            col -= 1;
-                                            if cx != 0 {
-                                                execute!(MoveLeft(1));
-                                            } else {
-                                                if text_edit.start_col != 0 {
-                                                    text_edit.start_col -= 1;
-                                                }
-                                            }
-                                        } else {
-                                            if line != 0 {
-                                                line -= 1;
-                                                let line_len = text_edit.rope.line(line).len_chars();
-                                                col = line_len - 1;
-                                                if cy != 0 {
-                                                    execute!(MoveTo(
-                                                        if col <= (term_size.width + 1) as usize {
-                                                            col as u16
-                                                        } else {
-                                                            term_size.width + 1
-                                                        },
-                                                        cy - 1
-                                                    ));
-                                                } else {
-                                                    if text_edit.start_line != 0 {
-                                                        text_edit.start_line -= 1;
-                                                        execute!(MoveTo(
-                                                            if col <= term_size.width as usize {
-                                                                col as u16
-                                                            } else {
-                                                                term_size.width + 1
-                                                            },
-                                                            cy,
-                                                        ));
-                                                    }
-                                                }
-                                                let new_col_start = line_len - (term_size.width as usize - 1).min(line_len);
-                                                text_edit.start_col = new_col_start;
-                                            }
-                                        }
-                                        if let Ok(()) = text_edit.rope.try_remove(char_index-1..char_index) {
-                                        }
-                                    }
-                                }
-                                max_prev_col = col;
+                            PaletteEvent::Run(command) => {
+                                palette = None;
+                                app_command = Some(command);
                             }
-                            KeyCode::Home => {
-                                col = 0;
-                                text_edit.start_col = 0;
-                                max_prev_col = col;
-                                execute!(MoveTo(0, cy));
+                        }
+                    }
+                    // Asking for a register takes the next key, and digits before it when playing.
+                    if let Event::Key(key_event) = &event
+                        && key_event.is_press()
+                        && let Some(prompt) = &mut macro_prompt {
+                        match (prompt, key_event.code) {
+                            (_, KeyCode::Esc) => macro_prompt = None,
+                            (MacroPrompt::Play(count), KeyCode::Char(digit @ '0'..='9')) => {
+                                count.push(digit);
+                                message = Some(macro_prompt.as_ref().expect("The prompt is open.").text());
                             }
-                            KeyCode::End => {
-                                let line_slice = text_edit.rope.line(line);
-                                let mut line_len = line_slice.len_chars();
-                                while line_len != 0 && matches!(line_slice.char(line_len - 1), '\n' | '\r') {
-                                    line_len -= 1;
-                                }
-                                col = line_len;
-                                if col - text_edit.start_col >= term_size.width as usize {
-                                    text_edit.start_col = col + 1 - term_size.width as usize;
-                                }
-                                let ncx = col - text_edit.start_col;
-                                max_prev_col = col;
-                                execute!(MoveTo(ncx as u16, cy));
+                            (_, KeyCode::Char(register)) if !Macros::is_valid_register(register) => {
+                                message = Some(format!("`{register}` is not a register. Use a letter or digit."));
+                                macro_prompt = None;
                             }
-                            KeyCode::Tab => {
-                                let next_indent = (col + 1).next_multiple_of(4);
-                                let space_count = (next_indent - col);
-                                const TAB_SPACES: &'static str = "    ";
-                                let line_start = text_edit.rope.line_to_char(line);
-                                let insert_idx = line_start + col;
-                                if let Ok(()) = text_edit.rope.try_insert(insert_idx, &TAB_SPACES[..space_count]) {
-                                    col = next_indent;
-                                    if col > text_edit.start_col + term_size.width as usize {
-                                        text_edit.start_col = col - term_size.width as usize;
-                                    }
-                                    let ncx = col - text_edit.start_col;
-                                    execute!(MoveTo(ncx as u16, cy));
-                                    max_prev_col = col;
-                                }
+                            (MacroPrompt::Record, KeyCode::Char(register)) => {
+                                text_edit.start_recording(register);
+                                macro_prompt = None;
                             }
-                            KeyCode::Enter => {
-                                let line_start = text_edit.rope.line_to_char(line);
-                                let char_index = line_start + col;
-                                if let Ok(()) = text_edit.rope.try_insert_char(char_index, '\n') {
-                                    col = 0;
-                                    text_edit.start_col = 0;
-                                    line += 1;
-                                    if cy + 1 != term_size.height {
-                                        execute!(MoveTo(0, cy + 1));
-                                    } else {
-                                        text_edit.start_line += 1;
-                                        execute!(MoveTo(0, cy));
-                                    }
-                                    max_prev_col = col;
+                            (MacroPrompt::Play(count), KeyCode::Char(register)) => {
+                                let count = count.parse().unwrap_or(1);
+                                if !text_edit.play_macro(register, count) {
+                                    message = Some(format!("Register {register} has no macro"));
                                 }
+                                macro_prompt = None;
                             }
-                            KeyCode::Char(chr) if chr != '\n' => {
-                                if line <= text_edit.rope.len_lines() {
-                                    let line_start = text_edit.rope.line_to_char(line);
-                                    let char_index = line_start + col;
-                                    text_edit.rope.try_insert_char(char_index, chr);
-                                    col += 1;
-                                    if cx + 1 != term_size.width {
-                                        execute!(MoveRight(1));
-                                    } else {
-                                        text_edit.start_col += 1;
-                                    }
-                                    max_prev_col = col;
-                                }
+                            _ => message = Some(macro_prompt.as_ref().expect("The prompt is open.").text()),
+                        }
+                        return Ok(());
+                    }
+                    // The diff view covers the editor and takes every key while it's open.
+                    if let Event::Key(key_event) = &event
+                        && key_event.is_press()
+                        && let Some(state) = &mut diff {
+                        if state.handle_key(*key_event, text_edit) == DiffEvent::Close {
+                            diff = None;
+                        }
+                        return Ok(());
+                    }
+                    // Completions take the keys they use, and narrow down as the word is typed.
+                    if let Event::Key(key_event) = &event
+                        && key_event.is_press()
+                        && let Some(state) = &mut completion {
+                        match state.handle_key(*key_event, text_edit) {
+                            CompletionEvent::Handled => return Ok(()),
+                            CompletionEvent::Ignored => (),
+                            CompletionEvent::Close => {
+                                completion = None;
+                                return Ok(());
                             }
-                            _ => (),
                         }
-                        Event::Mouse(mouse_event) => {
-                            match mouse_event.kind {
-                                MouseEventKind::Down(mouse_button) => {
-                                    
-                                },
-                                MouseEventKind::Up(mouse_button) => {
-                                    
-                                },
-                                MouseEventKind::Drag(mouse_button) => {
-                                    
-                                },
-                                MouseEventKind::Moved => {
-                                    
-                                },
-                                MouseEventKind::ScrollDown => {
-                                    let scroll = if mouse_event.modifiers.contains(KeyModifiers::ALT) {
-                                        LONG_SCROLL
-                                    } else {
-                                        SHORT_SCROLL
-                                    };
-                                    if mouse_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        text_edit.start_col += scroll;
-                                    } else {
-                                        text_edit.start_line += scroll;
-                                    }
-                                },
-                                MouseEventKind::ScrollUp => {
-                                    let scroll = if mouse_event.modifiers.contains(KeyModifiers::ALT) {
-                                        LONG_SCROLL
-                                    } else {
-                                        SHORT_SCROLL
-                                    };
-                                    if mouse_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        if text_edit.start_col >= scroll {
-                                            text_edit.start_col -= scroll;
-                                        } else {
-                                            text_edit.start_col = 0;
-                                        }
-                                    } else {
-                                        if text_edit.start_line >= scroll {
-                                            text_edit.start_line -= scroll;
-                                        } else {
-                                            text_edit.start_line = 0;
-                                        }
-                                    }
-                                },
-                                MouseEventKind::ScrollLeft => {
-                                    let scroll = if mouse_event.modifiers.contains(KeyModifiers::ALT) {
-                                        LONG_SCROLL
-                                    } else {
-                                        SHORT_SCROLL
-                                    };
-                                    if text_edit.start_col >= scroll {
-                                        text_edit.start_col -= scroll;
-                                    } else {
-                                        text_edit.start_col = 0;
-                                    }
-                                },
-                                MouseEventKind::ScrollRight => {
-                                    let scroll = if mouse_event.modifiers.contains(KeyModifiers::ALT) {
-                                        LONG_SCROLL
-                                    } else {
-                                        SHORT_SCROLL
-                                    };
-                                    text_edit.start_col += scroll;
-                                },
+                    }
+                    if let Event::Key(key_event) = &event
+                        && key_event.is_press()
+                        && let Some(dialog) = &mut go_to_line {
+                        match dialog.handle_key(*key_event, text_edit) {
+                            GoToLineEvent::Handled => return Ok(()),
+                            GoToLineEvent::Ignored => (),
+                            GoToLineEvent::Close => {
+                                go_to_line = None;
+                                return Ok(());
                             }
                         }
-                        Event::Resize(width, height) => {
-                            let width = width as usize;
-                            let height = height as usize;
-                            if col > text_edit.start_col + width {
-                                text_edit.start_col = col - width;
+                    }
+                    // The find bar gets the first look at keys, and passes on the ones it doesn't use.
+                    if let Event::Key(key_event) = &event
+                        && key_event.is_press()
+                        && let Some(bar) = &mut find_bar {
+                        match bar.handle_key(*key_event, text_edit) {
+                            FindBarEvent::Handled => return Ok(()),
+                            FindBarEvent::Ignored => (),
+                            FindBarEvent::Close => {
+                                text_edit.search = None;
+                                text_edit.current_match = None;
+                                find_bar = None;
+                                return Ok(());
                             }
-                            if line > text_edit.start_line + height {
-                                text_edit.start_line = line - height;
+                        }
+                    }
+                    match event {
+                        Event::Key(key_event) if key_event.is_press() => match key_event.code {
+                            _ if let Some(command) = app_binding(key_event, &keymap) => app_command = Some(command),
+                            // Emacs uses the control keys for itself.
+                            _ if let Keymap::Emacs(emacs) = &mut keymap => match emacs.handle_key(key_event, text_edit) {
+                                EmacsEvent::Handled => (),
+                                EmacsEvent::Quit => context.request_exit(game_loop::ExitRequest::Success),
+                                EmacsEvent::Workspace(command) => workspace_command = Some(command),
+                            },
+                            _ if let Keymap::Vim(vim) = &mut keymap => match vim.handle_key(key_event, text_edit) {
+                                VimEvent::Handled => (),
+                                VimEvent::Quit => close_pane = true,
+                                VimEvent::QuitAll { force } => quit_all = Some(force),
+                                VimEvent::Workspace(command) => workspace_command = Some(command),
+                            },
+                            KeyCode::Esc if text_edit.in_snippet() => text_edit.execute(&EditorCommand::ExitSnippet),
+                            KeyCode::Esc if !text_edit.extra_cursors.is_empty() => text_edit.execute(&EditorCommand::ClearExtraCursors),
+                            KeyCode::Esc => context.request_exit(game_loop::ExitRequest::Success),
+                            _ => {
+                                if let Some(command) = workspace_binding(key_event) {
+                                    workspace_command = Some(command);
+                                } else if let Some(command) = default_binding(key_event) {
+                                    text_edit.execute(&command);
+                                }
                             }
                         }
+                        Event::Mouse(mouse_event) if let Some(state) = &mut diff => match mouse_event.kind {
+                            MouseEventKind::ScrollDown => state.scroll_by(3),
+                            MouseEventKind::ScrollUp => state.scroll_by(-3),
+                            _ => (),
+                        }
+                        Event::Mouse(mouse_event) => match mouse_event.kind {
+                            // Clicks may focus another pane first, so they're handled below.
+                            MouseEventKind::Down(event::MouseButton::Left) => click = Some(mouse_event),
+                            _ => _ = text_edit.handle_mouse(mouse_event),
+                        }
+                        Event::Resize(_, _) => text_edit.request_scroll_to_cursor(),
+                        Event::Paste(_) if diff.is_some() => (),
                         Event::Paste(pasta) => {
                             // static COUNTER: AtomicU64 = AtomicU64::new(0);
                             // fn next_id() -> u64 {
                             //     COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
                             // }
                             // let pasta = format!("(Paste: {})", next_id());
-                            let line_start = text_edit.rope.line_to_char(line);
-                            let char_index = line_start + col;
-                            text_edit.rope.try_insert(char_index, &pasta);
-                            let mut new_line = line;
-                            let mut new_col = col;
-                            for chr in pasta.chars() {
-                                match chr {
-                                    '\n' => {
-                                        new_col = 0;
-                                        new_line += 1;
-                                    }
-                                    '\r' => continue,
-                                    c => new_col += 1,
-                                }
+                            let isearch = match &mut keymap {
+                                Keymap::Emacs(emacs) => emacs.isearch(),
+                                _ => None,
+                            };
+                            match (&mut palette, &mut go_to_line, isearch.or(find_bar.as_mut())) {
+                                (Some(palette), _, _) => palette.paste(&pasta),
+                                (None, Some(dialog), _) => dialog.paste(&pasta, text_edit),
+                                (None, None, Some(bar)) => bar.paste(&pasta, text_edit),
+                                (None, None, None) => text_edit.paste_text(&pasta),
                             }
-                            line = new_line;
-                            col = new_col;
-                            max_prev_col = col;
-                            let bottom = text_edit.start_line + term_size.height as usize;
-                            text_edit.start_line = text_edit.start_line
-                                .min(line)
-                                .max(line - term_size.height as usize);
-                            text_edit.start_col = text_edit.start_col
-                                .min(col)
-                                .max(col + 1 - term_size.width as usize);
-                            let ncx = col - text_edit.start_col;
-                            let ncy = line - text_edit.start_line;
-                            execute!(MoveTo(ncx as u16, ncy as u16));
                             context.request_render();
                         }
                         _ => (),
                     }
+                    match app_command {
+                        Some(AppCommand::Editor(command)) => text_edit.execute(&command),
+                        Some(AppCommand::Workspace(command)) => workspace_command = Some(command),
+                        Some(AppCommand::CommandPalette) => palette = Some(CommandPaletteState::new(palette_items())),
+                        Some(command @ (AppCommand::Find | AppCommand::Replace)) => {
+                            let mut bar = FindBarState::new(text_edit, command == AppCommand::Replace);
+                            bar.update(text_edit);
+                            find_bar = Some(bar);
+                        }
+                        Some(AppCommand::GoToLine) => go_to_line = Some(GoToLineState::new(text_edit)),
+                        // Buffers without a file have nothing to compare with.
+                        Some(AppCommand::CompareWithSaved) => diff = DiffState::saved(text_edit).ok(),
+                        // Requests need every buffer, so they wait until the editor is no longer borrowed.
+                        Some(command @ (AppCommand::GoToDefinition | AppCommand::Hover | AppCommand::Complete)) => lsp_request = Some(command),
+                        Some(AppCommand::CycleKeymap) => {
+                            keymap = match keymap {
+                                Keymap::Default => Keymap::Vim(Vim::new()),
+                                Keymap::Vim(_) => Keymap::Emacs(Emacs::new()),
+                                Keymap::Emacs(_) => Keymap::Default,
+                            };
+                            text_edit.clear_selection();
+                            text_edit.search = None;
+                            text_edit.current_match = None;
+                        }
+                        Some(AppCommand::CycleTheme) => theme_index = (theme_index + 1) % themes.len(),
+                        Some(AppCommand::RecordMacro) if text_edit.recording_macro().is_some() => _ = text_edit.stop_recording(),
+                        Some(AppCommand::RecordMacro) => {
+                            let prompt = MacroPrompt::Record;
+                            message = Some(prompt.text());
+                            macro_prompt = Some(prompt);
+                        }
+                        Some(AppCommand::PlayMacro) => {
+                            let prompt = MacroPrompt::Play(String::new());
+                            message = Some(prompt.text());
+                            macro_prompt = Some(prompt);
+                        }
+                        Some(AppCommand::Quit) => context.request_exit(game_loop::ExitRequest::Success),
+                        None => (),
+                    }
+                    if let Some(path) = &macros_path
+                        && was_recording.is_some()
+                        && text_edit.recording_macro() != was_recording {
+                        let register = was_recording.expect("A macro was being recorded.");
+                        message = Some(match text_edit.macros.borrow().save(path) {
+                            Ok(()) => format!("Recorded @{register}"),
+                            Err(err) => format!("Error saving macros: {err}"),
+                        });
+                    }
+                    // A snippet's tabstop with choices lists them when the cursor gets to it.
+                    if let Some(choices) = text_edit.take_snippet_choices() {
+                        completion = Some(CompletionState::choices(choices.into_iter().map(CompletionItem::new).collect(), text_edit));
+                    }
+                    if completion.as_mut().is_some_and(|state| !state.update(text_edit)) {
+                        completion = None;
+                    }
+                    if workspace_command.is_some() || close_pane || click.is_some() {
+                        // Searches and selections belong to the pane that is losing focus.
+                        go_to_line = None;
+                        diff = None;
+                        hover = None;
+                        completion = None;
+                        if find_bar.take().is_some() {
+                            text_edit.search = None;
+                            text_edit.current_match = None;
+                        }
+                        if let Keymap::Vim(vim) = &mut keymap {
+                            vim.reset(text_edit);
+                        }
+                    }
+                    if let Some(command) = workspace_command {
+                        workspace.execute(command);
+                    }
+                    // Vim's `:q` leaves with the last pane.
+                    if close_pane && !workspace.close_pane() {
+                        context.request_exit(game_loop::ExitRequest::Success);
+                    }
+                    // Vim's `:qa` checks every buffer, not only the one it was typed in.
+                    if let Some(force) = quit_all {
+                        match workspace.buffers.modified().next() {
+                            Some(buffer) if !force => if let Keymap::Vim(vim) = &mut keymap {
+                                vim.set_message(format!("E162: No write since last change for buffer \"{}\"", workspace.buffers.name(buffer)));
+                            },
+                            _ => {
+                                exit_confirmed |= force;
+                                context.request_exit(game_loop::ExitRequest::Success);
+                            }
+                        }
+                    }
+                    if let Some(mouse_event) = click {
+                        workspace.click(Position::new(mouse_event.column, mouse_event.row));
+                        workspace.editor_mut().handle_mouse(mouse_event);
+                    }
+                    if let Some(command) = lsp_request {
+                        let buffer = workspace.active_buffer();
+                        // Completions from the open buffers show right away, and the language server's join them.
+                        if command == AppCommand::Complete {
+                            completion = completer.complete(&workspace.buffers, buffer);
+                        }
+                        let result = match (&mut lsp, &command) {
+                            (None, AppCommand::Complete) => Ok(()),
+                            (None, _) => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No language server. Start one with --lsp=<command>")),
+                            (Some(client), AppCommand::Hover) => client.hover(&mut workspace.buffers, buffer),
+                            (Some(client), AppCommand::Complete) => client.completion(&mut workspace.buffers, buffer),
+                            (Some(client), _) => client.definition(&mut workspace.buffers, buffer),
+                        };
+                        if let Err(error) = result
+                            && (command != AppCommand::Complete || completion.is_none()) {
+                            message = Some(error.to_string());
+                        }
+                    } else if typed
+                        && completion.is_none()
+                        && palette.is_none()
+                        && go_to_line.is_none()
+                        && find_bar.is_none()
+                        && !matches!(&keymap, Keymap::Vim(vim) if vim.mode() != VimMode::Insert)
+                        && workspace.editor().version() != version {
+                        completion = completer.complete_typed(&workspace.buffers, workspace.active_buffer());
+                    }
                 },
                 GameEvent::Begin(game_settings) => {
                     execute!(MoveTo(0, 0));
                 },
                 GameEvent::Update => {
-                    
+                    // Large files load in the background; redraw as their progress changes.
+                    // A failed load leaves an empty, unnamed buffer.
+                    for (_, editor) in workspace.buffers.iter_mut() {
+                        if editor.is_loading() {
+                            _ = editor.poll_loading();
+                            context.request_render();
+                        }
+                        if editor.poll_follow() {
+                            context.request_render();
+                        }
+                        if let Ok(true) = editor.poll_autosave() {
+                            context.request_render();
+                        }
+                    }
+                    let events = lsp.as_mut().map(|client| client.poll(&mut workspace.buffers)).unwrap_or_default();
+                    for event in events {
+                        context.request_render();
+                        match event {
+                            LspEvent::Hover { buffer, position, text } => hover = Some((buffer, position, text)),
+                            // Completions are dropped if the cursor moved while they were on the way.
+                            LspEvent::Completion { buffer, position, items } => {
+                                let editor = workspace.editor();
+                                if workspace.active_buffer() == buffer && editor.cursor == position {
+                                    match &mut completion {
+                                        Some(state) => _ = state.extend(items, editor),
+                                        None => {
+                                            let state = CompletionState::new(items, editor);
+                                            completion = (!state.is_empty()).then_some(state);
+                                        }
+                                    }
+                                }
+                            }
+                            LspEvent::Definition { buffer, position } if diff.is_none() => {
+                                if workspace.active_buffer() != buffer {
+                                    workspace.new_tab(buffer);
+                                }
+                                workspace.editor_mut().execute(&EditorCommand::MoveTo(position));
+                            }
+                            LspEvent::Definition { .. } => (),
+                            LspEvent::Message(text) => message = Some(text),
+                            LspEvent::Exited => {
+                                message = Some(String::from("The language server exited"));
+                                lsp = None;
+                            }
+                        }
+                    }
                 },
                 GameEvent::Render => {
+                    let style = match &keymap {
+                        Keymap::Vim(vim) if vim.mode() != VimMode::Insert => SetCursorStyle::SteadyBlock,
+                        _ => SetCursorStyle::SteadyBar,
+                    };
+                    if cursor_style != Some(style) {
+                        execute!(style);
+                        cursor_style = Some(style);
+                    }
+                    let theme = &themes[theme_index];
                     terminal.draw(|frame| {
                         frame.buffer_mut().reset();
-                        let area = frame.area();
-                        // let text_area = Rect::new(area.x, area.y, area.width, area.height - 1);
-                        // let display_area = Rect::new(area.x, text_area.bottom(), area.width, 1);
-                        frame.render_stateful_widget(TextEdit, area, &mut text_edit);
-                        // let info = format!("start_line: {} start_col: {} line: {} col: {}, cx: {cx}, cy: {cy}", text_edit.start_line, text_edit.start_col, line, col);
-                        // frame.render_widget(info, display_area);
-                        
+                        let mut area = frame.area();
+                        let mode_line_area = (!matches!(keymap, Keymap::Default)).then(|| {
+                            let height = area.height.min(1);
+                            area.height -= height;
+                            Rect::new(area.x, area.bottom(), area.width, height)
+                        });
+                        let status_bar_area = Rect::new(area.x, area.bottom() - area.height.min(1), area.width, area.height.min(1));
+                        area.height -= status_bar_area.height;
+                        let mode = match &keymap {
+                            Keymap::Default => "",
+                            Keymap::Vim(vim) => vim.mode().name(),
+                            Keymap::Emacs(_) => "EMACS",
+                        };
+                        let find_bar = match &mut keymap {
+                            Keymap::Emacs(emacs) => emacs.isearch(),
+                            _ => None,
+                        }.or(find_bar.as_mut());
+                        let bar_area = find_bar.as_ref().map(|bar| {
+                            let height = bar.height().min(area.height);
+                            area.height -= height;
+                            Rect::new(area.x, area.bottom(), area.width, height)
+                        });
+                        let recovery_area = (!recoveries.is_empty()).then(|| {
+                            let height = area.height.min(1);
+                            area.height -= height;
+                            Rect::new(area.x, area.bottom(), area.width, height)
+                        });
+                        let go_to_line_area = go_to_line.is_some().then(|| {
+                            let height = area.height.min(1);
+                            area.height -= height;
+                            Rect::new(area.x, area.bottom(), area.width, height)
+                        });
+                        let tab_bar_area = Rect::new(area.x, area.y, area.width, area.height.min(1));
+                        area.y += tab_bar_area.height;
+                        area.height -= tab_bar_area.height;
+                        frame.render_stateful_widget(TabBar::new().theme(theme), tab_bar_area, &mut workspace);
+                        let text_edit_widget = TextEdit::new()
+                            .gutter(Gutter::default())
+                            .minimap(Minimap::default())
+                            .scrollbars(Scrollbars::default())
+                            .diagnostics(InlineDiagnostics::default())
+                            .theme(theme);
+                        frame.render_stateful_widget(WorkspaceView::new(text_edit_widget).theme(theme), area, &mut workspace);
+                        let mut status_bar = StatusBar::new(workspace.editor()).mode(mode).theme(theme);
+                        let recording = workspace.editor().recording_macro().map(|register| format!("recording @{register}"));
+                        if let Some(message) = message.as_ref().or(recording.as_ref()) {
+                            status_bar = status_bar.message(message);
+                        }
+                        frame.render_widget(status_bar, status_bar_area);
+                        if let Some((buffer, position, text)) = &hover
+                            && workspace.active_buffer() == *buffer
+                            && workspace.editor().cursor == *position {
+                            frame.render_widget(HoverPopup::new(text, workspace.editor()).theme(theme), area);
+                        }
+                        if let Some(state) = &mut completion {
+                            frame.render_stateful_widget(CompletionPopup::new(workspace.editor()).theme(theme), area, state);
+                        }
+                        let mut cursor_position = workspace.editor().cursor_screen_position;
+                        if let Some(state) = &mut diff {
+                            frame.render_stateful_widget(DiffView::new(workspace.editor()).theme(theme), area, state);
+                            cursor_position = None;
+                        }
+                        if let (Some(bar), Some(bar_area)) = (find_bar, bar_area) {
+                            frame.render_stateful_widget(FindBar::new().theme(theme), bar_area, bar);
+                            cursor_position = bar.cursor_screen_position;
+                        }
+                        if let (Some(dialog), Some(dialog_area)) = (&mut go_to_line, go_to_line_area) {
+                            frame.render_stateful_widget(GoToLineBar::new(workspace.editor()).theme(theme), dialog_area, dialog);
+                            cursor_position = dialog.cursor_screen_position;
+                        }
+                        if let (Some(recovery), Some(recovery_area)) = (recoveries.front(), recovery_area) {
+                            frame.render_widget(RecoveryBar::new(recovery, recoveries.len() - 1).theme(theme), recovery_area);
+                            cursor_position = None;
+                        }
+                        if let Some(mode_line_area) = mode_line_area {
+                            frame.buffer_mut().set_style(mode_line_area, theme.style("mode_line"));
+                        }
+                        if let (Keymap::Vim(vim), Some(mode_line_area)) = (&keymap, mode_line_area) {
+                            let mode_line = match (vim.command_line(), vim.message()) {
+                                (Some(command_line), _) => {
+                                    let x = mode_line_area.x + 1 + command_line.chars().count() as u16;
+                                    cursor_position = Some(Position::new(x.min(mode_line_area.right().saturating_sub(1)), mode_line_area.y));
+                                    format!(":{command_line}")
+                                }
+                                (None, Some(message)) => message.to_owned(),
+                                (None, None) if vim.mode() == VimMode::Normal => String::new(),
+                                (None, None) => format!("-- {} --", vim.mode().name()),
+                            };
+                            frame.render_widget(Line::from(mode_line), mode_line_area);
+                            let pending = vim.pending();
+                            let pending_width = pending.chars().count() as u16;
+                            if pending_width != 0 && pending_width < mode_line_area.width {
+                                let pending_area = Rect {
+                                    x: mode_line_area.right() - pending_width - 1,
+                                    width: pending_width,
+                                    ..mode_line_area
+                                };
+                                frame.render_widget(Line::from(pending), pending_area);
+                            }
+                        }
+                        if let (Keymap::Emacs(emacs), Some(mode_line_area)) = (&keymap, mode_line_area) {
+                            frame.render_widget(Line::from(emacs.message().unwrap_or(emacs.pending())), mode_line_area);
+                        }
+                        if let Some(state) = &mut palette {
+                            frame.render_stateful_widget(CommandPalette::new().theme(theme), frame.area(), state);
+                            cursor_position = state.cursor_screen_position;
+                        }
+                        if let Some(position) = cursor_position {
+                            frame.set_cursor_position(position);
+                        }
+                        color_depth.adapt_buffer(frame.buffer_mut());
                     })?;
-                    execute!(Show);
-                    execute!(MoveTo(cx, cy));
                 },
-                GameEvent::ExitRequested(cancellable_exit_request) => {
-                    
+                GameEvent::ExitRequested(request) => {
+                    let mut modified = workspace.buffers.modified();
+                    if let Some(buffer) = modified.next() && !exit_confirmed {
+                        message = Some(match modified.count() {
+                            0 => format!("{} has unsaved changes. Quit again to discard them.", workspace.buffers.name(buffer)),
+                            others => format!("{} buffers have unsaved changes. Quit again to discard them.", others + 1),
+                        });
+                        request.cancel();
+                        exit_warned = true;
+                        context.request_render();
+                    }
                 },
                 GameEvent::Exiting => {
-                    
+                    // The language server is stopped first, so that it isn't left running if
+                    // removing a swap file fails.
+                    if let Some(client) = lsp.take() {
+                        client.shutdown(Duration::from_secs(1));
+                    }
+                    workspace.buffers.remove_swap_files()?;
                 },
             }
             Ok(())
@@ -461,7 +670,118 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-struct HackerText;
+/// Which input layer keys go to.
+enum Keymap {
+    Default,
+    Vim(Vim),
+    Emacs(Emacs),
+}
+
+/// Everything the command palette can run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AppCommand {
+    Editor(EditorCommand),
+    Workspace(WorkspaceCommand),
+    CommandPalette,
+    Find,
+    Replace,
+    GoToLine,
+    CompareWithSaved,
+    GoToDefinition,
+    Hover,
+    Complete,
+    CycleKeymap,
+    CycleTheme,
+    /// Starts recording a macro, asking for the register, or stops the recording.
+    RecordMacro,
+    /// Asks for a count and a register, then plays that macro.
+    PlayMacro,
+    Quit,
+}
+
+/// What the status bar asks for after a macro command.
+enum MacroPrompt {
+    Record,
+    /// The digits of the repeat count typed so far.
+    Play(String),
+}
+
+impl MacroPrompt {
+    fn text(&self) -> String {
+        match self {
+            MacroPrompt::Record => String::from("Record macro into register:"),
+            MacroPrompt::Play(count) if count.is_empty() => String::from("Play macro from register (type digits first to repeat):"),
+            MacroPrompt::Play(count) => format!("Play macro {count} times from register:"),
+        }
+    }
+}
+
+/// Keys that the app handles before the keymap. Emacs uses the control keys for itself.
+fn app_binding(key: KeyEvent, keymap: &Keymap) -> Option<AppCommand> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    let command = match key.code {
+        KeyCode::Char('p' | 'P') if ctrl && shift => AppCommand::CommandPalette,
+        KeyCode::F(1) => AppCommand::CommandPalette,
+        KeyCode::F(2) => AppCommand::CycleKeymap,
+        KeyCode::F(12) => AppCommand::GoToDefinition,
+        _ if matches!(keymap, Keymap::Emacs(_)) => return None,
+        KeyCode::F(9) => AppCommand::RecordMacro,
+        KeyCode::F(10) => AppCommand::PlayMacro,
+        KeyCode::Char('q') if ctrl => AppCommand::Quit,
+        KeyCode::Char('f') if ctrl => AppCommand::Find,
+        // Plain Ctrl+H is what many terminals send for Ctrl+Backspace, and terminals without
+        // keyboard enhancement send Ctrl+Shift+H as Ctrl+H too, so Alt+R works everywhere.
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::ALT) => AppCommand::Replace,
+        KeyCode::Char('h' | 'H') if ctrl && shift => AppCommand::Replace,
+        KeyCode::Char('g') if ctrl => AppCommand::GoToLine,
+        KeyCode::Char(' ') if ctrl => AppCommand::Complete,
+        KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::ALT) => AppCommand::Hover,
+        _ => return None,
+    };
+    Some(command)
+}
+
+/// Every command in the palette, with its binding in the default keymap.
+fn palette_items() -> Vec<PaletteItem<AppCommand>> {
+    let app_commands = [
+        ("Find", AppCommand::Find),
+        ("Replace", AppCommand::Replace),
+        ("Go to Line", AppCommand::GoToLine),
+        ("Compare with Saved", AppCommand::CompareWithSaved),
+        ("Go to Definition", AppCommand::GoToDefinition),
+        ("Show Hover Info", AppCommand::Hover),
+        ("Show Completions", AppCommand::Complete),
+        ("Switch Keymap", AppCommand::CycleKeymap),
+        ("Switch Theme", AppCommand::CycleTheme),
+        ("Record Macro", AppCommand::RecordMacro),
+        ("Play Macro", AppCommand::PlayMacro),
+        ("Quit", AppCommand::Quit),
+    ];
+    let app_items = app_commands.into_iter().map(|(title, command)| {
+        let binding = find_binding(&command, |key| app_binding(key, &Keymap::Default));
+        PaletteItem::new(title, command).binding(binding)
+    });
+    let editor_items = NAMED_COMMANDS.iter().map(|(title, command)| {
+        PaletteItem::new(*title, AppCommand::Editor(command.clone())).binding(find_binding(command, default_binding))
+    });
+    let workspace_items = NAMED_WORKSPACE_COMMANDS.iter().map(|&(title, command)| {
+        PaletteItem::new(title, AppCommand::Workspace(command)).binding(find_binding(&command, workspace_binding))
+    });
+    app_items.chain(editor_items).chain(workspace_items).collect()
+}
+
+struct HackerText {
+    style: Style,
+}
+
+impl HackerText {
+    fn new(theme: &Theme) -> Self {
+        Self {
+            style: theme.style("hacker_text"),
+        }
+    }
+}
 
 impl Widget for HackerText {
     fn render(self, area: Rect, buf: &mut Buffer)
@@ -491,6 +811,7 @@ impl Widget for HackerText {
             // '|', '~', '`', ',', '.',
             // '\'', '"',
         ];
+        buf.set_style(area, self.style);
         for y in area.y..area.bottom() {
             for x in area.x..area.right() {
                 if let Some(cell) = buf.cell_mut((x, y)) {
@@ -515,6 +836,8 @@ fn run(mut terminal: DefaultTerminal) -> Result<()> {
     let mut x = 0u16;
     let mut y = 0u16;
     let mut last_frame_time = Instant::now() - Duration::from_secs(10);
+    let theme = Theme::default();
+    let color_depth = ColorDepth::detect();
     const FRAME_TIME: Duration = Duration::from_millis(16);
     'game_loop: loop {
         let next_time = last_frame_time + FRAME_TIME;
@@ -546,7 +869,7 @@ fn run(mut terminal: DefaultTerminal) -> Result<()> {
         }
         terminal.draw(|frame: &mut Frame| {
             let area = frame.area();
-            frame.render_widget(HackerText, area);
+            frame.render_widget(HackerText::new(&theme), area);
             let text = format!("(x: {}, y: {}, w: {}, h: {})\nCounter: {counter}\nKey: {pressed_key}\nText: {text_edit}", area.x, area.y, area.width, area.height);
             let para = Paragraph::new(text);
             frame.render_widget(para.block(ALL_BORDER), area);
@@ -556,9 +879,9 @@ fn run(mut terminal: DefaultTerminal) -> Result<()> {
             y = y.min(area.bottom() - 1);
             let me_rect = Rect::new(x, y, 1, 1);
             frame.render_widget("o", me_rect);
-            
+            color_depth.adapt_buffer(frame.buffer_mut());
         })?;
         counter += 1;
-        
+    
     }
 }
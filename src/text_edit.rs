@@ -1,84 +1,1722 @@
+pub mod ansi;
+pub mod brackets;
+pub mod clipboard;
+pub mod command;
+pub mod completion;
+pub mod diagnostics;
+pub mod diff;
+pub mod emacs;
+pub mod find_bar;
+pub mod fold;
+pub mod follow;
+pub mod go_to_line;
+pub mod gutter;
+pub mod highlight;
+pub mod history;
+pub mod large_file;
+pub mod line_ops;
+pub mod lsp;
+pub mod macros;
+pub mod minimap;
+pub mod motion;
+pub mod mouse;
+pub mod palette;
+pub mod scrollbar;
+pub mod search;
+pub mod snippet;
+pub mod status_bar;
+pub mod swap;
+pub mod vim;
+pub mod whitespace;
+pub mod workspace;
+pub mod wrap;
+
+use std::{collections::BTreeMap, io::BufWriter, ops::Range, path::{Path, PathBuf}, time::Instant};
+
 use ratatui::prelude::*;
-use ropey::Rope;
+use ropey::{Rope, RopeSlice};
+
+use ansi::{AnsiColors, AnsiSpan};
+use brackets::DEFAULT_AUTO_PAIRS;
+use clipboard::{MemoryClipboard, SharedClipboard};
+use diagnostics::{Diagnostics, InlineDiagnostics};
+use fold::{FoldMarker, FoldMethod, FoldRegions, Folds};
+use follow::{Follow, FollowEvent};
+use gutter::{Gutter, Signs};
+use highlight::{Highlighter, Highlighting, Language, SyntaxTheme, Token, DEFAULT_SYNTAX_THEME};
+use history::{Edit, History};
+use large_file::{FileLoader, LargeFileThresholds, LoadingView};
+use lsp::ChangeLog;
+use macros::{Macros, SharedMacros};
+use minimap::{Minimap, MinimapLayout};
+use mouse::MouseDrag;
+use scrollbar::Scrollbars;
+use search::Search;
+use snippet::{SharedSnippets, SnippetSession, Snippets};
+use swap::{Autosave, SwapFile};
+use whitespace::{LineWhitespace, ShowWhitespace, Whitespace};
+use wrap::{LineLayouts, SoftWrap};
+
+use crate::theme::Theme;
+
+/// A position in the buffer as a line index and a char column within that line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextPosition {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl TextPosition {
+    pub const fn new(line: usize, col: usize) -> Self {
+        Self {
+            line,
+            col,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SelectionKind {
+    /// From the anchor up to, but not including, the cursor.
+    Exclusive,
+    /// Includes the char under the cursor, as in Vim's visual mode.
+    Inclusive,
+    /// Every line from the anchor's to the cursor's, including the line ending.
+    Line,
+}
+
+/// A selection between `anchor` and the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Selection {
+    pub anchor: TextPosition,
+    pub kind: SelectionKind,
+}
 
 pub struct TextEditor {
     pub rope: Rope,
+    /// The file the buffer was loaded from or last saved to.
+    pub path: Option<PathBuf>,
     pub start_line: usize,
     pub start_col: usize,
+    /// The first visible row of `start_line` when soft wrap is on.
+    pub start_row: usize,
+    pub cursor: TextPosition,
+    /// Cursors besides `cursor`, kept in sync with edits. Typing, deleting, moving and pasting
+    /// happen at every cursor; other commands only use the primary one.
+    pub extra_cursors: Vec<TextPosition>,
+    pub selection: Option<Selection>,
+    /// The column that vertical movement tries to return to.
+    /// With soft wrap on, this is the column within the wrapped row.
+    pub preferred_col: usize,
+    pub soft_wrap: Option<SoftWrap>,
+    pub highlighting: Option<Highlighting>,
+    pub history: History,
+    /// The active search, whose matches are highlighted when rendering.
+    pub search: Option<Search>,
+    /// The match that search navigation last moved to.
+    pub current_match: Option<Range<usize>>,
+    pub signs: Signs,
+    /// Problems reported by a language server or linter. See [`TextEditor::set_diagnostics`].
+    diagnostics: Diagnostics,
+    pub folds: Folds,
+    pub fold_method: FoldMethod,
+    pub clipboard: SharedClipboard,
+    /// Enter keeps the indentation of the line, one level deeper after an opening bracket.
+    pub auto_indent: bool,
+    /// Typing the first char of a pair inserts both. See [`TextEditor::type_char`].
+    pub auto_pairs: Vec<(char, char)>,
+    /// What Tab expands. See [`TextEditor::expand_snippet`].
+    pub snippets: SharedSnippets,
+    /// Records the commands run, and plays them back. See [`TextEditor::play_macro`].
+    pub macros: SharedMacros,
+    /// Rejects every edit, for viewers. Text can still be added with [`TextEditor::append`].
+    pub read_only: bool,
+    /// Interprets ANSI color escapes in appended text when set. See [`AnsiColors`].
+    pub ansi: Option<AnsiColors>,
+    /// Above these sizes, files load in the background and highlighting and folding turn off.
+    pub large_file: LargeFileThresholds,
+    /// Swap files and saving on idle. See [`TextEditor::poll_autosave`].
+    pub autosave: Autosave,
+    /// Where the editor was drawn during the last render, gutter and scrollbars included.
+    pub area: Rect,
+    /// Where the text (excluding the gutter) was drawn during the last render.
+    pub text_area: Rect,
+    /// Where the scrollbars were drawn during the last render, if they were.
+    pub vertical_scrollbar: Option<Rect>,
+    pub horizontal_scrollbar: Option<Rect>,
+    minimap: Option<MinimapLayout>,
+    /// Where the cursor was drawn during the last render, if it was visible.
+    pub cursor_screen_position: Option<Position>,
+    /// Char indices kept for other panes on this buffer while they aren't focused, by pane,
+    /// and moved along with every edit.
+    pub(crate) parked_views: BTreeMap<usize, Vec<usize>>,
+    scroll_to_cursor: bool,
+    mouse_drag: Option<MouseDrag>,
+    layouts: LineLayouts,
+    /// Set while a large file is loading. The buffer is empty and read-only until it's done.
+    loader: Option<FileLoader>,
+    /// Where appended text comes from in follow mode.
+    follow: Option<Follow>,
+    /// Open while there are unsaved changes, if swap files are on.
+    swap: Option<SwapFile>,
+    /// The version when autosave last looked, and when the buffer got to it.
+    last_edit: (u64, Instant),
+    /// The version that autosave last failed to save, and when.
+    failed_save: Option<(u64, Instant)>,
+    /// Edits not yet sent to the language server, while one has the buffer open.
+    lsp_changes: Option<ChangeLog>,
+    /// The snippet whose tabstops are being filled in.
+    snippet: Option<SnippetSession>,
+    /// Whether the last line was visible during the last render, so that appending
+    /// text should scroll to keep the end in view.
+    viewport_at_end: bool,
+    /// Foldable regions, recomputed when the buffer or fold method changes.
+    fold_regions: Option<(u64, FoldMethod, FoldRegions)>,
+    version: u64,
+    saved_version: u64,
+    /// Set while undoing or redoing so that the replayed edits aren't recorded.
+    replaying: bool,
 }
 
 impl TextEditor {
+    pub const TAB_WIDTH: usize = 4;
+    
     pub fn new() -> Self {
         Self {
             rope: Rope::new(),
+            path: None,
             start_line: 0,
             start_col: 0,
+            start_row: 0,
+            cursor: TextPosition::default(),
+            extra_cursors: Vec::new(),
+            selection: None,
+            preferred_col: 0,
+            soft_wrap: None,
+            highlighting: None,
+            history: History::default(),
+            search: None,
+            current_match: None,
+            signs: Signs::default(),
+            diagnostics: Diagnostics::default(),
+            folds: Folds::default(),
+            fold_method: FoldMethod::default(),
+            clipboard: MemoryClipboard::shared(),
+            auto_indent: true,
+            auto_pairs: DEFAULT_AUTO_PAIRS.to_vec(),
+            snippets: SharedSnippets::new(Snippets::new()),
+            macros: Macros::shared(),
+            read_only: false,
+            ansi: None,
+            large_file: LargeFileThresholds::default(),
+            autosave: Autosave::default(),
+            area: Rect::ZERO,
+            text_area: Rect::ZERO,
+            vertical_scrollbar: None,
+            horizontal_scrollbar: None,
+            minimap: None,
+            cursor_screen_position: None,
+            parked_views: BTreeMap::new(),
+            scroll_to_cursor: false,
+            mouse_drag: None,
+            layouts: LineLayouts::default(),
+            loader: None,
+            follow: None,
+            swap: None,
+            last_edit: (0, Instant::now()),
+            failed_save: None,
+            lsp_changes: None,
+            snippet: None,
+            viewport_at_end: true,
+            fold_regions: None,
+            version: 0,
+            saved_version: 0,
+            replaying: false,
+        }
+    }
+    
+    /// Opens a file, loading it in the background if it's larger than
+    /// [`LargeFileThresholds::background_load`]. Call [`TextEditor::poll_loading`]
+    /// until it's done.
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let mut editor = Self::new();
+        editor.path = Some(path.to_owned());
+        if std::fs::metadata(path)?.len() >= editor.large_file.background_load {
+            editor.loader = Some(FileLoader::spawn(path)?);
+            return Ok(editor);
+        }
+        editor.rope = large_file::read_file(path)?;
+        editor.apply_large_file_thresholds();
+        Ok(editor)
+    }
+    
+    pub fn is_loading(&self) -> bool {
+        self.loader.is_some()
+    }
+    
+    /// Takes the text from a background load once it has finished, returning `true` if
+    /// anything changed. A failed load returns the error and leaves the buffer empty and
+    /// without a path, so that saving it can't overwrite the file.
+    pub fn poll_loading(&mut self) -> std::io::Result<bool> {
+        let Some(result) = self.loader.as_mut().and_then(FileLoader::poll) else {
+            return Ok(false);
+        };
+        self.loader = None;
+        let rope = result.inspect_err(|_| self.path = None)?;
+        self.replace_text(rope);
+        self.apply_large_file_thresholds();
+        Ok(true)
+    }
+    
+    /// Replaces the whole buffer with text that didn't come from editing it, such as a
+    /// file that finished loading. The buffer counts as unmodified, and can't be undone.
+    fn replace_text(&mut self, rope: Rope) {
+        let old_end_line = self.rope.len_lines() - 1;
+        self.rope = rope;
+        if let Some(changes) = &mut self.lsp_changes {
+            changes.resync();
+        }
+        self.snippet = None;
+        self.history = History::default();
+        self.version += 1;
+        self.saved_version = self.version;
+        self.lines_changed(0, old_end_line, self.rope.len_lines() - 1);
+        self.set_cursor(self.cursor);
+    }
+    
+    /// Whether edits are allowed: the buffer isn't [read-only](TextEditor::read_only) or loading.
+    pub fn is_editable(&self) -> bool {
+        !self.read_only && !self.is_loading()
+    }
+    
+    /// Adds text to the end of the buffer from outside, even when it's read-only.
+    /// It isn't recorded in the history and doesn't mark the buffer as modified.
+    /// Escapes are interpreted if [`TextEditor::ansi`] is set, and the view follows the
+    /// new text if the end of the buffer was visible.
+    pub fn append(&mut self, text: &str) {
+        let end = self.char_to_position(self.rope.len_chars());
+        // Taken out so that the colors just recorded for the last line aren't cleared as an edit.
+        let mut ansi = self.ansi.take();
+        let text = match &mut ansi {
+            Some(ansi) => ansi.strip(text, end),
+            None => text.to_owned(),
+        };
+        let was_modified = self.is_modified();
+        let end_char = self.rope.len_chars();
+        self.rope.insert(end_char, &text);
+        if let Some(changes) = &mut self.lsp_changes {
+            changes.inserted(&self.rope, end_char, &text);
+        }
+        self.version += 1;
+        if !was_modified {
+            self.saved_version = self.version;
+        }
+        self.lines_changed(end.line, end.line, self.rope.len_lines() - 1);
+        self.ansi = ansi;
+        if self.viewport_at_end {
+            self.set_cursor(TextPosition::new(self.rope.len_lines() - 1, 0));
+        }
+    }
+    
+    /// A read-only buffer that shows what `follow` reads, with ANSI colors, like a log viewer.
+    pub fn viewer(follow: Follow) -> Self {
+        let mut editor = Self::new();
+        editor.read_only = true;
+        editor.ansi = Some(AnsiColors::new());
+        editor.path = follow.path().map(Path::to_owned);
+        editor.follow(follow);
+        editor
+    }
+    
+    /// Starts appending everything that `follow` reads. Call [`TextEditor::poll_follow`]
+    /// regularly to take in new text.
+    pub fn follow(&mut self, follow: Follow) {
+        self.follow = Some(follow);
+    }
+    
+    pub fn stop_following(&mut self) {
+        self.follow = None;
+    }
+    
+    pub fn is_following(&self) -> bool {
+        self.follow.is_some()
+    }
+    
+    /// Appends what the followed file or stream produced since the last call, returning
+    /// `true` if anything changed. A truncated file starts the buffer over.
+    pub fn poll_follow(&mut self) -> bool {
+        let Some(follow) = &mut self.follow else {
+            return false;
+        };
+        let events = follow.poll();
+        if follow.is_closed() {
+            self.follow = None;
+        }
+        for event in &events {
+            match event {
+                FollowEvent::Text(text) => self.append(text),
+                FollowEvent::Truncated => {
+                    if let Some(ansi) = &mut self.ansi {
+                        ansi.clear();
+                    }
+                    self.replace_text(Rope::new());
+                }
+            }
+        }
+        !events.is_empty()
+    }
+    
+    /// Whether the buffer is over any of the [`LargeFileThresholds`].
+    pub fn is_large(&self) -> bool {
+        let len = self.rope.len_bytes() as u64;
+        len >= self.large_file.highlighting.min(self.large_file.folding)
+    }
+    
+    /// Sets the language from the path if the buffer is small enough to highlight, and turns
+    /// folding off if it's too large to fold.
+    fn apply_large_file_thresholds(&mut self) {
+        let len = self.rope.len_bytes() as u64;
+        if len >= self.large_file.highlighting {
+            self.clear_highlighter();
+        } else if let Some(language) = self.path.as_deref().and_then(Language::from_path) {
+            self.set_language(language);
+        }
+        if len >= self.large_file.folding {
+            self.fold_method = FoldMethod::Off;
+            self.folds.clear();
+        }
+    }
+    
+    /// Saves to [`TextEditor::path`].
+    /// Fails for read-only buffers, which can still be saved elsewhere with [`TextEditor::save_as`].
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No file name"));
+        };
+        if self.read_only {
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "The buffer is read-only"));
+        }
+        self.save_as(path)
+    }
+    
+    pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        if self.is_loading() {
+            return Err(std::io::Error::new(std::io::ErrorKind::ResourceBusy, "The file is still loading"));
+        }
+        let path = path.as_ref();
+        let mut writer = BufWriter::new(std::fs::File::create(path)?);
+        self.rope.write_to(&mut writer)?;
+        std::io::Write::flush(&mut writer)?;
+        self.path = Some(path.to_owned());
+        self.saved_version = self.version;
+        Ok(())
+    }
+    
+    /// Whether the buffer changed since it was opened or saved.
+    pub fn is_modified(&self) -> bool {
+        self.version != self.saved_version
+    }
+    
+    /// Incremented on every change to the buffer.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+    
+    pub fn set_highlighter(&mut self, highlighter: Box<dyn Highlighter>) {
+        self.highlighting = Some(Highlighting::new(highlighter, &self.rope));
+        self.fold_regions = None;
+    }
+    
+    pub fn set_language(&mut self, language: Language) {
+        self.set_highlighter(language.highlighter());
+    }
+    
+    pub fn clear_highlighter(&mut self) {
+        self.highlighting = None;
+        self.fold_regions = None;
+    }
+    
+    pub fn set_soft_wrap(&mut self, soft_wrap: Option<SoftWrap>) {
+        self.soft_wrap = soft_wrap;
+        self.start_row = 0;
+        if soft_wrap.is_some() {
+            self.start_col = 0;
+        }
+        self.scroll_to_cursor = true;
+    }
+    
+    /// Length of a line in chars, excluding the line ending.
+    pub fn line_len(&self, line: usize) -> usize {
+        rope_line_len(&self.rope, line)
+    }
+    
+    pub fn position_to_char(&self, position: TextPosition) -> usize {
+        let line = position.line.min(self.rope.len_lines() - 1);
+        self.rope.line_to_char(line) + position.col.min(self.line_len(line))
+    }
+    
+    pub fn char_to_position(&self, char_idx: usize) -> TextPosition {
+        let char_idx = char_idx.min(self.rope.len_chars());
+        let line = self.rope.char_to_line(char_idx);
+        TextPosition::new(line, char_idx - self.rope.line_to_char(line))
+    }
+    
+    pub fn cursor_char_index(&self) -> usize {
+        self.position_to_char(self.cursor)
+    }
+    
+    /// Moves the cursor (clamped to the buffer) and scrolls it into view on the next render.
+    /// Folds hiding the new position are opened.
+    pub fn set_cursor(&mut self, position: TextPosition) {
+        let line = position.line.min(self.rope.len_lines() - 1);
+        if let Some((header, _)) = self.folds.fold_containing(line) {
+            self.folds.unfold(header);
+        }
+        self.cursor = TextPosition::new(line, position.col.min(self.line_len(line)));
+        self.preferred_col = self.visual_col(self.cursor);
+        self.scroll_to_cursor = true;
+    }
+    
+    /// The column a position is drawn at relative to the start of its (possibly wrapped) row.
+    fn visual_col(&mut self, position: TextPosition) -> usize {
+        match self.soft_wrap {
+            Some(wrap) => {
+                let (row, row_start) = self.layouts.row_of(&self.rope, position.line, position.col);
+                let offset = if row == 0 { 0 } else { wrap.indicator_width() };
+                position.col - row_start + offset
+            }
+            None => position.col,
+        }
+    }
+    
+    /// Like [`TextEditor::set_cursor`] but keeps the preferred column for vertical movement.
+    fn set_cursor_line(&mut self, line: usize) {
+        let line = line.min(self.rope.len_lines() - 1);
+        self.cursor = TextPosition::new(line, self.preferred_col.min(self.line_len(line)));
+        self.scroll_to_cursor = true;
+    }
+    
+    pub fn request_scroll_to_cursor(&mut self) {
+        self.scroll_to_cursor = true;
+    }
+    
+    /// Starts a selection at the cursor, replacing any existing selection.
+    pub fn start_selection(&mut self, kind: SelectionKind) {
+        self.selection = Some(Selection {
+            anchor: self.cursor,
+            kind,
+        });
+    }
+    
+    pub fn clear_selection(&mut self) {
+        self.selection = None;
+    }
+    
+    /// The selected chars, or `None` if nothing is selected.
+    pub fn selection_range(&self) -> Option<Range<usize>> {
+        let selection = self.selection?;
+        let anchor = self.position_to_char(selection.anchor);
+        let cursor = self.cursor_char_index();
+        let range = match selection.kind {
+            SelectionKind::Exclusive => anchor.min(cursor)..anchor.max(cursor),
+            SelectionKind::Inclusive => anchor.min(cursor)..(anchor.max(cursor) + 1).min(self.rope.len_chars()),
+            SelectionKind::Line => {
+                let first = selection.anchor.line.min(self.cursor.line).min(self.rope.len_lines() - 1);
+                let last = selection.anchor.line.max(self.cursor.line).min(self.rope.len_lines() - 1);
+                self.rope.line_to_char(first)..self.rope.line_to_char(last + 1)
+            }
+        };
+        (!range.is_empty()).then_some(range)
+    }
+    
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection_range().map(|range| self.rope.slice(range).to_string())
+    }
+    
+    /// Removes the selected text and clears the selection. Returns `false` if nothing was selected.
+    pub fn delete_selection(&mut self) -> bool {
+        if !self.is_editable() {
+            return false;
+        }
+        let Some(range) = self.selection_range() else {
+            self.selection = None;
+            return false;
+        };
+        self.selection = None;
+        let start = range.start;
+        if self.try_remove(range).is_err() {
+            return false;
+        }
+        self.set_cursor(self.char_to_position(start));
+        true
+    }
+    
+    pub fn move_left(&mut self) {
+        if self.cursor.col != 0 {
+            self.set_cursor(TextPosition::new(self.cursor.line, self.cursor.col - 1));
+        } else if let Some(line) = self.folds.prev_visible(self.cursor.line) {
+            self.set_cursor(TextPosition::new(line, self.line_len(line)));
+        }
+    }
+    
+    pub fn move_right(&mut self) {
+        if self.cursor.col < self.line_len(self.cursor.line) {
+            self.set_cursor(TextPosition::new(self.cursor.line, self.cursor.col + 1));
+        } else if self.folds.next_visible(self.cursor.line) < self.rope.len_lines() {
+            self.set_cursor(TextPosition::new(self.folds.next_visible(self.cursor.line), 0));
+        }
+    }
+    
+    /// Moves one wrapped row up or down. Returns `false` if soft wrap is off.
+    fn move_visual_row(&mut self, down: bool) -> bool {
+        let Some(wrap) = self.soft_wrap else {
+            return false;
+        };
+        let line = self.cursor.line;
+        let (row, _) = self.layouts.row_of(&self.rope, line, self.cursor.col);
+        let (line, row) = if down {
+            if row + 1 < self.layouts.row_count(&self.rope, line) {
+                (line, row + 1)
+            } else if self.folds.next_visible(line) < self.rope.len_lines() {
+                (self.folds.next_visible(line), 0)
+            } else {
+                self.move_line_end();
+                return true;
+            }
+        } else if row != 0 {
+            (line, row - 1)
+        } else if let Some(prev) = self.folds.prev_visible(line) {
+            (prev, self.layouts.row_count(&self.rope, prev) - 1)
+        } else {
+            self.set_cursor(TextPosition::new(0, 0));
+            return true;
+        };
+        let (row_start, row_end) = self.layouts.row_range(&self.rope, line, row);
+        // The last column of a row that isn't the last belongs to the next row.
+        let max_col = if row + 1 < self.layouts.row_count(&self.rope, line) {
+            row_end - 1
+        } else {
+            row_end
+        };
+        let offset = if row == 0 { 0 } else { wrap.indicator_width() };
+        let col = (row_start + self.preferred_col.saturating_sub(offset)).min(max_col);
+        self.cursor = TextPosition::new(line, col);
+        self.scroll_to_cursor = true;
+        true
+    }
+    
+    pub fn move_up(&mut self) {
+        if self.move_visual_row(false) {
+            return;
+        }
+        if let Some(line) = self.folds.prev_visible(self.cursor.line) {
+            self.set_cursor_line(line);
+        } else {
+            self.set_cursor(TextPosition::new(0, 0));
+        }
+    }
+    
+    pub fn move_down(&mut self) {
+        if self.move_visual_row(true) {
+            return;
+        }
+        if self.folds.next_visible(self.cursor.line) < self.rope.len_lines() {
+            self.set_cursor_line(self.folds.next_visible(self.cursor.line));
+        } else {
+            self.set_cursor(TextPosition::new(self.cursor.line, self.line_len(self.cursor.line)));
+        }
+    }
+    
+    pub fn move_line_start(&mut self) {
+        self.set_cursor(TextPosition::new(self.cursor.line, 0));
+    }
+    
+    pub fn move_line_end(&mut self) {
+        self.set_cursor(TextPosition::new(self.cursor.line, self.line_len(self.cursor.line)));
+    }
+    
+    pub fn insert_char(&mut self, chr: char) {
+        if !self.is_editable() {
+            return;
+        }
+        let index = self.cursor_char_index();
+        if self.try_insert_char(index, chr).is_ok() {
+            self.set_cursor(self.char_to_position(index + 1));
+        }
+    }
+    
+    pub fn insert_str(&mut self, text: &str) {
+        if !self.is_editable() {
+            return;
+        }
+        let index = self.cursor_char_index();
+        if self.try_insert(index, text).is_ok() {
+            self.set_cursor(self.char_to_position(index + text.chars().count()));
+        }
+    }
+    
+    /// Breaks the line, indenting the new one if [`TextEditor::auto_indent`] is on.
+    pub fn insert_newline(&mut self) {
+        if self.auto_indent {
+            self.insert_indented_newline();
+        } else {
+            self.insert_char('\n');
+        }
+    }
+    
+    /// Inserts spaces up to the next tab stop.
+    pub fn insert_tab(&mut self) {
+        const TAB_SPACES: &str = "    ";
+        let next_indent = (self.cursor.col + 1).next_multiple_of(Self::TAB_WIDTH);
+        self.insert_str(&TAB_SPACES[..next_indent - self.cursor.col]);
+    }
+    
+    /// Length of the line break ending at `char_idx`, treating `\r\n` as one break.
+    fn line_break_before(&self, char_idx: usize) -> usize {
+        match (char_idx.checked_sub(2).map(|i| self.rope.char(i)), self.rope.char(char_idx - 1)) {
+            (Some('\r'), '\n') => 2,
+            _ => 1,
+        }
+    }
+    
+    /// Deletes the char before the cursor, or both halves of an empty auto pair like `(|)`.
+    pub fn backspace(&mut self) {
+        if !self.is_editable() {
+            return;
+        }
+        let index = self.cursor_char_index();
+        if index == 0 {
+            return;
+        }
+        if self.in_empty_pair() {
+            if self.try_remove(index - 1..index + 1).is_ok() {
+                self.set_cursor(self.char_to_position(index - 1));
+            }
+            return;
+        }
+        let len = if self.cursor.col == 0 {
+            self.line_break_before(index)
+        } else {
+            1
+        };
+        if self.try_remove(index - len..index).is_ok() {
+            self.set_cursor(self.char_to_position(index - len));
+        }
+    }
+    
+    pub fn delete(&mut self) {
+        if !self.is_editable() {
+            return;
+        }
+        let index = self.cursor_char_index();
+        if index >= self.rope.len_chars() {
+            return;
+        }
+        let len = if self.rope.char(index) == '\r' && index + 1 < self.rope.len_chars() && self.rope.char(index + 1) == '\n' {
+            2
+        } else {
+            1
+        };
+        if self.try_remove(index..index + len).is_ok() {
+            self.set_cursor(self.char_to_position(index));
+        }
+    }
+    
+    // All mutations of the rope should go through these so that line caches stay in sync.
+    // Both do nothing unless the buffer is editable.
+    pub fn try_insert(&mut self, char_idx: usize, text: &str) -> Result<(), ropey::Error> {
+        if !self.is_editable() {
+            return Ok(());
+        }
+        let cursor = self.cursor_char_index();
+        let extra_cursors = self.extra_cursor_indices();
+        let lines_before = self.rope.len_lines();
+        self.rope.try_insert(char_idx, text)?;
+        if let Some(changes) = &mut self.lsp_changes {
+            changes.inserted(&self.rope, char_idx, text);
+        }
+        self.record(Edit {
+            char_idx,
+            removed: String::new(),
+            inserted: text.to_owned(),
+        });
+        let start_line = self.rope.char_to_line(char_idx);
+        let added_lines = self.rope.len_lines() - lines_before;
+        self.lines_changed(start_line, start_line, start_line + added_lines);
+        let inserted_len = text.chars().count();
+        if char_idx < cursor {
+            self.cursor = self.char_to_position(cursor + inserted_len);
+        }
+        for (index, extra_cursor) in extra_cursors.into_iter().enumerate() {
+            if char_idx < extra_cursor {
+                self.extra_cursors[index] = self.char_to_position(extra_cursor + inserted_len);
+            }
+        }
+        for parked in self.parked_views.values_mut().flatten().filter(|parked| char_idx < **parked) {
+            *parked += inserted_len;
+        }
+        if let Some(source) = self.snippet.as_mut().and_then(|snippet| snippet.inserted(char_idx, inserted_len)) {
+            self.sync_mirrors(source);
+        }
+        Ok(())
+    }
+    
+    pub fn try_insert_char(&mut self, char_idx: usize, chr: char) -> Result<(), ropey::Error> {
+        let mut buf = [0u8; 4];
+        self.try_insert(char_idx, chr.encode_utf8(&mut buf))
+    }
+    
+    pub fn try_remove<R: std::ops::RangeBounds<usize>>(&mut self, char_range: R) -> Result<(), ropey::Error> {
+        use std::ops::Bound;
+        let start = match char_range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match char_range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.rope.len_chars(),
+        };
+        if !self.is_editable() {
+            return Ok(());
+        }
+        if start > end || end > self.rope.len_chars() {
+            // Let ropey produce the appropriate error.
+            return self.rope.try_remove(start..end);
+        }
+        let cursor = self.cursor_char_index();
+        let extra_cursors = self.extra_cursor_indices();
+        let start_line = self.rope.char_to_line(start);
+        let old_end_line = self.rope.char_to_line(end);
+        let lines_before = self.rope.len_lines();
+        let removed = if self.replaying {
+            String::new()
+        } else {
+            self.rope.slice(start..end).to_string()
+        };
+        if let Some(changes) = &mut self.lsp_changes {
+            changes.removing(&self.rope, start..end);
+        }
+        self.rope.try_remove(start..end)?;
+        self.record(Edit {
+            char_idx: start,
+            removed,
+            inserted: String::new(),
+        });
+        let removed_lines = lines_before - self.rope.len_lines();
+        self.lines_changed(start_line, old_end_line, old_end_line - removed_lines);
+        if start < cursor {
+            self.cursor = self.char_to_position(cursor - (cursor.min(end) - start));
+        }
+        for (index, extra_cursor) in extra_cursors.into_iter().enumerate() {
+            if start < extra_cursor {
+                self.extra_cursors[index] = self.char_to_position(extra_cursor - (extra_cursor.min(end) - start));
+            }
+        }
+        for parked in self.parked_views.values_mut().flatten().filter(|parked| start < **parked) {
+            *parked -= (*parked).min(end) - start;
+        }
+        if let Some(source) = self.snippet.as_mut().and_then(|snippet| snippet.removed(start..end)) {
+            self.sync_mirrors(source);
+        }
+        Ok(())
+    }
+    
+    fn extra_cursor_indices(&self) -> Vec<usize> {
+        self.extra_cursors.iter().map(|&cursor| self.position_to_char(cursor)).collect()
+    }
+    
+    fn record(&mut self, edit: Edit) {
+        self.version += 1;
+        if self.replaying {
+            return;
+        }
+        // Mirrored snippet edits undo along with the edit they mirror.
+        if self.snippet.as_ref().is_some_and(SnippetSession::is_mirroring) {
+            self.history.attach(edit, self.cursor);
+        } else {
+            self.history.record(edit, self.cursor);
+        }
+    }
+    
+    /// Starts grouping edits into a single undo step. Calls may be nested.
+    pub fn begin_transaction(&mut self) {
+        self.history.begin(self.cursor);
+    }
+    
+    pub fn end_transaction(&mut self) {
+        self.history.end();
+    }
+    
+    /// Runs `f` with all of its edits grouped into a single undo step.
+    pub fn transaction<R, F: FnOnce(&mut Self) -> R>(&mut self, f: F) -> R {
+        self.begin_transaction();
+        let result = f(self);
+        self.end_transaction();
+        result
+    }
+    
+    fn apply_edit(&mut self, char_idx: usize, remove: &str, insert: &str) {
+        let remove_len = remove.chars().count();
+        if remove_len != 0 {
+            self.try_remove(char_idx..char_idx + remove_len).expect("History out of sync with buffer.");
+        }
+        if !insert.is_empty() {
+            self.try_insert(char_idx, insert).expect("History out of sync with buffer.");
+        }
+    }
+    
+    pub fn undo(&mut self) -> bool {
+        if !self.is_editable() {
+            return false;
+        }
+        let Some(transaction) = self.history.pop_undo() else {
+            return false;
+        };
+        self.replaying = true;
+        for edit in transaction.edits.iter().rev() {
+            self.apply_edit(edit.char_idx, &edit.inserted, &edit.removed);
+        }
+        self.replaying = false;
+        self.extra_cursors.clear();
+        self.set_cursor(transaction.cursor_before);
+        self.history.push_redo(transaction);
+        true
+    }
+    
+    pub fn redo(&mut self) -> bool {
+        if !self.is_editable() {
+            return false;
+        }
+        let Some(transaction) = self.history.pop_redo() else {
+            return false;
+        };
+        self.replaying = true;
+        for edit in &transaction.edits {
+            self.apply_edit(edit.char_idx, &edit.removed, &edit.inserted);
+        }
+        self.replaying = false;
+        self.extra_cursors.clear();
+        if let Some(last) = transaction.edits.last() {
+            self.set_cursor(self.char_to_position(last.char_idx + last.inserted.chars().count()));
+        }
+        self.history.push_undo(transaction);
+        true
+    }
+    
+    /// Every cursor in document order, including the primary one.
+    pub fn cursors(&self) -> Vec<TextPosition> {
+        let mut cursors = self.extra_cursors.clone();
+        cursors.push(self.cursor);
+        cursors.sort();
+        cursors
+    }
+    
+    /// Adds a cursor on the line above the topmost cursor or below the bottommost one,
+    /// at the primary cursor's column.
+    pub fn add_cursor(&mut self, below: bool) {
+        let cursors = self.cursors();
+        let line = if below {
+            let last = cursors[cursors.len() - 1].line;
+            Some(self.folds.next_visible(last)).filter(|&line| line < self.rope.len_lines())
+        } else {
+            self.folds.prev_visible(cursors[0].line)
+        };
+        if let Some(line) = line {
+            self.extra_cursors.push(TextPosition::new(line, self.cursor.col.min(self.line_len(line))));
+        }
+    }
+    
+    pub fn clear_extra_cursors(&mut self) {
+        self.extra_cursors.clear();
+    }
+    
+    /// Runs `f` at every cursor in document order, as a single undo step.
+    /// `f` also gets the cursor's index in that order.
+    fn at_every_cursor<F: FnMut(&mut Self, usize)>(&mut self, mut f: F) {
+        if self.extra_cursors.is_empty() {
+            f(self, 0);
+            return;
+        }
+        // 0 is the primary cursor, and `n` is `extra_cursors[n - 1]`.
+        let mut order: Vec<usize> = (0..=self.extra_cursors.len()).collect();
+        order.sort_by_key(|&slot| if slot == 0 { self.cursor } else { self.extra_cursors[slot - 1] });
+        let mut preferred_col = self.preferred_col;
+        self.transaction(|editor| {
+            for (rank, slot) in order.into_iter().enumerate() {
+                if slot == 0 {
+                    editor.preferred_col = preferred_col;
+                    f(editor, rank);
+                    preferred_col = editor.preferred_col;
+                    continue;
+                }
+                std::mem::swap(&mut editor.cursor, &mut editor.extra_cursors[slot - 1]);
+                editor.preferred_col = editor.visual_col(editor.cursor);
+                f(editor, rank);
+                std::mem::swap(&mut editor.cursor, &mut editor.extra_cursors[slot - 1]);
+            }
+        });
+        self.preferred_col = preferred_col;
+        // Cursors that ran into each other become one.
+        let cursor = self.cursor;
+        self.extra_cursors.sort();
+        self.extra_cursors.dedup();
+        self.extra_cursors.retain(|&extra_cursor| extra_cursor != cursor);
+        self.scroll_to_cursor = true;
+    }
+    
+    /// The selection, or the cursor's whole line if nothing is selected.
+    fn copy_range(&self) -> Option<Range<usize>> {
+        self.selection_range().or_else(|| {
+            let line = self.cursor.line.min(self.rope.len_lines() - 1);
+            let range = self.rope.line_to_char(line)..self.rope.line_to_char(line + 1);
+            (!range.is_empty()).then_some(range)
+        })
+    }
+    
+    /// Copies the selection, or the cursor's line if nothing is selected.
+    /// Returns `false` if there was nothing to copy.
+    pub fn copy(&mut self) -> std::io::Result<bool> {
+        let Some(range) = self.copy_range() else {
+            return Ok(false);
+        };
+        let text = self.rope.slice(range).to_string();
+        self.clipboard.borrow_mut().set_text(&text)?;
+        Ok(true)
+    }
+    
+    /// Like [`TextEditor::copy`], and then removes the copied text.
+    /// Copies instead when the buffer isn't editable.
+    pub fn cut(&mut self) -> std::io::Result<bool> {
+        if !self.is_editable() {
+            return self.copy();
+        }
+        let Some(range) = self.copy_range() else {
+            return Ok(false);
+        };
+        let text = self.rope.slice(range.clone()).to_string();
+        self.clipboard.borrow_mut().set_text(&text)?;
+        self.selection = None;
+        let start = range.start;
+        if self.try_remove(range).is_ok() {
+            self.set_cursor(self.char_to_position(start));
+        }
+        Ok(true)
+    }
+    
+    /// Pastes the clipboard with [`TextEditor::paste_text`]. Returns `false` if the clipboard was empty.
+    pub fn paste(&mut self) -> std::io::Result<bool> {
+        let text = self.clipboard.borrow_mut().get_text()?;
+        match text {
+            Some(text) => {
+                self.paste_text(&text);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+    
+    /// Replaces the selection and inserts `text` at every cursor. When there are several
+    /// cursors and `text` has one line per cursor, each cursor gets its own line.
+    pub fn paste_text(&mut self, text: &str) {
+        let lines: Vec<&str> = text.lines().collect();
+        let distribute = !self.extra_cursors.is_empty() && lines.len() == self.extra_cursors.len() + 1;
+        self.transaction(|editor| {
+            editor.delete_selection();
+            editor.at_every_cursor(|editor, rank| {
+                editor.insert_str(if distribute { lines[rank] } else { text });
+            });
+        });
+    }
+    
+    fn lines_changed(&mut self, start_line: usize, old_end_line: usize, new_end_line: usize) {
+        if let Some(ansi) = &mut self.ansi {
+            ansi.lines_changed(start_line, old_end_line, new_end_line);
+        }
+        if let Some(highlighting) = &mut self.highlighting {
+            highlighting.lines_changed(start_line, old_end_line, new_end_line);
+        }
+        self.signs.lines_changed(start_line, old_end_line, new_end_line);
+        self.diagnostics.lines_changed(start_line, old_end_line, new_end_line);
+        self.layouts.lines_changed(start_line, old_end_line, new_end_line);
+        self.folds.lines_changed(start_line, old_end_line, new_end_line);
+    }
+    
+    /// The regions that can be folded with the current [`FoldMethod`].
+    pub fn fold_regions(&mut self) -> &FoldRegions {
+        let method = match self.fold_method {
+            FoldMethod::Auto if self.highlighting.is_some() => FoldMethod::Syntax,
+            FoldMethod::Auto => FoldMethod::Indent,
+            method => method,
+        };
+        if method == FoldMethod::Off {
+            return &EMPTY_FOLD_REGIONS;
+        }
+        let is_current = matches!(&self.fold_regions, Some((version, cached, _)) if *version == self.version && *cached == method);
+        if !is_current {
+            let regions = match (method, &mut self.highlighting) {
+                (FoldMethod::Syntax, Some(highlighting)) => {
+                    highlighting.update(&self.rope, self.rope.len_lines());
+                    fold::syntax_regions(&self.rope, highlighting)
+                }
+                _ => fold::indent_regions(&self.rope),
+            };
+            self.fold_regions = Some((self.version, method, regions));
+        }
+        &self.fold_regions.as_ref().expect("Fold regions were just computed.").2
+    }
+    
+    /// The innermost foldable region that starts on or contains `line`, as (header, end).
+    pub fn fold_region_at(&mut self, line: usize) -> Option<(usize, usize)> {
+        self.fold_regions()
+            .range(..=line)
+            .rev()
+            .find(|&(_, &end)| end >= line)
+            .map(|(&header, &end)| (header, end))
+    }
+    
+    pub fn fold_marker(&mut self, line: usize) -> Option<FoldMarker> {
+        if self.folds.is_folded(line) {
+            Some(FoldMarker::Folded)
+        } else if self.fold_regions().contains_key(&line) {
+            Some(FoldMarker::Open)
+        } else {
+            None
+        }
+    }
+    
+    /// Folds the region at the cursor, or unfolds it if the cursor line is a folded header.
+    pub fn toggle_fold(&mut self) {
+        let line = self.cursor.line;
+        if self.folds.unfold(line) {
+            return;
+        }
+        if let Some((header, end)) = self.fold_region_at(line) {
+            self.folds.fold(header, end);
+            if header != line {
+                self.set_cursor(TextPosition::new(header, self.cursor.col));
+            }
+        }
+    }
+    
+    pub fn fold_all(&mut self) {
+        let regions: Vec<(usize, usize)> = self.fold_regions().iter().map(|(&header, &end)| (header, end)).collect();
+        // Outer regions come first, so nested regions are skipped as hidden.
+        for (header, end) in regions {
+            self.folds.fold(header, end);
+        }
+        let header = self.folds.visible_line(self.cursor.line);
+        if header != self.cursor.line {
+            self.set_cursor(TextPosition::new(header, self.cursor.col));
         }
     }
+    
+    pub fn unfold_all(&mut self) {
+        self.folds.clear();
+        self.scroll_to_cursor = true;
+    }
+    
+    fn apply_scroll_to_cursor(&mut self, width: usize, height: usize) {
+        if !std::mem::take(&mut self.scroll_to_cursor) || width == 0 || height == 0 {
+            return;
+        }
+        if self.soft_wrap.is_some() {
+            self.scroll_wrapped_to_cursor(height);
+            return;
+        }
+        if self.cursor.line < self.start_line {
+            self.start_line = self.cursor.line;
+        } else if self.cursor.line - self.folds.hidden_in(self.start_line..self.cursor.line) >= self.start_line + height {
+            let mut top = self.cursor.line;
+            for _ in 1..height {
+                top = self.folds.prev_visible(top).unwrap_or(top);
+            }
+            self.start_line = top;
+        }
+        if self.cursor.col < self.start_col {
+            self.start_col = self.cursor.col;
+        } else if self.cursor.col >= self.start_col + width {
+            self.start_col = self.cursor.col + 1 - width;
+        }
+    }
+    
+    fn scroll_wrapped_to_cursor(&mut self, height: usize) {
+        let (cursor_row, _) = self.layouts.row_of(&self.rope, self.cursor.line, self.cursor.col);
+        let start = (self.start_line, self.start_row);
+        let mut top = (self.cursor.line, cursor_row);
+        if top < start {
+            (self.start_line, self.start_row) = top;
+            return;
+        }
+        // Walk back from the cursor row; if the current viewport start is reached, the cursor is visible.
+        for _ in 1..height {
+            if top == start {
+                return;
+            }
+            top = match top {
+                (line, row) if row != 0 => (line, row - 1),
+                (line, _) => match self.folds.prev_visible(line) {
+                    Some(prev) => (prev, self.layouts.row_count(&self.rope, prev) - 1),
+                    None => break,
+                },
+            };
+        }
+        if top > start {
+            (self.start_line, self.start_row) = top;
+        }
+    }
+    
+    /// The screen row after `(line, row)`, skipping folded lines. Rows are only wrapped
+    /// rows when soft wrap is on; otherwise every line is one row.
+    fn next_row(&mut self, (line, row): (usize, usize)) -> Option<(usize, usize)> {
+        if self.soft_wrap.is_some() && row + 1 < self.layouts.row_count(&self.rope, line) {
+            return Some((line, row + 1));
+        }
+        let next = self.folds.next_visible(line);
+        (next < self.rope.len_lines()).then_some((next, 0))
+    }
+    
+    fn prev_row(&mut self, (line, row): (usize, usize)) -> Option<(usize, usize)> {
+        if row != 0 {
+            return Some((line, row - 1));
+        }
+        let prev = self.folds.prev_visible(line)?;
+        let rows = if self.soft_wrap.is_some() { self.layouts.row_count(&self.rope, prev) } else { 1 };
+        Some((prev, rows - 1))
+    }
+    
+    /// The furthest the view can scroll down, as a line and wrapped row: the one that puts
+    /// the end of the buffer at the bottom of the text area.
+    pub fn max_scroll(&mut self) -> (usize, usize) {
+        let last = self.folds.visible_line(self.rope.len_lines() - 1);
+        let rows = if self.soft_wrap.is_some() { self.layouts.row_count(&self.rope, last) } else { 1 };
+        let mut top = (last, rows - 1);
+        for _ in 1..self.text_area.height.max(1) {
+            match self.prev_row(top) {
+                Some(prev) => top = prev,
+                None => break,
+            }
+        }
+        top
+    }
+    
+    /// Scrolls the view by screen rows without moving the cursor, stopping at either end of the buffer.
+    pub fn scroll_by(&mut self, rows: isize) {
+        let mut top = (self.start_line, self.start_row);
+        let max = self.max_scroll();
+        for _ in 0..rows.unsigned_abs() {
+            let next = if rows > 0 && top < max { self.next_row(top) } else if rows < 0 { self.prev_row(top) } else { None };
+            match next {
+                Some(next) => top = next,
+                None => break,
+            }
+        }
+        (self.start_line, self.start_row) = top.min(max);
+    }
+    
+    /// The length of the longest line in view, which is as far as the view scrolls sideways.
+    pub fn content_width(&self) -> usize {
+        let mut line = self.start_line;
+        let mut width = 0;
+        for _ in 0..self.text_area.height {
+            if line >= self.rope.len_lines() {
+                break;
+            }
+            width = width.max(self.line_len(line));
+            line = self.folds.next_visible(line);
+        }
+        width
+    }
+    
+    /// The furthest the view can scroll right: far enough to see past the end of the longest line in view.
+    pub fn max_start_col(&self) -> usize {
+        (self.content_width() + 1).saturating_sub(self.text_area.width as usize)
+    }
+    
+    /// Scrolls the view sideways without moving the cursor. Wrapped views don't scroll sideways.
+    pub fn scroll_cols_by(&mut self, cols: isize) {
+        if self.soft_wrap.is_some() {
+            return;
+        }
+        self.start_col = self.start_col.saturating_add_signed(cols).min(self.max_start_col());
+    }
 }
 
-pub struct TextEdit;
+static EMPTY_FOLD_REGIONS: FoldRegions = FoldRegions::new();
+
+/// Length of a line in chars, excluding the line ending.
+pub(crate) fn rope_line_len(rope: &Rope, line: usize) -> usize {
+    if line >= rope.len_lines() {
+        return 0;
+    }
+    let line_slice = rope.line(line);
+    let mut line_len = line_slice.len_chars();
+    while line_len != 0 && matches!(line_slice.char(line_len - 1), '\n' | '\r') {
+        line_len -= 1;
+    }
+    line_len
+}
 
-const RAINBOW_INDENT_COLORS: [Color; 6] = [
-    Color::Rgb(68, 17, 10),
-    Color::Rgb(70, 34, 6),
-    Color::Rgb(69, 58, 2),
-    Color::Rgb(7, 40, 24),
-    Color::Rgb(16, 30, 51),
-    Color::Rgb(26, 14, 45)
-];
+impl Default for TextEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-impl StatefulWidget for TextEdit {
+#[derive(Clone)]
+pub struct TextEdit<'a> {
+    syntax_theme: &'a SyntaxTheme,
+    gutter: Option<Gutter>,
+    minimap: Option<Minimap>,
+    scrollbars: Option<Scrollbars>,
+    diagnostics: Option<InlineDiagnostics>,
+    style: Style,
+    current_line_style: Option<Style>,
+    match_style: Style,
+    current_match_style: Style,
+    fold_placeholder_style: Style,
+    selection_style: Style,
+    extra_cursor_style: Style,
+    matching_bracket_style: Style,
+    wrap_indicator_style: Style,
+    whitespace: Whitespace,
+    /// Styles of the [`LoadingView`] shown while a large file loads, in place of the text.
+    loading_styles: [Style; 3],
+}
+
+impl<'a> TextEdit<'a> {
+    pub fn new() -> Self {
+        Self {
+            syntax_theme: &DEFAULT_SYNTAX_THEME,
+            gutter: None,
+            minimap: None,
+            scrollbars: None,
+            diagnostics: None,
+            style: Style::new(),
+            current_line_style: None,
+            match_style: Style::new().bg(Color::Rgb(81, 71, 34)),
+            current_match_style: Style::new().fg(Color::Black).bg(Color::Rgb(229, 192, 123)),
+            fold_placeholder_style: Style::new().fg(Color::DarkGray).bg(Color::Rgb(44, 49, 58)),
+            selection_style: Style::new().bg(Color::Rgb(62, 68, 81)),
+            extra_cursor_style: Style::new().add_modifier(Modifier::REVERSED),
+            matching_bracket_style: Style::new().bg(Color::Rgb(62, 68, 81)).add_modifier(Modifier::BOLD),
+            wrap_indicator_style: Style::new().fg(Color::DarkGray),
+            whitespace: Whitespace::default(),
+            loading_styles: [
+                Style::new().fg(Color::Gray),
+                Style::new().fg(Color::Rgb(97, 175, 239)),
+                Style::new().fg(Color::DarkGray),
+            ],
+        }
+    }
+    
+    /// Takes every style from the theme, including those of the gutter, minimap, scrollbars,
+    /// diagnostics and whitespace if they were set before this.
+    pub fn theme(mut self, theme: &'a Theme) -> Self {
+        self.syntax_theme = theme.syntax();
+        self.gutter = self.gutter.map(|gutter| gutter.theme(theme));
+        self.minimap = self.minimap.map(|minimap| minimap.theme(theme));
+        self.scrollbars = self.scrollbars.map(|scrollbars| scrollbars.theme(theme));
+        self.diagnostics = self.diagnostics.map(|diagnostics| diagnostics.theme(theme));
+        self.style = theme.style("editor");
+        self.current_line_style = Some(theme.style("editor.current_line"));
+        self.match_style = theme.style("editor.match");
+        self.current_match_style = theme.style("editor.current_match");
+        self.fold_placeholder_style = theme.style("editor.fold_placeholder");
+        self.selection_style = theme.style("editor.selection");
+        self.extra_cursor_style = theme.style("editor.extra_cursor");
+        self.matching_bracket_style = theme.style("editor.matching_bracket");
+        self.wrap_indicator_style = theme.style("editor.wrap_indicator");
+        self.whitespace = self.whitespace.theme(theme);
+        self.loading_styles = ["loading", "loading.gauge", "loading.gauge_unfilled"].map(|key| theme.style(key));
+        self
+    }
+    
+    /// Base style of the text area and gutter, under everything else.
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn syntax_theme(mut self, theme: &'a SyntaxTheme) -> Self {
+        self.syntax_theme = theme;
+        self
+    }
+    
+    pub fn gutter(mut self, gutter: Gutter) -> Self {
+        self.gutter = Some(gutter);
+        self
+    }
+    
+    pub fn minimap(mut self, minimap: Minimap) -> Self {
+        self.minimap = Some(minimap);
+        self
+    }
+    
+    pub fn scrollbars(mut self, scrollbars: Scrollbars) -> Self {
+        self.scrollbars = Some(scrollbars);
+        self
+    }
+    
+    /// Underlines [`TextEditor::diagnostics`] and shows their messages after the lines.
+    /// Their signs show in the gutter either way.
+    pub fn diagnostics(mut self, diagnostics: InlineDiagnostics) -> Self {
+        self.diagnostics = Some(diagnostics);
+        self
+    }
+    
+    pub fn current_line_style(mut self, style: Style) -> Self {
+        self.current_line_style = Some(style);
+        self
+    }
+    
+    /// Style patched onto search matches.
+    pub fn match_style(mut self, style: Style) -> Self {
+        self.match_style = style;
+        self
+    }
+    
+    /// Style patched onto the selected search match.
+    pub fn current_match_style(mut self, style: Style) -> Self {
+        self.current_match_style = style;
+        self
+    }
+    
+    pub fn selection_style(mut self, style: Style) -> Self {
+        self.selection_style = style;
+        self
+    }
+    
+    /// Style patched onto the cells under [`TextEditor::extra_cursors`]. The terminal draws the primary cursor.
+    pub fn extra_cursor_style(mut self, style: Style) -> Self {
+        self.extra_cursor_style = style;
+        self
+    }
+    
+    /// Style patched onto the bracket at the cursor and the one that matches it.
+    pub fn matching_bracket_style(mut self, style: Style) -> Self {
+        self.matching_bracket_style = style;
+        self
+    }
+    
+    /// How spaces, tabs, line endings and indentation are drawn.
+    pub fn whitespace(mut self, whitespace: Whitespace) -> Self {
+        self.whitespace = whitespace;
+        self
+    }
+    
+    /// Style of the marker drawn after the header line of a fold.
+    pub fn fold_placeholder_style(mut self, style: Style) -> Self {
+        self.fold_placeholder_style = style;
+        self
+    }
+    
+    /// Style of the indicator at the start of wrapped rows.
+    pub fn wrap_indicator_style(mut self, style: Style) -> Self {
+        self.wrap_indicator_style = style;
+        self
+    }
+}
+
+impl Default for TextEdit<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextEdit<'_> {
+    /// Draws the columns `cols` of a line starting at `x`, stopping at `right`.
+    #[allow(clippy::too_many_arguments)]
+    fn render_segment(
+        &self,
+        buf: &mut Buffer,
+        line: RopeSlice,
+        tokens: &[Token],
+        ansi_spans: &[AnsiSpan],
+        whitespace: &LineWhitespace,
+        line_start: usize,
+        matches: &[Range<usize>],
+        current_match: Option<&Range<usize>>,
+        selection: Option<&Range<usize>>,
+        cols: Range<usize>,
+        x: u16,
+        y: u16,
+        right: u16,
+    ) {
+        let first_col = cols.start;
+        let mut token_iter = tokens.iter().skip_while(|token| token.end <= first_col).peekable();
+        let mut ansi_iter = ansi_spans.iter().skip_while(|span| span.end <= first_col).peekable();
+        let mut match_iter = matches.iter().skip_while(|found| found.end <= line_start + first_col).peekable();
+        for (line_col, x) in cols.zip(x..right) {
+            while token_iter.next_if(|token| token.end <= line_col).is_some() {}
+            while ansi_iter.next_if(|span| span.end <= line_col).is_some() {}
+            let char_idx = line_start + line_col;
+            while match_iter.next_if(|found| found.end <= char_idx).is_some() {}
+            let match_style = match_iter.peek()
+                .filter(|found| found.start <= char_idx)
+                .map(|&found| if Some(found) == current_match {
+                    self.current_match_style
+                } else {
+                    self.match_style
+                });
+            let mut token_style = token_iter.peek()
+                .filter(|token| token.start <= line_col)
+                .map(|token| self.syntax_theme.style(token.kind))
+                .unwrap_or_default();
+            if let Some(span) = ansi_iter.peek().filter(|span| span.start <= line_col) {
+                token_style = token_style.patch(span.style);
+            }
+            let Some(cell) = buf.cell_mut((x, y)) else {
+                break;
+            };
+            match line.char(line_col) {
+                '\n' | '\r' => break,
+                chr if let Some((glyph, style)) = self.whitespace.cell(whitespace, line_col, chr) => {
+                    if let Some(bg) = token_style.bg {
+                        cell.set_bg(bg);
+                    }
+                    cell.set_char(glyph)
+                        .set_style(style);
+                }
+                c => {
+                    cell.set_char(c)
+                        .set_style(token_style);
+                }
+            }
+            if selection.is_some_and(|selection| selection.contains(&char_idx)) {
+                cell.set_style(self.selection_style);
+            }
+            if let Some(style) = match_style {
+                cell.set_style(style);
+            }
+        }
+    }
+}
+
+impl StatefulWidget for TextEdit<'_> {
     type State = TextEditor;
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        for (iy, y) in (area.y..area.bottom()).enumerate() {
-            let line_index = state.start_line + iy;
-            if line_index >= state.rope.len_lines() {
+        state.area = area;
+        state.vertical_scrollbar = None;
+        state.horizontal_scrollbar = None;
+        state.minimap = None;
+        if let Some(loader) = &state.loader {
+            state.text_area = area;
+            state.cursor_screen_position = None;
+            let [style, gauge_style, unfilled_style] = self.loading_styles;
+            buf.set_style(area, self.style);
+            LoadingView::new(loader)
+                .style(style)
+                .gauge_style(gauge_style)
+                .unfilled_style(unfilled_style)
+                .render(area, buf);
+            return;
+        }
+        buf.set_style(area, self.style);
+        let mut area = area;
+        let scrollbars = self.scrollbars.as_ref();
+        if scrollbars.is_some_and(|scrollbars| scrollbars.vertical) && area.width > 1 {
+            area.width -= 1;
+            state.vertical_scrollbar = Some(Rect::new(area.right(), area.y, 1, area.height));
+        }
+        let minimap = self.minimap.as_ref()
+            .filter(|minimap| minimap.width != 0 && area.width >= minimap.width.saturating_mul(4))
+            .map(|minimap| {
+                area.width -= minimap.width;
+                (minimap, Rect::new(area.right(), area.y, minimap.width, area.height))
+            });
+        let line_count = state.rope.len_lines();
+        let gutter_width = self.gutter.as_ref()
+            .map(|gutter| gutter.width(line_count).min(area.width))
+            .unwrap_or(0);
+        let mut text_area = Rect {
+            x: area.x + gutter_width,
+            width: area.width - gutter_width,
+            ..area
+        };
+        state.text_area = text_area;
+        if scrollbars.is_some_and(|scrollbars| scrollbars.horizontal)
+            && state.soft_wrap.is_none()
+            && area.height > 1
+            && state.content_width() >= text_area.width as usize {
+            area.height -= 1;
+            text_area.height -= 1;
+            state.text_area = text_area;
+            state.horizontal_scrollbar = Some(Rect::new(text_area.x, area.bottom(), text_area.width, 1));
+        }
+        match state.soft_wrap {
+            Some(wrap) => {
+                state.layouts.reset(wrap, text_area.width as usize);
+                state.start_col = 0;
+            }
+            None => state.layouts = LineLayouts::default(),
+        }
+        state.start_line = state.folds.visible_line(state.start_line.min(line_count - 1));
+        state.start_row = match state.soft_wrap {
+            Some(_) => state.start_row.min(state.layouts.row_count(&state.rope, state.start_line) - 1),
+            None => 0,
+        };
+        // Scrolling stops with the end of the buffer at the bottom of the view.
+        let max_scroll = state.max_scroll();
+        if (state.start_line, state.start_row) > max_scroll {
+            (state.start_line, state.start_row) = max_scroll;
+        }
+        state.apply_scroll_to_cursor(text_area.width as usize, text_area.height as usize);
+        state.start_col = state.start_col.min(state.max_start_col());
+        // Folded lines don't take up rows, so walk the visible lines to find the last one.
+        let mut last_visible = state.start_line;
+        for _ in 0..area.height {
+            if last_visible >= line_count {
                 break;
             }
+            last_visible = state.folds.next_visible(last_visible);
+        }
+        let last_visible = last_visible.min(line_count);
+        state.viewport_at_end = last_visible >= line_count;
+        if let Some(highlighting) = &mut state.highlighting {
+            highlighting.update(&state.rope, last_visible);
+        }
+        let matches = match &state.search {
+            Some(search) => {
+                let visible_start = state.rope.line_to_char(state.start_line);
+                let visible_end = state.rope.line_to_char(last_visible);
+                search.find_in_range(&state.rope, visible_start..visible_end)
+            }
+            None => Vec::new(),
+        };
+        let selection = state.selection_range();
+        let brackets = state.bracket_pair()
+            .map(|(at, matching)| [state.char_to_position(at), state.char_to_position(matching)]);
+        state.cursor_screen_position = None;
+        let mut line_index = state.start_line;
+        let mut row = state.start_row;
+        for y in area.y..area.bottom() {
+            let visible_line = (line_index < line_count).then_some(line_index);
+            if let Some(gutter) = &self.gutter {
+                let gutter_area = Rect::new(area.x, y, gutter_width, 1);
+                let gutter_line = visible_line.filter(|_| row == 0);
+                let fold_marker = match gutter_line {
+                    Some(line) if gutter.fold_column => state.fold_marker(line),
+                    _ => None,
+                };
+                gutter.render_line(gutter_area, buf, gutter_line, state.cursor.line, line_count, &state.signs, fold_marker);
+            }
+            if visible_line.is_none() {
+                continue;
+            }
+            if line_index == state.cursor.line && let Some(style) = self.current_line_style {
+                buf.set_style(Rect::new(text_area.x, y, text_area.width, 1), style);
+            }
+            let (cols, x, is_last_row) = match state.soft_wrap {
+                Some(wrap) => {
+                    let (row_start, row_end) = state.layouts.row_range(&state.rope, line_index, row);
+                    let is_last_row = row + 1 == state.layouts.row_count(&state.rope, line_index);
+                    let mut x = text_area.x;
+                    if let Some(indicator) = wrap.indicator.filter(|_| row != 0 && text_area.width != 0) {
+                        buf[(x, y)].set_char(indicator).set_style(self.wrap_indicator_style);
+                        x += wrap.indicator_width() as u16;
+                    }
+                    (row_start..row_end, x, is_last_row)
+                }
+                None => {
+                    let line_len = state.line_len(line_index);
+                    let end = (state.start_col + text_area.width as usize).min(line_len);
+                    (state.start_col.min(end)..end, text_area.x, true)
+                }
+            };
+            if line_index == state.cursor.line
+                && cols.start <= state.cursor.col
+                && (state.cursor.col < cols.end || is_last_row) {
+                let cursor_x = x as usize + (state.cursor.col - cols.start);
+                if cursor_x < text_area.right() as usize {
+                    state.cursor_screen_position = Some(Position::new(cursor_x as u16, y));
+                } else if state.soft_wrap.is_some() && text_area.width != 0 {
+                    // A cursor at the end of a full row is drawn on its last cell.
+                    state.cursor_screen_position = Some(Position::new(text_area.right() - 1, y));
+                }
+            }
             //               //             
             let line = state.rope.line(line_index);
-            if state.start_col >= line.len_chars() {
-                continue;
+            let tokens = state.highlighting.as_ref()
+                .map(|highlighting| highlighting.line_tokens(line_index))
+                .unwrap_or(&[]);
+            let ansi_spans = state.ansi.as_ref()
+                .map(|ansi| ansi.line_spans(line_index))
+                .unwrap_or(&[]);
+            let line_len = state.line_len(line_index);
+            let mut whitespace = LineWhitespace::new(line, line_len);
+            // Trailing whitespace that's being typed isn't an error yet.
+            whitespace.flag_trailing = self.whitespace.highlight_trailing
+                && !(line_index == state.cursor.line && state.cursor.col >= whitespace.trailing_start);
+            let line_start = state.rope.line_to_char(line_index);
+            let placeholder_x = x as usize + cols.len() + 1;
+            self.render_segment(
+                buf,
+                line,
+                tokens,
+                ansi_spans,
+                &whitespace,
+                line_start,
+                &matches,
+                state.current_match.as_ref(),
+                selection.as_ref(),
+                cols.clone(),
+                x,
+                y,
+                text_area.right(),
+            );
+            let show_line_ending = matches!(self.whitespace.show, ShowWhitespace::Trailing | ShowWhitespace::All);
+            if whitespace.carriage_return && show_line_ending && is_last_row && cols.end == line_len {
+                let cr_x = x as usize + cols.len();
+                if cr_x < text_area.right() as usize {
+                    buf[(cr_x as u16, y)].set_char(self.whitespace.carriage_return).set_style(self.whitespace.style);
+                }
             }
-            let line_end = line.slice(state.start_col..);
-            let line_len = line_end.len_chars();
-            let mut indent = true;
-            'inner: for (ix, x) in (area.x..area.right()).enumerate() {
-                let char_index = ix;
-                if char_index >= line_len {
-                    break 'inner;
+            for bracket in brackets.iter().flatten().filter(|bracket| bracket.line == line_index) {
+                if cols.contains(&bracket.col) {
+                    let bracket_x = x as usize + (bracket.col - cols.start);
+                    if bracket_x < text_area.right() as usize {
+                        buf.set_style(Rect::new(bracket_x as u16, y, 1, 1), self.matching_bracket_style);
+                    }
                 }
-                // ·
-                const SPACE_CHAR: char = '·';
-                const GROUP_CHAR: char = '┆';
-                if let Some(cell) = buf.cell_mut((x, y)) {
-                    match line_end.char(char_index) {
-                        '\n' => continue 'inner,
-                        ' ' => {
-                            if indent {
-                                let indent_idx = char_index / 4;
-                                let indent_color = RAINBOW_INDENT_COLORS[indent_idx % RAINBOW_INDENT_COLORS.len()];
-                                let cell_char = if char_index.is_multiple_of(4) {
-                                    GROUP_CHAR
-                                } else {
-                                    SPACE_CHAR
-                                };
-                                cell.set_char(cell_char)
-                                    .set_bg(indent_color)
-                                    .set_fg(Color::DarkGray);
-                            } else {
-                                cell.set_char(SPACE_CHAR)
-                                    .set_fg(Color::DarkGray);
-                            }
-                        }
-                        c => {
-                            indent = false;
-                            cell.set_char(c);
-                        }
+            }
+            for extra_cursor in state.extra_cursors.iter().filter(|cursor| cursor.line == line_index) {
+                if cols.start <= extra_cursor.col && (extra_cursor.col < cols.end || is_last_row) {
+                    let cursor_x = x as usize + (extra_cursor.col - cols.start);
+                    if cursor_x < text_area.right() as usize {
+                        buf.set_style(Rect::new(cursor_x as u16, y, 1, 1), self.extra_cursor_style);
                     }
                 }
             }
+            let is_folded = state.folds.is_folded(line_index);
+            if is_last_row && is_folded && placeholder_x < text_area.right() as usize {
+                let width = text_area.right() as usize - placeholder_x;
+                buf.set_stringn(placeholder_x as u16, y, "⋯", width, self.fold_placeholder_style);
+            }
+            if let Some(diagnostics) = &self.diagnostics && !state.diagnostics.is_empty() {
+                // Messages go after the fold placeholder, if there is one.
+                let message_x = is_last_row.then_some(placeholder_x + if is_folded { 2 } else { 0 });
+                diagnostics.render_row(buf, &state.diagnostics, line_index, line_len, cols.clone(), x, y, text_area.right(), message_x);
+            }
+            if state.soft_wrap.is_some() && !is_last_row {
+                row += 1;
+            } else {
+                line_index = state.folds.next_visible(line_index);
+                row = 0;
+            }
+        }
+        if let Some((minimap, minimap_area)) = minimap {
+            minimap.render(minimap_area, buf, state, self.syntax_theme, last_visible);
+        }
+        if let Some(scrollbars) = scrollbars {
+            if let Some(bar) = state.vertical_scrollbar {
+                scrollbars.render(bar, buf, state.vertical_scroll_metrics());
+            }
+            if let Some(bar) = state.horizontal_scrollbar {
+                scrollbars.render(bar, buf, state.horizontal_scroll_metrics());
+            }
         }
     }
-}
\ No newline at end of file
+}
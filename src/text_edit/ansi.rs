@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use ratatui::style::{Color, Modifier, Style};
+
+use super::TextPosition;
+
+/// A colored span of a line, in char offsets from the start of the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnsiSpan {
+    pub start: usize,
+    pub end: usize,
+    pub style: Style,
+}
+
+/// Colors from ANSI escape sequences in appended text.
+///
+/// The escapes are removed from the text and their colors kept here by line, so the
+/// buffer only holds what's visible. Colors are interpreted one line at a time: every
+/// line starts out unstyled, as most programs that color their logs reset at the end
+/// of each line anyway. Escapes other than SGR (`ESC [ ... m`) are dropped.
+#[derive(Debug, Clone, Default)]
+pub struct AnsiColors {
+    lines: BTreeMap<usize, Vec<AnsiSpan>>,
+    /// The style at the end of the appended text.
+    style: Style,
+    /// The start of an escape sequence that was split between appends.
+    pending: String,
+}
+
+impl AnsiColors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn line_spans(&self, line: usize) -> &[AnsiSpan] {
+        self.lines.get(&line).map(Vec::as_slice).unwrap_or(&[])
+    }
+    
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+    
+    /// Edited lines lose their colors, and lines after them move.
+    pub fn lines_changed(&mut self, start_line: usize, old_end_line: usize, new_end_line: usize) {
+        let moved = self.lines.split_off(&start_line);
+        self.lines.extend(moved.into_iter()
+            .filter(|&(line, _)| line > old_end_line)
+            .map(|(line, spans)| (line + new_end_line - old_end_line, spans)));
+    }
+    
+    /// Removes escape sequences from `text`, which is about to be appended at `end`,
+    /// and records its colors.
+    pub fn strip(&mut self, text: &str, end: TextPosition) -> String {
+        let mut input = std::mem::take(&mut self.pending);
+        input.push_str(text);
+        let mut plain = String::with_capacity(input.len());
+        let TextPosition { mut line, mut col } = end;
+        let mut rest = input.as_str();
+        while let Some(chr) = rest.chars().next() {
+            if chr == '\x1b' {
+                match escape_len(rest) {
+                    Some(len) => {
+                        if let Some(params) = rest[..len].strip_prefix("\x1b[").and_then(|csi| csi.strip_suffix('m')) {
+                            self.style = apply_sgr(self.style, params);
+                        }
+                        rest = &rest[len..];
+                    }
+                    None if rest.len() < MAX_ESCAPE_LEN => {
+                        self.pending.push_str(rest);
+                        break;
+                    }
+                    // Never terminated, so drop the escape and show the rest.
+                    None => rest = &rest[1..],
+                }
+                continue;
+            }
+            rest = &rest[chr.len_utf8()..];
+            plain.push(chr);
+            if chr == '\n' {
+                line += 1;
+                col = 0;
+                self.style = Style::default();
+                continue;
+            }
+            if self.style != Style::default() && chr != '\r' {
+                let spans = self.lines.entry(line).or_default();
+                match spans.last_mut() {
+                    Some(span) if span.end == col && span.style == self.style => span.end += 1,
+                    _ => spans.push(AnsiSpan {
+                        start: col,
+                        end: col + 1,
+                        style: self.style,
+                    }),
+                }
+            }
+            col += 1;
+        }
+        plain
+    }
+}
+
+/// Escapes longer than this that haven't ended by the end of an append are treated as garbage.
+const MAX_ESCAPE_LEN: usize = 256;
+
+/// Length in bytes of the escape sequence at the start of `text`, or `None` if it's cut off.
+fn escape_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    match bytes.get(1)? {
+        // CSI: parameter and intermediate bytes, then a final byte in `@..=~`.
+        b'[' => bytes[2..].iter()
+            .position(|byte| (0x40..=0x7E).contains(byte))
+            .map(|index| index + 3),
+        // OSC, ending with BEL or ST (`ESC \`).
+        b']' => bytes[2..].iter().enumerate().find_map(|(index, &byte)| match byte {
+            0x07 => Some(index + 3),
+            0x1B if bytes.get(index + 3) == Some(&b'\\') => Some(index + 4),
+            _ => None,
+        }),
+        _ => Some(1 + text[1..].chars().next()?.len_utf8()),
+    }
+}
+
+fn apply_sgr(mut style: Style, params: &str) -> Style {
+    // `ESC [ m` resets like `ESC [ 0 m`.
+    if params.is_empty() {
+        return Style::default();
+    }
+    let mut codes = params.split([';', ':']).map(|code| code.parse::<u8>().unwrap_or(0));
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            6 => style.add_modifier(Modifier::RAPID_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            8 => style.add_modifier(Modifier::HIDDEN),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            25 => style.remove_modifier(Modifier::SLOW_BLINK | Modifier::RAPID_BLINK),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            28 => style.remove_modifier(Modifier::HIDDEN),
+            29 => style.remove_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(basic_color(code - 30)),
+            38 => match extended_color(&mut codes) {
+                Some(color) => style.fg(color),
+                None => style,
+            },
+            39 => Style { fg: None, ..style },
+            40..=47 => style.bg(basic_color(code - 40)),
+            48 => match extended_color(&mut codes) {
+                Some(color) => style.bg(color),
+                None => style,
+            },
+            49 => Style { bg: None, ..style },
+            90..=97 => style.fg(bright_color(code - 90)),
+            100..=107 => style.bg(bright_color(code - 100)),
+            _ => style,
+        };
+    }
+    style
+}
+
+/// The rest of a `38` or `48` code: `5;n` for the 256-color palette or `2;r;g;b`.
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match codes.next()? {
+        5 => Some(Color::Indexed(codes.next()?)),
+        2 => Some(Color::Rgb(codes.next()?, codes.next()?, codes.next()?)),
+        _ => None,
+    }
+}
+
+fn basic_color(index: u8) -> Color {
+    [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::Gray,
+    ][index as usize]
+}
+
+fn bright_color(index: u8) -> Color {
+    [
+        Color::DarkGray,
+        Color::LightRed,
+        Color::LightGreen,
+        Color::LightYellow,
+        Color::LightBlue,
+        Color::LightMagenta,
+        Color::LightCyan,
+        Color::White,
+    ][index as usize]
+}
@@ -0,0 +1,163 @@
+use super::{highlight::TokenKind, TextEditor, TextPosition};
+
+/// Pairs that bracket matching and jumping work with.
+pub const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Brackets and quotes that close themselves when typed, by default.
+/// `'` is left out because it's also Rust's lifetime sigil.
+pub const DEFAULT_AUTO_PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+
+/// How far to look for a matching bracket, in chars, so that a stray bracket in a huge
+/// buffer doesn't stall every render.
+const MAX_BRACKET_SCAN: usize = 100_000;
+
+impl TextEditor {
+    /// Whether the char is inside a string or comment, going by the highlighter.
+    /// Always `false` without one.
+    fn in_literal(&mut self, char_idx: usize) -> bool {
+        let Some(highlighting) = &mut self.highlighting else {
+            return false;
+        };
+        let line = self.rope.char_to_line(char_idx);
+        let col = char_idx - self.rope.line_to_char(line);
+        highlighting.update(&self.rope, line + 1);
+        highlighting.line_tokens(line).iter().any(|token| {
+            token.start <= col && col < token.end
+                && matches!(token.kind, TokenKind::String | TokenKind::Escape | TokenKind::Comment | TokenKind::Code)
+        })
+    }
+    
+    /// The bracket that pairs with the one at `char_idx`. Brackets in strings and comments
+    /// are skipped, unless the one at `char_idx` is in one too.
+    pub fn matching_bracket(&mut self, char_idx: usize) -> Option<usize> {
+        let chr = self.rope.get_char(char_idx)?;
+        let (open, close) = BRACKETS.into_iter().find(|&(open, close)| chr == open || chr == close)?;
+        let forward = chr == open;
+        let skip_literals = !self.in_literal(char_idx);
+        let mut depth = 0usize;
+        // Ropes share their chunks, so the clone is cheap and leaves `self` free for `in_literal`.
+        let rope = self.rope.clone();
+        let mut chars = rope.chars_at(if forward { char_idx + 1 } else { char_idx });
+        for step in 1..=MAX_BRACKET_SCAN {
+            let next = if forward { chars.next() } else { chars.prev() }?;
+            if next != open && next != close {
+                continue;
+            }
+            let i = if forward { char_idx + step } else { char_idx - step };
+            if skip_literals && self.in_literal(i) {
+                continue;
+            }
+            if next == chr {
+                depth += 1;
+            } else if depth == 0 {
+                return Some(i);
+            } else {
+                depth -= 1;
+            }
+        }
+        None
+    }
+    
+    /// The bracket at the cursor, or else just before it, with its match.
+    pub fn bracket_pair(&mut self) -> Option<(usize, usize)> {
+        let cursor = self.cursor_char_index();
+        let before = (self.cursor.col != 0).then(|| cursor - 1);
+        [Some(cursor), before].into_iter()
+            .flatten()
+            .find_map(|char_idx| Some((char_idx, self.matching_bracket(char_idx)?)))
+    }
+    
+    /// Moves the cursor to the bracket that matches the one at or just before it.
+    pub fn jump_to_bracket(&mut self) -> bool {
+        match self.bracket_pair() {
+            Some((_, matching)) => {
+                self.set_cursor(self.char_to_position(matching));
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Inserts a char as if typed. Openers from [`TextEditor::auto_pairs`] get their closer,
+    /// and typing a closer that's already next to the cursor moves over it instead.
+    pub fn type_char(&mut self, chr: char) {
+        if !self.is_editable() || self.auto_pairs.is_empty() {
+            self.insert_char(chr);
+            return;
+        }
+        let cursor = self.cursor_char_index();
+        let line_len = self.line_len(self.cursor.line);
+        let next = (self.cursor.col < line_len).then(|| self.rope.char(cursor));
+        let prev = (self.cursor.col != 0).then(|| self.rope.char(cursor - 1));
+        let is_closer = self.auto_pairs.iter().any(|&(_, close)| close == chr);
+        if is_closer && next == Some(chr) {
+            self.move_right();
+            return;
+        }
+        let pair = self.auto_pairs.iter().find(|&&(open, _)| open == chr).copied();
+        // Only pair before whitespace or a closer, so that typing in front of a word works as usual.
+        let next_allows = next.is_none_or(|next| next.is_whitespace() || self.auto_pairs.iter().any(|&(_, close)| close == next));
+        // Quotes don't pair inside words, as in `don't`.
+        let prev_allows = pair.is_none_or(|(open, close)| open != close || prev.is_none_or(|prev| !prev.is_alphanumeric()));
+        match pair {
+            Some((open, close)) if next_allows && prev_allows => {
+                self.transaction(|editor| {
+                    editor.insert_str(&format!("{open}{close}"));
+                    editor.move_left();
+                });
+            }
+            _ => self.insert_char(chr),
+        }
+    }
+    
+    /// Whether the cursor is between an empty auto pair, like `(|)`.
+    pub(crate) fn in_empty_pair(&self) -> bool {
+        let cursor = self.cursor_char_index();
+        if self.cursor.col == 0 || self.cursor.col >= self.line_len(self.cursor.line) {
+            return false;
+        }
+        let (prev, next) = (self.rope.char(cursor - 1), self.rope.char(cursor));
+        self.auto_pairs.contains(&(prev, next))
+    }
+    
+    /// The leading whitespace of a line, up to `max_col`.
+    pub fn line_indent(&self, line: usize, max_col: usize) -> String {
+        self.rope.line(line).chars()
+            .take(max_col)
+            .take_while(|&chr| chr == ' ' || chr == '\t')
+            .collect()
+    }
+    
+    /// One level of indentation, matching what `indent` is made of.
+    fn indent_unit(indent: &str) -> String {
+        if indent.starts_with('\t') {
+            String::from("\t")
+        } else {
+            " ".repeat(Self::TAB_WIDTH)
+        }
+    }
+    
+    /// Breaks the line keeping its indentation, one level deeper after an opening bracket.
+    /// Between a pair of brackets, the closer moves to its own line.
+    pub(crate) fn insert_indented_newline(&mut self) {
+        let TextPosition { line, col } = self.cursor;
+        let indent = self.line_indent(line, col);
+        let line_slice = self.rope.line(line);
+        let before = (0..col).rev()
+            .map(|col| line_slice.char(col))
+            .find(|chr| !chr.is_whitespace());
+        let after = (col < self.line_len(line)).then(|| line_slice.char(col));
+        let opener = before.and_then(|before| BRACKETS.iter().find(|&&(open, _)| open == before));
+        let Some(&(_, close)) = opener else {
+            self.insert_str(&format!("\n{indent}"));
+            return;
+        };
+        let inner = format!("{indent}{}", Self::indent_unit(&indent));
+        if after == Some(close) {
+            self.insert_str(&format!("\n{inner}\n{indent}"));
+            self.set_cursor(TextPosition::new(line + 1, inner.chars().count()));
+        } else {
+            self.insert_str(&format!("\n{inner}"));
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+/// Where copied and cut text goes, and where paste reads from.
+pub trait Clipboard {
+    fn set_text(&mut self, text: &str) -> std::io::Result<()>;
+    /// `None` if the clipboard is empty or can't be read.
+    fn get_text(&mut self) -> std::io::Result<Option<String>>;
+}
+
+/// Clipboards are shared by every editor in the process.
+pub type SharedClipboard = Rc<RefCell<dyn Clipboard>>;
+
+/// A clipboard that only lives inside the process. Used by default and in tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn shared() -> SharedClipboard {
+        Rc::new(RefCell::new(Self::new()))
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn set_text(&mut self, text: &str) -> std::io::Result<()> {
+        self.text = Some(text.to_owned());
+        Ok(())
+    }
+    
+    fn get_text(&mut self) -> std::io::Result<Option<String>> {
+        Ok(self.text.clone())
+    }
+}
+
+/// Copies to the system clipboard with the OSC 52 terminal escape sequence, which works
+/// over SSH and without a GUI, as long as the terminal supports it.
+///
+/// Reading the system clipboard through OSC 52 needs the terminal's reply, which most
+/// terminals disable, so paste returns the text that was last copied here instead. Text
+/// copied in other programs arrives through the terminal's own paste as `Event::Paste`.
+pub struct Osc52Clipboard<W: Write> {
+    writer: W,
+    /// Wraps the sequence so that tmux passes it on to the outer terminal.
+    pub tmux_passthrough: bool,
+    last: MemoryClipboard,
+}
+
+impl<W: Write> Osc52Clipboard<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            tmux_passthrough: std::env::var_os("TMUX").is_some(),
+            last: MemoryClipboard::new(),
+        }
+    }
+}
+
+impl Osc52Clipboard<std::io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+}
+
+impl<W: Write> Clipboard for Osc52Clipboard<W> {
+    fn set_text(&mut self, text: &str) -> std::io::Result<()> {
+        let sequence = format!("\x1b]52;c;{}\x07", base64_encode(text.as_bytes()));
+        if self.tmux_passthrough {
+            // Escapes inside the passthrough are doubled.
+            write!(self.writer, "\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))?;
+        } else {
+            self.writer.write_all(sequence.as_bytes())?;
+        }
+        self.writer.flush()?;
+        self.last.set_text(text)
+    }
+    
+    fn get_text(&mut self) -> std::io::Result<Option<String>> {
+        self.last.get_text()
+    }
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| group | (byte as u32) << (16 - i * 8));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn base64_pads_partial_groups() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode("ü\n".as_bytes()), "w7wK");
+        assert_eq!(base64_encode(&[0xFF, 0xFE, 0xFD, 0x00]), "//79AA==");
+    }
+    
+    #[test]
+    fn osc52_writes_the_sequence_and_remembers_the_text() {
+        let mut clipboard = Osc52Clipboard::new(Vec::new());
+        clipboard.tmux_passthrough = false;
+        assert_eq!(clipboard.get_text().unwrap(), None);
+        clipboard.set_text("foo").unwrap();
+        assert_eq!(clipboard.writer, b"\x1b]52;c;Zm9v\x07");
+        assert_eq!(clipboard.get_text().unwrap().as_deref(), Some("foo"));
+        
+        let mut clipboard = Osc52Clipboard::new(Vec::new());
+        clipboard.tmux_passthrough = true;
+        clipboard.set_text("foo").unwrap();
+        assert_eq!(clipboard.writer, b"\x1bPtmux;\x1b\x1b]52;c;Zm9v\x07\x1b\\");
+    }
+}
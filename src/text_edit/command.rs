@@ -0,0 +1,318 @@
+use std::ops::Range;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use super::{wrap::SoftWrap, Selection, SelectionKind, TextEditor, TextPosition};
+
+/// Everything that input layers (key bindings, Vim mode, macros) can ask the editor to do.
+/// Input layers translate keys into commands instead of calling into the editor directly,
+/// so that the same behavior is reachable from every layer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EditorCommand {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    MoveLineStart,
+    MoveLineEnd,
+    /// Moves to the start of the word before the cursor.
+    MoveWordLeft,
+    /// Moves to the end of the word after the cursor.
+    MoveWordRight,
+    /// Moves to the blank line before the paragraph.
+    MoveParagraphUp,
+    /// Moves to the blank line after the paragraph.
+    MoveParagraphDown,
+    /// Moves a viewport height, scrolling along.
+    PageUp,
+    PageDown,
+    MoveDocumentStart,
+    MoveDocumentEnd,
+    /// Moves to the first non-blank char of a line, counting from 0, and drops any extra cursors.
+    GoToLine(usize),
+    /// Moves the cursor, clamped to the buffer, and drops any extra cursors.
+    MoveTo(TextPosition),
+    AddCursorAbove,
+    AddCursorBelow,
+    ClearExtraCursors,
+    StartSelection(SelectionKind),
+    SetSelection(Selection),
+    ClearSelection,
+    /// Types a char, closing brackets and quotes. See [`TextEditor::type_char`].
+    InsertChar(char),
+    /// Inserts at the cursor.
+    InsertText(String),
+    /// Inserts at a char index without moving the cursor (unless the text lands before it).
+    InsertAt {
+        char_idx: usize,
+        text: String,
+    },
+    /// Breaks the line with auto-indent.
+    InsertNewline,
+    /// Moves to the next tabstop of the snippet being filled in, or expands the snippet before
+    /// the cursor, before falling back to inserting spaces.
+    InsertTab,
+    /// Moves back to the previous tabstop of the snippet being filled in.
+    PrevTabstop,
+    /// Stops filling in the snippet, leaving the cursor where it is.
+    ExitSnippet,
+    Backspace,
+    Delete,
+    DeleteWordLeft,
+    DeleteWordRight,
+    DeleteRange(Range<usize>),
+    DeleteSelection,
+    /// Swaps the selected lines with the line above.
+    MoveLinesUp,
+    /// Swaps the selected lines with the line below.
+    MoveLinesDown,
+    DuplicateLines,
+    DeleteLines,
+    JoinLines,
+    /// Sorts the selected lines, or every line without a selection. Likewise for `ReverseLines`
+    /// and `UniqueLines`.
+    SortLines,
+    ReverseLines,
+    UniqueLines,
+    ToggleComment,
+    Undo,
+    Redo,
+    /// Groups the following edits into one undo step until the matching `EndUndoGroup`.
+    BeginUndoGroup,
+    EndUndoGroup,
+    /// Moves to the bracket matching the one at or before the cursor.
+    JumpToBracket,
+    ToggleFold,
+    FoldAll,
+    UnfoldAll,
+    ToggleSoftWrap,
+    /// Moves to the next diagnostic after the cursor, wrapping around. Likewise for `PrevDiagnostic`.
+    NextDiagnostic,
+    PrevDiagnostic,
+    /// Copies the selection, or the cursor's line if nothing is selected, to the clipboard.
+    Copy,
+    Cut,
+    /// Inserts the clipboard at every cursor.
+    Paste,
+}
+
+/// Commands that make sense to run on their own, with titles for menus and the command palette.
+pub const NAMED_COMMANDS: &[(&str, EditorCommand)] = &[
+    ("Undo", EditorCommand::Undo),
+    ("Redo", EditorCommand::Redo),
+    ("Copy", EditorCommand::Copy),
+    ("Cut", EditorCommand::Cut),
+    ("Paste", EditorCommand::Paste),
+    ("Delete Selection", EditorCommand::DeleteSelection),
+    ("Clear Selection", EditorCommand::ClearSelection),
+    ("Start Selection", EditorCommand::StartSelection(SelectionKind::Exclusive)),
+    ("Add Cursor Above", EditorCommand::AddCursorAbove),
+    ("Add Cursor Below", EditorCommand::AddCursorBelow),
+    ("Clear Extra Cursors", EditorCommand::ClearExtraCursors),
+    ("Cursor Left", EditorCommand::MoveLeft),
+    ("Cursor Right", EditorCommand::MoveRight),
+    ("Cursor Up", EditorCommand::MoveUp),
+    ("Cursor Down", EditorCommand::MoveDown),
+    ("Go to Line Start", EditorCommand::MoveLineStart),
+    ("Go to Line End", EditorCommand::MoveLineEnd),
+    ("Cursor Word Left", EditorCommand::MoveWordLeft),
+    ("Cursor Word Right", EditorCommand::MoveWordRight),
+    ("Go to Previous Paragraph", EditorCommand::MoveParagraphUp),
+    ("Go to Next Paragraph", EditorCommand::MoveParagraphDown),
+    ("Page Up", EditorCommand::PageUp),
+    ("Page Down", EditorCommand::PageDown),
+    ("Go to Start of File", EditorCommand::MoveDocumentStart),
+    ("Go to End of File", EditorCommand::MoveDocumentEnd),
+    ("Insert Newline", EditorCommand::InsertNewline),
+    ("Insert Tab", EditorCommand::InsertTab),
+    ("Go to Previous Tabstop", EditorCommand::PrevTabstop),
+    ("Exit Snippet", EditorCommand::ExitSnippet),
+    ("Delete Left", EditorCommand::Backspace),
+    ("Delete Right", EditorCommand::Delete),
+    ("Delete Word Left", EditorCommand::DeleteWordLeft),
+    ("Delete Word Right", EditorCommand::DeleteWordRight),
+    ("Move Line Up", EditorCommand::MoveLinesUp),
+    ("Move Line Down", EditorCommand::MoveLinesDown),
+    ("Duplicate Line", EditorCommand::DuplicateLines),
+    ("Delete Line", EditorCommand::DeleteLines),
+    ("Join Lines", EditorCommand::JoinLines),
+    ("Sort Lines", EditorCommand::SortLines),
+    ("Reverse Lines", EditorCommand::ReverseLines),
+    ("Remove Duplicate Lines", EditorCommand::UniqueLines),
+    ("Toggle Line Comment", EditorCommand::ToggleComment),
+    ("Go to Matching Bracket", EditorCommand::JumpToBracket),
+    ("Toggle Fold", EditorCommand::ToggleFold),
+    ("Fold All", EditorCommand::FoldAll),
+    ("Unfold All", EditorCommand::UnfoldAll),
+    ("Toggle Soft Wrap", EditorCommand::ToggleSoftWrap),
+    ("Go to Next Problem", EditorCommand::NextDiagnostic),
+    ("Go to Previous Problem", EditorCommand::PrevDiagnostic),
+];
+
+impl TextEditor {
+    pub fn execute(&mut self, command: &EditorCommand) {
+        self.record_command(command);
+        // Typing over the selected placeholder of a snippet replaces it, and deleting removes just it.
+        match command {
+            EditorCommand::Backspace | EditorCommand::Delete if self.take_placeholder() => return,
+            EditorCommand::InsertChar(_) | EditorCommand::InsertText(_) | EditorCommand::InsertNewline => _ = self.take_placeholder(),
+            _ => (),
+        }
+        match command {
+            EditorCommand::MoveLeft => self.at_every_cursor(|editor, _| editor.move_left()),
+            EditorCommand::MoveRight => self.at_every_cursor(|editor, _| editor.move_right()),
+            EditorCommand::MoveUp => self.at_every_cursor(|editor, _| editor.move_up()),
+            EditorCommand::MoveDown => self.at_every_cursor(|editor, _| editor.move_down()),
+            EditorCommand::MoveLineStart => self.at_every_cursor(|editor, _| editor.move_line_start()),
+            EditorCommand::MoveLineEnd => self.at_every_cursor(|editor, _| editor.move_line_end()),
+            EditorCommand::MoveWordLeft => self.at_every_cursor(|editor, _| editor.move_word_left()),
+            EditorCommand::MoveWordRight => self.at_every_cursor(|editor, _| editor.move_word_right()),
+            EditorCommand::MoveParagraphUp => self.at_every_cursor(|editor, _| editor.move_paragraph(false)),
+            EditorCommand::MoveParagraphDown => self.at_every_cursor(|editor, _| editor.move_paragraph(true)),
+            EditorCommand::PageUp => self.move_page(false),
+            EditorCommand::PageDown => self.move_page(true),
+            EditorCommand::MoveDocumentStart => {
+                self.clear_extra_cursors();
+                self.move_document_start();
+            }
+            EditorCommand::MoveDocumentEnd => {
+                self.clear_extra_cursors();
+                self.move_document_end();
+            }
+            &EditorCommand::GoToLine(line) => {
+                self.clear_extra_cursors();
+                self.go_to_line(line);
+            }
+            &EditorCommand::MoveTo(position) => {
+                self.clear_extra_cursors();
+                self.set_cursor(position);
+            }
+            EditorCommand::AddCursorAbove => self.add_cursor(false),
+            EditorCommand::AddCursorBelow => self.add_cursor(true),
+            EditorCommand::ClearExtraCursors => self.clear_extra_cursors(),
+            &EditorCommand::StartSelection(kind) => self.start_selection(kind),
+            &EditorCommand::SetSelection(selection) => self.selection = Some(selection),
+            EditorCommand::ClearSelection => self.clear_selection(),
+            &EditorCommand::InsertChar(chr) => self.at_every_cursor(|editor, _| editor.type_char(chr)),
+            EditorCommand::InsertText(text) => self.at_every_cursor(|editor, _| editor.insert_str(text)),
+            EditorCommand::InsertAt { char_idx, text } => {
+                _ = self.try_insert(*char_idx, text);
+                self.request_scroll_to_cursor();
+            }
+            EditorCommand::InsertNewline => self.at_every_cursor(|editor, _| editor.insert_newline()),
+            EditorCommand::InsertTab => {
+                if !self.next_tabstop() && !self.expand_snippet() {
+                    self.at_every_cursor(|editor, _| editor.insert_tab());
+                }
+                self.request_scroll_to_cursor();
+            }
+            EditorCommand::PrevTabstop => {
+                self.prev_tabstop();
+                self.request_scroll_to_cursor();
+            }
+            EditorCommand::ExitSnippet => _ = self.exit_snippet(),
+            EditorCommand::Backspace => self.at_every_cursor(|editor, _| editor.backspace()),
+            EditorCommand::Delete => self.at_every_cursor(|editor, _| editor.delete()),
+            EditorCommand::DeleteWordLeft => self.at_every_cursor(|editor, _| editor.delete_word_left()),
+            EditorCommand::DeleteWordRight => self.at_every_cursor(|editor, _| editor.delete_word_right()),
+            EditorCommand::DeleteRange(range) => {
+                _ = self.try_remove(range.clone());
+                self.request_scroll_to_cursor();
+            }
+            EditorCommand::DeleteSelection => _ = self.delete_selection(),
+            EditorCommand::MoveLinesUp => self.move_lines(false),
+            EditorCommand::MoveLinesDown => self.move_lines(true),
+            EditorCommand::DuplicateLines => self.duplicate_lines(),
+            EditorCommand::DeleteLines => self.delete_lines(),
+            EditorCommand::JoinLines => self.join_lines(),
+            EditorCommand::SortLines => self.sort_lines(),
+            EditorCommand::ReverseLines => self.reverse_lines(),
+            EditorCommand::UniqueLines => self.unique_lines(),
+            EditorCommand::ToggleComment => self.toggle_comment(),
+            EditorCommand::Undo => _ = self.undo(),
+            EditorCommand::Redo => _ = self.redo(),
+            EditorCommand::BeginUndoGroup => self.begin_transaction(),
+            EditorCommand::EndUndoGroup => self.end_transaction(),
+            EditorCommand::JumpToBracket => _ = self.jump_to_bracket(),
+            EditorCommand::ToggleFold => self.toggle_fold(),
+            EditorCommand::FoldAll => self.fold_all(),
+            EditorCommand::UnfoldAll => self.unfold_all(),
+            EditorCommand::ToggleSoftWrap => {
+                let soft_wrap = match self.soft_wrap {
+                    Some(_) => None,
+                    None => Some(SoftWrap::default()),
+                };
+                self.set_soft_wrap(soft_wrap);
+            }
+            EditorCommand::NextDiagnostic => {
+                self.clear_extra_cursors();
+                self.next_diagnostic();
+            }
+            EditorCommand::PrevDiagnostic => {
+                self.clear_extra_cursors();
+                self.prev_diagnostic();
+            }
+            // Clipboard errors (such as a closed terminal) leave the buffer untouched, so there is nothing to report.
+            EditorCommand::Copy => _ = self.copy(),
+            EditorCommand::Cut => _ = self.cut(),
+            EditorCommand::Paste => _ = self.paste(),
+        }
+    }
+}
+
+/// The default (non-modal) bindings for editing keys.
+pub fn default_binding(key: KeyEvent) -> Option<EditorCommand> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let command = match key.code {
+        KeyCode::Up if ctrl && alt => EditorCommand::AddCursorAbove,
+        KeyCode::Down if ctrl && alt => EditorCommand::AddCursorBelow,
+        KeyCode::Up if alt => EditorCommand::MoveLinesUp,
+        KeyCode::Down if alt => EditorCommand::MoveLinesDown,
+        KeyCode::Up if ctrl => EditorCommand::MoveParagraphUp,
+        KeyCode::Down if ctrl => EditorCommand::MoveParagraphDown,
+        KeyCode::Left if ctrl => EditorCommand::MoveWordLeft,
+        KeyCode::Right if ctrl => EditorCommand::MoveWordRight,
+        KeyCode::Home if ctrl => EditorCommand::MoveDocumentStart,
+        KeyCode::End if ctrl => EditorCommand::MoveDocumentEnd,
+        KeyCode::Up => EditorCommand::MoveUp,
+        KeyCode::Down => EditorCommand::MoveDown,
+        KeyCode::Left => EditorCommand::MoveLeft,
+        KeyCode::Right => EditorCommand::MoveRight,
+        KeyCode::Home => EditorCommand::MoveLineStart,
+        KeyCode::End => EditorCommand::MoveLineEnd,
+        // Ctrl and Alt with PageUp and PageDown switch tabs and buffers.
+        KeyCode::PageUp if !ctrl && !alt => EditorCommand::PageUp,
+        KeyCode::PageDown if !ctrl && !alt => EditorCommand::PageDown,
+        // Many terminals can't tell Ctrl+Backspace from Backspace (or from Ctrl+H), so Alt works too.
+        KeyCode::Delete if ctrl || alt => EditorCommand::DeleteWordRight,
+        KeyCode::Backspace if ctrl || alt => EditorCommand::DeleteWordLeft,
+        KeyCode::Char('h') if ctrl => EditorCommand::DeleteWordLeft,
+        KeyCode::Delete => EditorCommand::Delete,
+        KeyCode::Backspace => EditorCommand::Backspace,
+        KeyCode::Tab => EditorCommand::InsertTab,
+        KeyCode::BackTab => EditorCommand::PrevTabstop,
+        KeyCode::Enter => EditorCommand::InsertNewline,
+        KeyCode::Char('z') if ctrl => EditorCommand::Undo,
+        KeyCode::Char('y') if ctrl => EditorCommand::Redo,
+        KeyCode::Char('c') if ctrl => EditorCommand::Copy,
+        KeyCode::Char('x') if ctrl => EditorCommand::Cut,
+        KeyCode::Char('v') if ctrl => EditorCommand::Paste,
+        KeyCode::Char('d') if ctrl => EditorCommand::DuplicateLines,
+        KeyCode::Char('k') if ctrl => EditorCommand::DeleteLines,
+        KeyCode::Char('j') if ctrl => EditorCommand::JoinLines,
+        // Ctrl+/ arrives as Ctrl+7 in terminals without keyboard enhancements.
+        KeyCode::Char('/' | '7') if ctrl => EditorCommand::ToggleComment,
+        // Ctrl+] arrives as Ctrl+5 in terminals without keyboard enhancements.
+        KeyCode::Char(']' | '5') if ctrl => EditorCommand::JumpToBracket,
+        KeyCode::Char('z') if alt => EditorCommand::ToggleSoftWrap,
+        KeyCode::Char('f') if alt => EditorCommand::ToggleFold,
+        KeyCode::Char('o') if alt => EditorCommand::FoldAll,
+        KeyCode::Char('u') if alt => EditorCommand::UnfoldAll,
+        KeyCode::F(8) if key.modifiers.contains(KeyModifiers::SHIFT) => EditorCommand::PrevDiagnostic,
+        KeyCode::F(8) => EditorCommand::NextDiagnostic,
+        KeyCode::Char(chr) if !ctrl && !alt && chr != '\n' => EditorCommand::InsertChar(chr),
+        _ => return None,
+    };
+    Some(command)
+}
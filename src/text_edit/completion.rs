@@ -0,0 +1,506 @@
+use std::{cmp::Reverse, collections::{HashMap, HashSet}};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{prelude::*, widgets::Clear};
+
+use crate::theme::Theme;
+
+use super::{palette::fuzzy_match, workspace::{BufferId, BufferManager}, TextEditor, TextPosition};
+
+/// Something that can be inserted at the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    /// Shown in the list, and matched against what's been typed.
+    pub label: String,
+    /// Shown dimmed after the label, such as a type.
+    pub detail: Option<String>,
+    /// Inserted in place of the word at the cursor. The label is used if this is `None`.
+    pub insert_text: Option<String>,
+}
+
+impl CompletionItem {
+    pub fn new<S: Into<String>>(label: S) -> Self {
+        Self {
+            label: label.into(),
+            detail: None,
+            insert_text: None,
+        }
+    }
+    
+    pub fn detail<S: Into<String>>(mut self, detail: S) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+    
+    pub fn insert_text<S: Into<String>>(mut self, insert_text: S) -> Self {
+        self.insert_text = Some(insert_text.into());
+        self
+    }
+}
+
+/// What providers are asked to complete: the word ending at the cursor of `buffer`.
+pub struct CompletionContext<'a> {
+    pub buffers: &'a BufferManager,
+    pub buffer: BufferId,
+    pub editor: &'a TextEditor,
+    /// Where the word starts.
+    pub start: TextPosition,
+    /// What's been typed of it, which may be nothing.
+    pub prefix: String,
+}
+
+impl<'a> CompletionContext<'a> {
+    /// The context at the cursor of `buffer`, or `None` if there's no such buffer or text is selected.
+    pub fn new(buffers: &'a BufferManager, buffer: BufferId) -> Option<Self> {
+        let editor = buffers.get(buffer)?;
+        if editor.selection.is_some() {
+            return None;
+        }
+        let start = word_start(editor);
+        let prefix = editor.rope.slice(editor.position_to_char(start)..editor.cursor_char_index()).to_string();
+        Some(Self {
+            buffers,
+            buffer,
+            editor,
+            start,
+            prefix,
+        })
+    }
+}
+
+/// A source of completions. Items don't need to match the prefix, since they're filtered
+/// as the word is typed.
+pub trait CompletionProvider {
+    fn complete(&mut self, context: &CompletionContext<'_>) -> Vec<CompletionItem>;
+}
+
+/// Asks each of its providers for completions and puts them in one list, in the order the
+/// providers were added. Items with a label that's already in the list are left out.
+pub struct Completer {
+    providers: Vec<Box<dyn CompletionProvider>>,
+    /// Completions open on their own once a word this long has been typed.
+    pub auto_trigger: Option<usize>,
+}
+
+impl Completer {
+    /// A completer without providers, that doesn't open on its own.
+    pub fn new() -> Self {
+        Self {
+            providers: Vec::new(),
+            auto_trigger: None,
+        }
+    }
+    
+    pub fn provider(mut self, provider: Box<dyn CompletionProvider>) -> Self {
+        self.providers.push(provider);
+        self
+    }
+    
+    pub fn auto_trigger(mut self, auto_trigger: Option<usize>) -> Self {
+        self.auto_trigger = auto_trigger;
+        self
+    }
+    
+    /// Completions for the word at the cursor of `buffer`, or `None` if nothing matches.
+    pub fn complete(&mut self, buffers: &BufferManager, buffer: BufferId) -> Option<CompletionState> {
+        let context = CompletionContext::new(buffers, buffer)?;
+        let mut labels = HashSet::new();
+        let items = self.providers.iter_mut()
+            .flat_map(|provider| provider.complete(&context))
+            .filter(|item| labels.insert(item.label.clone()))
+            .collect();
+        let state = CompletionState::new(items, context.editor);
+        (!state.is_empty()).then_some(state)
+    }
+    
+    /// Like [`complete`](Self::complete), but only once [`auto_trigger`](Self::auto_trigger)
+    /// chars of a word have been typed. Call it after text is typed.
+    pub fn complete_typed(&mut self, buffers: &BufferManager, buffer: BufferId) -> Option<CompletionState> {
+        let min_len = self.auto_trigger?;
+        let editor = buffers.get(buffer)?;
+        let typed = editor.cursor.col.saturating_sub(word_start(editor).col);
+        if typed < min_len.max(1) {
+            return None;
+        }
+        self.complete(buffers, buffer)
+    }
+}
+
+impl Default for Completer {
+    /// Completes words from the open buffers, opening after three chars.
+    fn default() -> Self {
+        Self::new()
+            .provider(Box::new(WordProvider::default()))
+            .auto_trigger(Some(3))
+    }
+}
+
+/// Completes the words of every open buffer, so that names from one file can be typed in
+/// another. Words from other buffers are labelled with the buffer's name. Large files,
+/// and ones still loading, are skipped.
+pub struct WordProvider {
+    /// Shorter words aren't offered.
+    pub min_len: usize,
+    /// The words of each buffer and how often they appear, as of a version of the buffer.
+    cache: HashMap<BufferId, (u64, HashMap<String, usize>)>,
+}
+
+impl WordProvider {
+    pub fn new(min_len: usize) -> Self {
+        Self {
+            min_len,
+            cache: HashMap::new(),
+        }
+    }
+    
+    /// Counts the words of `editor` unless they were counted at its current version.
+    fn words(&mut self, buffer: BufferId, editor: &TextEditor) -> &HashMap<String, usize> {
+        let (version, words) = self.cache.entry(buffer).or_default();
+        if *version != editor.version() || words.is_empty() {
+            *version = editor.version();
+            words.clear();
+            let mut word = String::new();
+            for chr in editor.rope.chars().chain(std::iter::once(' ')) {
+                if is_word_char(chr) {
+                    word.push(chr);
+                } else if !word.is_empty() {
+                    // Numbers aren't worth completing.
+                    if !word.starts_with(|chr: char| chr.is_ascii_digit()) && word.chars().count() >= self.min_len {
+                        *words.entry(std::mem::take(&mut word)).or_default() += 1;
+                    } else {
+                        word.clear();
+                    }
+                }
+            }
+        }
+        words
+    }
+}
+
+impl Default for WordProvider {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl CompletionProvider for WordProvider {
+    fn complete(&mut self, context: &CompletionContext<'_>) -> Vec<CompletionItem> {
+        self.cache.retain(|&buffer, _| context.buffers.get(buffer).is_some());
+        // The word being typed is in the buffer too, and shouldn't complete itself.
+        let line = context.editor.rope.line(context.start.line);
+        let current: String = line.chars()
+            .skip(context.start.col)
+            .take_while(|&chr| is_word_char(chr))
+            .collect();
+        let buffers = std::iter::once(context.buffer)
+            .chain(context.buffers.ids().filter(|&buffer| buffer != context.buffer));
+        let mut items = Vec::new();
+        for buffer in buffers {
+            let Some(editor) = context.buffers.get(buffer) else {
+                continue;
+            };
+            if editor.is_large() || editor.is_loading() {
+                continue;
+            }
+            let detail = (buffer != context.buffer).then(|| context.buffers.name(buffer));
+            let mut words: Vec<&String> = self.words(buffer, editor).iter()
+                .filter(|&(word, &count)| count > usize::from(buffer == context.buffer && *word == current))
+                .map(|(word, _)| word)
+                .filter(|word| **word != context.prefix)
+                .collect();
+            words.sort();
+            items.extend(words.into_iter().map(|word| {
+                let item = CompletionItem::new(word.as_str());
+                match &detail {
+                    Some(detail) => item.detail(detail.as_str()),
+                    None => item,
+                }
+            }));
+        }
+        items
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionEvent {
+    Handled,
+    /// The key should go to the editor, after which [`CompletionState::update`] should be called.
+    Ignored,
+    Close,
+}
+
+/// Completions for the word at the cursor, narrowed down as it's typed.
+pub struct CompletionState {
+    items: Vec<CompletionItem>,
+    /// Where the word being completed starts.
+    start: TextPosition,
+    /// Indices into `items` that fuzzily match what's been typed, best first, with the
+    /// chars of their labels that matched.
+    matches: Vec<(usize, Vec<usize>)>,
+    selected: usize,
+    /// The first match shown, so that the selection stays in view.
+    scroll: usize,
+    /// Set for choices, which aren't filtered and stay open until the cursor moves from here.
+    fixed: Option<TextPosition>,
+}
+
+impl CompletionState {
+    /// Rows shown at once.
+    pub const PAGE: usize = 10;
+    
+    pub fn new(items: Vec<CompletionItem>, editor: &TextEditor) -> Self {
+        let mut completion = Self {
+            items,
+            start: word_start(editor),
+            matches: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            fixed: None,
+        };
+        completion.update(editor);
+        completion
+    }
+    
+    /// A list of choices for the selection, or the word at the cursor if nothing is selected,
+    /// such as those of a snippet's tabstop. Accepting one replaces it.
+    pub fn choices(items: Vec<CompletionItem>, editor: &TextEditor) -> Self {
+        let start = match editor.selection_range() {
+            Some(range) => editor.char_to_position(range.start),
+            None => word_start(editor),
+        };
+        Self {
+            matches: (0..items.len()).map(|index| (index, Vec::new())).collect(),
+            items,
+            start,
+            selected: 0,
+            scroll: 0,
+            fixed: Some(editor.cursor),
+        }
+    }
+    
+    /// Whether nothing matches what's been typed.
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+    
+    pub fn selected_item(&self) -> Option<&CompletionItem> {
+        self.matches.get(self.selected).map(|(index, _)| &self.items[*index])
+    }
+    
+    /// Where the word being completed starts.
+    pub fn start(&self) -> TextPosition {
+        self.start
+    }
+    
+    /// Adds items that arrived later, such as from a language server, leaving out labels
+    /// that are already listed.
+    pub fn extend(&mut self, items: Vec<CompletionItem>, editor: &TextEditor) -> bool {
+        let labels: HashSet<String> = self.items.iter().map(|item| item.label.clone()).collect();
+        self.items.extend(items.into_iter().filter(|item| !labels.contains(&item.label)));
+        self.update(editor)
+    }
+    
+    /// What's been typed of the word being completed.
+    fn prefix(&self, editor: &TextEditor) -> Option<String> {
+        let cursor = editor.cursor;
+        if cursor.line != self.start.line || cursor.col < self.start.col || editor.selection.is_some() {
+            return None;
+        }
+        let start = editor.position_to_char(self.start);
+        Some(editor.rope.slice(start..editor.cursor_char_index()).to_string())
+    }
+    
+    /// Narrows the list down to what's been typed since. Returns `false` if the cursor left
+    /// the word, or nothing matches, and the popup should close.
+    pub fn update(&mut self, editor: &TextEditor) -> bool {
+        if let Some(cursor) = self.fixed {
+            return editor.cursor == cursor && !self.matches.is_empty();
+        }
+        let Some(prefix) = self.prefix(editor) else {
+            return false;
+        };
+        if prefix.chars().any(|chr| !is_word_char(chr)) {
+            return false;
+        }
+        let mut matches: Vec<(i64, usize, Vec<usize>)> = self.items.iter()
+            .enumerate()
+            .filter_map(|(index, item)| fuzzy_match(&prefix, &item.label).map(|(score, positions)| (score, index, positions)))
+            .collect();
+        // Ties go to the shorter label, and then to the order the items came in.
+        matches.sort_by_key(|(score, index, _)| (Reverse(*score), self.items[*index].label.chars().count()));
+        self.matches = matches.into_iter().map(|(_, index, positions)| (index, positions)).collect();
+        self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+        !self.matches.is_empty()
+    }
+    
+    fn select(&mut self, selected: usize) {
+        self.selected = selected.min(self.matches.len().saturating_sub(1));
+    }
+    
+    /// Replaces the word being completed with the selected item, as one edit.
+    pub fn accept(&self, editor: &mut TextEditor) -> bool {
+        let Some(item) = self.selected_item() else {
+            return false;
+        };
+        let text = item.insert_text.as_deref().unwrap_or(&item.label);
+        let start = editor.position_to_char(self.start);
+        let end = editor.cursor_char_index().max(start);
+        editor.clear_selection();
+        editor.transaction(|editor| {
+            if editor.try_remove(start..end).is_ok() && editor.try_insert(start, text).is_ok() {
+                editor.set_cursor(editor.char_to_position(start + text.chars().count()));
+            }
+        });
+        true
+    }
+    
+    /// Up and Down (or Ctrl+P and Ctrl+N) select, Tab and Enter accept and Esc closes.
+    /// Everything else should go to the editor.
+    pub fn handle_key(&mut self, key: KeyEvent, editor: &mut TextEditor) -> CompletionEvent {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Up => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Char('p') if ctrl => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Down => self.select(self.selected + 1),
+            KeyCode::Char('n') if ctrl => self.select(self.selected + 1),
+            KeyCode::PageUp => self.select(self.selected.saturating_sub(Self::PAGE)),
+            KeyCode::PageDown => self.select(self.selected + Self::PAGE),
+            KeyCode::Tab | KeyCode::Enter if key.modifiers.is_empty() => {
+                self.accept(editor);
+                return CompletionEvent::Close;
+            }
+            KeyCode::Esc => return CompletionEvent::Close,
+            _ => return CompletionEvent::Ignored,
+        }
+        CompletionEvent::Handled
+    }
+}
+
+fn is_word_char(chr: char) -> bool {
+    chr.is_alphanumeric() || chr == '_'
+}
+
+/// The start of the word that ends at the cursor, or the cursor if there isn't one.
+fn word_start(editor: &TextEditor) -> TextPosition {
+    let line = editor.rope.line(editor.cursor.line);
+    let mut col = editor.cursor.col.min(editor.line_len(editor.cursor.line));
+    while col > 0 && is_word_char(line.char(col - 1)) {
+        col -= 1;
+    }
+    TextPosition::new(editor.cursor.line, col)
+}
+
+/// Where a popup of `size` goes next to `anchor`: below it if there's room in `bounds`,
+/// otherwise above it, and shifted left to fit.
+pub(crate) fn popup_area(anchor: Position, width: u16, height: u16, bounds: Rect) -> Rect {
+    let width = width.min(bounds.width);
+    let below = bounds.bottom().saturating_sub(anchor.y + 1);
+    let above = anchor.y.saturating_sub(bounds.y);
+    let (y, height) = if height <= below || below >= above {
+        (anchor.y + 1, height.min(below))
+    } else {
+        let height = height.min(above);
+        (anchor.y - height, height)
+    };
+    let x = anchor.x.min(bounds.right().saturating_sub(width)).max(bounds.x);
+    Rect::new(x, y, width, height)
+}
+
+/// Draws the completions in a list under the word being completed, within the area
+/// it's rendered to. Render it after the editor, so that it knows where the cursor is.
+pub struct CompletionPopup<'a> {
+    editor: &'a TextEditor,
+    style: Style,
+    selected_style: Style,
+    match_style: Style,
+    detail_style: Style,
+}
+
+impl<'a> CompletionPopup<'a> {
+    pub fn new(editor: &'a TextEditor) -> Self {
+        Self {
+            editor,
+            style: Style::new().bg(Color::Rgb(37, 41, 50)),
+            selected_style: Style::new().bg(Color::Rgb(62, 68, 81)),
+            match_style: Style::new().fg(Color::Rgb(97, 175, 239)).add_modifier(Modifier::BOLD),
+            detail_style: Style::new().fg(Color::DarkGray),
+        }
+    }
+    
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.style(theme.style("completion"))
+            .selected_style(theme.style("completion.selected"))
+            .match_style(theme.style("completion.match"))
+            .detail_style(theme.style("completion.detail"))
+    }
+    
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn selected_style(mut self, style: Style) -> Self {
+        self.selected_style = style;
+        self
+    }
+    
+    /// For the chars of labels that match what's been typed.
+    pub fn match_style(mut self, style: Style) -> Self {
+        self.match_style = style;
+        self
+    }
+    
+    pub fn detail_style(mut self, style: Style) -> Self {
+        self.detail_style = style;
+        self
+    }
+}
+
+impl StatefulWidget for CompletionPopup<'_> {
+    type State = CompletionState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let Some(cursor) = self.editor.cursor_screen_position else {
+            return;
+        };
+        if state.matches.is_empty() {
+            return;
+        }
+        let typed = self.editor.cursor.col.saturating_sub(state.start.col) as u16;
+        let anchor = Position::new(cursor.x.saturating_sub(typed), cursor.y);
+        let item_width = |item: &CompletionItem| {
+            let detail = item.detail.as_ref().map_or(0, |detail| detail.chars().count() + 2);
+            item.label.chars().count() + detail + 2
+        };
+        let width = state.matches.iter().map(|(index, _)| item_width(&state.items[*index])).max().unwrap_or(0).min(60);
+        let height = state.matches.len().min(CompletionState::PAGE);
+        let popup = popup_area(anchor, width as u16, height as u16, area);
+        if popup.is_empty() {
+            return;
+        }
+        Clear.render(popup, buf);
+        buf.set_style(popup, self.style);
+        let rows = popup.height as usize;
+        if state.selected < state.scroll {
+            state.scroll = state.selected;
+        } else if state.selected >= state.scroll + rows {
+            state.scroll = state.selected + 1 - rows;
+        }
+        for (row, (match_index, (item_index, positions))) in state.matches.iter().enumerate().skip(state.scroll).take(rows).enumerate() {
+            let y = popup.y + row as u16;
+            let item = &state.items[*item_index];
+            let row_style = if match_index == state.selected { self.selected_style } else { self.style };
+            buf.set_style(Rect::new(popup.x, y, popup.width, 1), row_style);
+            let (x, _) = buf.set_stringn(popup.x + 1, y, &item.label, popup.width.saturating_sub(2) as usize, Style::new());
+            for &position in positions {
+                let match_x = popup.x + 1 + position as u16;
+                if match_x < x && let Some(cell) = buf.cell_mut((match_x, y)) {
+                    cell.set_style(self.match_style);
+                }
+            }
+            if let Some(detail) = &item.detail {
+                let x = x + 1;
+                buf.set_stringn(x, y, detail, popup.right().saturating_sub(x + 1) as usize, self.detail_style);
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::ops::Range;
+
+use ratatui::prelude::*;
+
+use crate::theme::Theme;
+
+use super::{gutter::Sign, TextEditor, TextPosition};
+
+/// The sign group that diagnostics put their signs in.
+const SIGN_GROUP: &str = "diagnostics";
+
+/// Ordered from most to least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    /// From the numbers that the language server protocol uses, 1 being an error.
+    pub fn from_lsp(severity: u64) -> Option<Self> {
+        Some(match severity {
+            1 => Severity::Error,
+            2 => Severity::Warning,
+            3 => Severity::Information,
+            4 => Severity::Hint,
+            _ => return None,
+        })
+    }
+    
+    fn sign(self) -> Sign {
+        let (symbol, color, priority) = match self {
+            Severity::Error => ('E', Color::Rgb(224, 108, 117), 40),
+            Severity::Warning => ('W', Color::Rgb(229, 192, 123), 30),
+            Severity::Information => ('I', Color::Rgb(97, 175, 239), 20),
+            Severity::Hint => ('H', Color::Rgb(92, 99, 112), 10),
+        };
+        Sign {
+            group: SIGN_GROUP,
+            symbol,
+            style: Style::new().fg(color),
+            priority,
+        }
+    }
+}
+
+/// A problem in the buffer, such as one reported by a language server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub start: TextPosition,
+    pub end: TextPosition,
+    pub severity: Severity,
+    pub message: String,
+    /// What reported it, such as the name of a compiler or linter.
+    pub source: Option<String>,
+}
+
+impl Diagnostic {
+    /// The columns of `line` that the diagnostic covers, given the line's length.
+    /// Empty ranges are widened to one column so that they can be seen.
+    fn cols_on(&self, line: usize, line_len: usize) -> Option<Range<usize>> {
+        if line < self.start.line || line > self.end.line {
+            return None;
+        }
+        let start = if line == self.start.line { self.start.col } else { 0 };
+        let end = if line == self.end.line { self.end.col } else { line_len };
+        Some(start..end.max(start + 1))
+    }
+}
+
+/// The diagnostics of a buffer, ordered by where they start. Their lines are kept in sync
+/// with edits until they're replaced.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+    
+    /// The diagnostics that cover any part of `line`.
+    pub fn on_line(&self, line: usize) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+            .take_while(move |diagnostic| diagnostic.start.line <= line)
+            .filter(move |diagnostic| diagnostic.end.line >= line)
+    }
+    
+    /// Lines `start_line..=old_end_line` were replaced by `start_line..=new_end_line`.
+    pub(crate) fn lines_changed(&mut self, start_line: usize, old_end_line: usize, new_end_line: usize) {
+        if old_end_line == new_end_line {
+            return;
+        }
+        let shift = |line: usize| {
+            if line > old_end_line {
+                line + new_end_line - old_end_line
+            } else {
+                // Positions on removed lines collapse onto the last remaining line of the edit.
+                line.min(new_end_line.max(start_line))
+            }
+        };
+        for diagnostic in &mut self.items {
+            diagnostic.start.line = shift(diagnostic.start.line);
+            diagnostic.end.line = shift(diagnostic.end.line);
+        }
+    }
+}
+
+impl TextEditor {
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+    
+    /// Replaces all of the diagnostics, and their signs in the gutter.
+    pub fn set_diagnostics(&mut self, mut diagnostics: Vec<Diagnostic>) {
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.start, diagnostic.severity));
+        self.signs.clear_group(SIGN_GROUP);
+        for diagnostic in &diagnostics {
+            self.signs.add(diagnostic.start.line, diagnostic.severity.sign());
+        }
+        self.diagnostics.items = diagnostics;
+    }
+    
+    /// Moves the cursor to the start of the next diagnostic after it, wrapping around.
+    pub fn next_diagnostic(&mut self) -> bool {
+        let cursor = self.cursor;
+        let next = self.diagnostics.items.iter()
+            .find(|diagnostic| diagnostic.start > cursor)
+            .or_else(|| self.diagnostics.items.first())
+            .map(|diagnostic| diagnostic.start);
+        next.inspect(|&start| self.set_cursor(start)).is_some()
+    }
+    
+    /// Moves the cursor to the start of the last diagnostic before it, wrapping around.
+    pub fn prev_diagnostic(&mut self) -> bool {
+        let cursor = self.cursor;
+        let prev = self.diagnostics.items.iter()
+            .rfind(|diagnostic| diagnostic.start < cursor)
+            .or_else(|| self.diagnostics.items.last())
+            .map(|diagnostic| diagnostic.start);
+        prev.inspect(|&start| self.set_cursor(start)).is_some()
+    }
+}
+
+/// Underlines diagnostics in [`TextEdit`](super::TextEdit) and shows the message of the
+/// most severe one on each line after its end.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InlineDiagnostics {
+    pub underline: bool,
+    pub messages: bool,
+    /// By severity, from errors to hints.
+    pub styles: [Style; 4],
+}
+
+impl Default for InlineDiagnostics {
+    fn default() -> Self {
+        let style = |r, g, b| Style::new().fg(Color::Rgb(r, g, b));
+        Self {
+            underline: true,
+            messages: true,
+            styles: [
+                style(224, 108, 117),
+                style(229, 192, 123),
+                style(97, 175, 239),
+                style(92, 99, 112),
+            ],
+        }
+    }
+}
+
+impl InlineDiagnostics {
+    pub fn theme(mut self, theme: &Theme) -> Self {
+        self.styles = ["error", "warning", "information", "hint"].map(|severity| theme.style(&format!("diagnostic.{severity}")));
+        self
+    }
+    
+    fn style(&self, severity: Severity) -> Style {
+        self.styles[severity as usize]
+    }
+    
+    /// Draws the diagnostics of one row of `line`, showing `cols` from `x`. `message_x` is
+    /// where the message goes, and is only given for the last row of the line.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn render_row(
+        &self,
+        buf: &mut Buffer,
+        diagnostics: &Diagnostics,
+        line: usize,
+        line_len: usize,
+        cols: Range<usize>,
+        x: u16,
+        y: u16,
+        right: u16,
+        message_x: Option<usize>,
+    ) {
+        if self.underline {
+            // The last row has room for diagnostics at the end of the line.
+            let end_col = cols.end + usize::from(message_x.is_some());
+            for diagnostic in diagnostics.on_line(line) {
+                let Some(covered) = diagnostic.cols_on(line, line_len) else {
+                    continue;
+                };
+                let start = covered.start.max(cols.start);
+                let end = covered.end.min(end_col);
+                if start >= end {
+                    continue;
+                }
+                let start_x = x as usize + (start - cols.start);
+                let width = (end - start).min((right as usize).saturating_sub(start_x));
+                let color = self.style(diagnostic.severity).fg.unwrap_or(Color::Reset);
+                let style = Style::new().add_modifier(Modifier::UNDERLINED).underline_color(color);
+                buf.set_style(Rect::new(start_x as u16, y, width as u16, 1), style);
+            }
+        }
+        let Some(message_x) = message_x.filter(|_| self.messages) else {
+            return;
+        };
+        let worst = diagnostics.on_line(line)
+            .filter(|diagnostic| diagnostic.start.line == line)
+            .min_by_key(|diagnostic| diagnostic.severity);
+        if let Some(diagnostic) = worst
+            && message_x < right as usize {
+            let message = format!("● {}", diagnostic.message.lines().next().unwrap_or_default());
+            buf.set_stringn(message_x as u16, y, message, right as usize - message_x, self.style(diagnostic.severity));
+        }
+    }
+}
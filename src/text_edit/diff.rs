@@ -0,0 +1,784 @@
+use std::{hash::Hasher, ops::Range, path::Path, time::{Duration, Instant}};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::prelude::*;
+use ropey::{Rope, RopeSlice};
+use twox_hash::XxHash64;
+
+use crate::theme::Theme;
+
+use super::{large_file::read_file, TextEditor};
+
+/// Lines of context kept above a hunk when jumping to it.
+const HUNK_MARGIN: usize = 3;
+/// Texts with few lines in common can take a long time to diff exactly. Past this, what's
+/// left to compare counts as a single change.
+const TIME_LIMIT: Duration = Duration::from_millis(500);
+
+/// A run of lines that differ between the old and new text.
+/// One of the ranges is empty for lines that were only added or only removed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// The lines that differ between `old` and `new`, in order.
+pub fn diff_lines(old: &Rope, new: &Rope) -> Vec<Hunk> {
+    // Comparing hashes is much cheaper than comparing lines, and collisions are unlikely enough.
+    let hash = |line: RopeSlice| {
+        let mut hasher = XxHash64::default();
+        line.chunks().for_each(|chunk| hasher.write(chunk.as_bytes()));
+        hasher.finish()
+    };
+    let old: Vec<u64> = old.lines().map(hash).collect();
+    let new: Vec<u64> = new.lines().map(hash).collect();
+    diff(&old, &new)
+}
+
+/// The runs that differ between `old` and `new`, found with the linear space version of
+/// Myers' algorithm.
+fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    let mut matches = Vec::new();
+    let max_d = (old.len() + new.len()).div_ceil(2) + 1;
+    let mut forward = Diagonals::new(max_d);
+    let mut backward = Diagonals::new(max_d);
+    let mut search = Search {
+        forward: &mut forward,
+        backward: &mut backward,
+        deadline: Instant::now() + TIME_LIMIT,
+    };
+    conquer(old, 0..old.len(), new, 0..new.len(), &mut search, &mut matches);
+    let mut hunks = Vec::new();
+    let (mut old_start, mut new_start) = (0, 0);
+    for (old_index, new_index) in matches.into_iter().chain([(old.len(), new.len())]) {
+        if old_index > old_start || new_index > new_start {
+            hunks.push(Hunk {
+                old: old_start..old_index,
+                new: new_start..new_index,
+            });
+        }
+        (old_start, new_start) = (old_index + 1, new_index + 1);
+    }
+    hunks
+}
+
+/// The furthest x reached on each diagonal k = x - y, indexed from `-max_d`.
+struct Diagonals {
+    offset: isize,
+    x: Vec<usize>,
+}
+
+impl Diagonals {
+    fn new(max_d: usize) -> Self {
+        Self {
+            offset: max_d as isize,
+            x: vec![0; 2 * max_d + 2],
+        }
+    }
+}
+
+impl std::ops::Index<isize> for Diagonals {
+    type Output = usize;
+    fn index(&self, k: isize) -> &usize {
+        &self.x[(k + self.offset) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for Diagonals {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.x[(k + self.offset) as usize]
+    }
+}
+
+/// The scratch space for finding middle snakes, and when to give up.
+struct Search<'a> {
+    forward: &'a mut Diagonals,
+    backward: &'a mut Diagonals,
+    deadline: Instant,
+}
+
+/// Adds the pairs of equal items in the ranges to `matches`, in order.
+fn conquer<T: PartialEq>(
+    old: &[T],
+    mut old_range: Range<usize>,
+    new: &[T],
+    mut new_range: Range<usize>,
+    search: &mut Search,
+    matches: &mut Vec<(usize, usize)>,
+) {
+    while !old_range.is_empty() && !new_range.is_empty() && old[old_range.start] == new[new_range.start] {
+        matches.push((old_range.start, new_range.start));
+        old_range.start += 1;
+        new_range.start += 1;
+    }
+    let mut suffix = 0;
+    while !old_range.is_empty() && !new_range.is_empty() && old[old_range.end - 1] == new[new_range.end - 1] {
+        old_range.end -= 1;
+        new_range.end -= 1;
+        suffix += 1;
+    }
+    if !old_range.is_empty() && !new_range.is_empty()
+        && let Some((old_split, new_split)) = middle_snake(old, old_range.clone(), new, new_range.clone(), search) {
+        conquer(old, old_range.start..old_split, new, new_range.start..new_split, search, matches);
+        conquer(old, old_split..old_range.end, new, new_split..new_range.end, search, matches);
+    }
+    matches.extend((0..suffix).map(|i| (old_range.end + i, new_range.end + i)));
+}
+
+/// Where a shortest edit script through the ranges crosses its middle, searching from
+/// both ends at once. `None` if the search ran out of time.
+fn middle_snake<T: PartialEq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    search: &mut Search,
+) -> Option<(usize, usize)> {
+    let Search { forward, backward, deadline } = search;
+    let (n, m) = (old_range.len() as isize, new_range.len() as isize);
+    let delta = n - m;
+    let odd = delta & 1 == 1;
+    forward[1] = 0;
+    backward[1] = 0;
+    let max_d = (n + m + 1) / 2 + 1;
+    for d in 0..max_d {
+        if Instant::now() > *deadline {
+            return None;
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[k - 1] < forward[k + 1]) {
+                forward[k + 1] as isize
+            } else {
+                forward[k - 1] as isize + 1
+            };
+            let mut y = x - k;
+            let (snake_x, snake_y) = (x, y);
+            while x < n && (0..m).contains(&y) && old[old_range.start + x as usize] == new[new_range.start + y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[k] = x as usize;
+            if odd && (k - delta).abs() < d && x + backward[delta - k] as isize >= n {
+                return Some((old_range.start + snake_x as usize, new_range.start + snake_y as usize));
+            }
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[k - 1] < backward[k + 1]) {
+                backward[k + 1] as isize
+            } else {
+                backward[k - 1] as isize + 1
+            };
+            let mut y = x - k;
+            while x < n && (0..m).contains(&y) && old[old_range.end - x as usize - 1] == new[new_range.end - y as usize - 1] {
+                x += 1;
+                y += 1;
+            }
+            backward[k] = x as usize;
+            if !odd && (k - delta).abs() <= d && x + forward[delta - k] as isize >= n {
+                return Some((old_range.end - x as usize, new_range.end - y as usize));
+            }
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DiffMode {
+    /// The old text on the left and the new on the right, with changed lines side by side.
+    #[default]
+    SideBySide,
+    /// One column, with removed lines above the lines that replaced them.
+    Inline,
+}
+
+/// A row of the diff: a line of either side, or of both when they're lined up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct DiffRow {
+    old: Option<usize>,
+    new: Option<usize>,
+    hunk: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffEvent {
+    Handled,
+    Ignored,
+    Close,
+}
+
+/// Compares a buffer with older text, such as another buffer or the file on disk. The
+/// buffer is the new side, so reverting a hunk edits it. See [`DiffView`].
+pub struct DiffState {
+    old: Rope,
+    /// Shown above the old side.
+    pub old_name: String,
+    pub mode: DiffMode,
+    /// The first row in view.
+    pub scroll: usize,
+    hunks: Vec<Hunk>,
+    rows: Vec<DiffRow>,
+    current_hunk: Option<usize>,
+    /// The buffer version and mode that `hunks` and `rows` were made for.
+    diffed: Option<(u64, DiffMode)>,
+    /// Rows that fit during the last render, for paging.
+    height: usize,
+}
+
+impl DiffState {
+    /// Compares a buffer with `old`. Another buffer can be compared by passing its rope.
+    pub fn new<S: Into<String>>(old: Rope, old_name: S) -> Self {
+        Self {
+            old,
+            old_name: old_name.into(),
+            mode: DiffMode::default(),
+            scroll: 0,
+            hunks: Vec::new(),
+            rows: Vec::new(),
+            current_hunk: None,
+            diffed: None,
+            height: 0,
+        }
+    }
+    
+    /// Compares a buffer with the file it was loaded from or last saved to.
+    pub fn saved(editor: &TextEditor) -> std::io::Result<Self> {
+        let Some(path) = editor.path.as_deref() else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No file name"));
+        };
+        let old = read_file(path)?;
+        let name = path.file_name().map(Path::new).unwrap_or(path).display();
+        Ok(Self::new(old, format!("{name} (saved)")))
+    }
+    
+    /// Diffs again if the buffer changed. Rendering does this too.
+    pub fn update(&mut self, editor: &TextEditor) {
+        if self.diffed == Some((editor.version(), self.mode)) {
+            return;
+        }
+        if self.diffed.is_none_or(|(version, _)| version != editor.version()) {
+            self.hunks = diff_lines(&self.old, &editor.rope);
+        }
+        self.diffed = Some((editor.version(), self.mode));
+        self.rows = self.layout(editor.rope.len_lines());
+        self.current_hunk = match self.current_hunk {
+            _ if self.hunks.is_empty() => None,
+            Some(hunk) => Some(hunk.min(self.hunks.len() - 1)),
+            None => Some(0),
+        };
+        self.scroll = self.scroll.min(self.rows.len().saturating_sub(1));
+    }
+    
+    /// Lines the two sides up, hunk by hunk.
+    fn layout(&self, new_len: usize) -> Vec<DiffRow> {
+        let mut rows = Vec::new();
+        let (mut old_line, mut new_line) = (0, 0);
+        let context = |rows: &mut Vec<DiffRow>, old: Range<usize>, new: usize| {
+            rows.extend(old.enumerate().map(|(i, old)| DiffRow {
+                old: Some(old),
+                new: Some(new + i),
+                hunk: None,
+            }));
+        };
+        for (index, hunk) in self.hunks.iter().enumerate() {
+            context(&mut rows, old_line..hunk.old.start, new_line);
+            let hunk_row = |old: Option<usize>, new: Option<usize>| DiffRow { old, new, hunk: Some(index) };
+            match self.mode {
+                DiffMode::SideBySide => {
+                    let len = hunk.old.len().max(hunk.new.len());
+                    rows.extend((0..len).map(|i| hunk_row(
+                        Some(hunk.old.start + i).filter(|line| hunk.old.contains(line)),
+                        Some(hunk.new.start + i).filter(|line| hunk.new.contains(line)),
+                    )));
+                }
+                DiffMode::Inline => {
+                    rows.extend(hunk.old.clone().map(|line| hunk_row(Some(line), None)));
+                    rows.extend(hunk.new.clone().map(|line| hunk_row(None, Some(line))));
+                }
+            }
+            (old_line, new_line) = (hunk.old.end, hunk.new.end);
+        }
+        context(&mut rows, old_line..self.old.len_lines(), new_line);
+        debug_assert_eq!(rows.last().and_then(|row| row.new), new_len.checked_sub(1));
+        rows
+    }
+    
+    pub fn old(&self) -> &Rope {
+        &self.old
+    }
+    
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+    
+    pub fn current_hunk(&self) -> Option<usize> {
+        self.current_hunk
+    }
+    
+    /// Makes `hunk` the current one and scrolls it into view, a few lines below the top.
+    pub fn select_hunk(&mut self, hunk: usize) -> bool {
+        if hunk >= self.hunks.len() {
+            return false;
+        }
+        self.current_hunk = Some(hunk);
+        if let Some(row) = self.rows.iter().position(|row| row.hunk == Some(hunk)) {
+            self.scroll = row.saturating_sub(HUNK_MARGIN);
+        }
+        true
+    }
+    
+    pub fn next_hunk(&mut self) -> bool {
+        self.select_hunk(self.current_hunk.map_or(0, |hunk| hunk + 1))
+    }
+    
+    pub fn prev_hunk(&mut self) -> bool {
+        self.current_hunk.and_then(|hunk| hunk.checked_sub(1)).is_some_and(|hunk| self.select_hunk(hunk))
+    }
+    
+    /// Puts the old lines of the current hunk back in the buffer, as a single undo step.
+    pub fn revert_hunk(&mut self, editor: &mut TextEditor) -> bool {
+        self.update(editor);
+        let Some(hunk) = self.current_hunk.and_then(|hunk| self.hunks.get(hunk)) else {
+            return false;
+        };
+        let old = self.old.slice(line_char_range(&self.old, &hunk.old)).to_string();
+        let new = line_char_range(&editor.rope, &hunk.new);
+        let reverted = editor.transaction(|editor| {
+            editor.try_remove(new.clone()).is_ok() && editor.try_insert(new.start, &old).is_ok()
+        });
+        if reverted {
+            editor.set_cursor(editor.char_to_position(new.start));
+        }
+        reverted
+    }
+    
+    pub fn scroll_by(&mut self, rows: isize) {
+        self.scroll = self.scroll.saturating_add_signed(rows).min(self.rows.len().saturating_sub(1));
+    }
+    
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            DiffMode::SideBySide => DiffMode::Inline,
+            DiffMode::Inline => DiffMode::SideBySide,
+        };
+    }
+    
+    /// `n` and `p` move between hunks, `r` reverts the current one and `Tab` switches
+    /// between side by side and inline. Esc or `q` closes the diff.
+    pub fn handle_key(&mut self, key: KeyEvent, editor: &mut TextEditor) -> DiffEvent {
+        if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return DiffEvent::Ignored;
+        }
+        self.update(editor);
+        let page = self.height.max(1) as isize;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return DiffEvent::Close,
+            KeyCode::Char('n' | ']') => _ = self.next_hunk(),
+            KeyCode::Char('p' | '[') => _ = self.prev_hunk(),
+            KeyCode::Char('r') => _ = self.revert_hunk(editor),
+            KeyCode::Tab => self.toggle_mode(),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_by(page),
+            KeyCode::PageUp => self.scroll_by(-page),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => self.scroll = self.rows.len().saturating_sub(1),
+            _ => return DiffEvent::Ignored,
+        }
+        DiffEvent::Handled
+    }
+}
+
+/// The chars of `lines`, line endings included.
+fn line_char_range(rope: &Rope, lines: &Range<usize>) -> Range<usize> {
+    rope.line_to_char(lines.start)..rope.line_to_char(lines.end)
+}
+
+/// The chars of a line without its line ending, with tabs expanded.
+fn display_chars(line: RopeSlice) -> Vec<char> {
+    let mut chars = Vec::new();
+    for chr in line.chars().filter(|&chr| chr != '\n' && chr != '\r') {
+        if chr == '\t' {
+            let width = TextEditor::TAB_WIDTH - chars.len() % TextEditor::TAB_WIDTH;
+            chars.extend(std::iter::repeat_n(' ', width));
+        } else {
+            chars.push(chr);
+        }
+    }
+    chars
+}
+
+/// The columns that changed within a pair of lines.
+struct LineChanges {
+    old: Vec<Range<usize>>,
+    new: Vec<Range<usize>>,
+}
+
+/// `None` if the lines have too little in common for picking out changes to help, in
+/// which case the whole lines count as changed.
+fn changed_cols(old: &[char], new: &[char]) -> Option<LineChanges> {
+    let hunks = diff(old, new);
+    let changed: usize = hunks.iter().map(|hunk| hunk.old.len().max(hunk.new.len())).sum();
+    if changed * 2 > old.len().max(new.len()) {
+        return None;
+    }
+    let (old, new) = hunks.into_iter().map(|hunk| (hunk.old, hunk.new)).unzip();
+    Some(LineChanges { old, new })
+}
+
+/// Shows a [`DiffState`], side by side or inline. Changed lines are colored, and the
+/// changes within them more strongly.
+pub struct DiffView<'a> {
+    editor: &'a TextEditor,
+    new_name: String,
+    style: Style,
+    header_style: Style,
+    gutter_style: Style,
+    current_hunk_style: Style,
+    added_style: Style,
+    removed_style: Style,
+    added_text_style: Style,
+    removed_text_style: Style,
+    filler_style: Style,
+}
+
+impl<'a> DiffView<'a> {
+    /// The editor is the new side.
+    pub fn new(editor: &'a TextEditor) -> Self {
+        let new_name = editor.path.as_deref()
+            .and_then(Path::file_name)
+            .map_or_else(|| String::from("[No Name]"), |name| name.to_string_lossy().into_owned());
+        Self {
+            editor,
+            new_name,
+            style: Style::new(),
+            header_style: Style::new().bg(Color::Rgb(37, 41, 50)).add_modifier(Modifier::BOLD),
+            gutter_style: Style::new().fg(Color::DarkGray),
+            current_hunk_style: Style::new().fg(Color::Rgb(229, 192, 123)),
+            added_style: Style::new().bg(Color::Rgb(40, 58, 42)),
+            removed_style: Style::new().bg(Color::Rgb(66, 40, 44)),
+            added_text_style: Style::new().bg(Color::Rgb(58, 94, 60)),
+            removed_text_style: Style::new().bg(Color::Rgb(110, 52, 58)),
+            filler_style: Style::new().fg(Color::Rgb(62, 68, 81)),
+        }
+    }
+    
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.style(theme.style("diff"))
+            .header_style(theme.style("diff.header"))
+            .gutter_style(theme.style("diff.gutter"))
+            .current_hunk_style(theme.style("diff.current_hunk"))
+            .added_style(theme.style("diff.added"))
+            .removed_style(theme.style("diff.removed"))
+            .added_text_style(theme.style("diff.added_text"))
+            .removed_text_style(theme.style("diff.removed_text"))
+            .filler_style(theme.style("diff.filler"))
+    }
+    
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn header_style(mut self, style: Style) -> Self {
+        self.header_style = style;
+        self
+    }
+    
+    pub fn gutter_style(mut self, style: Style) -> Self {
+        self.gutter_style = style;
+        self
+    }
+    
+    /// Patched onto the line numbers of the current hunk.
+    pub fn current_hunk_style(mut self, style: Style) -> Self {
+        self.current_hunk_style = style;
+        self
+    }
+    
+    pub fn added_style(mut self, style: Style) -> Self {
+        self.added_style = style;
+        self
+    }
+    
+    pub fn removed_style(mut self, style: Style) -> Self {
+        self.removed_style = style;
+        self
+    }
+    
+    /// Patched onto the changes within added lines.
+    pub fn added_text_style(mut self, style: Style) -> Self {
+        self.added_text_style = style;
+        self
+    }
+    
+    /// Patched onto the changes within removed lines.
+    pub fn removed_text_style(mut self, style: Style) -> Self {
+        self.removed_text_style = style;
+        self
+    }
+    
+    /// For the side of a row that has no line, across from added or removed lines.
+    pub fn filler_style(mut self, style: Style) -> Self {
+        self.filler_style = style;
+        self
+    }
+    
+    /// The partner of a changed line on the other side, for finding changes within it.
+    fn partner(state: &DiffState, row: DiffRow) -> Option<(usize, usize)> {
+        let hunk = &state.hunks[row.hunk?];
+        match (row.old, row.new) {
+            (Some(old), Some(new)) => Some((old, new)),
+            (Some(old), None) => Some((old, hunk.new.start + (old - hunk.old.start))).filter(|(_, new)| hunk.new.contains(new)),
+            (None, Some(new)) => Some((hunk.old.start + (new - hunk.new.start), new)).filter(|(old, _)| hunk.old.contains(old)),
+            (None, None) => None,
+        }
+    }
+    
+    /// Draws a line of one side with its number. `sign` marks inline rows.
+    #[allow(clippy::too_many_arguments)]
+    fn render_line(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        number_width: usize,
+        number: Option<usize>,
+        sign: Option<char>,
+        chars: Option<&[char]>,
+        changed: Option<&[Range<usize>]>,
+        line_style: Style,
+        text_style: Style,
+        gutter_style: Style,
+    ) {
+        let number = number.map_or_else(String::new, |line| (line + 1).to_string());
+        let gutter = match sign {
+            Some(sign) => format!("{number:>number_width$} {sign} "),
+            None => format!("{number:>number_width$} "),
+        };
+        let (x, _) = buf.set_stringn(area.x, area.y, &gutter, area.width as usize, gutter_style);
+        let text_area = Rect::new(x, area.y, area.right().saturating_sub(x), 1);
+        let Some(chars) = chars else {
+            // Hatched, so that it's clear there's no line there.
+            let filler: String = "╱".repeat(text_area.width as usize);
+            buf.set_string(text_area.x, text_area.y, filler, self.filler_style);
+            return;
+        };
+        buf.set_style(text_area, line_style);
+        for (col, (&chr, x)) in chars.iter().zip(text_area.x..text_area.right()).enumerate() {
+            let cell = &mut buf[(x, area.y)];
+            cell.set_char(chr);
+            if changed.is_some_and(|changed| changed.iter().any(|range| range.contains(&col))) {
+                cell.set_style(text_style);
+            }
+        }
+    }
+}
+
+impl StatefulWidget for DiffView<'_> {
+    type State = DiffState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        buf.set_style(area, self.style);
+        state.update(self.editor);
+        if area.height < 2 {
+            return;
+        }
+        let header_area = Rect::new(area.x, area.y, area.width, 1);
+        let body = Rect::new(area.x, area.y + 1, area.width, area.height - 1);
+        state.height = body.height as usize;
+        let position = match state.current_hunk {
+            Some(hunk) => format!("hunk {}/{}", hunk + 1, state.hunks.len()),
+            None => String::from("no changes"),
+        };
+        buf.set_style(header_area, self.header_style);
+        let number_width = self.editor.rope.len_lines().max(state.old.len_lines()).to_string().len();
+        let (columns, separator) = match state.mode {
+            DiffMode::SideBySide => {
+                let half = body.width.saturating_sub(1) / 2;
+                let left = Rect::new(body.x, body.y, half, body.height);
+                let right = Rect::new(body.x + half + 1, body.y, body.width - half - 1, body.height);
+                buf.set_stringn(left.x, header_area.y, format!(" {}", state.old_name), left.width as usize, self.header_style);
+                buf.set_stringn(right.x, header_area.y, format!(" {}", self.new_name), right.width as usize, self.header_style);
+                (vec![left, right], Some(Rect::new(body.x + half, body.y, 1, body.height)))
+            }
+            DiffMode::Inline => {
+                let title = format!(" {} → {}", state.old_name, self.new_name);
+                buf.set_stringn(body.x, header_area.y, title, body.width as usize, self.header_style);
+                (vec![body], None)
+            }
+        };
+        let position_x = area.right().saturating_sub(position.len() as u16 + 1);
+        buf.set_stringn(position_x, header_area.y, &position, area.right().saturating_sub(position_x) as usize, self.header_style);
+        if let Some(separator) = separator {
+            for y in separator.top()..separator.bottom() {
+                buf[(separator.x, y)].set_char('│').set_style(self.gutter_style);
+            }
+        }
+        for (y, row) in (body.top()..body.bottom()).zip(state.rows.iter().skip(state.scroll).copied()) {
+            let gutter_style = if row.hunk.is_some() && row.hunk == state.current_hunk {
+                self.gutter_style.patch(self.current_hunk_style)
+            } else {
+                self.gutter_style
+            };
+            let old_chars = row.old.map(|line| display_chars(state.old.line(line)));
+            let new_chars = row.new.map(|line| display_chars(self.editor.rope.line(line)));
+            // Changes within a line are only worked out for the rows in view.
+            let changed = Self::partner(state, row).and_then(|(old, new)| {
+                let old = old_chars.clone().unwrap_or_else(|| display_chars(state.old.line(old)));
+                let new = new_chars.clone().unwrap_or_else(|| display_chars(self.editor.rope.line(new)));
+                changed_cols(&old, &new)
+            });
+            let (old_changed, new_changed) = changed.map_or((None, None), |changes| (Some(changes.old), Some(changes.new)));
+            let (old_style, new_style) = match row.hunk {
+                Some(_) => (self.removed_style, self.added_style),
+                None => (Style::new(), Style::new()),
+            };
+            match state.mode {
+                DiffMode::SideBySide => {
+                    let [left, right] = [columns[0], columns[1]].map(|column| Rect::new(column.x, y, column.width, 1));
+                    self.render_line(left, buf, number_width, row.old, None, old_chars.as_deref(), old_changed.as_deref(), old_style, self.removed_text_style, gutter_style);
+                    self.render_line(right, buf, number_width, row.new, None, new_chars.as_deref(), new_changed.as_deref(), new_style, self.added_text_style, gutter_style);
+                }
+                DiffMode::Inline => {
+                    let line_area = Rect::new(columns[0].x, y, columns[0].width, 1);
+                    if row.new.is_none() {
+                        self.render_line(line_area, buf, number_width, row.old, Some('-'), old_chars.as_deref(), old_changed.as_deref(), old_style, self.removed_text_style, gutter_style);
+                    } else {
+                        let sign = if row.hunk.is_some() { '+' } else { ' ' };
+                        self.render_line(line_area, buf, number_width, row.new, Some(sign), new_chars.as_deref(), new_changed.as_deref(), new_style, self.added_text_style, gutter_style);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_edit::command::EditorCommand;
+    
+    fn hunk(old: Range<usize>, new: Range<usize>) -> Hunk {
+        Hunk { old, new }
+    }
+    
+    /// The length of the longest common subsequence, the slow way.
+    fn lcs_len(old: &[u8], new: &[u8]) -> usize {
+        let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lengths[i][j] = if old[i] == new[j] {
+                    lengths[i + 1][j + 1] + 1
+                } else {
+                    lengths[i + 1][j].max(lengths[i][j + 1])
+                };
+            }
+        }
+        lengths[0][0]
+    }
+    
+    /// Checks that the hunks turn `old` into `new`, and that they change as little as can be.
+    fn check(old: &[u8], new: &[u8]) {
+        let hunks = diff(old, new);
+        let (mut old_end, mut new_end) = (0, 0);
+        for hunk in &hunks {
+            assert!(!hunk.old.is_empty() || !hunk.new.is_empty(), "{hunks:?}");
+            assert!(hunk.old.start >= old_end && hunk.new.start >= new_end, "{hunks:?}");
+            assert_eq!(old[old_end..hunk.old.start], new[new_end..hunk.new.start], "{hunks:?}");
+            (old_end, new_end) = (hunk.old.end, hunk.new.end);
+        }
+        assert_eq!(old[old_end..], new[new_end..], "{hunks:?}");
+        let changed: usize = hunks.iter().map(|hunk| hunk.old.len() + hunk.new.len()).sum();
+        assert_eq!(changed, old.len() + new.len() - 2 * lcs_len(old, new), "{old:?} {new:?} {hunks:?}");
+    }
+    
+    #[test]
+    fn diffs_are_minimal() {
+        let texts = ["", "a", "ab", "abc", "abcabba", "cbabac", "xaxbx", "aaaa", "bbb", "abcdefg", "gfedcba", "acegbdf"];
+        for old in texts {
+            for new in texts {
+                check(old.as_bytes(), new.as_bytes());
+            }
+        }
+    }
+    
+    #[test]
+    fn diffs_lines() {
+        let old = Rope::from_str("one\ntwo\nthree\nfour\n");
+        assert_eq!(diff_lines(&old, &old), []);
+        let new = Rope::from_str("one\n2\nthree\nfour\nfive\n");
+        assert_eq!(diff_lines(&old, &new), [hunk(1..2, 1..2), hunk(4..4, 4..5)]);
+        let new = Rope::from_str("zero\none\nfour\n");
+        assert_eq!(diff_lines(&old, &new), [hunk(0..0, 0..1), hunk(1..3, 2..2)]);
+        // A missing line ending changes the last line.
+        let new = Rope::from_str("one\ntwo\nthree\nfour");
+        assert_eq!(diff_lines(&old, &new), [hunk(3..5, 3..4)]);
+    }
+    
+    fn editor(text: &str) -> TextEditor {
+        let mut editor = TextEditor::new();
+        editor.execute(&EditorCommand::InsertText(text.to_owned()));
+        editor
+    }
+    
+    #[test]
+    fn lays_out_rows() {
+        let new = editor("a\nB\nC\nd");
+        let mut state = DiffState::new(Rope::from_str("a\nb\nd"), "old");
+        state.update(&new);
+        let row = |old, new, hunk| DiffRow { old, new, hunk };
+        assert_eq!(state.rows, [
+            row(Some(0), Some(0), None),
+            row(Some(1), Some(1), Some(0)),
+            row(None, Some(2), Some(0)),
+            row(Some(2), Some(3), None),
+        ]);
+        state.toggle_mode();
+        state.update(&new);
+        assert_eq!(state.rows, [
+            row(Some(0), Some(0), None),
+            row(Some(1), None, Some(0)),
+            row(None, Some(1), Some(0)),
+            row(None, Some(2), Some(0)),
+            row(Some(2), Some(3), None),
+        ]);
+    }
+    
+    #[test]
+    fn moves_between_and_reverts_hunks() {
+        let mut new = editor("1\nb\nc\n4\ne\n");
+        let mut state = DiffState::new(Rope::from_str("a\nb\nc\nd\ne\n"), "old");
+        state.update(&new);
+        assert_eq!(state.hunks(), [hunk(0..1, 0..1), hunk(3..4, 3..4)]);
+        assert_eq!(state.current_hunk(), Some(0));
+        assert!(!state.prev_hunk());
+        assert!(state.next_hunk());
+        assert!(!state.next_hunk());
+        assert_eq!(state.current_hunk(), Some(1));
+        
+        assert!(state.revert_hunk(&mut new));
+        assert_eq!(new.rope, "1\nb\nc\nd\ne\n");
+        state.update(&new);
+        assert_eq!(state.hunks(), [hunk(0..1, 0..1)]);
+        assert_eq!(state.current_hunk(), Some(0));
+        assert!(state.revert_hunk(&mut new));
+        assert_eq!(new.rope, "a\nb\nc\nd\ne\n");
+        state.update(&new);
+        assert_eq!(state.current_hunk(), None);
+        assert!(!state.revert_hunk(&mut new));
+        
+        // Each revert is one undo step.
+        new.undo();
+        assert_eq!(new.rope, "1\nb\nc\nd\ne\n");
+    }
+    
+    #[test]
+    fn finds_changes_within_lines() {
+        let chars = |text: &str| text.chars().collect::<Vec<_>>();
+        let changes = changed_cols(&chars("let value = 1;"), &chars("let value = 10;")).unwrap();
+        #[allow(clippy::single_range_in_vec_init, reason = "Lists of changes that happen to have one change.")]
+        let expected = (vec![13..13], vec![13..14]);
+        assert_eq!((changes.old, changes.new), expected);
+        assert!(changed_cols(&chars("abcd"), &chars("wxyz")).is_none());
+        assert_eq!(display_chars(RopeSlice::from("a\tb\r\n")), chars("a   b"));
+    }
+}
@@ -0,0 +1,629 @@
+use std::{collections::VecDeque, ops::Range};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::Direction;
+
+use super::{
+    command::{default_binding, EditorCommand},
+    find_bar::{FindBarEvent, FindBarState},
+    workspace::{workspace_binding, WorkspaceCommand},
+    Selection,
+    SelectionKind,
+    TextEditor,
+    TextPosition,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EmacsEvent {
+    Handled,
+    /// `C-x C-c`.
+    Quit,
+    /// Window and buffer commands such as `C-x 2`, for whoever owns the panes.
+    Workspace(WorkspaceCommand),
+}
+
+/// Killed text, newest first. Consecutive kills are joined into a single entry.
+#[derive(Debug, Clone, Default)]
+pub struct KillRing {
+    entries: VecDeque<String>,
+}
+
+impl KillRing {
+    pub const MAX_ENTRIES: usize = 60;
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    pub fn push(&mut self, text: String) {
+        self.entries.push_front(text);
+        self.entries.truncate(Self::MAX_ENTRIES);
+    }
+    
+    /// Joins `text` onto the newest entry, in front of it for backward kills.
+    pub fn append(&mut self, text: &str, backward: bool) {
+        match self.entries.front_mut() {
+            Some(newest) if backward => newest.insert_str(0, text),
+            Some(newest) => newest.push_str(text),
+            None => self.push(text.to_owned()),
+        }
+    }
+    
+    /// The entry `index` places back from the newest one, wrapping around the ring.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.entries.get(index % self.entries.len()).map(String::as_str)
+    }
+}
+
+/// What the previous command was, for the commands that behave differently when repeated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LastCommand {
+    Other,
+    Kill,
+    /// The yanked text's range at each cursor and its kill ring index, so that `M-y` can replace it.
+    Yank {
+        ranges: Vec<Range<usize>>,
+        index: usize,
+    },
+}
+
+struct Isearch {
+    bar: FindBarState,
+    /// Where `C-g` returns to.
+    origin: TextPosition,
+}
+
+fn is_word_char(chr: char) -> bool {
+    chr.is_alphanumeric()
+}
+
+/// The register that `C-x (` records into and `C-x e` plays, since Emacs has one keyboard macro.
+pub const MACRO_REGISTER: char = 'e';
+
+/// An Emacs (and Readline) style input layer that drives a [`TextEditor`] through [`EditorCommand`]s.
+/// Keys without an Emacs binding fall through to [`default_binding`].
+pub struct Emacs {
+    pub kill_ring: KillRing,
+    last_command: LastCommand,
+    /// The mark stays set after the region is deactivated, so that `C-x C-x` can return to it.
+    mark: Option<TextPosition>,
+    /// Set after `C-x` while waiting for the rest of the key sequence.
+    prefix: bool,
+    isearch: Option<Isearch>,
+    last_isearch: String,
+    message: Option<String>,
+}
+
+impl Emacs {
+    pub fn new() -> Self {
+        Self {
+            kill_ring: KillRing::default(),
+            last_command: LastCommand::Other,
+            mark: None,
+            prefix: false,
+            isearch: None,
+            last_isearch: String::new(),
+            message: None,
+        }
+    }
+    
+    /// The result of the last command, shown in the echo area.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+    
+    /// The unfinished key sequence.
+    pub fn pending(&self) -> &str {
+        if self.prefix { "C-x-" } else { "" }
+    }
+    
+    /// The find bar of the incremental search in progress.
+    pub fn isearch(&mut self) -> Option<&mut FindBarState> {
+        self.isearch.as_mut().map(|isearch| &mut isearch.bar)
+    }
+    
+    pub fn handle_key(&mut self, key: KeyEvent, editor: &mut TextEditor) -> EmacsEvent {
+        self.message = None;
+        // Keys that incremental search doesn't use end it and then run as usual.
+        if self.isearch.is_some() && self.handle_isearch_key(key, editor) {
+            return EmacsEvent::Handled;
+        }
+        let last_command = std::mem::replace(&mut self.last_command, LastCommand::Other);
+        if std::mem::take(&mut self.prefix) {
+            return self.handle_prefix_key(key, editor);
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let command = match key.code {
+            KeyCode::Char('a') if ctrl => EditorCommand::MoveLineStart,
+            KeyCode::Char('e') if ctrl => EditorCommand::MoveLineEnd,
+            KeyCode::Char('f') if ctrl => EditorCommand::MoveRight,
+            KeyCode::Char('b') if ctrl => EditorCommand::MoveLeft,
+            KeyCode::Char('n') if ctrl => EditorCommand::MoveDown,
+            KeyCode::Char('p') if ctrl => EditorCommand::MoveUp,
+            KeyCode::Char('f') if alt => EditorCommand::MoveTo(editor.char_to_position(self.forward_word(editor))),
+            KeyCode::Char('b') if alt => EditorCommand::MoveTo(editor.char_to_position(self.backward_word(editor))),
+            KeyCode::Char('<') if alt => EditorCommand::MoveTo(TextPosition::new(0, 0)),
+            KeyCode::Char('>') if alt => EditorCommand::MoveTo(editor.char_to_position(editor.rope.len_chars())),
+            KeyCode::Char('v') if ctrl => return self.scroll_page(editor, EditorCommand::MoveDown),
+            KeyCode::Char('v') if alt => return self.scroll_page(editor, EditorCommand::MoveUp),
+            KeyCode::Char('d') if ctrl => {
+                self.deactivate_mark(editor);
+                EditorCommand::Delete
+            }
+            KeyCode::Char('k') if ctrl => {
+                let range = self.kill_line_range(editor);
+                self.kill(editor, range, false, &last_command);
+                return EmacsEvent::Handled;
+            }
+            KeyCode::Char('d') if alt => {
+                let range = editor.cursor_char_index()..self.forward_word(editor);
+                self.kill(editor, range, false, &last_command);
+                return EmacsEvent::Handled;
+            }
+            KeyCode::Backspace if alt => {
+                let range = self.backward_word(editor)..editor.cursor_char_index();
+                self.kill(editor, range, true, &last_command);
+                return EmacsEvent::Handled;
+            }
+            KeyCode::Char('w') if ctrl => {
+                match editor.selection_range() {
+                    Some(range) => self.kill(editor, range, false, &last_command),
+                    None => self.message = Some(String::from("The mark is not active now")),
+                }
+                return EmacsEvent::Handled;
+            }
+            KeyCode::Char('w') if alt => {
+                match editor.selected_text() {
+                    Some(text) => {
+                        self.kill_ring.push(text);
+                        self.copy_to_clipboard(editor);
+                        self.deactivate_mark(editor);
+                    }
+                    None => self.message = Some(String::from("The mark is not active now")),
+                }
+                return EmacsEvent::Handled;
+            }
+            KeyCode::Char('y') if ctrl => {
+                self.pull_clipboard(editor);
+                self.yank(editor, 0);
+                return EmacsEvent::Handled;
+            }
+            KeyCode::Char('y') if alt => {
+                self.yank_pop(editor, last_command);
+                return EmacsEvent::Handled;
+            }
+            // Terminals send C-SPC as C-@.
+            KeyCode::Char(' ' | '@') if ctrl => {
+                self.mark = Some(editor.cursor);
+                self.message = Some(String::from("Mark set"));
+                EditorCommand::StartSelection(SelectionKind::Exclusive)
+            }
+            KeyCode::Char('g') if ctrl => {
+                self.message = Some(String::from("Quit"));
+                EditorCommand::ClearSelection
+            }
+            // C-/ arrives as C-_ or C-7 depending on the terminal.
+            KeyCode::Char('/' | '_' | '7') if ctrl => EditorCommand::Undo,
+            KeyCode::Char('o') if ctrl => {
+                let cursor = editor.cursor;
+                self.deactivate_mark(editor);
+                editor.execute(&EditorCommand::InsertText(String::from("\n")));
+                EditorCommand::MoveTo(cursor)
+            }
+            KeyCode::Char('s') if ctrl => return self.start_isearch(editor, false),
+            KeyCode::Char('r') if ctrl => return self.start_isearch(editor, true),
+            KeyCode::Char('x') if ctrl => {
+                self.prefix = true;
+                return EmacsEvent::Handled;
+            }
+            _ if let Some(command) = workspace_binding(key) => return EmacsEvent::Workspace(command),
+            _ => match default_binding(key) {
+                Some(command) => {
+                    if !Self::is_motion(&command) {
+                        self.deactivate_mark(editor);
+                    }
+                    command
+                }
+                None => return EmacsEvent::Handled,
+            },
+        };
+        editor.execute(&command);
+        EmacsEvent::Handled
+    }
+    
+    fn handle_prefix_key(&mut self, key: KeyEvent, editor: &mut TextEditor) -> EmacsEvent {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => return EmacsEvent::Quit,
+            KeyCode::Char('s') if ctrl => {
+                self.message = Some(match editor.save() {
+                    Ok(()) => match &editor.path {
+                        Some(path) => format!("Wrote {}", path.display()),
+                        None => String::from("Wrote buffer"),
+                    },
+                    Err(err) => format!("Error saving: {err}"),
+                });
+            }
+            KeyCode::Char('x') if ctrl => self.exchange_point_and_mark(editor),
+            KeyCode::Char('h') if !ctrl => {
+                editor.execute(&EditorCommand::MoveTo(TextPosition::new(0, 0)));
+                self.mark = Some(editor.cursor);
+                editor.execute(&EditorCommand::StartSelection(SelectionKind::Exclusive));
+                editor.execute(&EditorCommand::MoveTo(editor.char_to_position(editor.rope.len_chars())));
+                // Like Emacs, point ends up at the start and the mark at the end.
+                self.exchange_point_and_mark(editor);
+            }
+            KeyCode::Char('u') if !ctrl => editor.execute(&EditorCommand::Undo),
+            KeyCode::Char('(') => {
+                self.message = Some(match editor.recording_macro() {
+                    Some(_) => String::from("Already defining keyboard macro"),
+                    None => {
+                        editor.start_recording(MACRO_REGISTER);
+                        String::from("Defining kbd macro...")
+                    }
+                });
+            }
+            KeyCode::Char(')') => {
+                self.message = Some(match editor.stop_recording() {
+                    Some(_) => String::from("Keyboard macro defined"),
+                    None => String::from("Not defining kbd macro"),
+                });
+            }
+            // Like Emacs, this ends a macro being defined before playing it.
+            KeyCode::Char('e') if !ctrl => {
+                editor.stop_recording();
+                if !editor.play_macro(MACRO_REGISTER, 1) {
+                    self.message = Some(String::from("No kbd macro has been defined"));
+                }
+            }
+            KeyCode::Char('2') => return EmacsEvent::Workspace(WorkspaceCommand::Split(Direction::Vertical)),
+            KeyCode::Char('3') => return EmacsEvent::Workspace(WorkspaceCommand::Split(Direction::Horizontal)),
+            KeyCode::Char('o') if !ctrl => return EmacsEvent::Workspace(WorkspaceCommand::FocusNext),
+            KeyCode::Char('0') => return EmacsEvent::Workspace(WorkspaceCommand::ClosePane),
+            KeyCode::Char('1') => return EmacsEvent::Workspace(WorkspaceCommand::CloseOtherPanes),
+            KeyCode::Right => return EmacsEvent::Workspace(WorkspaceCommand::NextBuffer),
+            KeyCode::Left => return EmacsEvent::Workspace(WorkspaceCommand::PrevBuffer),
+            KeyCode::Char('g') if ctrl => self.message = Some(String::from("Quit")),
+            _ => self.message = Some(format!("C-x {} is undefined", key.code)),
+        }
+        EmacsEvent::Handled
+    }
+    
+    /// `C-s`/`C-r` move between matches, or reuse the previous search if nothing has been typed yet.
+    fn repeat_isearch(&mut self, editor: &mut TextEditor, backward: bool) {
+        let Some(isearch) = &mut self.isearch else {
+            return;
+        };
+        isearch.bar.backward = backward;
+        if isearch.bar.query.is_empty() {
+            isearch.bar.query = self.last_isearch.clone();
+            isearch.bar.update(editor);
+        } else if backward {
+            isearch.bar.find_prev(editor);
+        } else {
+            isearch.bar.find_next(editor);
+        }
+    }
+    
+    /// Returns `false` if the key ended the search without being used.
+    fn handle_isearch_key(&mut self, key: KeyEvent, editor: &mut TextEditor) -> bool {
+        let Some(isearch) = &mut self.isearch else {
+            return false;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char('s') if ctrl => self.repeat_isearch(editor, false),
+            KeyCode::Char('r') if ctrl => self.repeat_isearch(editor, true),
+            KeyCode::Char('g') if ctrl => {
+                let origin = isearch.origin;
+                self.end_isearch(editor);
+                editor.execute(&EditorCommand::MoveTo(origin));
+                self.message = Some(String::from("Quit"));
+            }
+            KeyCode::Enter | KeyCode::Esc => self.end_isearch(editor),
+            KeyCode::Backspace | KeyCode::Char(_) if !ctrl && !alt => {
+                if isearch.bar.handle_key(key, editor) == FindBarEvent::Close {
+                    self.end_isearch(editor);
+                }
+            }
+            _ => {
+                self.end_isearch(editor);
+                return false;
+            }
+        }
+        true
+    }
+    
+    fn start_isearch(&mut self, editor: &mut TextEditor, backward: bool) -> EmacsEvent {
+        self.deactivate_mark(editor);
+        let mut bar = FindBarState::new(editor, false);
+        bar.query.clear();
+        bar.backward = backward;
+        bar.update(editor);
+        self.isearch = Some(Isearch {
+            bar,
+            origin: editor.cursor,
+        });
+        EmacsEvent::Handled
+    }
+    
+    fn end_isearch(&mut self, editor: &mut TextEditor) {
+        if let Some(isearch) = self.isearch.take() {
+            self.mark = Some(isearch.origin);
+            if !isearch.bar.query.is_empty() {
+                self.last_isearch = isearch.bar.query;
+            }
+        }
+        editor.search = None;
+        editor.current_match = None;
+    }
+    
+    fn is_motion(command: &EditorCommand) -> bool {
+        matches!(
+            command,
+            EditorCommand::MoveLeft
+            | EditorCommand::MoveRight
+            | EditorCommand::MoveUp
+            | EditorCommand::MoveDown
+            | EditorCommand::MoveLineStart
+            | EditorCommand::MoveLineEnd
+            | EditorCommand::MoveWordLeft
+            | EditorCommand::MoveWordRight
+            | EditorCommand::MoveParagraphUp
+            | EditorCommand::MoveParagraphDown
+            | EditorCommand::PageUp
+            | EditorCommand::PageDown
+            | EditorCommand::MoveDocumentStart
+            | EditorCommand::MoveDocumentEnd
+            | EditorCommand::GoToLine(_)
+            | EditorCommand::MoveTo(_)
+        )
+    }
+    
+    fn deactivate_mark(&mut self, editor: &mut TextEditor) {
+        if editor.selection.is_some() {
+            editor.execute(&EditorCommand::ClearSelection);
+        }
+    }
+    
+    /// Swaps point and mark, and activates the region between them.
+    fn exchange_point_and_mark(&mut self, editor: &mut TextEditor) {
+        let Some(mark) = editor.selection.map(|selection| selection.anchor).or(self.mark) else {
+            self.message = Some(String::from("No mark set in this buffer"));
+            return;
+        };
+        let point = editor.cursor;
+        editor.execute(&EditorCommand::MoveTo(mark));
+        editor.execute(&EditorCommand::SetSelection(Selection {
+            anchor: point,
+            kind: SelectionKind::Exclusive,
+        }));
+        self.mark = Some(point);
+    }
+    
+    fn scroll_page(&mut self, editor: &mut TextEditor, command: EditorCommand) -> EmacsEvent {
+        // Emacs keeps two lines of context when scrolling by a screenful.
+        let lines = (editor.text_area.height as usize).saturating_sub(2).max(1);
+        for _ in 0..lines {
+            editor.execute(&command);
+        }
+        EmacsEvent::Handled
+    }
+    
+    /// The end of the next word after the cursor.
+    fn forward_word(&self, editor: &TextEditor) -> usize {
+        let len = editor.rope.len_chars();
+        let mut index = editor.cursor_char_index();
+        while index < len && !is_word_char(editor.rope.char(index)) {
+            index += 1;
+        }
+        while index < len && is_word_char(editor.rope.char(index)) {
+            index += 1;
+        }
+        index
+    }
+    
+    /// The start of the word before the cursor.
+    fn backward_word(&self, editor: &TextEditor) -> usize {
+        let mut index = editor.cursor_char_index();
+        while index > 0 && !is_word_char(editor.rope.char(index - 1)) {
+            index -= 1;
+        }
+        while index > 0 && is_word_char(editor.rope.char(index - 1)) {
+            index -= 1;
+        }
+        index
+    }
+    
+    /// The rest of the line, or the line break if only whitespace is left.
+    fn kill_line_range(&self, editor: &TextEditor) -> Range<usize> {
+        let start = editor.cursor_char_index();
+        let line = editor.cursor.line.min(editor.rope.len_lines() - 1);
+        let line_end = editor.rope.line_to_char(line) + editor.line_len(line);
+        let rest_is_blank = editor.rope.slice(start.min(line_end)..line_end).chars().all(char::is_whitespace);
+        if rest_is_blank {
+            start..editor.rope.line_to_char((line + 1).min(editor.rope.len_lines())).max(line_end)
+        } else {
+            start..line_end
+        }
+    }
+    
+    fn kill(&mut self, editor: &mut TextEditor, range: Range<usize>, backward: bool, last_command: &LastCommand) {
+        self.last_command = LastCommand::Kill;
+        self.deactivate_mark(editor);
+        if range.is_empty() {
+            return;
+        }
+        let text = editor.rope.slice(range.clone()).to_string();
+        if *last_command == LastCommand::Kill {
+            self.kill_ring.append(&text, backward);
+        } else {
+            self.kill_ring.push(text);
+        }
+        self.copy_to_clipboard(editor);
+        let start = range.start;
+        editor.execute(&EditorCommand::DeleteRange(range));
+        editor.execute(&EditorCommand::MoveTo(editor.char_to_position(start)));
+    }
+    
+    /// The newest kill goes to the clipboard as well, for pasting in other programs.
+    fn copy_to_clipboard(&self, editor: &TextEditor) {
+        if let Some(text) = self.kill_ring.get(0) {
+            _ = editor.clipboard.borrow_mut().set_text(text);
+        }
+    }
+    
+    /// Text copied since the last kill, outside of Emacs's keys, becomes the newest kill.
+    fn pull_clipboard(&mut self, editor: &TextEditor) {
+        let Ok(Some(text)) = editor.clipboard.borrow_mut().get_text() else {
+            return;
+        };
+        if !text.is_empty() && self.kill_ring.get(0) != Some(text.as_str()) {
+            self.kill_ring.push(text);
+        }
+    }
+    
+    /// Inserts the kill ring entry `index` places back at every cursor, leaving the mark at
+    /// the primary cursor's start.
+    fn yank(&mut self, editor: &mut TextEditor, index: usize) {
+        let Some(text) = self.kill_ring.get(index).map(str::to_owned) else {
+            self.message = Some(String::from("Kill ring is empty"));
+            return;
+        };
+        self.deactivate_mark(editor);
+        self.mark = Some(editor.cursor);
+        editor.execute(&EditorCommand::InsertText(text.clone()));
+        // Each cursor is left at the end of its copy.
+        let len = text.chars().count();
+        let ranges = std::iter::once(editor.cursor)
+            .chain(editor.extra_cursors.iter().copied())
+            .map(|cursor| {
+                let end = editor.position_to_char(cursor);
+                end.saturating_sub(len)..end
+            })
+            .collect();
+        self.last_command = LastCommand::Yank {
+            ranges,
+            index,
+        };
+    }
+    
+    /// Replaces the text that was just yanked with the next older kill.
+    fn yank_pop(&mut self, editor: &mut TextEditor, last_command: LastCommand) {
+        let LastCommand::Yank { mut ranges, index } = last_command else {
+            self.message = Some(String::from("Previous command was not a yank"));
+            return;
+        };
+        editor.execute(&EditorCommand::BeginUndoGroup);
+        // Removing the last copy first leaves the others where they were,
+        // and each cursor moves back to the start of its copy.
+        ranges.sort_by_key(|range| range.start);
+        for range in ranges.into_iter().rev() {
+            editor.execute(&EditorCommand::DeleteRange(range));
+        }
+        self.yank(editor, (index + 1) % self.kill_ring.len());
+        editor.execute(&EditorCommand::EndUndoGroup);
+    }
+}
+
+impl Default for Emacs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn editor(text: &str) -> TextEditor {
+        let mut editor = TextEditor::new();
+        editor.execute(&EditorCommand::InsertText(text.to_owned()));
+        editor.execute(&EditorCommand::MoveTo(TextPosition::new(0, 0)));
+        editor
+    }
+    
+    fn press(emacs: &mut Emacs, editor: &mut TextEditor, modifiers: KeyModifiers, chr: char) {
+        emacs.handle_key(KeyEvent::new(KeyCode::Char(chr), modifiers), editor);
+    }
+    
+    #[test]
+    fn kill_ring_joins_and_wraps() {
+        let mut ring = KillRing::default();
+        assert_eq!(ring.get(0), None);
+        ring.append("world", false);
+        ring.append("hello ", true);
+        ring.push(String::from("newer"));
+        ring.append("!", false);
+        assert_eq!(ring.len(), 2);
+        assert_eq!(ring.get(0), Some("newer!"));
+        assert_eq!(ring.get(1), Some("hello world"));
+        assert_eq!(ring.get(2), Some("newer!"));
+        for i in 0..KillRing::MAX_ENTRIES {
+            ring.push(i.to_string());
+        }
+        assert_eq!(ring.len(), KillRing::MAX_ENTRIES);
+        assert_eq!(ring.get(0), Some("59"));
+    }
+    
+    #[test]
+    fn consecutive_kills_yank_as_one() {
+        let mut emacs = Emacs::new();
+        let mut editor = editor("one two\nthree\n");
+        press(&mut emacs, &mut editor, KeyModifiers::CONTROL, 'k');
+        press(&mut emacs, &mut editor, KeyModifiers::CONTROL, 'k');
+        assert_eq!(editor.rope.to_string(), "three\n");
+        assert_eq!(emacs.kill_ring.get(0), Some("one two\n"));
+        // Any other command in between starts a new entry.
+        press(&mut emacs, &mut editor, KeyModifiers::CONTROL, 'a');
+        press(&mut emacs, &mut editor, KeyModifiers::ALT, 'd');
+        assert_eq!(emacs.kill_ring.get(0), Some("three"));
+        press(&mut emacs, &mut editor, KeyModifiers::CONTROL, 'y');
+        press(&mut emacs, &mut editor, KeyModifiers::ALT, 'y');
+        assert_eq!(editor.rope.to_string(), "one two\n\n");
+        press(&mut emacs, &mut editor, KeyModifiers::ALT, 'y');
+        assert_eq!(editor.rope.to_string(), "three\n");
+    }
+    
+    #[test]
+    fn yank_pop_replaces_the_yank_at_every_cursor() {
+        let mut emacs = Emacs::new();
+        let mut editor = editor("a\nb\n");
+        emacs.kill_ring.push(String::from("one "));
+        emacs.kill_ring.push(String::from("two "));
+        editor.extra_cursors.push(TextPosition::new(1, 0));
+        press(&mut emacs, &mut editor, KeyModifiers::CONTROL, 'y');
+        assert_eq!(editor.rope.to_string(), "two a\ntwo b\n");
+        press(&mut emacs, &mut editor, KeyModifiers::ALT, 'y');
+        assert_eq!(editor.rope.to_string(), "one a\none b\n");
+        assert_eq!(editor.cursor, TextPosition::new(0, 4));
+        assert_eq!(editor.extra_cursors, [TextPosition::new(1, 4)]);
+    }
+    
+    #[test]
+    fn kills_round_trip_through_the_clipboard() {
+        let mut emacs = Emacs::new();
+        let mut editor = editor("hello world");
+        press(&mut emacs, &mut editor, KeyModifiers::ALT, 'd');
+        assert_eq!(editor.clipboard.borrow_mut().get_text().unwrap().as_deref(), Some("hello"));
+        
+        // Text copied elsewhere is yanked first, and the kills are still there behind it.
+        editor.clipboard.borrow_mut().set_text("pasted").unwrap();
+        press(&mut emacs, &mut editor, KeyModifiers::CONTROL, 'y');
+        assert_eq!(editor.rope.to_string(), "pasted world");
+        press(&mut emacs, &mut editor, KeyModifiers::ALT, 'y');
+        assert_eq!(editor.rope.to_string(), "hello world");
+        // The clipboard isn't pulled in twice.
+        press(&mut emacs, &mut editor, KeyModifiers::CONTROL, 'y');
+        assert_eq!(emacs.kill_ring.len(), 2);
+    }
+}
@@ -0,0 +1,332 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::prelude::*;
+
+use crate::theme::Theme;
+
+use super::{search::{Search, SearchOptions}, TextEditor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FindField {
+    Find,
+    Replace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FindBarEvent {
+    Handled,
+    Ignored,
+    Close,
+}
+
+/// Stop counting matches past this many so that typing stays responsive in huge buffers.
+const MAX_COUNTED_MATCHES: usize = 9999;
+
+pub struct FindBarState {
+    pub query: String,
+    pub replacement: String,
+    pub options: SearchOptions,
+    pub focus: FindField,
+    pub show_replace: bool,
+    /// Incremental search looks for the nearest match before the origin instead of after it.
+    pub backward: bool,
+    /// Where the search started, so that incremental search restarts from here as the query changes.
+    origin: usize,
+    status: String,
+    error: bool,
+    pub cursor_screen_position: Option<Position>,
+}
+
+impl FindBarState {
+    pub fn new(editor: &TextEditor, show_replace: bool) -> Self {
+        let (query, options) = match &editor.search {
+            Some(search) => (search.pattern().to_owned(), search.options()),
+            None => (String::new(), SearchOptions::default()),
+        };
+        Self {
+            query,
+            replacement: String::new(),
+            options,
+            focus: FindField::Find,
+            show_replace,
+            backward: false,
+            origin: editor.cursor_char_index(),
+            status: String::new(),
+            error: false,
+            cursor_screen_position: None,
+        }
+    }
+    
+    pub fn height(&self) -> u16 {
+        if self.show_replace { 2 } else { 1 }
+    }
+    
+    /// Recompiles the search and jumps to the first match at or after the origin
+    /// (or the last one starting before it when searching backward).
+    pub fn update(&mut self, editor: &mut TextEditor) {
+        match Search::new(&self.query, self.options) {
+            Ok(search) => {
+                self.error = false;
+                let found = if self.backward {
+                    let before = self.origin + self.query.chars().count();
+                    search.find_prev(&editor.rope, before.saturating_sub(1), true)
+                } else {
+                    search.find_next(&editor.rope, self.origin, true)
+                };
+                editor.search = Some(search);
+                self.select(editor, found);
+            }
+            Err(err) => {
+                self.error = true;
+                self.status = err.to_string();
+                editor.search = None;
+                editor.current_match = None;
+            }
+        }
+    }
+    
+    fn select(&mut self, editor: &mut TextEditor, found: Option<std::ops::Range<usize>>) {
+        if let Some(found) = &found {
+            editor.set_cursor(editor.char_to_position(found.start));
+        }
+        editor.current_match = found;
+        self.update_status(editor);
+    }
+    
+    fn update_status(&mut self, editor: &TextEditor) {
+        let Some(search) = editor.search.as_ref().filter(|search| !search.is_empty()) else {
+            self.status.clear();
+            return;
+        };
+        let mut count = 0;
+        let mut index = None;
+        search.for_each_match(&editor.rope, 0..editor.rope.len_chars(), |found| {
+            count += 1;
+            if editor.current_match.as_ref() == Some(&found) {
+                index = Some(count);
+            }
+            count < MAX_COUNTED_MATCHES
+        });
+        let more = if count == MAX_COUNTED_MATCHES { "+" } else { "" };
+        self.status = match (count, index) {
+            (0, _) => String::from("No results"),
+            (_, Some(index)) => format!("{index} of {count}{more}"),
+            (_, None) => format!("{count}{more} results"),
+        };
+    }
+    
+    pub fn find_next(&mut self, editor: &mut TextEditor) {
+        let Some(search) = &editor.search else {
+            return;
+        };
+        let from = match &editor.current_match {
+            Some(current) => current.end,
+            None => editor.cursor_char_index(),
+        };
+        let found = search.find_next(&editor.rope, from, true);
+        if let Some(found) = &found {
+            self.origin = found.start;
+        }
+        self.select(editor, found);
+    }
+    
+    pub fn find_prev(&mut self, editor: &mut TextEditor) {
+        let Some(search) = &editor.search else {
+            return;
+        };
+        let before = match &editor.current_match {
+            Some(current) => current.start,
+            None => editor.cursor_char_index(),
+        };
+        let found = search.find_prev(&editor.rope, before, true);
+        if let Some(found) = &found {
+            self.origin = found.start;
+        }
+        self.select(editor, found);
+    }
+    
+    /// Replaces the current match (if it still matches) and moves to the next one.
+    pub fn replace(&mut self, editor: &mut TextEditor) {
+        if !editor.is_editable() {
+            self.status = String::from("Read-only");
+            return;
+        }
+        let Some(search) = &editor.search else {
+            return;
+        };
+        let Some(current) = editor.current_match.clone().filter(|current| search.is_match(&editor.rope, current)) else {
+            self.find_next(editor);
+            return;
+        };
+        let replacement = search.expand_replacement(&editor.rope, &current, &self.replacement);
+        let replacement_len = replacement.chars().count();
+        editor.transaction(|editor| {
+            editor.try_remove(current.clone()).and_then(|_| editor.try_insert(current.start, &replacement))
+        }).expect("Match was validated against the buffer.");
+        let found = editor.search.as_ref()
+            .and_then(|search| search.find_next(&editor.rope, current.start + replacement_len, true));
+        if let Some(found) = &found {
+            self.origin = found.start;
+        }
+        self.select(editor, found);
+    }
+    
+    /// Replaces every match as a single undo step.
+    pub fn replace_all(&mut self, editor: &mut TextEditor) {
+        if !editor.is_editable() {
+            self.status = String::from("Read-only");
+            return;
+        }
+        let Some(search) = &editor.search else {
+            return;
+        };
+        let matches = search.find_all(&editor.rope);
+        let replacements: Vec<String> = matches.iter()
+            .map(|found| search.expand_replacement(&editor.rope, found, &self.replacement))
+            .collect();
+        editor.transaction(|editor| {
+            // Back to front so that earlier ranges stay valid.
+            for (found, replacement) in matches.iter().zip(&replacements).rev() {
+                editor.try_remove(found.clone()).expect("Match was found in the buffer.");
+                editor.try_insert(found.start, replacement).expect("Match was found in the buffer.");
+            }
+        });
+        editor.current_match = None;
+        editor.request_scroll_to_cursor();
+        self.status = format!("Replaced {}", matches.len());
+    }
+    
+    fn toggle(&mut self, editor: &mut TextEditor, toggle: fn(&mut SearchOptions)) {
+        toggle(&mut self.options);
+        self.update(editor);
+    }
+    
+    pub fn handle_key(&mut self, key: KeyEvent, editor: &mut TextEditor) -> FindBarEvent {
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Esc => return FindBarEvent::Close,
+            KeyCode::Char('c') if alt => self.toggle(editor, |options| options.case_sensitive ^= true),
+            KeyCode::Char('w') if alt => self.toggle(editor, |options| options.whole_word ^= true),
+            KeyCode::Char('r') if alt => self.toggle(editor, |options| options.regex ^= true),
+            KeyCode::Char('a') if alt && self.show_replace => self.replace_all(editor),
+            KeyCode::Enter if ctrl && alt && self.show_replace => self.replace_all(editor),
+            KeyCode::Enter if self.focus == FindField::Replace => self.replace(editor),
+            KeyCode::Enter | KeyCode::F(3) | KeyCode::Down if shift => self.find_prev(editor),
+            KeyCode::Enter | KeyCode::F(3) | KeyCode::Down => self.find_next(editor),
+            KeyCode::Up => self.find_prev(editor),
+            KeyCode::Tab | KeyCode::BackTab if self.show_replace => {
+                self.focus = match self.focus {
+                    FindField::Find => FindField::Replace,
+                    FindField::Replace => FindField::Find,
+                };
+            }
+            KeyCode::Backspace => match self.focus {
+                FindField::Find => {
+                    self.query.pop();
+                    self.update(editor);
+                }
+                FindField::Replace => _ = self.replacement.pop(),
+            },
+            KeyCode::Char(chr) if !ctrl && !alt => match self.focus {
+                FindField::Find => {
+                    self.query.push(chr);
+                    self.update(editor);
+                }
+                FindField::Replace => self.replacement.push(chr),
+            },
+            _ => return FindBarEvent::Ignored,
+        }
+        FindBarEvent::Handled
+    }
+    
+    /// Pasted text goes into the focused field.
+    pub fn paste(&mut self, text: &str, editor: &mut TextEditor) {
+        let text = text.lines().next().unwrap_or_default();
+        match self.focus {
+            FindField::Find => {
+                self.query.push_str(text);
+                self.update(editor);
+            }
+            FindField::Replace => self.replacement.push_str(text),
+        }
+    }
+}
+
+pub struct FindBar {
+    style: Style,
+    toggle_on_style: Style,
+    toggle_off_style: Style,
+    error_style: Style,
+}
+
+impl FindBar {
+    pub fn new() -> Self {
+        Self {
+            style: Style::new().bg(Color::Rgb(37, 41, 50)),
+            toggle_on_style: Style::new().fg(Color::Black).bg(Color::Rgb(97, 175, 239)),
+            toggle_off_style: Style::new().fg(Color::DarkGray),
+            error_style: Style::new().fg(Color::Rgb(224, 108, 117)),
+        }
+    }
+    
+    pub fn theme(mut self, theme: &Theme) -> Self {
+        self.style = theme.style("find_bar");
+        self.toggle_on_style = theme.style("find_bar.toggle_on");
+        self.toggle_off_style = theme.style("find_bar.toggle_off");
+        self.error_style = theme.style("find_bar.error");
+        self
+    }
+}
+
+impl Default for FindBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatefulWidget for FindBar {
+    type State = FindBarState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        buf.set_style(area, self.style);
+        state.cursor_screen_position = None;
+        const LABEL_WIDTH: u16 = 9;
+        let toggles = [
+            ("Aa", state.options.case_sensitive),
+            ("W", state.options.whole_word),
+            (".*", state.options.regex),
+        ];
+        let toggles_width: u16 = toggles.iter().map(|(label, _)| label.len() as u16 + 3).sum();
+        let status_width = (state.status.chars().count() as u16 + 2).min(area.width / 3);
+        let input_width = area.width.saturating_sub(LABEL_WIDTH + toggles_width + status_width);
+        let rows = [
+            (FindField::Find, " Find:", &state.query),
+            (FindField::Replace, " Replace:", &state.replacement),
+        ];
+        for ((field, label, text), y) in rows.into_iter().zip(area.y..area.bottom()) {
+            buf.set_stringn(area.x, y, label, LABEL_WIDTH as usize, self.style);
+            // Show the end of the text if it doesn't fit.
+            let text_len = text.chars().count();
+            let skip = text_len.saturating_sub(input_width.saturating_sub(1) as usize);
+            let visible: String = text.chars().skip(skip).collect();
+            let input_x = area.x + LABEL_WIDTH;
+            buf.set_stringn(input_x, y, &visible, input_width as usize, self.style);
+            if field == state.focus {
+                let cursor_x = input_x + (text_len - skip) as u16;
+                if cursor_x < area.right() {
+                    state.cursor_screen_position = Some(Position::new(cursor_x, y));
+                }
+            }
+            if field == FindField::Find {
+                let mut x = input_x + input_width;
+                for (label, enabled) in toggles {
+                    let style = if enabled { self.toggle_on_style } else { self.toggle_off_style };
+                    buf.set_stringn(x + 1, y, format!("[{label}]"), (area.right().saturating_sub(x + 1)) as usize, style);
+                    x += label.len() as u16 + 3;
+                }
+                let status_style = if state.error { self.error_style } else { self.style };
+                buf.set_stringn(x + 1, y, &state.status, area.right().saturating_sub(x + 1) as usize, status_style);
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+
+use ropey::Rope;
+
+use super::{highlight::{Highlighting, TokenKind}, TextEditor};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FoldMethod {
+    /// `Syntax` when a highlighter is active, otherwise `Indent`.
+    #[default]
+    Auto,
+    /// A line folds every following line that is blank or indented deeper than it.
+    Indent,
+    /// Bracket pairs that span lines, ignoring brackets in strings and comments.
+    /// The closing bracket's line stays visible.
+    Syntax,
+    /// Nothing folds. Used for buffers above [`LargeFileThresholds::folding`](super::large_file::LargeFileThresholds::folding).
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FoldMarker {
+    /// The line starts a region that can be folded.
+    Open,
+    Folded,
+}
+
+/// Foldable regions as header line -> last line hidden when folded.
+pub type FoldRegions = BTreeMap<usize, usize>;
+
+fn line_indent(rope: &Rope, line: usize) -> Option<usize> {
+    let mut indent = 0usize;
+    for chr in rope.line(line).chars() {
+        match chr {
+            ' ' => indent += 1,
+            '\t' => indent = (indent + 1).next_multiple_of(TextEditor::TAB_WIDTH),
+            '\n' | '\r' => return None,
+            _ => return Some(indent),
+        }
+    }
+    None
+}
+
+pub fn indent_regions(rope: &Rope) -> FoldRegions {
+    let mut regions = FoldRegions::new();
+    // Headers whose regions are still open, as (indent, line).
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last_content = 0;
+    for line in 0..rope.len_lines() {
+        let Some(indent) = line_indent(rope, line) else {
+            continue;
+        };
+        while let Some(&(header_indent, header)) = open.last() && header_indent >= indent {
+            open.pop();
+            if last_content > header {
+                regions.insert(header, last_content);
+            }
+        }
+        open.push((indent, line));
+        last_content = line;
+    }
+    for (_, header) in open {
+        if last_content > header {
+            regions.insert(header, last_content);
+        }
+    }
+    regions
+}
+
+/// `highlighting` must be up to date for the whole rope.
+pub fn syntax_regions(rope: &Rope, highlighting: &Highlighting) -> FoldRegions {
+    let mut regions = FoldRegions::new();
+    let mut open: Vec<(char, usize)> = Vec::new();
+    for line_index in 0..rope.len_lines() {
+        let mut token_iter = highlighting.line_tokens(line_index).iter().peekable();
+        for (col, chr) in rope.line(line_index).chars().enumerate() {
+            let opening = match chr {
+                '{' | '[' | '(' => chr,
+                '}' => '{',
+                ']' => '[',
+                ')' => '(',
+                _ => continue,
+            };
+            while token_iter.next_if(|token| token.end <= col).is_some() {}
+            let in_literal = token_iter.peek().is_some_and(|token| {
+                token.start <= col && matches!(token.kind, TokenKind::String | TokenKind::Escape | TokenKind::Comment | TokenKind::Code)
+            });
+            if in_literal {
+                continue;
+            }
+            if opening == chr {
+                open.push((chr, line_index));
+                continue;
+            }
+            // Unmatched closers are ignored, and unclosed openers inside a pair are dropped.
+            if let Some(index) = open.iter().rposition(|&(open_chr, _)| open_chr == opening) {
+                let (_, open_line) = open[index];
+                open.truncate(index);
+                if line_index > open_line + 1 {
+                    let end = regions.entry(open_line).or_insert(0);
+                    *end = (*end).max(line_index - 1);
+                }
+            }
+        }
+    }
+    regions
+}
+
+/// Folded regions as header line -> last hidden line. Folds never overlap.
+/// Line numbers are kept in sync with edits to the buffer.
+#[derive(Debug, Clone, Default)]
+pub struct Folds {
+    folded: BTreeMap<usize, usize>,
+}
+
+impl Folds {
+    pub fn is_empty(&self) -> bool {
+        self.folded.is_empty()
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> {
+        self.folded.iter().map(|(&header, &end)| (header, end))
+    }
+    
+    pub fn clear(&mut self) {
+        self.folded.clear();
+    }
+    
+    pub fn is_folded(&self, header: usize) -> bool {
+        self.folded.contains_key(&header)
+    }
+    
+    /// Folds `header + 1..=end`, absorbing any folds inside it. Does nothing if `header` is hidden.
+    pub fn fold(&mut self, header: usize, end: usize) {
+        if end <= header || self.is_hidden(header) {
+            return;
+        }
+        let nested: Vec<usize> = self.folded.range(header + 1..=end).map(|(&line, _)| line).collect();
+        for line in nested {
+            self.folded.remove(&line);
+        }
+        self.folded.insert(header, end);
+    }
+    
+    pub fn unfold(&mut self, header: usize) -> bool {
+        self.folded.remove(&header).is_some()
+    }
+    
+    /// The fold that hides `line`, as (header, end).
+    pub fn fold_containing(&self, line: usize) -> Option<(usize, usize)> {
+        self.folded.range(..line)
+            .next_back()
+            .filter(|&(_, &end)| end >= line)
+            .map(|(&header, &end)| (header, end))
+    }
+    
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.fold_containing(line).is_some()
+    }
+    
+    /// `line` if it is visible, otherwise the header of the fold that hides it.
+    pub fn visible_line(&self, line: usize) -> usize {
+        self.fold_containing(line).map_or(line, |(header, _)| header)
+    }
+    
+    /// The first visible line after `line`. May be past the end of the buffer.
+    pub fn next_visible(&self, line: usize) -> usize {
+        match self.folded.get(&line) {
+            Some(&end) => end + 1,
+            None => self.fold_containing(line + 1).map_or(line + 1, |(_, end)| end + 1),
+        }
+    }
+    
+    pub fn prev_visible(&self, line: usize) -> Option<usize> {
+        line.checked_sub(1).map(|line| self.visible_line(line))
+    }
+    
+    /// Number of hidden lines in `lines`, which must start on a visible line.
+    pub fn hidden_in(&self, lines: std::ops::Range<usize>) -> usize {
+        self.folded.range(lines.clone())
+            .map(|(&header, &end)| end.min(lines.end.saturating_sub(1)).saturating_sub(header))
+            .sum()
+    }
+    
+    /// Lines `start_line..=old_end_line` were replaced by `start_line..=new_end_line`.
+    /// Folds that the edit touches are opened, except for edits within a single header line.
+    pub(crate) fn lines_changed(&mut self, start_line: usize, old_end_line: usize, new_end_line: usize) {
+        if self.folded.is_empty() {
+            return;
+        }
+        let folded = std::mem::take(&mut self.folded);
+        for (header, end) in folded {
+            if end < start_line {
+                self.folded.insert(header, end);
+            } else if header > old_end_line {
+                self.folded.insert(header + new_end_line - old_end_line, end + new_end_line - old_end_line);
+            } else if header == start_line && old_end_line == header && new_end_line == header {
+                self.folded.insert(header, end);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_edit::highlight::Language;
+    
+    #[test]
+    fn indent_regions_skip_blank_lines() {
+        let rope = Rope::from_str("fn a() {\n    x\n\n    y\n}\nfn b() {\n\tz\n}\n");
+        assert_eq!(indent_regions(&rope), FoldRegions::from([(0, 3), (5, 6)]));
+        assert!(indent_regions(&Rope::from_str("a\nb\n")).is_empty());
+    }
+    
+    #[test]
+    fn syntax_regions_ignore_literals() {
+        let rope = Rope::from_str("fn a() {\n    let s = \"{\";\n    call(\n        1,\n    );\n}\n// {\n[1,\n2]\n");
+        let mut highlighting = Highlighting::new(Language::Rust.highlighter(), &rope);
+        highlighting.update(&rope, rope.len_lines());
+        assert_eq!(syntax_regions(&rope, &highlighting), FoldRegions::from([(0, 4), (2, 3)]));
+    }
+    
+    #[test]
+    fn folds_nest_and_hide_lines() {
+        let mut folds = Folds::default();
+        folds.fold(2, 5);
+        folds.fold(0, 8);
+        assert_eq!(folds.iter().collect::<Vec<_>>(), [(0, 8)]);
+        folds.fold(3, 4);
+        assert_eq!(folds.iter().count(), 1, "A hidden line can't be folded.");
+        assert!(!folds.is_hidden(0));
+        assert!(folds.is_hidden(8));
+        assert!(!folds.is_hidden(9));
+        assert!(folds.unfold(0));
+        assert!(!folds.unfold(0));
+        
+        folds.fold(1, 3);
+        folds.fold(6, 7);
+        assert_eq!(folds.visible_line(7), 6);
+        assert_eq!(folds.next_visible(0), 1);
+        assert_eq!(folds.next_visible(1), 4);
+        assert_eq!(folds.next_visible(5), 6);
+        assert_eq!(folds.prev_visible(4), Some(1));
+        assert_eq!(folds.prev_visible(0), None);
+        assert_eq!(folds.hidden_in(0..10), 3);
+        assert_eq!(folds.hidden_in(0..3), 1);
+    }
+    
+    #[test]
+    fn folds_follow_edits() {
+        let mut folds = Folds::default();
+        folds.fold(1, 3);
+        folds.fold(6, 7);
+        // Two lines inserted between the folds move only the second one.
+        folds.lines_changed(4, 4, 6);
+        assert_eq!(folds.iter().collect::<Vec<_>>(), [(1, 3), (8, 9)]);
+        // Editing a header line alone leaves its fold closed.
+        folds.lines_changed(8, 8, 8);
+        assert!(folds.is_folded(8));
+        // Editing a hidden line opens the fold around it.
+        folds.lines_changed(2, 2, 2);
+        assert_eq!(folds.iter().collect::<Vec<_>>(), [(8, 9)]);
+        // So does joining a header with the line before it.
+        folds.lines_changed(7, 8, 7);
+        assert!(folds.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender, TryRecvError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::large_file::Utf8Decoder;
+
+/// How long to wait before checking a followed file for new content again.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks read ahead of the editor. Bounds memory when the source is faster than appending.
+const CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FollowEvent {
+    Text(String),
+    /// The file got shorter, usually because it was truncated or rotated, so it's being
+    /// read again from the start.
+    Truncated,
+}
+
+/// Reads text from a file that's still being written, or from a pipe, on a background
+/// thread, like `tail -f`.
+pub struct Follow {
+    path: Option<PathBuf>,
+    receiver: Receiver<FollowEvent>,
+    closed: bool,
+    /// Tells a thread following a file to stop, since it never finishes on its own.
+    stop: Arc<AtomicBool>,
+}
+
+impl Follow {
+    /// Follows a file from its start. Reading never ends; new content is picked up as it's written.
+    pub fn file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let file = std::fs::File::open(&path)?;
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        std::thread::Builder::new()
+            .name(String::from("follow file"))
+            .spawn(move || follow_file(file, sender, &thread_stop))?;
+        Ok(Self {
+            path: Some(path),
+            receiver,
+            closed: false,
+            stop,
+        })
+    }
+    
+    /// Reads a stream, such as stdin when it's a pipe, until it ends.
+    pub fn reader<R: Read + Send + 'static>(reader: R) -> std::io::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        std::thread::Builder::new()
+            .name(String::from("follow stream"))
+            .spawn(move || follow_reader(reader, sender))?;
+        Ok(Self {
+            path: None,
+            receiver,
+            closed: false,
+            stop: Arc::default(),
+        })
+    }
+    
+    pub fn stdin() -> std::io::Result<Self> {
+        Self::reader(std::io::stdin())
+    }
+    
+    /// The followed file, or `None` for streams.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+    
+    /// Whether the stream ended, or reading failed. Followed files only close on errors.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+    
+    /// Events that arrived since the last call, without blocking.
+    pub fn poll(&mut self) -> Vec<FollowEvent> {
+        let mut events = Vec::new();
+        // Stop at the capacity so that a fast source can't keep this busy forever.
+        while events.len() < CHANNEL_CAPACITY {
+            match self.receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        events
+    }
+}
+
+impl Drop for Follow {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Sends everything after the current position, returning `false` once the receiver is gone.
+fn send_available<R: Read>(reader: &mut R, decoder: &mut Utf8Decoder, chunk: &mut [u8], sender: &SyncSender<FollowEvent>) -> std::io::Result<bool> {
+    loop {
+        let read = match reader.read(chunk) {
+            Ok(0) => return Ok(true),
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        let text = decoder.decode(&chunk[..read]);
+        if !text.is_empty() && sender.send(FollowEvent::Text(text)).is_err() {
+            return Ok(false);
+        }
+    }
+}
+
+fn follow_file(mut file: std::fs::File, sender: SyncSender<FollowEvent>, stop: &AtomicBool) -> std::io::Result<()> {
+    let mut decoder = Utf8Decoder::default();
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    while send_available(&mut file, &mut decoder, &mut chunk, &sender)? {
+        std::thread::sleep(POLL_INTERVAL);
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        let position = file.stream_position()?;
+        if file.metadata()?.len() < position {
+            file.seek(SeekFrom::Start(0))?;
+            decoder = Utf8Decoder::default();
+            if sender.send(FollowEvent::Truncated).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn follow_reader<R: Read>(mut reader: R, sender: SyncSender<FollowEvent>) -> std::io::Result<()> {
+    let mut decoder = Utf8Decoder::default();
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    if send_available(&mut reader, &mut decoder, &mut chunk, &sender)? {
+        let text = decoder.finish();
+        if !text.is_empty() {
+            _ = sender.send(FollowEvent::Text(text));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::prelude::*;
+
+use crate::theme::Theme;
+
+use super::{TextEditor, TextPosition};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GoToLineEvent {
+    Handled,
+    Ignored,
+    Close,
+}
+
+/// Asks for a line number, as `line` or `line:col` counting from 1, and previews it as it's typed.
+pub struct GoToLineState {
+    pub input: String,
+    /// Where the cursor was when the dialog opened, for going back on Esc.
+    origin: TextPosition,
+    start_line: usize,
+    error: bool,
+    pub cursor_screen_position: Option<Position>,
+}
+
+impl GoToLineState {
+    pub fn new(editor: &TextEditor) -> Self {
+        Self {
+            input: String::new(),
+            origin: editor.cursor,
+            start_line: editor.start_line,
+            error: false,
+            cursor_screen_position: None,
+        }
+    }
+    
+    /// The 0-based line and column typed so far, or `None` if it isn't a position.
+    fn target(&self) -> Option<(usize, Option<usize>)> {
+        let (line, col) = match self.input.split_once(':') {
+            Some((line, col)) => (line, Some(col)),
+            None => (self.input.as_str(), None),
+        };
+        let line = line.trim().parse::<usize>().ok()?.checked_sub(1)?;
+        let col = match col.map(str::trim) {
+            Some(col) if !col.is_empty() => Some(col.parse::<usize>().ok()?.saturating_sub(1)),
+            _ => None,
+        };
+        Some((line, col))
+    }
+    
+    fn update(&mut self, editor: &mut TextEditor) {
+        self.error = false;
+        match self.target() {
+            Some((line, None)) => editor.go_to_line(line),
+            Some((line, Some(col))) => editor.set_cursor(TextPosition::new(line, col)),
+            None if self.input.is_empty() => self.restore(editor),
+            None => self.error = true,
+        }
+    }
+    
+    fn restore(&self, editor: &mut TextEditor) {
+        editor.set_cursor(self.origin);
+        editor.start_line = self.start_line;
+    }
+    
+    pub fn handle_key(&mut self, key: KeyEvent, editor: &mut TextEditor) -> GoToLineEvent {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Esc => {
+                self.restore(editor);
+                return GoToLineEvent::Close;
+            }
+            KeyCode::Enter => {
+                editor.clear_extra_cursors();
+                return GoToLineEvent::Close;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.update(editor);
+            }
+            KeyCode::Char(chr @ ('0'..='9' | ':')) if !ctrl && !alt => {
+                self.input.push(chr);
+                self.update(editor);
+            }
+            KeyCode::Char(_) if !ctrl && !alt => (),
+            _ => return GoToLineEvent::Ignored,
+        }
+        GoToLineEvent::Handled
+    }
+    
+    pub fn paste(&mut self, text: &str, editor: &mut TextEditor) {
+        self.input.extend(text.chars().filter(|&chr| chr.is_ascii_digit() || chr == ':'));
+        self.update(editor);
+    }
+}
+
+pub struct GoToLineBar<'a> {
+    editor: &'a TextEditor,
+    style: Style,
+    hint_style: Style,
+    error_style: Style,
+}
+
+impl<'a> GoToLineBar<'a> {
+    /// The editor is used for showing the number of lines.
+    pub fn new(editor: &'a TextEditor) -> Self {
+        Self {
+            editor,
+            style: Style::new().bg(Color::Rgb(37, 41, 50)),
+            hint_style: Style::new().fg(Color::DarkGray),
+            error_style: Style::new().fg(Color::Rgb(224, 108, 117)),
+        }
+    }
+    
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.style(theme.style("go_to_line"))
+            .hint_style(theme.style("go_to_line.hint"))
+            .error_style(theme.style("go_to_line.error"))
+    }
+    
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn hint_style(mut self, style: Style) -> Self {
+        self.hint_style = style;
+        self
+    }
+    
+    pub fn error_style(mut self, style: Style) -> Self {
+        self.error_style = style;
+        self
+    }
+}
+
+impl StatefulWidget for GoToLineBar<'_> {
+    type State = GoToLineState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        buf.set_style(area, self.style);
+        state.cursor_screen_position = None;
+        if area.is_empty() {
+            return;
+        }
+        let prompt = format!(" Go to line: {}", state.input);
+        let prompt_width = prompt.chars().count();
+        buf.set_stringn(area.x, area.y, &prompt, area.width as usize, self.style);
+        let cursor_x = area.x as usize + prompt_width;
+        if cursor_x < area.right() as usize {
+            state.cursor_screen_position = Some(Position::new(cursor_x as u16, area.y));
+        }
+        let (hint, hint_style) = if state.error {
+            (String::from("Not a line number"), self.error_style)
+        } else {
+            (format!("1-{}, or line:column", self.editor.rope.len_lines()), self.hint_style)
+        };
+        let hint_x = cursor_x + 2;
+        if hint_x < area.right() as usize {
+            buf.set_stringn(hint_x as u16, area.y, &hint, area.right() as usize - hint_x, hint_style);
+        }
+    }
+}
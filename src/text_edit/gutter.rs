@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use ratatui::prelude::*;
+
+use crate::theme::Theme;
+
+use super::fold::FoldMarker;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineNumbers {
+    Absolute,
+    /// Distance from the cursor line. The cursor line itself shows its absolute number.
+    Relative,
+}
+
+#[derive(Debug, Clone)]
+pub struct Gutter {
+    pub line_numbers: Option<LineNumbers>,
+    pub sign_column: bool,
+    /// Shows which lines can be folded, after the line numbers.
+    pub fold_column: bool,
+    pub style: Style,
+    pub line_number_style: Style,
+    pub current_line_number_style: Style,
+    pub fold_marker_style: Style,
+}
+
+impl Default for Gutter {
+    fn default() -> Self {
+        Self {
+            line_numbers: Some(LineNumbers::Absolute),
+            sign_column: true,
+            fold_column: true,
+            style: Style::new(),
+            line_number_style: Style::new().fg(Color::DarkGray),
+            current_line_number_style: Style::new().fg(Color::Yellow),
+            fold_marker_style: Style::new().fg(Color::Gray),
+        }
+    }
+}
+
+impl Gutter {
+    const MIN_NUMBER_WIDTH: usize = 3;
+    
+    pub fn theme(mut self, theme: &Theme) -> Self {
+        self.style = theme.style("gutter");
+        self.line_number_style = theme.style("gutter.line_number");
+        self.current_line_number_style = theme.style("gutter.current_line_number");
+        self.fold_marker_style = theme.style("gutter.fold_marker");
+        self
+    }
+    
+    fn number_width(&self, line_count: usize) -> usize {
+        (line_count.max(1).ilog10() as usize + 1).max(Self::MIN_NUMBER_WIDTH)
+    }
+    
+    /// Total width including the separator between the gutter and the text.
+    pub fn width(&self, line_count: usize) -> u16 {
+        let signs = if self.sign_column { 1 } else { 0 };
+        let folds = if self.fold_column { 1 } else { 0 };
+        let numbers = match self.line_numbers {
+            Some(_) => self.number_width(line_count),
+            None => 0,
+        };
+        if signs + numbers + folds == 0 {
+            return 0;
+        }
+        (signs + numbers + folds + 1) as u16
+    }
+    
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn render_line(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        line_index: Option<usize>,
+        cursor_line: usize,
+        line_count: usize,
+        signs: &Signs,
+        fold_marker: Option<FoldMarker>,
+    ) {
+        buf.set_style(area, self.style);
+        let Some(line_index) = line_index else {
+            return;
+        };
+        let mut x = area.x;
+        if self.sign_column {
+            if let Some(sign) = signs.top(line_index) {
+                buf[(x, area.y)].set_char(sign.symbol).set_style(sign.style);
+            }
+            x += 1;
+        }
+        if let Some(line_numbers) = self.line_numbers {
+            let width = self.number_width(line_count);
+            let is_current = line_index == cursor_line;
+            let number = match line_numbers {
+                LineNumbers::Relative if !is_current => line_index.abs_diff(cursor_line),
+                _ => line_index + 1,
+            };
+            let style = if is_current {
+                self.current_line_number_style
+            } else {
+                self.line_number_style
+            };
+            let text = format!("{number:>width$}");
+            let number_area = Rect::new(x, area.y, (area.right() - x).min(width as u16), 1);
+            buf.set_stringn(number_area.x, number_area.y, text, number_area.width as usize, style);
+            x += number_area.width;
+        }
+        if self.fold_column && x < area.right() {
+            let symbol = match fold_marker {
+                Some(FoldMarker::Open) => '▾',
+                Some(FoldMarker::Folded) => '▸',
+                None => ' ',
+            };
+            buf[(x, area.y)].set_char(symbol).set_style(self.fold_marker_style);
+        }
+    }
+}
+
+/// A marker shown in the sign column, e.g. a diagnostic, breakpoint or git change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sign {
+    /// Lets callers replace their own signs without touching anyone else's.
+    pub group: &'static str,
+    pub symbol: char,
+    pub style: Style,
+    /// When a line has several signs, the highest priority one is shown.
+    pub priority: i32,
+}
+
+/// Signs by line. Line numbers are kept in sync with edits to the buffer.
+#[derive(Debug, Clone, Default)]
+pub struct Signs {
+    lines: BTreeMap<usize, Vec<Sign>>,
+}
+
+impl Signs {
+    pub fn add(&mut self, line: usize, sign: Sign) {
+        self.lines.entry(line).or_default().push(sign);
+    }
+    
+    pub fn get(&self, line: usize) -> &[Sign] {
+        self.lines.get(&line).map(Vec::as_slice).unwrap_or(&[])
+    }
+    
+    pub fn top(&self, line: usize) -> Option<&Sign> {
+        self.get(line).iter().max_by_key(|sign| sign.priority)
+    }
+    
+    pub fn clear_group(&mut self, group: &str) {
+        self.lines.retain(|_, signs| {
+            signs.retain(|sign| sign.group != group);
+            !signs.is_empty()
+        });
+    }
+    
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Sign)> {
+        self.lines.iter().flat_map(|(&line, signs)| signs.iter().map(move |sign| (line, sign)))
+    }
+    
+    /// Lines `start_line..=old_end_line` were replaced by `start_line..=new_end_line`.
+    pub(crate) fn lines_changed(&mut self, start_line: usize, old_end_line: usize, new_end_line: usize) {
+        if old_end_line == new_end_line || self.lines.is_empty() {
+            return;
+        }
+        let after = self.lines.split_off(&(old_end_line + 1));
+        // Signs on removed lines collapse onto the last remaining line of the edit.
+        let removed = self.lines.split_off(&(new_end_line + 1));
+        for (_, signs) in removed {
+            self.lines.entry(new_end_line.max(start_line)).or_default().extend(signs);
+        }
+        for (line, signs) in after {
+            self.lines.insert(line + new_end_line - old_end_line, signs);
+        }
+    }
+}
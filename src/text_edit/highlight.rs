@@ -0,0 +1,590 @@
+mod json;
+mod markdown;
+mod rust;
+mod shell;
+mod toml;
+
+use std::path::Path;
+
+use ratatui::style::{Color, Modifier, Style};
+use ropey::Rope;
+
+pub use json::JsonHighlighter;
+pub use markdown::MarkdownHighlighter;
+pub use rust::RustHighlighter;
+pub use shell::ShellHighlighter;
+pub use toml::TomlHighlighter;
+
+/// Lexer state carried from the end of one line to the start of the next.
+/// `0` is always the initial state.
+pub type LineState = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Keyword,
+    Type,
+    Function,
+    Macro,
+    Variable,
+    Constant,
+    Number,
+    String,
+    Escape,
+    Comment,
+    Operator,
+    Punctuation,
+    Attribute,
+    Lifetime,
+    Key,
+    Heading,
+    Emphasis,
+    Strong,
+    Code,
+    Link,
+    Quote,
+    ListMarker,
+}
+
+impl TokenKind {
+    pub const COUNT: usize = 22;
+    
+    pub const ALL: [TokenKind; TokenKind::COUNT] = [
+        TokenKind::Keyword,
+        TokenKind::Type,
+        TokenKind::Function,
+        TokenKind::Macro,
+        TokenKind::Variable,
+        TokenKind::Constant,
+        TokenKind::Number,
+        TokenKind::String,
+        TokenKind::Escape,
+        TokenKind::Comment,
+        TokenKind::Operator,
+        TokenKind::Punctuation,
+        TokenKind::Attribute,
+        TokenKind::Lifetime,
+        TokenKind::Key,
+        TokenKind::Heading,
+        TokenKind::Emphasis,
+        TokenKind::Strong,
+        TokenKind::Code,
+        TokenKind::Link,
+        TokenKind::Quote,
+        TokenKind::ListMarker,
+    ];
+    
+    /// The name used for the token kind in theme files.
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenKind::Keyword => "keyword",
+            TokenKind::Type => "type",
+            TokenKind::Function => "function",
+            TokenKind::Macro => "macro",
+            TokenKind::Variable => "variable",
+            TokenKind::Constant => "constant",
+            TokenKind::Number => "number",
+            TokenKind::String => "string",
+            TokenKind::Escape => "escape",
+            TokenKind::Comment => "comment",
+            TokenKind::Operator => "operator",
+            TokenKind::Punctuation => "punctuation",
+            TokenKind::Attribute => "attribute",
+            TokenKind::Lifetime => "lifetime",
+            TokenKind::Key => "key",
+            TokenKind::Heading => "heading",
+            TokenKind::Emphasis => "emphasis",
+            TokenKind::Strong => "strong",
+            TokenKind::Code => "code",
+            TokenKind::Link => "link",
+            TokenKind::Quote => "quote",
+            TokenKind::ListMarker => "list_marker",
+        }
+    }
+}
+
+/// A highlighted span of a line, in char offsets from the start of the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub start: usize,
+    pub end: usize,
+    pub kind: TokenKind,
+}
+
+pub trait Highlighter {
+    fn name(&self) -> &str;
+    /// Lex a single line (including its line ending, if any) starting in `state`,
+    /// pushing tokens in order and returning the state at the end of the line.
+    fn highlight_line(&self, line: &[char], state: LineState, tokens: &mut Vec<Token>) -> LineState;
+    /// What starts a line comment, for toggling comments. `None` if the language has none.
+    fn line_comment(&self) -> Option<&str> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    Toml,
+    Json,
+    Markdown,
+    Shell,
+}
+
+impl Language {
+    pub const ALL: [Language; 5] = [
+        Language::Rust,
+        Language::Toml,
+        Language::Json,
+        Language::Markdown,
+        Language::Shell,
+    ];
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "Rust",
+            Language::Toml => "TOML",
+            Language::Json => "JSON",
+            Language::Markdown => "Markdown",
+            Language::Shell => "Shell",
+        }
+    }
+    
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        Some(match ext.to_ascii_lowercase().as_str() {
+            "rs" => Language::Rust,
+            "toml" => Language::Toml,
+            "json" | "jsonc" => Language::Json,
+            "md" | "markdown" => Language::Markdown,
+            "sh" | "bash" | "zsh" => Language::Shell,
+            _ => return None,
+        })
+    }
+    
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some("Cargo.lock") => return Some(Language::Toml),
+            Some(".bashrc" | ".bash_profile" | ".profile" | ".zshrc") => return Some(Language::Shell),
+            _ => (),
+        }
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_extension)
+    }
+    
+    pub fn highlighter(self) -> Box<dyn Highlighter> {
+        match self {
+            Language::Rust => Box::new(RustHighlighter),
+            Language::Toml => Box::new(TomlHighlighter),
+            Language::Json => Box::new(JsonHighlighter),
+            Language::Markdown => Box::new(MarkdownHighlighter),
+            Language::Shell => Box::new(ShellHighlighter),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LineCache {
+    tokens: Vec<Token>,
+    end_state: LineState,
+    dirty: bool,
+}
+
+impl LineCache {
+    // Never produced by a lexer, so the first lex of a new line always counts as a state change.
+    const UNKNOWN_STATE: LineState = LineState::MAX;
+    const DIRTY: Self = Self {
+        tokens: Vec::new(),
+        end_state: Self::UNKNOWN_STATE,
+        dirty: true,
+    };
+}
+
+/// Per-line highlight cache for a rope. Edits only dirty the lines they touch;
+/// lines after an edit are re-lexed only while their start state keeps changing.
+pub struct Highlighting {
+    highlighter: Box<dyn Highlighter>,
+    lines: Vec<LineCache>,
+    first_dirty: usize,
+    line_buf: Vec<char>,
+}
+
+impl Highlighting {
+    pub fn new(highlighter: Box<dyn Highlighter>, rope: &Rope) -> Self {
+        Self {
+            highlighter,
+            lines: vec![LineCache::DIRTY; rope.len_lines()],
+            first_dirty: 0,
+            line_buf: Vec::new(),
+        }
+    }
+    
+    pub fn highlighter(&self) -> &dyn Highlighter {
+        self.highlighter.as_ref()
+    }
+    
+    /// Lines `start_line..=old_end_line` were replaced by `start_line..=new_end_line`.
+    pub fn lines_changed(&mut self, start_line: usize, old_end_line: usize, new_end_line: usize) {
+        let old_end = (old_end_line + 1).min(self.lines.len());
+        let start = start_line.min(old_end);
+        // Remember the old end state of the edited region so that re-lexing can stop
+        // as soon as the new lines end up in the same state.
+        let old_state = self.lines.get(old_end_line)
+            .map(|cache| cache.end_state)
+            .unwrap_or(LineCache::UNKNOWN_STATE);
+        self.lines.splice(start..old_end, std::iter::repeat_n(LineCache::DIRTY, new_end_line + 1 - start_line));
+        if let Some(last) = self.lines.get_mut(new_end_line) {
+            last.end_state = old_state;
+        }
+        self.first_dirty = self.first_dirty.min(start);
+    }
+    
+    /// Re-lex any dirty lines before `end_line`.
+    pub fn update(&mut self, rope: &Rope, end_line: usize) {
+        if self.lines.len() != rope.len_lines() {
+            // The rope was modified behind our back, so start over.
+            self.lines = vec![LineCache::DIRTY; rope.len_lines()];
+            self.first_dirty = 0;
+        }
+        let end_line = end_line.min(self.lines.len());
+        if self.first_dirty >= end_line {
+            return;
+        }
+        let mut state = match self.first_dirty {
+            0 => 0,
+            line => self.lines[line - 1].end_state,
+        };
+        for line_index in self.first_dirty..end_line {
+            let cache = &mut self.lines[line_index];
+            if !cache.dirty {
+                state = cache.end_state;
+                continue;
+            }
+            self.line_buf.clear();
+            self.line_buf.extend(rope.line(line_index).chars());
+            cache.tokens.clear();
+            let end_state = self.highlighter.highlight_line(&self.line_buf, state, &mut cache.tokens);
+            cache.dirty = false;
+            if end_state != cache.end_state {
+                cache.end_state = end_state;
+                if let Some(next) = self.lines.get_mut(line_index + 1) {
+                    next.dirty = true;
+                }
+            }
+            state = end_state;
+        }
+        self.first_dirty = end_line;
+    }
+    
+    pub fn line_tokens(&self, line_index: usize) -> &[Token] {
+        match self.lines.get(line_index) {
+            Some(cache) if !cache.dirty => &cache.tokens,
+            _ => &[],
+        }
+    }
+    
+    pub fn line_state(&self, line_index: usize) -> Option<LineState> {
+        match self.lines.get(line_index) {
+            Some(cache) if !cache.dirty => Some(cache.end_state),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxTheme {
+    styles: [Style; TokenKind::COUNT],
+}
+
+impl SyntaxTheme {
+    pub const fn new(default: Style) -> Self {
+        Self {
+            styles: [default; TokenKind::COUNT],
+        }
+    }
+    
+    pub fn style(&self, kind: TokenKind) -> Style {
+        self.styles[kind as usize]
+    }
+    
+    pub fn set_style(&mut self, kind: TokenKind, style: Style) {
+        self.styles[kind as usize] = style;
+    }
+    
+    pub const fn with(mut self, kind: TokenKind, style: Style) -> Self {
+        self.styles[kind as usize] = style;
+        self
+    }
+}
+
+impl Default for SyntaxTheme {
+    fn default() -> Self {
+        DEFAULT_SYNTAX_THEME
+    }
+}
+
+const fn fg(r: u8, g: u8, b: u8) -> Style {
+    Style::new().fg(Color::Rgb(r, g, b))
+}
+
+pub const DEFAULT_SYNTAX_THEME: SyntaxTheme = SyntaxTheme::new(Style::new())
+    .with(TokenKind::Keyword, fg(198, 120, 221))
+    .with(TokenKind::Type, fg(229, 192, 123))
+    .with(TokenKind::Function, fg(97, 175, 239))
+    .with(TokenKind::Macro, fg(86, 182, 194))
+    .with(TokenKind::Variable, fg(224, 108, 117))
+    .with(TokenKind::Constant, fg(209, 154, 102))
+    .with(TokenKind::Number, fg(209, 154, 102))
+    .with(TokenKind::String, fg(152, 195, 121))
+    .with(TokenKind::Escape, fg(86, 182, 194))
+    .with(TokenKind::Comment, fg(92, 99, 112).add_modifier(Modifier::ITALIC))
+    .with(TokenKind::Operator, fg(171, 178, 191))
+    .with(TokenKind::Punctuation, fg(130, 137, 151))
+    .with(TokenKind::Attribute, fg(209, 154, 102))
+    .with(TokenKind::Lifetime, fg(198, 120, 221).add_modifier(Modifier::ITALIC))
+    .with(TokenKind::Key, fg(224, 108, 117))
+    .with(TokenKind::Heading, fg(224, 108, 117).add_modifier(Modifier::BOLD))
+    .with(TokenKind::Emphasis, Style::new().add_modifier(Modifier::ITALIC))
+    .with(TokenKind::Strong, Style::new().add_modifier(Modifier::BOLD))
+    .with(TokenKind::Code, fg(152, 195, 121))
+    .with(TokenKind::Link, fg(97, 175, 239).add_modifier(Modifier::UNDERLINED))
+    .with(TokenKind::Quote, fg(92, 99, 112))
+    .with(TokenKind::ListMarker, fg(209, 154, 102));
+
+/// Small cursor over a line used by the built-in lexers.
+pub(crate) struct Scanner<'a> {
+    pub chars: &'a [char],
+    pub pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(chars: &'a [char]) -> Self {
+        Self {
+            chars,
+            pos: 0,
+        }
+    }
+    
+    pub fn is_done(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+    
+    pub fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    
+    pub fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+    
+    pub fn bump(&mut self) -> Option<char> {
+        let chr = self.peek()?;
+        self.pos += 1;
+        Some(chr)
+    }
+    
+    pub fn eat(&mut self, chr: char) -> bool {
+        if self.peek() == Some(chr) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    
+    pub fn starts_with(&self, text: &str) -> bool {
+        let mut rest = self.chars[self.pos.min(self.chars.len())..].iter();
+        text.chars().all(|chr| rest.next() == Some(&chr))
+    }
+    
+    pub fn eat_str(&mut self, text: &str) -> bool {
+        if self.starts_with(text) {
+            self.pos += text.chars().count();
+            true
+        } else {
+            false
+        }
+    }
+    
+    pub fn eat_while<F: FnMut(char) -> bool>(&mut self, mut pred: F) -> usize {
+        let start = self.pos;
+        while let Some(chr) = self.peek() {
+            if !pred(chr) {
+                break;
+            }
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+    
+    pub fn skip_to_end(&mut self) {
+        self.pos = self.chars.len();
+    }
+    
+    pub fn text(&self, start: usize) -> String {
+        self.chars[start..self.pos].iter().collect()
+    }
+    
+    pub fn is_text(&self, start: usize, text: &str) -> bool {
+        self.chars[start..self.pos].iter().copied().eq(text.chars())
+    }
+}
+
+pub(crate) fn push_token(tokens: &mut Vec<Token>, start: usize, end: usize, kind: TokenKind) {
+    if start >= end {
+        return;
+    }
+    // Merge adjacent tokens of the same kind so that renderers see fewer spans.
+    if let Some(last) = tokens.last_mut()
+        && last.end == start
+        && last.kind == kind {
+        last.end = end;
+        return;
+    }
+    tokens.push(Token {
+        start,
+        end,
+        kind,
+    });
+}
+
+pub(crate) fn is_ident_start(chr: char) -> bool {
+    chr == '_' || chr.is_alphabetic()
+}
+
+pub(crate) fn is_ident_continue(chr: char) -> bool {
+    chr == '_' || chr.is_alphanumeric()
+}
+
+/// Scans a number literal (decimal, hex, octal, binary, floats and exponents, with `_` separators).
+pub(crate) fn scan_number(scanner: &mut Scanner) {
+    if scanner.peek() == Some('0') && matches!(scanner.peek_at(1), Some('x' | 'o' | 'b' | 'X' | 'O' | 'B')) {
+        scanner.pos += 2;
+        scanner.eat_while(|c| c.is_ascii_hexdigit() || c == '_');
+    } else {
+        scanner.eat_while(|c| c.is_ascii_digit() || c == '_');
+        if scanner.peek() == Some('.') && scanner.peek_at(1).is_some_and(|c| c.is_ascii_digit()) {
+            scanner.pos += 1;
+            scanner.eat_while(|c| c.is_ascii_digit() || c == '_');
+        }
+        if matches!(scanner.peek(), Some('e' | 'E'))
+            && (scanner.peek_at(1).is_some_and(|c| c.is_ascii_digit())
+                || (matches!(scanner.peek_at(1), Some('+' | '-')) && scanner.peek_at(2).is_some_and(|c| c.is_ascii_digit()))) {
+            scanner.pos += 2;
+            scanner.eat_while(|c| c.is_ascii_digit() || c == '_');
+        }
+    }
+    // Type suffixes like `u8` or `f32`.
+    scanner.eat_while(is_ident_continue);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// The text and kind of every token on one line, lexed from the initial state.
+    fn tokens(language: Language, line: &str) -> (Vec<(String, TokenKind)>, LineState) {
+        let chars: Vec<char> = line.chars().collect();
+        let mut tokens = Vec::new();
+        let state = language.highlighter().highlight_line(&chars, 0, &mut tokens);
+        let spans = tokens.iter().map(|token| (chars[token.start..token.end].iter().collect(), token.kind)).collect();
+        (spans, state)
+    }
+    
+    fn has(language: Language, line: &str, text: &str, kind: TokenKind) -> bool {
+        tokens(language, line).0.iter().any(|(span, span_kind)| span == text && *span_kind == kind)
+    }
+    
+    #[test]
+    fn scanner_compares_text_by_chars() {
+        let chars: Vec<char> = "grüße!".chars().collect();
+        let mut scanner = Scanner::new(&chars);
+        scanner.eat_while(char::is_alphabetic);
+        assert!(scanner.is_text(0, "grüße"));
+        assert!(!scanner.is_text(0, "gruße"));
+        assert!(!scanner.is_text(0, "grüß"));
+        assert!(scanner.eat_str("!"));
+        assert!(scanner.is_done());
+    }
+    
+    #[test]
+    fn rust_tokens() {
+        let line = "pub fn main() -> u8 { let x = 0x1F_u8; println!(\"a\\n{x}\"); MAX } // done";
+        assert!(has(Language::Rust, line, "pub", TokenKind::Keyword));
+        assert!(has(Language::Rust, line, "main", TokenKind::Function));
+        assert!(has(Language::Rust, line, "u8", TokenKind::Type));
+        assert!(has(Language::Rust, line, "0x1F_u8", TokenKind::Number));
+        assert!(has(Language::Rust, line, "println!", TokenKind::Macro));
+        assert!(has(Language::Rust, line, "\\n", TokenKind::Escape));
+        assert!(has(Language::Rust, line, "MAX", TokenKind::Constant));
+        assert!(has(Language::Rust, line, "// done", TokenKind::Comment));
+        assert!(has(Language::Rust, "fn f<'a>(c: char) { '\\'' }", "'a", TokenKind::Lifetime));
+        assert!(has(Language::Rust, "fn f<'a>(c: char) { '\\'' }", "'\\''", TokenKind::String));
+        assert!(has(Language::Rust, "#[derive(Debug)]", "#[derive(Debug)]", TokenKind::Attribute));
+    }
+    
+    #[test]
+    fn rust_state_carries_across_lines() {
+        let highlighter = Language::Rust.highlighter();
+        let mut tokens = Vec::new();
+        let chars = |text: &str| text.chars().collect::<Vec<_>>();
+        let nested = highlighter.highlight_line(&chars("/* outer /* inner */\n"), 0, &mut tokens);
+        assert_ne!(nested, 0);
+        assert_eq!(highlighter.highlight_line(&chars("still */ code\n"), nested, &mut tokens), 0);
+        let raw = highlighter.highlight_line(&chars("let s = r#\"quote \" inside\n"), 0, &mut tokens);
+        assert_ne!(raw, 0);
+        assert_eq!(highlighter.highlight_line(&chars("\"#;\n"), raw, &mut tokens), 0);
+        let string = highlighter.highlight_line(&chars("let s = \"open\n"), 0, &mut tokens);
+        assert_ne!(string, 0);
+        assert_eq!(highlighter.highlight_line(&chars("closed\";\n"), string, &mut tokens), 0);
+    }
+    
+    #[test]
+    fn other_languages() {
+        assert!(has(Language::Json, r#"{"key": true, "n": -1.5e3, "z": null}"#, "true", TokenKind::Constant));
+        assert!(has(Language::Json, r#"{"key": true, "n": -1.5e3, "z": null}"#, "-1.5e3", TokenKind::Number));
+        assert!(has(Language::Json, r#"{"key": true, "n": -1.5e3, "z": null}"#, "null", TokenKind::Constant));
+        assert!(!has(Language::Json, r#"{"a": trueish}"#, "trueish", TokenKind::Constant));
+        assert!(has(Language::Toml, "enabled = false", "false", TokenKind::Constant));
+        assert!(has(Language::Toml, "[package] # table", "package", TokenKind::Type));
+        assert!(has(Language::Toml, "x = inf", "inf", TokenKind::Number));
+        assert!(has(Language::Toml, "when = 1979-05-27", "1979-05-27", TokenKind::Number));
+        assert!(has(Language::Markdown, "# Title", "# Title", TokenKind::Heading));
+        assert!(has(Language::Shell, "echo $HOME # comment", "# comment", TokenKind::Comment));
+        assert_eq!(Language::from_path("dir/Cargo.lock"), Some(Language::Toml));
+        assert_eq!(Language::from_path("script.SH"), Some(Language::Shell));
+        assert_eq!(Language::from_path("notes.txt"), None);
+    }
+    
+    #[test]
+    fn cache_relexes_only_until_the_state_settles() {
+        let mut rope = Rope::from_str("a\nb\nc\nd\n");
+        let mut highlighting = Highlighting::new(Language::Rust.highlighter(), &rope);
+        highlighting.update(&rope, rope.len_lines());
+        assert!(highlighting.line_tokens(2).is_empty());
+        assert_eq!(highlighting.line_state(3), Some(0));
+        
+        // Opening a block comment on line 0 reaches every line after it.
+        rope.insert(0, "/*");
+        highlighting.lines_changed(0, 0, 0);
+        assert_eq!(highlighting.line_state(0), None);
+        highlighting.update(&rope, rope.len_lines());
+        for line in 0..4 {
+            assert_eq!(highlighting.line_tokens(line).first().map(|token| token.kind), Some(TokenKind::Comment), "line {line}");
+        }
+        
+        // Closing it on line 1 brings the rest back.
+        rope.insert(rope.line_to_char(1) + 1, "*/");
+        highlighting.lines_changed(1, 1, 1);
+        highlighting.update(&rope, rope.len_lines());
+        assert_eq!(highlighting.line_state(1), Some(0));
+        assert!(highlighting.line_tokens(2).is_empty());
+        
+        // Inserting a line keeps the cache the same length as the rope.
+        rope.insert(rope.line_to_char(2), "let x;\n");
+        highlighting.lines_changed(2, 2, 3);
+        highlighting.update(&rope, rope.len_lines());
+        assert_eq!(highlighting.line_tokens(2).first().map(|token| token.kind), Some(TokenKind::Keyword));
+        assert!(highlighting.line_tokens(3).is_empty());
+    }
+}
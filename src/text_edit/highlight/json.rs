@@ -0,0 +1,125 @@
+use super::{push_token, scan_number, Highlighter, LineState, Scanner, Token, TokenKind};
+
+const NORMAL: LineState = 0;
+const BLOCK_COMMENT: LineState = 1;
+
+pub struct JsonHighlighter;
+
+impl JsonHighlighter {
+    fn string(scanner: &mut Scanner, tokens: &mut Vec<Token>, kind: TokenKind) {
+        let mut start = scanner.pos;
+        scanner.pos += 1;
+        while let Some(chr) = scanner.peek() {
+            match chr {
+                '\\' => {
+                    push_token(tokens, start, scanner.pos, kind);
+                    let escape_start = scanner.pos;
+                    scanner.pos += 1;
+                    if scanner.bump() == Some('u') {
+                        scanner.pos = (scanner.pos + 4).min(scanner.chars.len());
+                    }
+                    push_token(tokens, escape_start, scanner.pos, TokenKind::Escape);
+                    start = scanner.pos;
+                }
+                '"' => {
+                    scanner.pos += 1;
+                    break;
+                }
+                '\n' | '\r' => break,
+                _ => scanner.pos += 1,
+            }
+        }
+        push_token(tokens, start, scanner.pos, kind);
+    }
+    
+    /// Scans to the end of a block comment, returning whether it was closed.
+    fn block_comment(scanner: &mut Scanner) -> bool {
+        while !scanner.is_done() {
+            if scanner.eat_str("*/") {
+                return true;
+            }
+            scanner.pos += 1;
+        }
+        false
+    }
+    
+    /// A string is an object key if the next significant char is `:`.
+    fn is_key(scanner: &Scanner) -> bool {
+        let mut index = scanner.pos + 1;
+        let mut escaped = false;
+        while let Some(&chr) = scanner.chars.get(index) {
+            index += 1;
+            match chr {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => break,
+                _ => (),
+            }
+        }
+        scanner.chars[index.min(scanner.chars.len())..]
+            .iter()
+            .find(|c| !c.is_whitespace())
+            == Some(&':')
+    }
+}
+
+impl Highlighter for JsonHighlighter {
+    fn name(&self) -> &str {
+        "JSON"
+    }
+    
+    // JSON strings can't span lines, so the only state that crosses lines is
+    // a `/* */` comment (accepted for JSONC).
+    fn highlight_line(&self, line: &[char], state: LineState, tokens: &mut Vec<Token>) -> LineState {
+        let mut scanner = Scanner::new(line);
+        if state == BLOCK_COMMENT {
+            let closed = Self::block_comment(&mut scanner);
+            push_token(tokens, 0, scanner.pos, TokenKind::Comment);
+            if !closed {
+                return BLOCK_COMMENT;
+            }
+        }
+        while let Some(chr) = scanner.peek() {
+            let start = scanner.pos;
+            match chr {
+                '"' => {
+                    let kind = if Self::is_key(&scanner) {
+                        TokenKind::Key
+                    } else {
+                        TokenKind::String
+                    };
+                    Self::string(&mut scanner, tokens, kind);
+                }
+                '/' if scanner.starts_with("//") => {
+                    scanner.skip_to_end();
+                    push_token(tokens, start, scanner.pos, TokenKind::Comment);
+                }
+                '/' if scanner.starts_with("/*") => {
+                    scanner.pos += 2;
+                    let closed = Self::block_comment(&mut scanner);
+                    push_token(tokens, start, scanner.pos, TokenKind::Comment);
+                    if !closed {
+                        return BLOCK_COMMENT;
+                    }
+                }
+                '-' | '0'..='9' => {
+                    scanner.eat('-');
+                    scan_number(&mut scanner);
+                    push_token(tokens, start, scanner.pos, TokenKind::Number);
+                }
+                '{' | '}' | '[' | ']' | ',' | ':' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::Punctuation);
+                }
+                c if c.is_ascii_alphabetic() => {
+                    scanner.eat_while(|c| c.is_ascii_alphanumeric());
+                    if scanner.is_text(start, "true") || scanner.is_text(start, "false") || scanner.is_text(start, "null") {
+                        push_token(tokens, start, scanner.pos, TokenKind::Constant);
+                    }
+                }
+                _ => scanner.pos += 1,
+            }
+        }
+        NORMAL
+    }
+}
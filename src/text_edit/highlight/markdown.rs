@@ -0,0 +1,191 @@
+use super::{push_token, Highlighter, LineState, Scanner, Token, TokenKind};
+
+// The low byte is the state kind, the rest is the length of the opening fence.
+const NORMAL: u32 = 0;
+const BACKTICK_FENCE: u32 = 1;
+const TILDE_FENCE: u32 = 2;
+
+pub struct MarkdownHighlighter;
+
+impl MarkdownHighlighter {
+    /// Returns the fence char and length if the line opens or closes a code fence.
+    fn fence(scanner: &Scanner) -> Option<(char, usize)> {
+        let indent = scanner.chars.iter().take_while(|&&c| c == ' ').count();
+        if indent > 3 {
+            return None;
+        }
+        let fence_char = *scanner.chars.get(indent)?;
+        if fence_char != '`' && fence_char != '~' {
+            return None;
+        }
+        let len = scanner.chars[indent..].iter().take_while(|&&c| c == fence_char).count();
+        (len >= 3).then_some((fence_char, len))
+    }
+    
+    /// Finds the closing delimiter for an inline span, which must not be preceded by whitespace.
+    fn find_closing(scanner: &Scanner, from: usize, delim: &str) -> Option<usize> {
+        let delim: Vec<char> = delim.chars().collect();
+        let chars = scanner.chars;
+        (from + 1..chars.len()).find(|&index| {
+            chars[index..].starts_with(&delim) && !chars[index - 1].is_whitespace()
+        })
+    }
+    
+    fn inline(scanner: &mut Scanner, tokens: &mut Vec<Token>) {
+        while let Some(chr) = scanner.peek() {
+            let start = scanner.pos;
+            match chr {
+                '\\' if scanner.peek_at(1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                    scanner.pos += 2;
+                    push_token(tokens, start, scanner.pos, TokenKind::Escape);
+                }
+                '`' => {
+                    let ticks = scanner.eat_while(|c| c == '`');
+                    let delim: String = std::iter::repeat_n('`', ticks).collect();
+                    let content_start = scanner.pos;
+                    if let Some(end) = Self::find_closing(scanner, content_start - 1, &delim) {
+                        scanner.pos = end + ticks;
+                        push_token(tokens, start, scanner.pos, TokenKind::Code);
+                    }
+                }
+                '*' | '_' => {
+                    let strong = scanner.peek_at(1) == Some(chr);
+                    let delim: String = std::iter::repeat_n(chr, if strong { 2 } else { 1 }).collect();
+                    let content_start = start + delim.len();
+                    let opens = scanner.chars.get(content_start).is_some_and(|c| !c.is_whitespace());
+                    match Self::find_closing(scanner, content_start, &delim).filter(|_| opens) {
+                        Some(end) => {
+                            scanner.pos = end + delim.len();
+                            let kind = if strong {
+                                TokenKind::Strong
+                            } else {
+                                TokenKind::Emphasis
+                            };
+                            push_token(tokens, start, scanner.pos, kind);
+                        }
+                        None => scanner.pos = content_start,
+                    }
+                }
+                '[' | '!' if chr == '[' || scanner.peek_at(1) == Some('[') => {
+                    // [text](url) or ![alt](url)
+                    let text_start = start + usize::from(chr == '!');
+                    let text_end = scanner.chars[text_start..]
+                        .iter()
+                        .position(|&c| c == ']')
+                        .map(|offset| text_start + offset);
+                    let url_end = text_end
+                        .filter(|&end| scanner.chars.get(end + 1) == Some(&'('))
+                        .and_then(|end| scanner.chars[end..].iter().position(|&c| c == ')').map(|offset| end + offset + 1));
+                    match url_end {
+                        Some(end) => {
+                            scanner.pos = end;
+                            push_token(tokens, start, scanner.pos, TokenKind::Link);
+                        }
+                        None => scanner.pos += 1,
+                    }
+                }
+                '<' if scanner.chars[start..].iter().position(|&c| c == '>').is_some_and(|end| {
+                    let inner = &scanner.chars[start + 1..start + end];
+                    inner.contains(&':') && !inner.contains(&' ')
+                }) => {
+                    scanner.eat_while(|c| c != '>');
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::Link);
+                }
+                _ => scanner.pos += 1,
+            }
+        }
+    }
+}
+
+impl Highlighter for MarkdownHighlighter {
+    fn name(&self) -> &str {
+        "Markdown"
+    }
+    
+    fn highlight_line(&self, line: &[char], state: LineState, tokens: &mut Vec<Token>) -> LineState {
+        let mut scanner = Scanner::new(line);
+        let fence = Self::fence(&scanner);
+        match state & 0xFF {
+            BACKTICK_FENCE | TILDE_FENCE => {
+                let fence_char = if state & 0xFF == BACKTICK_FENCE { '`' } else { '~' };
+                let fence_len = (state >> 8) as usize;
+                push_token(tokens, 0, line.len(), TokenKind::Code);
+                let closes = fence.is_some_and(|(chr, len)| {
+                    let mut rest = line.iter().skip_while(|&&c| c == ' ' || c == chr);
+                    chr == fence_char && len >= fence_len && rest.all(|c| c.is_whitespace())
+                });
+                return if closes { NORMAL } else { state };
+            }
+            _ => (),
+        }
+        if let Some((chr, len)) = fence {
+            push_token(tokens, 0, line.len(), TokenKind::Code);
+            let kind = if chr == '`' { BACKTICK_FENCE } else { TILDE_FENCE };
+            return kind | ((len as u32) << 8);
+        }
+        scanner.eat_while(|c| c == ' ');
+        let start = scanner.pos;
+        match scanner.peek() {
+            Some('#') => {
+                let level = scanner.eat_while(|c| c == '#');
+                if level <= 6 && scanner.peek().is_none_or(char::is_whitespace) {
+                    push_token(tokens, start, line.len(), TokenKind::Heading);
+                    return NORMAL;
+                }
+                scanner.pos = start;
+            }
+            Some('>') => {
+                scanner.eat_while(|c| c == '>' || c == ' ');
+                push_token(tokens, start, scanner.pos, TokenKind::Quote);
+                let quote_start = scanner.pos;
+                let token_start = tokens.len();
+                Self::inline(&mut scanner, tokens);
+                // Plain text inside a quote gets the quote style.
+                let spans: Vec<Token> = tokens.drain(token_start..).collect();
+                let mut pos = quote_start;
+                for token in spans {
+                    push_token(tokens, pos, token.start, TokenKind::Quote);
+                    push_token(tokens, token.start, token.end, token.kind);
+                    pos = token.end;
+                }
+                push_token(tokens, pos, line.len(), TokenKind::Quote);
+                return NORMAL;
+            }
+            Some('-' | '*' | '_') => {
+                // Thematic breaks: three or more of the same char, optionally spaced.
+                let chr = scanner.peek().unwrap();
+                let rest = &line[start..];
+                let count = rest.iter().filter(|&&c| c == chr).count();
+                if count >= 3 && rest.iter().all(|&c| c == chr || c.is_whitespace()) {
+                    push_token(tokens, start, line.len(), TokenKind::Punctuation);
+                    return NORMAL;
+                }
+            }
+            _ => (),
+        }
+        // List markers: `-`, `*`, `+` or `1.`/`1)` followed by a space.
+        let marker_len = match scanner.peek() {
+            Some('-' | '*' | '+') => 1,
+            Some(c) if c.is_ascii_digit() => {
+                let digits = scanner.chars[start..].iter().take_while(|c| c.is_ascii_digit()).count();
+                match scanner.peek_at(digits) {
+                    Some('.' | ')') => digits + 1,
+                    _ => 0,
+                }
+            }
+            _ => 0,
+        };
+        if marker_len != 0 && scanner.peek_at(marker_len).is_some_and(char::is_whitespace) {
+            scanner.pos += marker_len;
+            push_token(tokens, start, scanner.pos, TokenKind::ListMarker);
+            // Task list checkboxes.
+            if scanner.starts_with(" [ ]") || scanner.starts_with(" [x]") || scanner.starts_with(" [X]") {
+                scanner.pos += 4;
+                push_token(tokens, scanner.pos - 3, scanner.pos, TokenKind::ListMarker);
+            }
+        }
+        Self::inline(&mut scanner, tokens);
+        NORMAL
+    }
+}
@@ -0,0 +1,269 @@
+use super::{
+    is_ident_continue, is_ident_start, push_token, scan_number,
+    Highlighter, LineState, Scanner, Token, TokenKind,
+};
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+    "union", "unsafe", "use", "where", "while", "yield", "gen", "try",
+];
+
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize",
+    "i8", "i16", "i32", "i64", "i128", "isize", "f32", "f64",
+];
+
+const CONSTANTS: &[&str] = &["true", "false", "None", "Some", "Ok", "Err"];
+
+// The low byte is the state kind, the rest is a parameter (comment depth or raw string hashes).
+const NORMAL: u32 = 0;
+const BLOCK_COMMENT: u32 = 1;
+const STRING: u32 = 2;
+const RAW_STRING: u32 = 3;
+
+fn state(kind: u32, param: u32) -> LineState {
+    kind | (param << 8)
+}
+
+pub struct RustHighlighter;
+
+impl RustHighlighter {
+    /// Scans the rest of a block comment, returning the remaining depth (0 if it closed).
+    fn block_comment(scanner: &mut Scanner, mut depth: u32) -> u32 {
+        while !scanner.is_done() {
+            if scanner.eat_str("*/") {
+                depth -= 1;
+                if depth == 0 {
+                    return 0;
+                }
+            } else if scanner.eat_str("/*") {
+                depth += 1;
+            } else {
+                scanner.pos += 1;
+            }
+        }
+        depth
+    }
+    
+    /// Scans the rest of a string, returning whether it was closed.
+    fn string(scanner: &mut Scanner, tokens: &mut Vec<Token>) -> bool {
+        let mut start = scanner.pos;
+        while let Some(chr) = scanner.peek() {
+            match chr {
+                '\\' => {
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    let escape_start = scanner.pos;
+                    scanner.pos += 1;
+                    if scanner.eat('u') && scanner.eat('{') {
+                        scanner.eat_while(|c| c != '}' && c != '"');
+                        scanner.eat('}');
+                    } else if scanner.eat('x') {
+                        scanner.pos = (scanner.pos + 2).min(scanner.chars.len());
+                    } else {
+                        scanner.bump();
+                    }
+                    push_token(tokens, escape_start, scanner.pos, TokenKind::Escape);
+                    start = scanner.pos;
+                }
+                '"' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    return true;
+                }
+                _ => scanner.pos += 1,
+            }
+        }
+        push_token(tokens, start, scanner.pos, TokenKind::String);
+        false
+    }
+    
+    /// Matches `r"`, `r#"`, `br"`, `cr##"` and so on, returning the prefix length and hash count.
+    fn raw_string_start(scanner: &Scanner) -> Option<(usize, u32)> {
+        let prefix = if scanner.starts_with("r") {
+            1
+        } else if scanner.starts_with("br") || scanner.starts_with("cr") {
+            2
+        } else {
+            return None;
+        };
+        let mut offset = prefix;
+        while scanner.peek_at(offset) == Some('#') {
+            offset += 1;
+        }
+        (scanner.peek_at(offset) == Some('"')).then_some((offset + 1, (offset - prefix) as u32))
+    }
+    
+    /// Scans the rest of a raw string, returning whether it was closed.
+    fn raw_string(scanner: &mut Scanner, hashes: u32) -> bool {
+        while let Some(chr) = scanner.bump() {
+            if chr == '"' {
+                let mut count = 0;
+                while count < hashes && scanner.eat('#') {
+                    count += 1;
+                }
+                if count == hashes {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Highlighter for RustHighlighter {
+    fn name(&self) -> &str {
+        "Rust"
+    }
+    
+    fn line_comment(&self) -> Option<&str> {
+        Some("//")
+    }
+    
+    fn highlight_line(&self, line: &[char], state_in: LineState, tokens: &mut Vec<Token>) -> LineState {
+        let mut scanner = Scanner::new(line);
+        match state_in & 0xFF {
+            BLOCK_COMMENT => {
+                let depth = Self::block_comment(&mut scanner, state_in >> 8);
+                push_token(tokens, 0, scanner.pos, TokenKind::Comment);
+                if depth != 0 {
+                    return state(BLOCK_COMMENT, depth);
+                }
+            }
+            STRING if !Self::string(&mut scanner, tokens) => return state(STRING, 0),
+            RAW_STRING => {
+                let hashes = state_in >> 8;
+                let closed = Self::raw_string(&mut scanner, hashes);
+                push_token(tokens, 0, scanner.pos, TokenKind::String);
+                if !closed {
+                    return state_in;
+                }
+            }
+            _ => (),
+        }
+        let mut prev_ident_was_fn = false;
+        while let Some(chr) = scanner.peek() {
+            let start = scanner.pos;
+            match chr {
+                '/' if scanner.starts_with("//") => {
+                    scanner.skip_to_end();
+                    push_token(tokens, start, scanner.pos, TokenKind::Comment);
+                }
+                '/' if scanner.starts_with("/*") => {
+                    scanner.pos += 2;
+                    let depth = Self::block_comment(&mut scanner, 1);
+                    push_token(tokens, start, scanner.pos, TokenKind::Comment);
+                    if depth != 0 {
+                        return state(BLOCK_COMMENT, depth);
+                    }
+                }
+                '"' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    if !Self::string(&mut scanner, tokens) {
+                        return state(STRING, 0);
+                    }
+                }
+                'r' | 'b' | 'c' if let Some((prefix_len, hashes)) = Self::raw_string_start(&scanner) => {
+                    scanner.pos += prefix_len;
+                    let closed = Self::raw_string(&mut scanner, hashes);
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    if !closed {
+                        return state(RAW_STRING, hashes);
+                    }
+                }
+                'b' | 'c' if scanner.peek_at(1) == Some('"') => {
+                    scanner.pos += 2;
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    if !Self::string(&mut scanner, tokens) {
+                        return state(STRING, 0);
+                    }
+                }
+                '\'' => {
+                    // Either a char literal or a lifetime/label.
+                    let is_char = matches!(
+                        (scanner.peek_at(1), scanner.peek_at(2)),
+                        (Some('\\'), _) | (Some(_), Some('\''))
+                    );
+                    scanner.pos += 1;
+                    if is_char {
+                        if scanner.eat('\\') {
+                            scanner.bump();
+                        }
+                        scanner.eat_while(|c| c != '\'' && c != '\n');
+                        scanner.eat('\'');
+                        push_token(tokens, start, scanner.pos, TokenKind::String);
+                    } else {
+                        scanner.eat_while(is_ident_continue);
+                        push_token(tokens, start, scanner.pos, TokenKind::Lifetime);
+                    }
+                }
+                '#' if scanner.starts_with("#[") || scanner.starts_with("#![") => {
+                    let mut depth = 0usize;
+                    while let Some(chr) = scanner.bump() {
+                        match chr {
+                            '[' => depth += 1,
+                            ']' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                            _ => (),
+                        }
+                    }
+                    push_token(tokens, start, scanner.pos, TokenKind::Attribute);
+                }
+                c if c.is_ascii_digit() => {
+                    scan_number(&mut scanner);
+                    push_token(tokens, start, scanner.pos, TokenKind::Number);
+                }
+                c if is_ident_start(c) => {
+                    scanner.eat_while(is_ident_continue);
+                    let word: String = scanner.text(start);
+                    let kind = if scanner.peek() == Some('!') && scanner.peek_at(1) != Some('=') {
+                        scanner.pos += 1;
+                        Some(TokenKind::Macro)
+                    } else if KEYWORDS.contains(&word.as_str()) {
+                        Some(TokenKind::Keyword)
+                    } else if CONSTANTS.contains(&word.as_str()) {
+                        Some(TokenKind::Constant)
+                    } else if PRIMITIVES.contains(&word.as_str()) {
+                        Some(TokenKind::Type)
+                    } else if prev_ident_was_fn || scanner.peek() == Some('(') || scanner.starts_with("::<") {
+                        Some(TokenKind::Function)
+                    } else if word.chars().all(|c| c.is_uppercase() || c.is_ascii_digit() || c == '_') && word.len() > 1 {
+                        Some(TokenKind::Constant)
+                    } else if word.starts_with(char::is_uppercase) {
+                        Some(TokenKind::Type)
+                    } else {
+                        None
+                    };
+                    prev_ident_was_fn = word == "fn";
+                    if let Some(kind) = kind {
+                        push_token(tokens, start, scanner.pos, kind);
+                    }
+                    continue;
+                }
+                c if c.is_whitespace() => {
+                    scanner.eat_while(char::is_whitespace);
+                    continue;
+                }
+                '(' | ')' | '[' | ']' | '{' | '}' | ',' | ';' | '.' | ':' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::Punctuation);
+                }
+                _ => {
+                    scanner.eat_while(|c| "+-*/%=<>!&|^?@~".contains(c));
+                    if scanner.pos == start {
+                        scanner.pos += 1;
+                    }
+                    push_token(tokens, start, scanner.pos, TokenKind::Operator);
+                }
+            }
+            prev_ident_was_fn = false;
+        }
+        state(NORMAL, 0)
+    }
+}
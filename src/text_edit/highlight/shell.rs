@@ -0,0 +1,177 @@
+use super::{is_ident_continue, is_ident_start, push_token, Highlighter, LineState, Scanner, Token, TokenKind};
+
+const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+    "in", "function", "select", "return", "break", "continue", "local", "export", "readonly",
+    "declare", "typeset", "unset", "shift", "exit", "source", "eval", "exec", "trap",
+];
+
+const NORMAL: LineState = 0;
+const SINGLE_QUOTE: LineState = 1;
+const DOUBLE_QUOTE: LineState = 2;
+
+pub struct ShellHighlighter;
+
+impl ShellHighlighter {
+    /// `$name`, `$1`, `$?`, `${...}` and `$(` (only the `$(` is highlighted for command substitution).
+    fn variable(scanner: &mut Scanner, tokens: &mut Vec<Token>) {
+        let start = scanner.pos;
+        scanner.pos += 1;
+        match scanner.peek() {
+            Some('{') => {
+                scanner.eat_while(|c| c != '}');
+                scanner.eat('}');
+            }
+            Some('(') => {
+                scanner.pos += 1;
+                push_token(tokens, start, scanner.pos, TokenKind::Punctuation);
+                return;
+            }
+            Some(c) if is_ident_start(c) => {
+                scanner.eat_while(is_ident_continue);
+            }
+            Some(c) if c.is_ascii_digit() || "?!#@*$-".contains(c) => {
+                scanner.pos += 1;
+            }
+            _ => return,
+        }
+        push_token(tokens, start, scanner.pos, TokenKind::Variable);
+    }
+    
+    /// Scans the rest of a double quoted string, returning whether it was closed.
+    fn double_quoted(scanner: &mut Scanner, tokens: &mut Vec<Token>) -> bool {
+        let mut start = scanner.pos;
+        while let Some(chr) = scanner.peek() {
+            match chr {
+                '\\' => {
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    let escape_start = scanner.pos;
+                    scanner.pos += 1;
+                    scanner.bump();
+                    push_token(tokens, escape_start, scanner.pos, TokenKind::Escape);
+                    start = scanner.pos;
+                }
+                '$' => {
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    Self::variable(scanner, tokens);
+                    start = scanner.pos;
+                }
+                '"' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    return true;
+                }
+                _ => scanner.pos += 1,
+            }
+        }
+        push_token(tokens, start, scanner.pos, TokenKind::String);
+        false
+    }
+    
+    /// Scans the rest of a single quoted string, returning whether it was closed.
+    fn single_quoted(scanner: &mut Scanner, tokens: &mut Vec<Token>) -> bool {
+        let start = scanner.pos;
+        scanner.eat_while(|c| c != '\'');
+        let closed = scanner.eat('\'');
+        push_token(tokens, start, scanner.pos, TokenKind::String);
+        closed
+    }
+}
+
+impl Highlighter for ShellHighlighter {
+    fn name(&self) -> &str {
+        "Shell"
+    }
+    
+    fn line_comment(&self) -> Option<&str> {
+        Some("#")
+    }
+    
+    fn highlight_line(&self, line: &[char], state: LineState, tokens: &mut Vec<Token>) -> LineState {
+        let mut scanner = Scanner::new(line);
+        match state {
+            SINGLE_QUOTE if !Self::single_quoted(&mut scanner, tokens) => return SINGLE_QUOTE,
+            DOUBLE_QUOTE if !Self::double_quoted(&mut scanner, tokens) => return DOUBLE_QUOTE,
+            _ => (),
+        }
+        // Whether the next word is in command position (where keywords and commands appear).
+        let mut command_position = true;
+        while let Some(chr) = scanner.peek() {
+            let start = scanner.pos;
+            match chr {
+                '#' if start == 0 || scanner.chars[start - 1].is_whitespace() => {
+                    scanner.skip_to_end();
+                    push_token(tokens, start, scanner.pos, TokenKind::Comment);
+                }
+                '\'' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    if !Self::single_quoted(&mut scanner, tokens) {
+                        return SINGLE_QUOTE;
+                    }
+                    command_position = false;
+                }
+                '"' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    if !Self::double_quoted(&mut scanner, tokens) {
+                        return DOUBLE_QUOTE;
+                    }
+                    command_position = false;
+                }
+                '$' => {
+                    Self::variable(&mut scanner, tokens);
+                    if scanner.pos == start {
+                        scanner.pos += 1;
+                    }
+                    command_position = scanner.chars[scanner.pos - 1] == '(';
+                }
+                '\\' => {
+                    scanner.pos = (scanner.pos + 2).min(line.len());
+                    push_token(tokens, start, scanner.pos, TokenKind::Escape);
+                }
+                '|' | '&' | ';' | '(' | ')' | '{' | '}' | '`' => {
+                    scanner.eat_while(|c| "|&;(){}`".contains(c));
+                    push_token(tokens, start, scanner.pos, TokenKind::Operator);
+                    command_position = true;
+                }
+                '<' | '>' => {
+                    scanner.eat_while(|c| c == '<' || c == '>' || c == '&');
+                    push_token(tokens, start, scanner.pos, TokenKind::Operator);
+                }
+                c if c.is_whitespace() => {
+                    scanner.eat_while(char::is_whitespace);
+                }
+                c if c.is_ascii_digit() && command_position => {
+                    scanner.eat_while(|c| c.is_ascii_digit());
+                    push_token(tokens, start, scanner.pos, TokenKind::Number);
+                }
+                '-' if !command_position => {
+                    // Command line flags.
+                    scanner.eat_while(|c| !c.is_whitespace() && c != '=' && c != ';');
+                    push_token(tokens, start, scanner.pos, TokenKind::Attribute);
+                }
+                _ => {
+                    scanner.eat_while(|c| !c.is_whitespace() && !"'\"$|&;(){}<>`\\".contains(c));
+                    let word = scanner.text(start);
+                    if let Some(eq) = word.find('=').filter(|&eq| eq > 0 && command_position) {
+                        // Assignment: NAME=value
+                        let name_end = start + word[..eq].chars().count();
+                        push_token(tokens, start, name_end, TokenKind::Variable);
+                        push_token(tokens, name_end, name_end + 1, TokenKind::Operator);
+                    } else if KEYWORDS.contains(&word.as_str()) {
+                        push_token(tokens, start, scanner.pos, TokenKind::Keyword);
+                        // Most keywords are followed by another command.
+                        command_position = !matches!(word.as_str(), "for" | "case" | "in" | "select" | "function");
+                        continue;
+                    } else if command_position {
+                        // Command names and function definitions.
+                        push_token(tokens, start, scanner.pos, TokenKind::Function);
+                    }
+                    command_position = false;
+                }
+            }
+        }
+        NORMAL
+    }
+}
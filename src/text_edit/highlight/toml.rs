@@ -0,0 +1,170 @@
+use super::{push_token, Highlighter, LineState, Scanner, Token, TokenKind};
+
+const NORMAL: LineState = 0;
+const MULTILINE_BASIC: LineState = 1;
+const MULTILINE_LITERAL: LineState = 2;
+
+pub struct TomlHighlighter;
+
+impl TomlHighlighter {
+    /// Scans a string body up to `quote`, returning whether it was closed.
+    fn string(scanner: &mut Scanner, tokens: &mut Vec<Token>, quote: &str, escapes: bool) -> bool {
+        let mut start = scanner.pos;
+        while !scanner.is_done() {
+            if scanner.eat_str(quote) {
+                // Multiline strings may end with up to two extra quotes.
+                if quote.len() == 3 {
+                    let chr = quote.chars().next().unwrap();
+                    let mut extra = 0;
+                    while extra < 2 && scanner.eat(chr) {
+                        extra += 1;
+                    }
+                }
+                push_token(tokens, start, scanner.pos, TokenKind::String);
+                return true;
+            }
+            if escapes && scanner.peek() == Some('\\') {
+                push_token(tokens, start, scanner.pos, TokenKind::String);
+                let escape_start = scanner.pos;
+                scanner.pos += 1;
+                match scanner.bump() {
+                    Some('u') => scanner.pos = (scanner.pos + 4).min(scanner.chars.len()),
+                    Some('U') => scanner.pos = (scanner.pos + 8).min(scanner.chars.len()),
+                    _ => (),
+                }
+                push_token(tokens, escape_start, scanner.pos, TokenKind::Escape);
+                start = scanner.pos;
+            } else {
+                scanner.pos += 1;
+            }
+        }
+        push_token(tokens, start, scanner.pos, TokenKind::String);
+        false
+    }
+    
+    fn is_bare_key_char(chr: char) -> bool {
+        chr.is_ascii_alphanumeric() || chr == '_' || chr == '-'
+    }
+}
+
+impl Highlighter for TomlHighlighter {
+    fn name(&self) -> &str {
+        "TOML"
+    }
+    
+    fn line_comment(&self) -> Option<&str> {
+        Some("#")
+    }
+    
+    fn highlight_line(&self, line: &[char], state: LineState, tokens: &mut Vec<Token>) -> LineState {
+        let mut scanner = Scanner::new(line);
+        match state {
+            MULTILINE_BASIC if !Self::string(&mut scanner, tokens, "\"\"\"", true) => return MULTILINE_BASIC,
+            MULTILINE_LITERAL if !Self::string(&mut scanner, tokens, "'''", false) => return MULTILINE_LITERAL,
+            _ => (),
+        }
+        // Keys appear at the start of a line (or after `{`/`,` in inline tables) and before `=`.
+        let mut expect_key = state == NORMAL;
+        let mut brackets = Vec::new();
+        while let Some(chr) = scanner.peek() {
+            let start = scanner.pos;
+            match chr {
+                '#' => {
+                    scanner.skip_to_end();
+                    push_token(tokens, start, scanner.pos, TokenKind::Comment);
+                }
+                '[' if expect_key && brackets.is_empty() => {
+                    // Table header: [table] or [[array.of.tables]]
+                    scanner.eat_while(|c| c == '[');
+                    push_token(tokens, start, scanner.pos, TokenKind::Punctuation);
+                    let name_start = scanner.pos;
+                    scanner.eat_while(|c| c != ']' && c != '#' && c != '\n');
+                    push_token(tokens, name_start, scanner.pos, TokenKind::Type);
+                    let close_start = scanner.pos;
+                    scanner.eat_while(|c| c == ']');
+                    push_token(tokens, close_start, scanner.pos, TokenKind::Punctuation);
+                    expect_key = false;
+                }
+                '"' if scanner.starts_with("\"\"\"") => {
+                    scanner.pos += 3;
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    if !Self::string(&mut scanner, tokens, "\"\"\"", true) {
+                        return MULTILINE_BASIC;
+                    }
+                }
+                '\'' if scanner.starts_with("'''") => {
+                    scanner.pos += 3;
+                    push_token(tokens, start, scanner.pos, TokenKind::String);
+                    if !Self::string(&mut scanner, tokens, "'''", false) {
+                        return MULTILINE_LITERAL;
+                    }
+                }
+                '"' | '\'' => {
+                    scanner.pos += 1;
+                    let kind_index = tokens.len();
+                    push_token(tokens, start, start + 1, TokenKind::String);
+                    if chr == '"' {
+                        Self::string(&mut scanner, tokens, "\"", true);
+                    } else {
+                        Self::string(&mut scanner, tokens, "'", false);
+                    }
+                    if expect_key {
+                        for token in &mut tokens[kind_index..] {
+                            if token.kind == TokenKind::String {
+                                token.kind = TokenKind::Key;
+                            }
+                        }
+                    }
+                }
+                c if c.is_whitespace() => {
+                    scanner.eat_while(char::is_whitespace);
+                }
+                '=' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::Operator);
+                    expect_key = false;
+                }
+                '{' | '[' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::Punctuation);
+                    brackets.push(chr);
+                    expect_key = chr == '{';
+                }
+                '}' | ']' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::Punctuation);
+                    brackets.pop();
+                }
+                ',' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::Punctuation);
+                    // Inside an inline table a `,` is followed by another key; inside an array it isn't.
+                    expect_key = brackets.last() == Some(&'{');
+                }
+                '.' => {
+                    scanner.pos += 1;
+                    push_token(tokens, start, scanner.pos, TokenKind::Punctuation);
+                }
+                c if Self::is_bare_key_char(c) || c == '+' => {
+                    scanner.eat_while(|c| Self::is_bare_key_char(c) || matches!(c, '+' | ':' | '.'));
+                    if expect_key {
+                        // Each segment of a dotted key is its own token, the `.` is punctuation.
+                        scanner.pos = start;
+                        scanner.eat_while(Self::is_bare_key_char);
+                        push_token(tokens, start, scanner.pos, TokenKind::Key);
+                    } else if scanner.is_text(start, "true") || scanner.is_text(start, "false") {
+                        push_token(tokens, start, scanner.pos, TokenKind::Constant);
+                    } else if scanner.is_text(start, "inf") || scanner.is_text(start, "nan")
+                        || c.is_ascii_digit() || c == '+' || c == '-' {
+                        // Numbers, dates and times all share the number style.
+                        push_token(tokens, start, scanner.pos, TokenKind::Number);
+                    }
+                }
+                _ => {
+                    scanner.pos += 1;
+                }
+            }
+        }
+        NORMAL
+    }
+}
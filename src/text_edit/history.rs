@@ -0,0 +1,143 @@
+use super::TextPosition;
+
+/// A single change to the buffer: `removed` was replaced by `inserted` at `char_idx`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub char_idx: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    fn is_typing(&self) -> bool {
+        self.removed.is_empty() && self.inserted.chars().count() == 1 && !self.inserted.contains('\n')
+    }
+    
+    fn is_single_delete(&self) -> bool {
+        self.inserted.is_empty() && self.removed.chars().count() == 1 && !self.removed.contains('\n')
+    }
+}
+
+/// A group of edits that is undone and redone as one step.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub edits: Vec<Edit>,
+    pub cursor_before: TextPosition,
+}
+
+impl Transaction {
+    fn new(cursor_before: TextPosition) -> Self {
+        Self {
+            edits: Vec::new(),
+            cursor_before,
+        }
+    }
+    
+    /// Whether `edit` continues a run of typing or deleting that should undo as one step.
+    fn can_merge(&self, edit: &Edit) -> bool {
+        let [.., last] = self.edits.as_slice() else {
+            return false;
+        };
+        if last.is_typing() && edit.is_typing() {
+            let word_break = edit.inserted.starts_with(char::is_whitespace)
+                && !last.inserted.starts_with(char::is_whitespace);
+            edit.char_idx == last.char_idx + 1 && !word_break
+        } else if last.is_single_delete() && edit.is_single_delete() {
+            // Backspace runs move left, delete runs stay put.
+            edit.char_idx + 1 == last.char_idx || edit.char_idx == last.char_idx
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+    open: Option<Transaction>,
+    depth: usize,
+    /// Prevents the next edit from merging into the previous transaction.
+    sealed: bool,
+}
+
+impl History {
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+    
+    /// Ends the current run of typing so the next edit starts a new undo step.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+    
+    pub(crate) fn begin(&mut self, cursor: TextPosition) {
+        if self.depth == 0 {
+            self.open = Some(Transaction::new(cursor));
+        }
+        self.depth += 1;
+    }
+    
+    pub(crate) fn end(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 && let Some(transaction) = self.open.take() && !transaction.edits.is_empty() {
+            self.undo.push(transaction);
+            self.sealed = true;
+        }
+    }
+    
+    pub(crate) fn record(&mut self, edit: Edit, cursor_before: TextPosition) {
+        self.redo.clear();
+        if let Some(open) = &mut self.open {
+            open.edits.push(edit);
+            return;
+        }
+        match self.undo.last_mut() {
+            Some(last) if !self.sealed && last.can_merge(&edit) => last.edits.push(edit),
+            _ => {
+                let mut transaction = Transaction::new(cursor_before);
+                transaction.edits.push(edit);
+                self.undo.push(transaction);
+            }
+        }
+        self.sealed = false;
+    }
+    
+    /// Adds `edit` to the last undo step, for edits that follow from the one before them.
+    pub(crate) fn attach(&mut self, edit: Edit, cursor_before: TextPosition) {
+        if let Some(open) = &mut self.open {
+            open.edits.push(edit);
+        } else if let Some(last) = self.undo.last_mut() {
+            last.edits.push(edit);
+        } else {
+            self.record(edit, cursor_before);
+        }
+    }
+    
+    pub(crate) fn pop_undo(&mut self) -> Option<Transaction> {
+        self.sealed = true;
+        self.undo.pop()
+    }
+    
+    pub(crate) fn pop_redo(&mut self) -> Option<Transaction> {
+        self.sealed = true;
+        self.redo.pop()
+    }
+    
+    pub(crate) fn push_undo(&mut self, transaction: Transaction) {
+        self.undo.push(transaction);
+    }
+    
+    pub(crate) fn push_redo(&mut self, transaction: Transaction) {
+        self.redo.push(transaction);
+    }
+}
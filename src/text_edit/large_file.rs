@@ -0,0 +1,312 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+};
+
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressDrawTarget};
+use ratatui::{prelude::*, widgets::LineGauge};
+use ropey::{Rope, RopeBuilder};
+
+use crate::theme::Theme;
+
+/// Sizes in bytes above which loading moves to the background and expensive features turn off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LargeFileThresholds {
+    /// Files at least this large are loaded on a background thread.
+    pub background_load: u64,
+    /// Syntax highlighting works from the top of the buffer down to the viewport.
+    pub highlighting: u64,
+    /// Finding foldable regions scans the whole buffer after every edit.
+    pub folding: u64,
+}
+
+impl Default for LargeFileThresholds {
+    fn default() -> Self {
+        const MIB: u64 = 1024 * 1024;
+        Self {
+            background_load: 8 * MIB,
+            highlighting: 4 * MIB,
+            folding: 8 * MIB,
+        }
+    }
+}
+
+/// Read size for each step of loading, which is also how often progress updates.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Builds a [`Rope`] from a file on a background thread, decoding it like [`read_file`].
+pub struct FileLoader {
+    path: PathBuf,
+    /// Counts bytes read. It is never drawn by indicatif itself; [`LoadingView`] shows it.
+    progress: ProgressBar,
+    handle: Option<JoinHandle<std::io::Result<Rope>>>,
+}
+
+impl FileLoader {
+    pub fn spawn<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let file = std::fs::File::open(&path)?;
+        let len = file.metadata()?.len();
+        let progress = ProgressBar::with_draw_target(Some(len), ProgressDrawTarget::hidden());
+        let thread_progress = progress.clone();
+        let handle = std::thread::Builder::new()
+            .name(String::from("file loader"))
+            .spawn(move || {
+                let rope = load(file, &thread_progress);
+                thread_progress.finish();
+                rope
+            })?;
+        Ok(Self {
+            path,
+            progress,
+            handle: Some(handle),
+        })
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    pub fn progress(&self) -> &ProgressBar {
+        &self.progress
+    }
+    
+    pub fn is_finished(&self) -> bool {
+        self.handle.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+    
+    /// The loaded rope once the thread is done, without blocking.
+    pub fn poll(&mut self) -> Option<std::io::Result<Rope>> {
+        if !self.is_finished() {
+            return None;
+        }
+        self.handle.take().map(|handle| {
+            handle.join().unwrap_or_else(|_| Err(std::io::Error::other("File loader panicked.")))
+        })
+    }
+}
+
+/// Reads a file that is small enough to load right away.
+///
+/// Invalid UTF-8 is replaced with U+FFFD rather than failing the load, since files (logs
+/// especially) aren't always clean, and whether a file opens shouldn't depend on its size.
+pub(crate) fn read_file(path: &Path) -> std::io::Result<Rope> {
+    load(std::fs::File::open(path)?, &ProgressBar::hidden())
+}
+
+fn load(mut file: std::fs::File, progress: &ProgressBar) -> std::io::Result<Rope> {
+    let mut builder = RopeBuilder::new();
+    let mut decoder = Utf8Decoder::default();
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        builder.append(&decoder.decode(&chunk[..read]));
+        progress.inc(read as u64);
+    }
+    builder.append(&decoder.finish());
+    Ok(builder.finish())
+}
+
+/// Lossy UTF-8 decoding of a byte stream that arrives in pieces, keeping chars that were
+/// split between pieces intact. Invalid bytes become U+FFFD.
+#[derive(Debug, Default)]
+pub(crate) struct Utf8Decoder {
+    /// The start of a char that the last piece ended in the middle of.
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        let joined;
+        let mut bytes = if self.pending.is_empty() {
+            bytes
+        } else {
+            self.pending.extend_from_slice(bytes);
+            joined = std::mem::take(&mut self.pending);
+            &joined[..]
+        };
+        let mut text = String::with_capacity(bytes.len());
+        while !bytes.is_empty() {
+            match std::str::from_utf8(bytes) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    break;
+                }
+                Err(err) => {
+                    let (valid, rest) = bytes.split_at(err.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).expect("Prefix was validated."));
+                    match err.error_len() {
+                        Some(invalid) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            bytes = &rest[invalid..];
+                        }
+                        None => {
+                            self.pending.extend_from_slice(rest);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        text
+    }
+    
+    /// Whatever is left when the stream ends.
+    pub fn finish(&mut self) -> String {
+        if std::mem::take(&mut self.pending).is_empty() {
+            String::new()
+        } else {
+            String::from(char::REPLACEMENT_CHARACTER)
+        }
+    }
+}
+
+/// Shown in place of the text while a [`FileLoader`] runs.
+pub struct LoadingView<'a> {
+    loader: &'a FileLoader,
+    style: Style,
+    gauge_style: Style,
+    unfilled_style: Style,
+}
+
+impl<'a> LoadingView<'a> {
+    pub fn new(loader: &'a FileLoader) -> Self {
+        Self {
+            loader,
+            style: Style::new().fg(Color::Gray),
+            gauge_style: Style::new().fg(Color::Rgb(97, 175, 239)),
+            unfilled_style: Style::new().fg(Color::DarkGray),
+        }
+    }
+    
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.style(theme.style("loading"))
+            .gauge_style(theme.style("loading.gauge"))
+            .unfilled_style(theme.style("loading.gauge_unfilled"))
+    }
+    
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn gauge_style(mut self, style: Style) -> Self {
+        self.gauge_style = style;
+        self
+    }
+    
+    /// Style of the part of the gauge that is still to load.
+    pub fn unfilled_style(mut self, style: Style) -> Self {
+        self.unfilled_style = style;
+        self
+    }
+}
+
+impl Widget for LoadingView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let progress = self.loader.progress();
+        let position = progress.position();
+        let len = progress.length().unwrap_or(0);
+        let ratio = if len == 0 { 1.0 } else { (position as f64 / len as f64).min(1.0) };
+        let name = self.loader.path().file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let title = format!("Loading {name}");
+        let details = format!(
+            "{} / {} ({}/s, {} left)",
+            HumanBytes(position),
+            HumanBytes(len),
+            HumanBytes(progress.per_sec() as u64),
+            HumanDuration(progress.eta()),
+        );
+        let width = area.width.min(60);
+        let x = area.x + (area.width - width) / 2;
+        let y = area.y + area.height.saturating_sub(3) / 2;
+        let rows = [
+            Rect::new(x, y, width, 1),
+            Rect::new(x, y + 1, width, 1),
+            Rect::new(x, y + 2, width, 1),
+        ].map(|row| row.intersection(area));
+        Line::styled(title, self.style).centered().render(rows[0], buf);
+        LineGauge::default()
+            .ratio(ratio)
+            .filled_style(self.gauge_style)
+            .unfilled_style(self.unfilled_style)
+            .style(self.style)
+            .render(rows[1], buf);
+        Line::styled(details, self.style).centered().render(rows[2], buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// A file of its own for each test, removed when it's done.
+    struct TempFile(PathBuf);
+    
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("hacker-large-file-{name}-{}", std::process::id()));
+            std::fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+    
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            _ = std::fs::remove_file(&self.0);
+        }
+    }
+    
+    #[test]
+    fn decoder_keeps_split_chars_together() {
+        let bytes = "a🦀ü".as_bytes();
+        for split in 0..=bytes.len() {
+            let mut decoder = Utf8Decoder::default();
+            let mut text = decoder.decode(&bytes[..split]);
+            text.push_str(&decoder.decode(&bytes[split..]));
+            text.push_str(&decoder.finish());
+            assert_eq!(text, "a🦀ü", "split at {split}");
+        }
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.decode(b"\xF0\x9F"), "");
+        assert_eq!(decoder.decode(b"\xA6"), "");
+        assert_eq!(decoder.decode(b"\x80!"), "🦀!");
+    }
+    
+    #[test]
+    fn decoder_replaces_invalid_bytes() {
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.decode(b"a\xFFb\xC3("), "a\u{FFFD}b\u{FFFD}(");
+        // A char cut off by the end of the stream is replaced too.
+        assert_eq!(decoder.decode(b"c\xE2\x82"), "c");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+        assert_eq!(decoder.finish(), "");
+    }
+    
+    #[test]
+    fn small_and_large_files_load_the_same() {
+        // Chars straddle the chunk boundaries, and one byte is invalid.
+        let mut contents = "é".repeat(CHUNK_SIZE).into_bytes();
+        contents.insert(CHUNK_SIZE + 1, 0xFF);
+        let file = TempFile::new("load", &contents);
+        let expected = String::from_utf8_lossy(&contents);
+        assert_eq!(read_file(&file.0).unwrap(), expected);
+        
+        let mut loader = FileLoader::spawn(&file.0).unwrap();
+        let rope = loop {
+            if let Some(result) = loader.poll() {
+                break result.unwrap();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+        assert_eq!(rope, expected);
+        assert_eq!(loader.progress().position(), contents.len() as u64);
+        assert!(loader.poll().is_none());
+    }
+}
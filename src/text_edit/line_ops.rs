@@ -0,0 +1,268 @@
+use std::ops::RangeInclusive;
+
+use super::{status_bar::LineEnding, Language, Selection, SelectionKind, TextEditor, TextPosition};
+
+impl TextEditor {
+    /// The last line with text. A buffer ending in a line break has an empty line after it,
+    /// which line operations leave alone so that the final line break stays put.
+    fn last_text_line(&self) -> usize {
+        let last = self.rope.len_lines() - 1;
+        if last > 0 && self.line_len(last) == 0 { last - 1 } else { last }
+    }
+    
+    /// The lines that the selection touches, or the cursor's line. A selection that ends at
+    /// the start of a line doesn't include that line.
+    pub fn selected_lines(&self) -> RangeInclusive<usize> {
+        let Some(range) = self.selection_range() else {
+            return self.cursor.line..=self.cursor.line;
+        };
+        let first = self.rope.char_to_line(range.start);
+        let end = self.char_to_position(range.end);
+        let last = if end.col == 0 && end.line > first { end.line - 1 } else { end.line };
+        first..=last
+    }
+    
+    /// [`TextEditor::selected_lines`] without the empty line after a final line break.
+    fn selected_text_lines(&self) -> Option<RangeInclusive<usize>> {
+        let lines = self.selected_lines();
+        let last = (*lines.end()).min(self.last_text_line());
+        (*lines.start() <= last).then(|| *lines.start()..=last)
+    }
+    
+    fn line_text(&self, line: usize) -> String {
+        self.rope.line(line).chars().take(self.line_len(line)).collect()
+    }
+    
+    /// Replaces the text of `lines` (but not the line break after them) with `new_lines`,
+    /// joined with the buffer's line ending.
+    fn replace_lines(&mut self, lines: RangeInclusive<usize>, new_lines: &[String]) {
+        let start = self.rope.line_to_char(*lines.start());
+        let end = self.rope.line_to_char(*lines.end()) + self.line_len(*lines.end());
+        let text = new_lines.join(LineEnding::detect(&self.rope).as_str());
+        if self.rope.slice(start..end) == text.as_str() {
+            return;
+        }
+        self.transaction(|editor| {
+            editor.try_remove(start..end).and_then(|_| editor.try_insert(start, &text))
+        }).expect("Lines are within the buffer.");
+    }
+    
+    /// Puts the cursor back where it was before an edit, `delta` lines away, and moves the
+    /// selection anchor along.
+    fn shift_selection_lines(&mut self, cursor: TextPosition, delta: isize) {
+        if let Some(selection) = &mut self.selection {
+            selection.anchor.line = selection.anchor.line.saturating_add_signed(delta);
+        }
+        self.set_cursor(TextPosition::new(cursor.line.saturating_add_signed(delta), cursor.col));
+    }
+    
+    /// Selects whole lines, from the start of `first` to the end of `last`.
+    fn select_lines(&mut self, first: usize, last: usize) {
+        self.selection = Some(Selection {
+            anchor: TextPosition::new(first, 0),
+            kind: SelectionKind::Exclusive,
+        });
+        self.set_cursor(TextPosition::new(last, self.line_len(last)));
+    }
+    
+    /// Swaps the selected lines with the line above or below them.
+    pub fn move_lines(&mut self, down: bool) {
+        if !self.is_editable() {
+            return;
+        }
+        let Some(lines) = self.selected_text_lines() else {
+            return;
+        };
+        let (first, last) = (*lines.start(), *lines.end());
+        if (down && last >= self.last_text_line()) || (!down && first == 0) {
+            return;
+        }
+        let cursor = self.cursor;
+        let mut block: Vec<String> = lines.map(|line| self.line_text(line)).collect();
+        self.transaction(|editor| {
+            if down {
+                block.insert(0, editor.line_text(last + 1));
+                editor.replace_lines(first..=last + 1, &block);
+                editor.shift_selection_lines(cursor, 1);
+            } else {
+                block.push(editor.line_text(first - 1));
+                editor.replace_lines(first - 1..=last, &block);
+                editor.shift_selection_lines(cursor, -1);
+            }
+        });
+    }
+    
+    /// Copies the selected lines below themselves, and moves the cursor and selection onto the copy.
+    pub fn duplicate_lines(&mut self) {
+        if !self.is_editable() {
+            return;
+        }
+        let lines = self.selected_lines();
+        let (first, last) = (*lines.start(), *lines.end());
+        let line_ending = LineEnding::detect(&self.rope).as_str();
+        let block: Vec<String> = lines.map(|line| self.line_text(line)).collect();
+        let text = format!("{line_ending}{}", block.join(line_ending));
+        let end = self.rope.line_to_char(last) + self.line_len(last);
+        let cursor = self.cursor;
+        self.transaction(|editor| {
+            editor.try_insert(end, &text).expect("Lines are within the buffer.");
+            editor.shift_selection_lines(cursor, (last + 1 - first) as isize);
+        });
+    }
+    
+    /// Removes the selected lines, line breaks included.
+    pub fn delete_lines(&mut self) {
+        if !self.is_editable() {
+            return;
+        }
+        let lines = self.selected_lines();
+        let (first, last) = (*lines.start(), *lines.end());
+        let range = if last + 1 < self.rope.len_lines() {
+            self.rope.line_to_char(first)..self.rope.line_to_char(last + 1)
+        } else if first > 0 {
+            // The last line has no line break of its own, so take the one before it.
+            self.rope.line_to_char(first - 1) + self.line_len(first - 1)..self.rope.len_chars()
+        } else {
+            0..self.rope.len_chars()
+        };
+        self.selection = None;
+        let col = self.preferred_col;
+        self.transaction(|editor| {
+            editor.try_remove(range).expect("Lines are within the buffer.");
+        });
+        let line = first.min(self.rope.len_lines() - 1);
+        self.set_cursor(TextPosition::new(line, col));
+    }
+    
+    /// Joins the selected lines into one, or the cursor's line with the next. Indentation
+    /// at the joins is replaced by a single space.
+    pub fn join_lines(&mut self) {
+        if !self.is_editable() {
+            return;
+        }
+        let lines = self.selected_lines();
+        let first = *lines.start();
+        let last = (*lines.end()).max(first + 1).min(self.rope.len_lines() - 1);
+        self.selection = None;
+        self.transaction(|editor| {
+            for _ in first..last {
+                let text = editor.line_text(first);
+                let trimmed_len = text.trim_end().chars().count();
+                let next = editor.line_text(first + 1);
+                let next_indent = next.chars().take_while(|chr| chr.is_whitespace()).count();
+                let start = editor.rope.line_to_char(first) + trimmed_len;
+                let end = editor.rope.line_to_char(first + 1) + next_indent;
+                editor.try_remove(start..end).expect("Lines are within the buffer.");
+                let col = if trimmed_len == 0 || next_indent == next.chars().count() {
+                    trimmed_len
+                } else {
+                    editor.try_insert_char(start, ' ').expect("Lines are within the buffer.");
+                    trimmed_len + 1
+                };
+                editor.set_cursor(TextPosition::new(first, col));
+            }
+        });
+    }
+    
+    /// Applies `f` to the selected lines, or to every line if there is no selection, as one
+    /// undo step. A selection is kept over the changed lines.
+    fn transform_lines<F: FnOnce(&mut Vec<String>)>(&mut self, f: F) {
+        if !self.is_editable() {
+            return;
+        }
+        let had_selection = self.selection_range().is_some();
+        let lines = match self.selected_text_lines() {
+            Some(lines) if had_selection => lines,
+            Some(_) | None => 0..=self.last_text_line(),
+        };
+        let first = *lines.start();
+        let mut new_lines: Vec<String> = lines.clone().map(|line| self.line_text(line)).collect();
+        f(&mut new_lines);
+        self.replace_lines(lines, &new_lines);
+        if had_selection {
+            self.select_lines(first, first + new_lines.len().max(1) - 1);
+        } else {
+            self.set_cursor(self.cursor);
+        }
+    }
+    
+    pub fn sort_lines(&mut self) {
+        self.transform_lines(|lines| lines.sort());
+    }
+    
+    pub fn reverse_lines(&mut self) {
+        self.transform_lines(|lines| lines.reverse());
+    }
+    
+    /// Removes repeated lines, keeping the first of each.
+    pub fn unique_lines(&mut self) {
+        self.transform_lines(|lines| {
+            let mut seen = std::collections::HashSet::new();
+            lines.retain(|line| seen.insert(line.clone()));
+        });
+    }
+    
+    /// What starts a line comment in the buffer's language, going by the highlighter, or by
+    /// the file name for buffers too large to highlight.
+    pub fn line_comment(&self) -> Option<String> {
+        match &self.highlighting {
+            Some(highlighting) => highlighting.highlighter().line_comment().map(str::to_owned),
+            None => {
+                let language = self.path.as_deref().and_then(Language::from_path)?;
+                language.highlighter().line_comment().map(str::to_owned)
+            }
+        }
+    }
+    
+    /// Comments out the selected lines, or uncomments them if they are all comments already.
+    /// Comment markers line up at the smallest indentation, and blank lines are left alone.
+    pub fn toggle_comment(&mut self) {
+        if !self.is_editable() {
+            return;
+        }
+        let Some(marker) = self.line_comment() else {
+            return;
+        };
+        let Some(lines) = self.selected_text_lines() else {
+            return;
+        };
+        let indents: Vec<(usize, usize)> = lines
+            .filter(|&line| !self.line_text(line).trim().is_empty())
+            .map(|line| (line, self.line_indent(line, self.line_len(line)).chars().count()))
+            .collect();
+        let Some(min_indent) = indents.iter().map(|&(_, indent)| indent).min() else {
+            return;
+        };
+        let commented = indents.iter().all(|&(line, _)| self.line_text(line).trim_start().starts_with(marker.as_str()));
+        let marker_len = marker.chars().count();
+        // The selection anchor isn't moved by edits like the cursor is, so follow it here.
+        let mut anchor = self.selection.map(|selection| self.position_to_char(selection.anchor));
+        self.transaction(|editor| {
+            for (line, indent) in indents {
+                let line_start = editor.rope.line_to_char(line);
+                let (start, removed, inserted) = if commented {
+                    let after = editor.rope.get_char(line_start + indent + marker_len);
+                    let len = marker_len + usize::from(after == Some(' '));
+                    (line_start + indent, len, 0)
+                } else {
+                    (line_start + min_indent, 0, marker_len + 1)
+                };
+                if removed != 0 {
+                    editor.try_remove(start..start + removed).expect("Lines are within the buffer.");
+                } else {
+                    editor.try_insert(start, &format!("{marker} ")).expect("Lines are within the buffer.");
+                }
+                if let Some(anchor) = &mut anchor && start < *anchor {
+                    *anchor = (*anchor + inserted).saturating_sub(removed).max(start);
+                }
+            }
+        });
+        if let (Some(selection), Some(anchor)) = (&mut self.selection, anchor) {
+            selection.anchor = {
+                let line = self.rope.char_to_line(anchor);
+                TextPosition::new(line, anchor - self.rope.line_to_char(line))
+            };
+        }
+        self.set_cursor(self.cursor);
+    }
+}
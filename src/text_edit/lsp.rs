@@ -0,0 +1,873 @@
+pub mod json;
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+    time::{Duration, Instant},
+};
+
+use ratatui::{prelude::*, widgets::{Block, Borders, Clear, Paragraph, Wrap}};
+use ropey::Rope;
+
+use crate::theme::Theme;
+
+use super::{
+    completion::{popup_area, CompletionItem},
+    diagnostics::{Diagnostic, Severity},
+    highlight::Language,
+    rope_line_len,
+    workspace::{BufferId, BufferManager},
+    TextEditor,
+    TextPosition,
+};
+use json::Json;
+
+/// Messages larger than this are refused rather than allocated.
+const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+/// Edits are sent one by one until there are this many, and then as the whole text.
+const MAX_CHANGES: usize = 1024;
+const REQUEST_CANCELLED: f64 = -32800.0;
+const CONTENT_MODIFIED: f64 = -32801.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+/// Reads one message framed with a `Content-Length` header, returning `None` once the
+/// stream ends.
+pub fn read_message<R: BufRead>(reader: &mut R) -> std::io::Result<Option<String>> {
+    let invalid = |message| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let mut len = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length") {
+            len = value.trim().parse::<usize>().ok();
+        }
+    }
+    let len = len.ok_or_else(|| invalid("Missing Content-Length"))?;
+    if len > MAX_MESSAGE_LEN {
+        return Err(invalid("Message too large"));
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body).map(Some).map_err(|_| invalid("Message isn't UTF-8"))
+}
+
+/// Writes one message with a `Content-Length` header.
+pub fn write_message<W: Write>(writer: &mut W, message: &Json) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+/// `file://` URI of a path, made absolute first.
+pub fn path_to_uri(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            _ = write!(uri, "%{byte:02X}");
+        }
+    }
+    uri
+}
+
+/// The path of a `file://` URI, or `None` for other schemes.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut index = 0;
+    while index < encoded.len() {
+        let hex = encoded.get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (encoded[index], hex) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                bytes.push(byte);
+                index += 1;
+            }
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // Drive letters come after a slash, as in `file:///C:/`.
+    let path = match path.strip_prefix('/') {
+        Some(rest) if rest.as_bytes().get(1) == Some(&b':') => rest.to_owned(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+/// The name of the language that servers know it by.
+fn language_id(path: &Path) -> &'static str {
+    match Language::from_path(path) {
+        Some(Language::Rust) => "rust",
+        Some(Language::Toml) => "toml",
+        Some(Language::Json) => "json",
+        Some(Language::Markdown) => "markdown",
+        Some(Language::Shell) => "shellscript",
+        None => "plaintext",
+    }
+}
+
+/// What the `character` of a position counts. Servers use UTF-16 unless they agree to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            PositionEncoding::Utf8 => "utf-8",
+            PositionEncoding::Utf16 => "utf-16",
+            PositionEncoding::Utf32 => "utf-32",
+        }
+    }
+    
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "utf-8" => PositionEncoding::Utf8,
+            "utf-16" => PositionEncoding::Utf16,
+            "utf-32" => PositionEncoding::Utf32,
+            _ => return None,
+        })
+    }
+    
+    /// The position of a char index, as an object with a `line` and `character`.
+    pub fn position(self, rope: &Rope, char_idx: usize) -> Json {
+        let line = rope.char_to_line(char_idx);
+        let line_start = rope.line_to_char(line);
+        let character = match self {
+            PositionEncoding::Utf8 => rope.char_to_byte(char_idx) - rope.char_to_byte(line_start),
+            PositionEncoding::Utf16 => rope.char_to_utf16_cu(char_idx) - rope.char_to_utf16_cu(line_start),
+            PositionEncoding::Utf32 => char_idx - line_start,
+        };
+        Json::object([("line", line.into()), ("character", character.into())])
+    }
+    
+    /// The char index of a position. Positions past the end of their line are moved back to
+    /// it, and those past the last line go to the end of the text.
+    pub fn char_index(self, rope: &Rope, position: &Json) -> Option<usize> {
+        let line = position["line"].as_usize()?;
+        let character = position["character"].as_usize()?;
+        if line >= rope.len_lines() {
+            return Some(rope.len_chars());
+        }
+        let line_start = rope.line_to_char(line);
+        let char_idx = match self {
+            PositionEncoding::Utf8 => {
+                let byte = rope.char_to_byte(line_start).saturating_add(character);
+                rope.byte_to_char(byte.min(rope.len_bytes()))
+            }
+            PositionEncoding::Utf16 => {
+                let unit = rope.char_to_utf16_cu(line_start).saturating_add(character);
+                rope.utf16_cu_to_char(unit.min(rope.len_utf16_cu()))
+            }
+            PositionEncoding::Utf32 => line_start.saturating_add(character),
+        };
+        Some(char_idx.min(line_start + rope_line_len(rope, line)))
+    }
+    
+    pub fn range(self, rope: &Rope, chars: Range<usize>) -> Json {
+        Json::object([
+            ("start", self.position(rope, chars.start)),
+            ("end", self.position(rope, chars.end)),
+        ])
+    }
+}
+
+/// The edits made to a buffer since they were last sent to a language server.
+pub(crate) struct ChangeLog {
+    encoding: PositionEncoding,
+    /// `TextDocumentContentChangeEvent`s, in order.
+    changes: Vec<Json>,
+    /// Set when the text changed in a way that wasn't recorded, so that all of it has to be sent.
+    full: bool,
+}
+
+impl ChangeLog {
+    fn new(encoding: PositionEncoding) -> Self {
+        Self {
+            encoding,
+            changes: Vec::new(),
+            full: false,
+        }
+    }
+    
+    fn push(&mut self, range: Json, text: &str) {
+        if self.full {
+            return;
+        }
+        if self.changes.len() == MAX_CHANGES {
+            self.resync();
+            return;
+        }
+        self.changes.push(Json::object([("range", range), ("text", text.into())]));
+    }
+    
+    /// `text` was just inserted at `char_idx`.
+    pub(crate) fn inserted(&mut self, rope: &Rope, char_idx: usize, text: &str) {
+        let position = self.encoding.position(rope, char_idx);
+        let range = Json::object([("start", position.clone()), ("end", position)]);
+        self.push(range, text);
+    }
+    
+    /// `chars` are about to be removed.
+    pub(crate) fn removing(&mut self, rope: &Rope, chars: Range<usize>) {
+        let range = self.encoding.range(rope, chars);
+        self.push(range, "");
+    }
+    
+    /// The whole text changed.
+    pub(crate) fn resync(&mut self) {
+        self.full = true;
+        self.changes.clear();
+    }
+    
+    /// The changes to send, leaving the log empty.
+    fn take(&mut self, rope: &Rope, incremental: bool) -> Vec<Json> {
+        let changes = std::mem::take(&mut self.changes);
+        if std::mem::take(&mut self.full) || !incremental {
+            return vec![Json::object([("text", rope.to_string().into())])];
+        }
+        changes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LspEvent {
+    /// The server described what's at `position`, as plain text or Markdown.
+    Hover {
+        buffer: BufferId,
+        position: TextPosition,
+        text: String,
+    },
+    /// Completions for the word at `position`.
+    Completion {
+        buffer: BufferId,
+        position: TextPosition,
+        items: Vec<CompletionItem>,
+    },
+    /// Where the symbol asked about is defined. The buffer has been opened if it wasn't already.
+    Definition {
+        buffer: BufferId,
+        position: TextPosition,
+    },
+    /// Something the server wants the user to know, including errors in answer to requests.
+    Message(String),
+    /// The server stopped, or closed its output.
+    Exited,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncKind {
+    None,
+    Full,
+    Incremental,
+}
+
+/// What the server said it can do when it was initialized.
+#[derive(Debug, Clone, Copy)]
+struct Capabilities {
+    encoding: PositionEncoding,
+    sync: SyncKind,
+    hover: bool,
+    completion: bool,
+    definition: bool,
+}
+
+impl Capabilities {
+    fn parse(capabilities: &Json) -> Self {
+        let sync = match &capabilities["textDocumentSync"] {
+            Json::Object(_) => &capabilities["textDocumentSync"]["change"],
+            kind => kind,
+        };
+        let provides = |name: &str| !matches!(capabilities[name], Json::Null | Json::Bool(false));
+        Self {
+            encoding: capabilities["positionEncoding"].as_str()
+                .and_then(PositionEncoding::from_name)
+                .unwrap_or_default(),
+            sync: match sync.as_u64() {
+                Some(1) => SyncKind::Full,
+                Some(2) => SyncKind::Incremental,
+                _ => SyncKind::None,
+            },
+            hover: provides("hoverProvider"),
+            completion: provides("completionProvider"),
+            definition: provides("definitionProvider"),
+        }
+    }
+}
+
+/// A request waiting for its response, with what's needed to make sense of it.
+#[derive(Debug, Clone, Copy)]
+enum Request {
+    Initialize,
+    Hover(BufferId, TextPosition),
+    Completion(BufferId, TextPosition),
+    Definition,
+    Shutdown,
+}
+
+/// A buffer that the server has been told about.
+struct Document {
+    uri: String,
+    version: u64,
+}
+
+/// Talks to a language server over its stdin and stdout. Buffers with a path are opened on
+/// the server as they're found by [`LspClient::poll`], and their edits are sent to it from
+/// there on. Diagnostics go straight to the buffers; everything else comes back as
+/// [`LspEvent`]s.
+pub struct LspClient {
+    child: Child,
+    /// Messages for the thread that writes to the server. Dropping it closes the server's input.
+    sender: Option<Sender<Json>>,
+    receiver: Receiver<Json>,
+    /// Only buffers in this language are sent, or every buffer with a path if `None`.
+    language: Option<Language>,
+    /// `None` until the server answers the initialize request.
+    capabilities: Option<Capabilities>,
+    next_id: u64,
+    pending: HashMap<u64, Request>,
+    documents: HashMap<BufferId, Document>,
+    exited: bool,
+}
+
+impl LspClient {
+    /// Starts the server and asks it to initialize for the workspace at `root`. Its stderr
+    /// is discarded.
+    pub fn spawn(mut command: Command, root: &Path, language: Option<Language>) -> std::io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, outgoing) = mpsc::channel::<Json>();
+        std::thread::Builder::new()
+            .name(String::from("lsp writer"))
+            .spawn(move || {
+                for message in outgoing {
+                    if write_message(&mut stdin, &message).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        let (incoming, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name(String::from("lsp reader"))
+            .spawn(move || {
+                let mut reader = BufReader::new(stdout);
+                while let Ok(Some(body)) = read_message(&mut reader) {
+                    // Messages that aren't JSON are skipped, but a broken frame ends the stream.
+                    if let Ok(message) = Json::parse(&body)
+                        && incoming.send(message).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        let mut client = Self {
+            child,
+            sender: Some(sender),
+            receiver,
+            language,
+            capabilities: None,
+            next_id: 0,
+            pending: HashMap::new(),
+            documents: HashMap::new(),
+            exited: false,
+        };
+        let root_uri = path_to_uri(root);
+        let root_name = root.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let encodings = [PositionEncoding::Utf32, PositionEncoding::Utf8, PositionEncoding::Utf16]
+            .map(|encoding| Json::from(encoding.name()));
+        let params = Json::object([
+            ("processId", std::process::id().into()),
+            ("clientInfo", Json::object([("name", "hacker".into())])),
+            ("rootUri", root_uri.clone().into()),
+            ("workspaceFolders", vec![Json::object([("uri", root_uri.into()), ("name", root_name.into())])].into()),
+            ("capabilities", Json::object([
+                ("general", Json::object([("positionEncodings", encodings.to_vec().into())])),
+                ("textDocument", Json::object([
+                    ("synchronization", Json::object([("dynamicRegistration", false.into())])),
+                    ("publishDiagnostics", Json::object([("versionSupport", true.into())])),
+                    ("hover", Json::object([("contentFormat", vec!["markdown".into(), "plaintext".into()].into())])),
+                    ("completion", Json::object([("completionItem", Json::object([("snippetSupport", false.into())]))])),
+                    ("definition", Json::object([("linkSupport", true.into())])),
+                ])),
+            ])),
+        ]);
+        client.request("initialize", params, Request::Initialize);
+        Ok(client)
+    }
+    
+    /// Whether the server has answered the initialize request.
+    pub fn is_ready(&self) -> bool {
+        self.capabilities.is_some()
+    }
+    
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+    
+    fn send(&self, message: Json) {
+        if let Some(sender) = &self.sender {
+            // A closed channel means the server is gone, which poll reports.
+            _ = sender.send(message);
+        }
+    }
+    
+    fn notify(&self, method: &str, params: Json) {
+        self.send(Json::object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)]));
+    }
+    
+    fn request(&mut self, method: &str, params: Json, request: Request) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, request);
+        self.send(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.into()),
+            ("method", method.into()),
+            ("params", params),
+        ]));
+    }
+    
+    fn respond(&self, id: Json, result: Result<Json, (f64, &str)>) {
+        let mut response = Json::object([("jsonrpc", "2.0".into()), ("id", id)]);
+        match result {
+            Ok(result) => response.set("result", result),
+            Err((code, message)) => response.set("error", Json::object([("code", code.into()), ("message", message.into())])),
+        }
+        self.send(response);
+    }
+    
+    /// Handles whatever the server sent since the last call, and brings it up to date with
+    /// the buffers: new ones are opened, edited ones sent and removed ones closed. Call it
+    /// regularly.
+    pub fn poll(&mut self, buffers: &mut BufferManager) -> Vec<LspEvent> {
+        let mut events = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(message) => self.handle(message, buffers, &mut events),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if !self.exited {
+                        self.exited = true;
+                        self.sender = None;
+                        events.push(LspEvent::Exited);
+                    }
+                    break;
+                }
+            }
+        }
+        self.sync(buffers);
+        events
+    }
+    
+    fn handle(&mut self, message: Json, buffers: &mut BufferManager, events: &mut Vec<LspEvent>) {
+        let Some(method) = message["method"].as_str() else {
+            let Some(request) = message["id"].as_u64().and_then(|id| self.pending.remove(&id)) else {
+                return;
+            };
+            let error = &message["error"];
+            if !error.is_null() {
+                let code = error["code"].as_f64();
+                if code != Some(REQUEST_CANCELLED) && code != Some(CONTENT_MODIFIED) {
+                    let text = error["message"].as_str().unwrap_or("Unknown error");
+                    events.push(LspEvent::Message(format!("Language server error: {text}")));
+                }
+                return;
+            }
+            self.handle_response(request, &message["result"], buffers, events);
+            return;
+        };
+        let params = &message["params"];
+        if let Some(id) = message.get("id") {
+            // Requests from the server are answered as simply as they can be.
+            let result = match method {
+                "workspace/configuration" => {
+                    let items = params["items"].as_array().map_or(0, <[Json]>::len);
+                    Ok(Json::Array(vec![Json::Null; items]))
+                }
+                "window/workDoneProgress/create" | "client/registerCapability" | "client/unregisterCapability" => Ok(Json::Null),
+                "workspace/workspaceFolders" => Ok(Json::Null),
+                _ => Err((METHOD_NOT_FOUND, "Method not found")),
+            };
+            self.respond(id.clone(), result);
+            return;
+        }
+        match method {
+            "textDocument/publishDiagnostics" => self.publish_diagnostics(params, buffers),
+            "window/showMessage" => {
+                if let Some(text) = params["message"].as_str() {
+                    events.push(LspEvent::Message(text.to_owned()));
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    fn handle_response(&mut self, request: Request, result: &Json, buffers: &mut BufferManager, events: &mut Vec<LspEvent>) {
+        match request {
+            Request::Initialize => {
+                self.capabilities = Some(Capabilities::parse(&result["capabilities"]));
+                self.notify("initialized", Json::object([]));
+            }
+            Request::Hover(buffer, position) => {
+                let text = hover_text(&result["contents"]);
+                if !text.trim().is_empty() {
+                    events.push(LspEvent::Hover { buffer, position, text });
+                }
+            }
+            Request::Completion(buffer, position) => {
+                let items = match result {
+                    Json::Array(items) => items.as_slice(),
+                    result => result["items"].as_array().unwrap_or_default(),
+                };
+                let items = items.iter().filter_map(completion_item).collect();
+                events.push(LspEvent::Completion { buffer, position, items });
+            }
+            Request::Definition => {
+                let location = match result {
+                    Json::Array(locations) => locations.first().unwrap_or(&Json::Null),
+                    location => location,
+                };
+                if location.is_null() {
+                    events.push(LspEvent::Message(String::from("No definition found")));
+                    return;
+                }
+                match self.open_location(location, buffers) {
+                    Ok((buffer, position)) => events.push(LspEvent::Definition { buffer, position }),
+                    Err(error) => events.push(LspEvent::Message(error.to_string())),
+                }
+            }
+            Request::Shutdown => {
+                self.notify("exit", Json::Null);
+                self.sender = None;
+            }
+        }
+    }
+    
+    /// Opens the file of a `Location` or `LocationLink`, returning where it points to.
+    fn open_location(&mut self, location: &Json, buffers: &mut BufferManager) -> std::io::Result<(BufferId, TextPosition)> {
+        let (uri, range) = match location.get("targetUri") {
+            Some(uri) => (uri, &location["targetSelectionRange"]),
+            None => (&location["uri"], &location["range"]),
+        };
+        let path = uri.as_str().and_then(uri_to_path).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::Unsupported, "The definition isn't in a file")
+        })?;
+        let buffer = buffers.open(path)?;
+        let editor = buffers.get(buffer).expect("The buffer was just opened.");
+        let start = &range["start"];
+        let position = if editor.is_loading() {
+            // Good enough for text that isn't there yet, unless the line has wide chars.
+            TextPosition::new(start["line"].as_usize().unwrap_or(0), start["character"].as_usize().unwrap_or(0))
+        } else {
+            let encoding = self.capabilities.map(|capabilities| capabilities.encoding).unwrap_or_default();
+            let char_idx = encoding.char_index(&editor.rope, start).unwrap_or(0);
+            editor.char_to_position(char_idx)
+        };
+        Ok((buffer, position))
+    }
+    
+    fn publish_diagnostics(&self, params: &Json, buffers: &mut BufferManager) {
+        let uri = params["uri"].as_str();
+        let Some((&buffer, document)) = self.documents.iter().find(|(_, document)| Some(document.uri.as_str()) == uri) else {
+            return;
+        };
+        // Diagnostics for text that has since changed would be in the wrong places.
+        if params["version"].as_u64().is_some_and(|version| version != document.version) {
+            return;
+        }
+        let Some(editor) = buffers.get_mut(buffer) else {
+            return;
+        };
+        let encoding = self.capabilities.map(|capabilities| capabilities.encoding).unwrap_or_default();
+        let position = |position: &Json| {
+            let char_idx = encoding.char_index(&editor.rope, position)?;
+            Some(editor.char_to_position(char_idx))
+        };
+        let diagnostics = params["diagnostics"].as_array().unwrap_or_default().iter()
+            .filter_map(|diagnostic| Some(Diagnostic {
+                start: position(&diagnostic["range"]["start"])?,
+                end: position(&diagnostic["range"]["end"])?,
+                severity: diagnostic["severity"].as_u64().and_then(Severity::from_lsp).unwrap_or(Severity::Error),
+                message: diagnostic["message"].as_str()?.to_owned(),
+                source: diagnostic["source"].as_str().map(str::to_owned),
+            }))
+            .collect();
+        editor.set_diagnostics(diagnostics);
+    }
+    
+    /// Whether the server should be told about the buffer.
+    fn serves(&self, editor: &TextEditor) -> bool {
+        let Some(path) = &editor.path else {
+            return false;
+        };
+        !editor.is_loading() && self.language.is_none_or(|language| Language::from_path(path) == Some(language))
+    }
+    
+    /// Opens, changes and closes documents on the server to match the buffers.
+    fn sync(&mut self, buffers: &mut BufferManager) {
+        let Some(capabilities) = self.capabilities.filter(|_| !self.exited) else {
+            return;
+        };
+        let closed: Vec<BufferId> = self.documents.iter()
+            .filter(|&(&buffer, document)| {
+                buffers.get(buffer)
+                    .filter(|editor| self.serves(editor))
+                    .and_then(|editor| editor.path.as_deref())
+                    .is_none_or(|path| path_to_uri(path) != document.uri)
+            })
+            .map(|(&buffer, _)| buffer)
+            .collect();
+        for buffer in closed {
+            let document = self.documents.remove(&buffer).expect("The document is open.");
+            self.notify("textDocument/didClose", Json::object([("textDocument", Json::object([("uri", document.uri.into())]))]));
+            if let Some(editor) = buffers.get_mut(buffer) {
+                editor.lsp_changes = None;
+                editor.set_diagnostics(Vec::new());
+            }
+        }
+        let mut notifications = Vec::new();
+        for (buffer, editor) in buffers.iter_mut() {
+            if !self.serves(editor) {
+                continue;
+            }
+            let version = editor.version();
+            match self.documents.get_mut(&buffer) {
+                None => {
+                    let uri = path_to_uri(editor.path.as_deref().expect("Served buffers have a path."));
+                    let language = language_id(editor.path.as_deref().expect("Served buffers have a path."));
+                    notifications.push(("textDocument/didOpen", Json::object([("textDocument", Json::object([
+                        ("uri", uri.clone().into()),
+                        ("languageId", language.into()),
+                        ("version", version.into()),
+                        ("text", editor.rope.to_string().into()),
+                    ]))])));
+                    editor.lsp_changes = Some(ChangeLog::new(capabilities.encoding));
+                    self.documents.insert(buffer, Document { uri, version });
+                }
+                Some(document) if document.version != version => {
+                    document.version = version;
+                    let rope = &editor.rope;
+                    let changes = editor.lsp_changes
+                        .get_or_insert_with(|| {
+                            let mut changes = ChangeLog::new(capabilities.encoding);
+                            changes.resync();
+                            changes
+                        })
+                        .take(rope, capabilities.sync == SyncKind::Incremental);
+                    if capabilities.sync != SyncKind::None && !changes.is_empty() {
+                        notifications.push(("textDocument/didChange", Json::object([
+                            ("textDocument", Json::object([("uri", document.uri.clone().into()), ("version", version.into())])),
+                            ("contentChanges", changes.into()),
+                        ])));
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+        for (method, params) in notifications {
+            self.notify(method, params);
+        }
+    }
+    
+    /// Brings the server up to date and finds where the cursor of `buffer` is for a request.
+    fn position_params(&mut self, buffers: &mut BufferManager, buffer: BufferId, supported: bool) -> std::io::Result<(Json, TextPosition)> {
+        if self.exited {
+            return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "The language server has exited"));
+        }
+        let Some(capabilities) = self.capabilities else {
+            return Err(std::io::Error::new(std::io::ErrorKind::ResourceBusy, "The language server is still starting"));
+        };
+        if !supported {
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "The language server doesn't support that"));
+        }
+        self.sync(buffers);
+        let (Some(document), Some(editor)) = (self.documents.get(&buffer), buffers.get(buffer)) else {
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "The language server doesn't handle this buffer"));
+        };
+        let params = Json::object([
+            ("textDocument", Json::object([("uri", document.uri.clone().into())])),
+            ("position", capabilities.encoding.position(&editor.rope, editor.cursor_char_index())),
+        ]);
+        Ok((params, editor.cursor))
+    }
+    
+    /// Asks what's at the cursor of `buffer`. The answer comes as [`LspEvent::Hover`].
+    pub fn hover(&mut self, buffers: &mut BufferManager, buffer: BufferId) -> std::io::Result<()> {
+        let supported = self.capabilities.is_some_and(|capabilities| capabilities.hover);
+        let (params, position) = self.position_params(buffers, buffer, supported)?;
+        self.request("textDocument/hover", params, Request::Hover(buffer, position));
+        Ok(())
+    }
+    
+    /// Asks for completions at the cursor of `buffer`. They come as [`LspEvent::Completion`].
+    pub fn completion(&mut self, buffers: &mut BufferManager, buffer: BufferId) -> std::io::Result<()> {
+        let supported = self.capabilities.is_some_and(|capabilities| capabilities.completion);
+        let (params, position) = self.position_params(buffers, buffer, supported)?;
+        self.request("textDocument/completion", params, Request::Completion(buffer, position));
+        Ok(())
+    }
+    
+    /// Asks where the symbol at the cursor of `buffer` is defined. The answer comes as
+    /// [`LspEvent::Definition`].
+    pub fn definition(&mut self, buffers: &mut BufferManager, buffer: BufferId) -> std::io::Result<()> {
+        let supported = self.capabilities.is_some_and(|capabilities| capabilities.definition);
+        let (params, _) = self.position_params(buffers, buffer, supported)?;
+        self.request("textDocument/definition", params, Request::Definition);
+        Ok(())
+    }
+    
+    /// Asks the server to shut down and exit, waiting up to `timeout` for it to, and kills it
+    /// if it doesn't.
+    pub fn shutdown(mut self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        if self.is_ready() && !self.exited {
+            self.request("shutdown", Json::Null, Request::Shutdown);
+            while self.sender.is_some() {
+                let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+                    break;
+                };
+                match self.receiver.recv_timeout(left) {
+                    Ok(message) if message["method"].is_null()
+                        && message["id"].as_u64().and_then(|id| self.pending.get(&id)).is_some_and(|request| matches!(request, Request::Shutdown)) => {
+                        self.notify("exit", Json::Null);
+                        self.sender = None;
+                    }
+                    Ok(_) => {}
+                    Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        }
+        self.sender = None;
+        while Instant::now() < deadline {
+            if !matches!(self.child.try_wait(), Ok(None)) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            _ = self.child.kill();
+            _ = self.child.wait();
+        }
+    }
+}
+
+/// The text of hover contents, which can be `MarkupContent`, a `MarkedString` or a list of them.
+fn hover_text(contents: &Json) -> String {
+    match contents {
+        Json::String(text) => text.clone(),
+        Json::Array(contents) => contents.iter().map(hover_text).collect::<Vec<_>>().join("\n\n"),
+        contents => contents["value"].as_str().unwrap_or_default().to_owned(),
+    }
+}
+
+fn completion_item(item: &Json) -> Option<CompletionItem> {
+    let mut completion = CompletionItem::new(item["label"].as_str()?);
+    completion.detail = item["detail"].as_str().map(str::to_owned);
+    completion.insert_text = item["textEdit"]["newText"].as_str()
+        .or_else(|| item["insertText"].as_str())
+        .map(str::to_owned);
+    Some(completion)
+}
+
+/// Shows hover text in a box next to the cursor, within the area it's rendered to. Render
+/// it after the editor, so that it knows where the cursor is.
+pub struct HoverPopup<'a> {
+    text: &'a str,
+    editor: &'a TextEditor,
+    style: Style,
+    border_style: Style,
+}
+
+impl<'a> HoverPopup<'a> {
+    /// Longest side of the box, borders included.
+    const MAX_WIDTH: u16 = 72;
+    const MAX_HEIGHT: u16 = 16;
+    
+    pub fn new(text: &'a str, editor: &'a TextEditor) -> Self {
+        Self {
+            text,
+            editor,
+            style: Style::new().bg(Color::Rgb(37, 41, 50)),
+            border_style: Style::new().fg(Color::DarkGray),
+        }
+    }
+    
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.style(theme.style("hover"))
+            .border_style(theme.style("hover.border"))
+    }
+    
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn border_style(mut self, style: Style) -> Self {
+        self.border_style = style;
+        self
+    }
+}
+
+impl Widget for HoverPopup<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let Some(cursor) = self.editor.cursor_screen_position else {
+            return;
+        };
+        let text = self.text.trim();
+        let longest = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+        let width = (longest as u16).saturating_add(2).min(Self::MAX_WIDTH).min(area.width);
+        let inner_width = width.saturating_sub(2).max(1) as usize;
+        let rows: usize = text.lines().map(|line| line.chars().count().div_ceil(inner_width).max(1)).sum();
+        let height = (rows as u16).saturating_add(2).min(Self::MAX_HEIGHT);
+        let popup = popup_area(cursor, width, height, area);
+        if popup.height < 3 {
+            return;
+        }
+        Clear.render(popup, buf);
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(self.border_style)
+            .style(self.style);
+        Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(block)
+            .render(popup, buf);
+    }
+}
@@ -0,0 +1,568 @@
+//! Keyboard macros, recorded as the [`EditorCommand`]s that keys turn into rather than the
+//! keys themselves. They replay the same way whatever the keymap, the scroll position or the
+//! size of the window.
+//!
+//! Positions in commands such as [`EditorCommand::MoveTo`] are recorded relative to the cursor,
+//! so that a macro recorded on one line does the same thing on the next. An input layer that
+//! works out a range itself, like Vim's `dw`, is recorded as that range rather than the motion.
+//! Undo groups aren't recorded, since each playback is undone as one step.
+//!
+//! Macros are saved as text, one register per block. Commands with a title in
+//! [`NAMED_COMMANDS`] are written as the title, and positions as `<lines>:<cols>` from the cursor.
+//! `type` is a typed char, which closes brackets, and `insert` is text inserted as it is. Every
+//! other command has a syntax of its own, so nothing is lost by saving:
+//!
+//! ```text
+//! macro a
+//! Go to Line Start
+//! type "/"
+//! insert "/ "
+//! move 1:-3
+//! endmacro
+//! ```
+
+use std::{cell::RefCell, collections::BTreeMap, fmt, path::{Path, PathBuf}, rc::Rc};
+
+use super::{command::{EditorCommand, NAMED_COMMANDS}, lsp::json::Json, Selection, SelectionKind, TextEditor, TextPosition};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MacroError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
+/// Every buffer records into and plays from the macros of the [`BufferManager`](super::workspace::BufferManager).
+pub type SharedMacros = Rc<RefCell<Macros>>;
+
+/// How far a position is from the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Offset {
+    lines: isize,
+    cols: isize,
+}
+
+impl Offset {
+    fn between(cursor: TextPosition, position: TextPosition) -> Self {
+        Self {
+            lines: position.line as isize - cursor.line as isize,
+            cols: position.col as isize - cursor.col as isize,
+        }
+    }
+    
+    fn from(self, cursor: TextPosition) -> TextPosition {
+        TextPosition::new(cursor.line.saturating_add_signed(self.lines), cursor.col.saturating_add_signed(self.cols))
+    }
+    
+    fn parse(text: &str) -> Option<Self> {
+        let (lines, cols) = text.split_once(':')?;
+        Some(Self {
+            lines: lines.parse().ok()?,
+            cols: cols.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.lines, self.cols)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum MacroStep {
+    /// A command without positions, which replays as it is.
+    Command(EditorCommand),
+    MoveTo(Offset),
+    InsertAt {
+        at: Offset,
+        text: String,
+    },
+    DeleteRange(Offset, Offset),
+    SetSelection {
+        anchor: Offset,
+        kind: SelectionKind,
+    },
+}
+
+impl MacroStep {
+    /// The step for `command` run with the cursor where it is in `editor`, or `None` if it
+    /// isn't recorded.
+    fn new(command: &EditorCommand, editor: &TextEditor) -> Option<Self> {
+        let cursor = editor.cursor;
+        let offset = |char_idx: usize| Offset::between(cursor, editor.char_to_position(char_idx));
+        Some(match command {
+            EditorCommand::BeginUndoGroup | EditorCommand::EndUndoGroup => return None,
+            &EditorCommand::MoveTo(position) => MacroStep::MoveTo(Offset::between(cursor, position)),
+            EditorCommand::InsertAt { char_idx, text } => MacroStep::InsertAt {
+                at: offset(*char_idx),
+                text: text.clone(),
+            },
+            EditorCommand::DeleteRange(range) => MacroStep::DeleteRange(offset(range.start), offset(range.end)),
+            &EditorCommand::SetSelection(selection) => MacroStep::SetSelection {
+                anchor: Offset::between(cursor, selection.anchor),
+                kind: selection.kind,
+            },
+            command => MacroStep::Command(command.clone()),
+        })
+    }
+    
+    /// The command to run with the cursor where it is in `editor`.
+    fn command(&self, editor: &TextEditor) -> EditorCommand {
+        let cursor = editor.cursor;
+        let char_idx = |offset: Offset| editor.position_to_char(offset.from(cursor));
+        match self {
+            MacroStep::Command(command) => command.clone(),
+            &MacroStep::MoveTo(offset) => EditorCommand::MoveTo(offset.from(cursor)),
+            MacroStep::InsertAt { at, text } => EditorCommand::InsertAt {
+                char_idx: char_idx(*at),
+                text: text.clone(),
+            },
+            &MacroStep::DeleteRange(start, end) => EditorCommand::DeleteRange(char_idx(start)..char_idx(end).max(char_idx(start))),
+            &MacroStep::SetSelection { anchor, kind } => EditorCommand::SetSelection(Selection {
+                anchor: editor.char_to_position(char_idx(anchor)),
+                kind,
+            }),
+        }
+    }
+    
+    fn parse(line: &str) -> Result<Self, String> {
+        if let Some((_, command)) = NAMED_COMMANDS.iter().find(|(title, _)| *title == line) {
+            return Ok(MacroStep::Command(command.clone()));
+        }
+        let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+        let offset = |text: &str| Offset::parse(text).ok_or_else(|| format!("Expected `<lines>:<cols>`, not `{text}`"));
+        let string = |text: &str| match Json::parse(text) {
+            Ok(Json::String(text)) => Ok(text),
+            _ => Err(format!("Expected a quoted string, not `{text}`")),
+        };
+        let kind = |text: &str| match text {
+            "exclusive" => Ok(SelectionKind::Exclusive),
+            "inclusive" => Ok(SelectionKind::Inclusive),
+            "line" => Ok(SelectionKind::Line),
+            _ => Err(format!("Unknown selection kind `{text}`")),
+        };
+        let number = |text: &str| text.parse::<usize>().map_err(|_| format!("Expected a number, not `{text}`"));
+        let position = |text: &str| text.split_once(':')
+            .and_then(|(line, col)| Some(TextPosition::new(line.parse().ok()?, col.parse().ok()?)))
+            .ok_or_else(|| format!("Expected `<line>:<col>`, not `{text}`"));
+        let pair = || args.split_once(' ').ok_or_else(|| format!("Expected two arguments for `{name}`"));
+        Ok(match name {
+            "type" => {
+                let text = string(args)?;
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(chr), None) => MacroStep::Command(EditorCommand::InsertChar(chr)),
+                    _ => return Err(format!("Expected one char to type, not `{args}`")),
+                }
+            }
+            "move" => MacroStep::MoveTo(offset(args)?),
+            // Text at the cursor is quoted straight after `insert`, and text elsewhere after an offset.
+            "insert" if args.starts_with('"') => MacroStep::Command(EditorCommand::InsertText(string(args)?)),
+            "insert" => {
+                let (at, text) = pair()?;
+                MacroStep::InsertAt {
+                    at: offset(at)?,
+                    text: string(text)?,
+                }
+            }
+            "delete" => {
+                let (start, end) = pair()?;
+                MacroStep::DeleteRange(offset(start)?, offset(end)?)
+            }
+            "select" => {
+                let (anchor, kind_name) = pair()?;
+                MacroStep::SetSelection {
+                    anchor: offset(anchor)?,
+                    kind: kind(kind_name)?,
+                }
+            }
+            "start-selection" => MacroStep::Command(EditorCommand::StartSelection(kind(args)?)),
+            "go-to-line" => MacroStep::Command(EditorCommand::GoToLine(number(args)?)),
+            "begin-undo-group" => MacroStep::Command(EditorCommand::BeginUndoGroup),
+            "end-undo-group" => MacroStep::Command(EditorCommand::EndUndoGroup),
+            // Commands with absolute positions, which recording turns into the steps above.
+            "move-to" => MacroStep::Command(EditorCommand::MoveTo(position(args)?)),
+            "insert-at" => {
+                let (char_idx, text) = pair()?;
+                MacroStep::Command(EditorCommand::InsertAt {
+                    char_idx: number(char_idx)?,
+                    text: string(text)?,
+                })
+            }
+            "delete-chars" => {
+                let (start, end) = pair()?;
+                MacroStep::Command(EditorCommand::DeleteRange(number(start)?..number(end)?))
+            }
+            "select-at" => {
+                let (anchor, kind_name) = pair()?;
+                MacroStep::Command(EditorCommand::SetSelection(Selection {
+                    anchor: position(anchor)?,
+                    kind: kind(kind_name)?,
+                }))
+            }
+            _ => return Err(format!("Unknown command `{line}`")),
+        })
+    }
+}
+
+impl fmt::Display for MacroStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let string = |text: &str| Json::String(text.to_owned());
+        let kind = |kind: SelectionKind| match kind {
+            SelectionKind::Exclusive => "exclusive",
+            SelectionKind::Inclusive => "inclusive",
+            SelectionKind::Line => "line",
+        };
+        match self {
+            MacroStep::Command(command) => match command {
+                EditorCommand::InsertChar(chr) => write!(f, "type {}", string(&chr.to_string())),
+                EditorCommand::InsertText(text) => write!(f, "insert {}", string(text)),
+                EditorCommand::StartSelection(selection_kind) => write!(f, "start-selection {}", kind(*selection_kind)),
+                EditorCommand::GoToLine(line) => write!(f, "go-to-line {line}"),
+                EditorCommand::BeginUndoGroup => f.write_str("begin-undo-group"),
+                EditorCommand::EndUndoGroup => f.write_str("end-undo-group"),
+                EditorCommand::MoveTo(position) => write!(f, "move-to {}:{}", position.line, position.col),
+                EditorCommand::InsertAt { char_idx, text } => write!(f, "insert-at {char_idx} {}", string(text)),
+                EditorCommand::DeleteRange(range) => write!(f, "delete-chars {} {}", range.start, range.end),
+                EditorCommand::SetSelection(selection) => {
+                    write!(f, "select-at {}:{} {}", selection.anchor.line, selection.anchor.col, kind(selection.kind))
+                }
+                // Listed rather than matched with `_`, so that new commands have to be given a syntax.
+                EditorCommand::MoveLeft | EditorCommand::MoveRight | EditorCommand::MoveUp | EditorCommand::MoveDown
+                | EditorCommand::MoveLineStart | EditorCommand::MoveLineEnd | EditorCommand::MoveWordLeft | EditorCommand::MoveWordRight
+                | EditorCommand::MoveParagraphUp | EditorCommand::MoveParagraphDown | EditorCommand::PageUp | EditorCommand::PageDown
+                | EditorCommand::MoveDocumentStart | EditorCommand::MoveDocumentEnd | EditorCommand::AddCursorAbove
+                | EditorCommand::AddCursorBelow | EditorCommand::ClearExtraCursors | EditorCommand::ClearSelection
+                | EditorCommand::InsertNewline | EditorCommand::InsertTab | EditorCommand::PrevTabstop | EditorCommand::ExitSnippet
+                | EditorCommand::Backspace | EditorCommand::Delete | EditorCommand::DeleteWordLeft | EditorCommand::DeleteWordRight
+                | EditorCommand::DeleteSelection | EditorCommand::MoveLinesUp | EditorCommand::MoveLinesDown
+                | EditorCommand::DuplicateLines | EditorCommand::DeleteLines | EditorCommand::JoinLines | EditorCommand::SortLines
+                | EditorCommand::ReverseLines | EditorCommand::UniqueLines | EditorCommand::ToggleComment | EditorCommand::Undo
+                | EditorCommand::Redo | EditorCommand::JumpToBracket | EditorCommand::ToggleFold | EditorCommand::FoldAll
+                | EditorCommand::UnfoldAll | EditorCommand::ToggleSoftWrap | EditorCommand::NextDiagnostic
+                | EditorCommand::PrevDiagnostic | EditorCommand::Copy | EditorCommand::Cut | EditorCommand::Paste => {
+                    let (title, _) = NAMED_COMMANDS.iter()
+                        .find(|(_, named)| named == command)
+                        .expect("Every command without arguments has a title.");
+                    f.write_str(title)
+                }
+            },
+            MacroStep::MoveTo(offset) => write!(f, "move {offset}"),
+            MacroStep::InsertAt { at, text } => write!(f, "insert {at} {}", string(text)),
+            MacroStep::DeleteRange(start, end) => write!(f, "delete {start} {end}"),
+            MacroStep::SetSelection { anchor, kind: selection_kind } => write!(f, "select {anchor} {}", kind(*selection_kind)),
+        }
+    }
+}
+
+/// A recorded sequence of editor commands.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Macro {
+    steps: Vec<MacroStep>,
+}
+
+impl Macro {
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// Macros by register, and the one being recorded.
+#[derive(Debug, Clone, Default)]
+pub struct Macros {
+    registers: BTreeMap<char, Macro>,
+    recording: Option<(char, Macro)>,
+    /// Played again by [`TextEditor::play_last_macro`].
+    last_played: Option<char>,
+}
+
+impl Macros {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn shared() -> SharedMacros {
+        Rc::new(RefCell::new(Self::new()))
+    }
+    
+    /// Where macros are kept between sessions: `hacker/macros` in the user's state directory.
+    pub fn default_path() -> PathBuf {
+        let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+        var("XDG_STATE_HOME")
+            .or_else(|| var("HOME").map(|home| home.join(".local").join("state")))
+            .or_else(|| var("LOCALAPPDATA"))
+            .unwrap_or_else(std::env::temp_dir)
+            .join("hacker")
+            .join("macros")
+    }
+    
+    /// Registers are letters and digits.
+    pub fn is_valid_register(register: char) -> bool {
+        register.is_ascii_alphanumeric()
+    }
+    
+    pub fn get(&self, register: char) -> Option<&Macro> {
+        self.registers.get(&register)
+    }
+    
+    /// The registers that hold macros.
+    pub fn registers(&self) -> impl Iterator<Item = char> + '_ {
+        self.registers.keys().copied()
+    }
+    
+    /// The register being recorded into, if any.
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|&(register, _)| register)
+    }
+    
+    /// Starts recording into `register`, whose macro is replaced once recording stops.
+    /// Any recording in progress is stopped first.
+    pub fn start_recording(&mut self, register: char) -> bool {
+        if !Self::is_valid_register(register) {
+            return false;
+        }
+        self.stop_recording();
+        self.recording = Some((register, Macro::default()));
+        true
+    }
+    
+    /// Stops recording, returning the register that was recorded into.
+    pub fn stop_recording(&mut self) -> Option<char> {
+        let (register, recorded) = self.recording.take()?;
+        self.registers.insert(register, recorded);
+        Some(register)
+    }
+    
+    fn record(&mut self, command: &EditorCommand, editor: &TextEditor) {
+        if let Some((_, recording)) = &mut self.recording
+            && let Some(step) = MacroStep::new(command, editor) {
+            recording.steps.push(step);
+        }
+    }
+    
+    /// Loads saved macros, or none if there's no file yet.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, MacroError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+    
+    /// Saves every register, creating the directory if needed.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_string())
+    }
+    
+    pub fn parse(text: &str) -> Result<Self, MacroError> {
+        let mut macros = Self::new();
+        let mut current: Option<(char, Macro)> = None;
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| MacroError::Parse { line: index + 1, message };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match &mut current {
+                None => {
+                    let register = line.strip_prefix("macro ")
+                        .and_then(|register| {
+                            let mut chars = register.trim().chars();
+                            chars.next().filter(|_| chars.next().is_none())
+                        })
+                        .filter(|&register| Self::is_valid_register(register))
+                        .ok_or_else(|| error(String::from("Expected `macro <register>`")))?;
+                    current = Some((register, Macro::default()));
+                }
+                Some(_) if line == "endmacro" => {
+                    let (register, recorded) = current.take().expect("A macro is open.");
+                    macros.registers.insert(register, recorded);
+                }
+                Some((_, recorded)) => recorded.steps.push(MacroStep::parse(line).map_err(error)?),
+            }
+        }
+        if let Some((register, _)) = current {
+            return Err(MacroError::Parse {
+                line: text.lines().count(),
+                message: format!("Macro `{register}` has no `endmacro`"),
+            });
+        }
+        Ok(macros)
+    }
+}
+
+impl fmt::Display for Macros {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (register, recorded) in &self.registers {
+            writeln!(f, "macro {register}")?;
+            for step in &recorded.steps {
+                writeln!(f, "{step}")?;
+            }
+            writeln!(f, "endmacro")?;
+        }
+        Ok(())
+    }
+}
+
+impl TextEditor {
+    /// Adds `command` to the macro being recorded, if there is one. Called before it runs.
+    pub(crate) fn record_command(&self, command: &EditorCommand) {
+        // Commands run while a macro plays are recorded, so that macros can be built from others.
+        if let Ok(mut macros) = self.macros.try_borrow_mut() {
+            macros.record(command, self);
+        }
+    }
+    
+    pub fn start_recording(&mut self, register: char) -> bool {
+        self.macros.borrow_mut().start_recording(register)
+    }
+    
+    pub fn stop_recording(&mut self) -> Option<char> {
+        self.macros.borrow_mut().stop_recording()
+    }
+    
+    /// The register being recorded into, if any.
+    pub fn recording_macro(&self) -> Option<char> {
+        self.macros.borrow().recording()
+    }
+    
+    /// Plays the macro in `register` `count` times, as one undo step. Returns `false` if the
+    /// register is empty.
+    pub fn play_macro(&mut self, register: char, count: usize) -> bool {
+        let steps = match self.macros.borrow().get(register) {
+            Some(recorded) if !recorded.is_empty() => recorded.steps.clone(),
+            _ => return false,
+        };
+        self.macros.borrow_mut().last_played = Some(register);
+        self.transaction(|editor| {
+            for _ in 0..count {
+                for step in &steps {
+                    let command = step.command(editor);
+                    editor.execute(&command);
+                }
+            }
+        });
+        true
+    }
+    
+    /// Plays the macro that was played last again, like Vim's `@@`.
+    pub fn play_last_macro(&mut self, count: usize) -> bool {
+        let last_played = self.macros.borrow().last_played;
+        last_played.is_some_and(|register| self.play_macro(register, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn round_trip(step: MacroStep) {
+        let line = step.to_string();
+        assert_eq!(MacroStep::parse(&line), Ok(step), "{line}");
+    }
+    
+    #[test]
+    fn every_command_round_trips() {
+        for (_, command) in NAMED_COMMANDS {
+            round_trip(MacroStep::Command(command.clone()));
+        }
+        let selection = Selection {
+            anchor: TextPosition::new(2, 3),
+            kind: SelectionKind::Line,
+        };
+        for command in [
+            EditorCommand::InsertChar('"'),
+            EditorCommand::InsertChar('\n'),
+            EditorCommand::InsertText(String::from("two words\t\"quoted\"\n")),
+            EditorCommand::StartSelection(SelectionKind::Inclusive),
+            EditorCommand::GoToLine(41),
+            EditorCommand::BeginUndoGroup,
+            EditorCommand::EndUndoGroup,
+            EditorCommand::MoveTo(TextPosition::new(7, 0)),
+            EditorCommand::InsertAt { char_idx: 12, text: String::from("a b") },
+            EditorCommand::DeleteRange(3..9),
+            EditorCommand::SetSelection(selection),
+        ] {
+            round_trip(MacroStep::Command(command));
+        }
+        let offset = |lines, cols| Offset { lines, cols };
+        round_trip(MacroStep::MoveTo(offset(-1, 4)));
+        round_trip(MacroStep::InsertAt { at: offset(0, -2), text: String::from("x y") });
+        round_trip(MacroStep::DeleteRange(offset(0, 0), offset(1, -5)));
+        round_trip(MacroStep::SetSelection { anchor: offset(-3, 0), kind: SelectionKind::Exclusive });
+    }
+    
+    #[test]
+    fn reports_the_line_of_errors() {
+        let line = |text: &str| match Macros::parse(text) {
+            Err(MacroError::Parse { line, .. }) => line,
+            result => panic!("Expected a parse error, got {result:?}"),
+        };
+        assert_eq!(line("macro a\nUndo\nmove 1\nendmacro\n"), 3);
+        assert_eq!(line("# Comment\nmacro ab\n"), 2);
+        assert_eq!(line("macro a\ntype \"ab\"\nendmacro\n"), 2);
+        assert_eq!(line("macro a\nUndo\n"), 2);
+        assert_eq!(line("Undo\n"), 1);
+    }
+    
+    fn editor(text: &str) -> TextEditor {
+        let mut editor = TextEditor::new();
+        editor.execute(&EditorCommand::InsertText(text.to_owned()));
+        editor.execute(&EditorCommand::MoveTo(TextPosition::new(0, 0)));
+        editor
+    }
+    
+    #[test]
+    fn replays_relative_to_the_cursor() {
+        let mut editor = editor("one\ntwo\nthree\n");
+        assert!(editor.start_recording('a'));
+        editor.execute(&EditorCommand::MoveLineEnd);
+        editor.execute(&EditorCommand::InsertChar(';'));
+        editor.execute(&EditorCommand::MoveTo(TextPosition::new(1, 0)));
+        assert_eq!(editor.stop_recording(), Some('a'));
+        assert_eq!(editor.rope, "one;\ntwo\nthree\n");
+        
+        assert!(editor.play_macro('a', 2));
+        assert_eq!(editor.rope, "one;\ntwo;\nthree;\n");
+        assert!(editor.undo());
+        assert_eq!(editor.rope, "one;\ntwo\nthree\n");
+        assert!(!editor.play_macro('b', 1));
+    }
+    
+    #[test]
+    fn saved_macros_replay_the_same_way() {
+        let mut recorder = editor("a b\nc d\n");
+        recorder.start_recording('q');
+        recorder.execute(&EditorCommand::MoveWordRight);
+        recorder.execute(&EditorCommand::SetSelection(Selection {
+            anchor: TextPosition::new(0, 0),
+            kind: SelectionKind::Exclusive,
+        }));
+        recorder.execute(&EditorCommand::Cut);
+        recorder.execute(&EditorCommand::InsertText(String::from("(x)")));
+        recorder.execute(&EditorCommand::DeleteRange(4..5));
+        recorder.execute(&EditorCommand::MoveDown);
+        recorder.execute(&EditorCommand::MoveLineStart);
+        recorder.stop_recording();
+        assert_eq!(recorder.rope, "(x) \nc d\n");
+        
+        let saved = recorder.macros.borrow().to_string();
+        let loaded = Macros::parse(&saved).unwrap();
+        assert_eq!(loaded.get('q'), recorder.macros.borrow().get('q'), "{saved}");
+        assert_eq!(loaded.to_string(), saved);
+        let mut editor = editor("a b\nc d\n");
+        editor.macros = Rc::new(RefCell::new(loaded));
+        editor.play_macro('q', 2);
+        assert_eq!(editor.rope, "(x) \n(x) \n");
+    }
+}
@@ -0,0 +1,195 @@
+use ratatui::prelude::*;
+
+use crate::theme::Theme;
+
+use super::{highlight::SyntaxTheme, TextEditor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MinimapMode {
+    /// Braille dots, 4 lines and 2 dot columns to a cell.
+    #[default]
+    Braille,
+    /// Half blocks, 2 lines and 1 column to a cell, for fonts without braille.
+    Blocks,
+}
+
+impl MinimapMode {
+    fn lines_per_row(self) -> usize {
+        match self {
+            MinimapMode::Braille => 4,
+            MinimapMode::Blocks => 2,
+        }
+    }
+    
+    fn dots_per_cell(self) -> usize {
+        match self {
+            MinimapMode::Braille => 2,
+            MinimapMode::Blocks => 1,
+        }
+    }
+    
+    /// The glyph with the dots in `dots` set, `dots[column][line]`.
+    fn glyph(self, dots: [[bool; 4]; 2]) -> char {
+        match self {
+            MinimapMode::Braille => {
+                // Braille numbers its dots down the left column first, with the bottom row last.
+                const BITS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+                let bits = (0..2)
+                    .flat_map(|x| (0..4).map(move |y| (x, y)))
+                    .filter(|&(x, y)| dots[x][y])
+                    .fold(0, |bits, (x, y)| bits | BITS[x][y]);
+                char::from_u32(0x2800 + bits).expect("Braille patterns are chars.")
+            }
+            MinimapMode::Blocks => match (dots[0][0], dots[0][1]) {
+                (false, false) => ' ',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (true, true) => '█',
+            },
+        }
+    }
+}
+
+/// A compressed view of the whole buffer beside the text of [`TextEdit`](super::TextEdit),
+/// with the lines in view, search matches and signs picked out. Clicking or dragging on it
+/// scrolls there. It's left out of panes narrower than four times its width.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Minimap {
+    pub mode: MinimapMode,
+    /// In cells.
+    pub width: u16,
+    /// How many chars of text each column of dots stands for.
+    pub chars_per_dot: usize,
+    /// Colors each cell like the first highlighted token in it.
+    pub syntax_colors: bool,
+    pub style: Style,
+    /// Patched onto the rows of lines that are in view.
+    pub viewport_style: Style,
+    /// Patched onto cells with search matches in them.
+    pub match_style: Style,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self {
+            mode: MinimapMode::Braille,
+            width: 12,
+            chars_per_dot: 2,
+            syntax_colors: true,
+            style: Style::new().fg(Color::Rgb(92, 99, 112)),
+            viewport_style: Style::new().bg(Color::Rgb(44, 49, 58)),
+            match_style: Style::new().bg(Color::Rgb(166, 137, 76)),
+        }
+    }
+}
+
+/// Where the minimap was drawn and which lines it showed, for mapping clicks back to lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct MinimapLayout {
+    pub area: Rect,
+    pub first_line: usize,
+    pub lines_per_row: usize,
+}
+
+impl MinimapLayout {
+    pub fn line_at(&self, y: u16) -> usize {
+        let row = y.clamp(self.area.y, self.area.bottom().saturating_sub(1)) - self.area.y;
+        self.first_line + row as usize * self.lines_per_row
+    }
+}
+
+impl Minimap {
+    pub fn theme(mut self, theme: &Theme) -> Self {
+        self.style = theme.style("minimap");
+        self.viewport_style = theme.style("minimap.viewport");
+        self.match_style = theme.style("minimap.match");
+        self
+    }
+    
+    /// Draws the minimap for the view that was just drawn, whose last visible line is
+    /// `last_visible`, and remembers where it went in `state`.
+    pub(crate) fn render(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        state: &mut TextEditor,
+        syntax_theme: &SyntaxTheme,
+        last_visible: usize,
+    ) {
+        buf.set_style(area, self.style);
+        let lines_per_row = self.mode.lines_per_row();
+        let chars_per_cell = self.mode.dots_per_cell() * self.chars_per_dot.max(1);
+        let line_count = state.rope.len_lines();
+        // Buffers too long to fit scroll along with the view, so that both ends are reachable.
+        let map_lines = area.height as usize * lines_per_row;
+        let first_line = match line_count.saturating_sub(map_lines) {
+            0 => 0,
+            overflow => {
+                let max_start = state.max_scroll().0.max(1);
+                (state.start_line.min(max_start) * overflow / max_start) / lines_per_row * lines_per_row
+            }
+        };
+        let end_line = (first_line + map_lines).min(line_count);
+        state.minimap = Some(MinimapLayout { area, first_line, lines_per_row });
+        if self.syntax_colors && let Some(highlighting) = &mut state.highlighting {
+            highlighting.update(&state.rope, end_line);
+        }
+        let width = area.width as usize * chars_per_cell;
+        for (y, first) in (area.y..area.bottom()).zip((first_line..end_line).step_by(lines_per_row)) {
+            let lines: Vec<(usize, Vec<char>)> = (first..(first + lines_per_row).min(end_line))
+                .map(|line| (line, state.rope.line(line).chars().take(state.line_len(line).min(width)).collect()))
+                .collect();
+            if first < last_visible && state.start_line < first + lines.len() {
+                buf.set_style(Rect::new(area.x, y, area.width, 1), self.viewport_style);
+            }
+            for (cell_x, x) in (area.x..area.right()).enumerate() {
+                let mut dots = [[false; 4]; 2];
+                let mut color = None;
+                for (dy, (line, chars)) in lines.iter().enumerate() {
+                    for (dx, column) in dots.iter_mut().take(self.mode.dots_per_cell()).enumerate() {
+                        let start = cell_x * chars_per_cell + dx * self.chars_per_dot.max(1);
+                        let span = chars.get(start..(start + self.chars_per_dot.max(1)).min(chars.len())).unwrap_or(&[]);
+                        let Some(offset) = span.iter().position(|chr| !chr.is_whitespace()) else {
+                            continue;
+                        };
+                        column[dy] = true;
+                        if color.is_none() && self.syntax_colors && let Some(highlighting) = &state.highlighting {
+                            let col = start + offset;
+                            color = highlighting.line_tokens(*line).iter()
+                                .find(|token| token.start <= col && col < token.end)
+                                .and_then(|token| syntax_theme.style(token.kind).fg);
+                        }
+                    }
+                }
+                let cell = &mut buf[(x, y)];
+                cell.set_char(self.mode.glyph(dots));
+                if let Some(color) = color {
+                    cell.set_fg(color);
+                }
+            }
+            // Signs, such as diagnostics, color their whole row.
+            let sign_color = (first..first + lines.len())
+                .filter_map(|line| state.signs.top(line))
+                .max_by_key(|sign| sign.priority)
+                .and_then(|sign| sign.style.fg);
+            if let Some(color) = sign_color {
+                buf.set_style(Rect::new(area.x, y, area.width, 1), Style::new().fg(color));
+            }
+        }
+        let Some(search) = &state.search else {
+            return;
+        };
+        let range = state.rope.line_to_char(first_line)..state.rope.line_to_char(end_line);
+        for found in search.find_in_range(&state.rope, range) {
+            let start = state.char_to_position(found.start);
+            let end = state.char_to_position(found.end.max(found.start + 1) - 1);
+            let y = area.y + ((start.line - first_line) / lines_per_row) as u16;
+            let last_col = if end.line == start.line { end.col } else { start.col };
+            let first_cell = (start.col / chars_per_cell) as u16;
+            let last_cell = ((last_col / chars_per_cell) as u16).min(area.width.saturating_sub(1));
+            if first_cell < area.width {
+                buf.set_style(Rect::new(area.x + first_cell, y, last_cell - first_cell + 1, 1), self.match_style);
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{TextEditor, TextPosition};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SegmentKind {
+    Word,
+    Space,
+    Punctuation,
+}
+
+/// A line split at Unicode word boundaries (UAX #29), in char columns. Runs of whitespace
+/// and of punctuation are merged so that word motions skip them in one step.
+fn line_segments(line: &str) -> Vec<(Range<usize>, SegmentKind)> {
+    let mut segments: Vec<(Range<usize>, SegmentKind)> = Vec::new();
+    let mut col = 0;
+    for segment in line.split_word_bounds().flat_map(split_code_punctuation) {
+        let kind = if segment.chars().any(char::is_alphanumeric) {
+            SegmentKind::Word
+        } else if segment.chars().all(char::is_whitespace) {
+            SegmentKind::Space
+        } else {
+            SegmentKind::Punctuation
+        };
+        let end = col + segment.chars().count();
+        match segments.last_mut() {
+            Some((range, last_kind)) if *last_kind == kind && kind != SegmentKind::Word => range.end = end,
+            _ => segments.push((col..end, kind)),
+        }
+        col = end;
+    }
+    segments
+}
+
+/// UAX #29 keeps `foo.bar` and `a:b` together as one word, which suits prose but not code,
+/// so ASCII punctuation other than `_` is split off.
+fn split_code_punctuation(segment: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (index, chr) in segment.char_indices() {
+        if chr.is_ascii_punctuation() && chr != '_' {
+            pieces.extend([&segment[start..index], &segment[index..index + 1]]);
+            start = index + 1;
+        }
+    }
+    pieces.push(&segment[start..]);
+    pieces.retain(|piece| !piece.is_empty());
+    pieces
+}
+
+impl TextEditor {
+    fn segments(&self, line: usize) -> Vec<(Range<usize>, SegmentKind)> {
+        let text: String = self.rope.line(line).chars().take(self.line_len(line)).collect();
+        line_segments(&text)
+    }
+    
+    /// The end of the word at or after `position`. Line ends are stops of their own.
+    pub fn next_word_end(&self, position: TextPosition) -> TextPosition {
+        let TextPosition { line, col } = position;
+        if col >= self.line_len(line) {
+            let next = self.folds.next_visible(line);
+            return if next < self.rope.len_lines() {
+                TextPosition::new(next, 0)
+            } else {
+                position
+            };
+        }
+        let segments = self.segments(line);
+        let mut current = segments.iter().skip_while(|(range, _)| range.end <= col);
+        let end = match current.next() {
+            Some((_, SegmentKind::Space)) => current.next().map_or(self.line_len(line), |(range, _)| range.end),
+            Some((range, _)) => range.end,
+            None => self.line_len(line),
+        };
+        TextPosition::new(line, end)
+    }
+    
+    /// The start of the word before `position`. Line starts are stops of their own.
+    pub fn prev_word_start(&self, position: TextPosition) -> TextPosition {
+        let TextPosition { line, col } = position;
+        if col == 0 {
+            return match self.folds.prev_visible(line) {
+                Some(prev) => TextPosition::new(prev, self.line_len(prev)),
+                None => position,
+            };
+        }
+        let segments = self.segments(line);
+        let mut current = segments.iter().rev().skip_while(|(range, _)| range.start >= col);
+        let start = match current.next() {
+            Some((_, SegmentKind::Space)) => current.next().map_or(0, |(range, _)| range.start),
+            Some((range, _)) => range.start,
+            None => 0,
+        };
+        TextPosition::new(line, start)
+    }
+    
+    pub fn move_word_left(&mut self) {
+        self.set_cursor(self.prev_word_start(self.cursor));
+    }
+    
+    pub fn move_word_right(&mut self) {
+        self.set_cursor(self.next_word_end(self.cursor));
+    }
+    
+    /// Deletes back to the start of the word before the cursor, or the selection if there is one.
+    pub fn delete_word_left(&mut self) {
+        if self.delete_selection() || !self.is_editable() {
+            return;
+        }
+        let start = self.position_to_char(self.prev_word_start(self.cursor));
+        let end = self.cursor_char_index();
+        if start < end && self.try_remove(start..end).is_ok() {
+            self.set_cursor(self.char_to_position(start));
+        }
+    }
+    
+    /// Deletes up to the end of the word after the cursor, or the selection if there is one.
+    pub fn delete_word_right(&mut self) {
+        if self.delete_selection() || !self.is_editable() {
+            return;
+        }
+        let start = self.cursor_char_index();
+        let end = self.position_to_char(self.next_word_end(self.cursor));
+        if start < end && self.try_remove(start..end).is_ok() {
+            self.set_cursor(self.char_to_position(start));
+        }
+    }
+    
+    fn is_blank_line(&self, line: usize) -> bool {
+        self.rope.line(line).chars().all(char::is_whitespace)
+    }
+    
+    /// Moves to the next (or previous) blank line after a paragraph, or to the end (or start)
+    /// of the buffer if there are no more.
+    pub fn move_paragraph(&mut self, forward: bool) {
+        let last = self.rope.len_lines() - 1;
+        let mut line = self.cursor.line;
+        if forward {
+            while line < last && self.is_blank_line(line) {
+                line += 1;
+            }
+            while line < last && !self.is_blank_line(line) {
+                line += 1;
+            }
+            let col = if self.is_blank_line(line) { 0 } else { self.line_len(line) };
+            self.set_cursor(TextPosition::new(line, col));
+        } else {
+            while line > 0 && self.is_blank_line(line) {
+                line -= 1;
+            }
+            while line > 0 && !self.is_blank_line(line) {
+                line -= 1;
+            }
+            self.set_cursor(TextPosition::new(line, 0));
+        }
+    }
+    
+    /// Moves a viewport height up or down, scrolling the view along so that the cursor
+    /// stays on the same screen row where possible.
+    pub fn move_page(&mut self, down: bool) {
+        let height = (self.text_area.height as usize).max(1);
+        let mut moved = 0;
+        for _ in 0..height {
+            let before = self.cursor;
+            if down {
+                self.move_down();
+            } else {
+                self.move_up();
+            }
+            if self.cursor.line == before.line && self.soft_wrap.is_none() {
+                break;
+            }
+            moved += 1;
+        }
+        // Wrapped rows don't map onto lines, so wrapped views only scroll as far as the cursor needs.
+        if self.soft_wrap.is_some() {
+            return;
+        }
+        for _ in 0..moved {
+            self.start_line = if down {
+                self.folds.next_visible(self.start_line).min(self.rope.len_lines() - 1)
+            } else {
+                match self.folds.prev_visible(self.start_line) {
+                    Some(line) => line,
+                    None => break,
+                }
+            };
+        }
+    }
+    
+    /// Ctrl+Home.
+    pub fn move_document_start(&mut self) {
+        self.set_cursor(TextPosition::new(0, 0));
+    }
+    
+    /// Ctrl+End.
+    pub fn move_document_end(&mut self) {
+        self.set_cursor(self.char_to_position(self.rope.len_chars()));
+    }
+    
+    /// Moves to the first non-blank char of a line, counting from 0, clamped to the buffer.
+    pub fn go_to_line(&mut self, line: usize) {
+        let line = line.min(self.rope.len_lines() - 1);
+        let col = self.line_indent(line, self.line_len(line)).chars().count();
+        self.set_cursor(TextPosition::new(line, col));
+    }
+}
@@ -0,0 +1,173 @@
+use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::prelude::*;
+
+use super::{scrollbar::ScrollMetrics, Selection, SelectionKind, TextEditor, TextPosition};
+
+/// Rows or columns per step of the mouse wheel, and per step with Alt held.
+const WHEEL_SCROLL: isize = 2;
+const FAST_WHEEL_SCROLL: isize = 10;
+
+/// What the left mouse button has been dragging since it went down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum MouseDrag {
+    /// Selecting from where the button went down.
+    Text(TextPosition),
+    /// Scrolling through the minimap.
+    Minimap,
+    /// Dragging a scrollbar thumb, held `grab` cells from its start.
+    VerticalThumb { grab: u16 },
+    HorizontalThumb { grab: u16 },
+}
+
+impl TextEditor {
+    /// The buffer position drawn at a screen position during the last render. Positions
+    /// outside the text area are moved onto its nearest edge, so the gutter maps to the
+    /// start of the row. Rows below the end of the buffer map to the end of the buffer.
+    pub fn screen_to_position(&mut self, position: Position) -> TextPosition {
+        let area = self.text_area;
+        if area.is_empty() {
+            return self.cursor;
+        }
+        if let Some(wrap) = self.soft_wrap {
+            self.layouts.reset(wrap, area.width as usize);
+        }
+        let x = (position.x.clamp(area.x, area.right() - 1) - area.x) as usize;
+        let y = position.y.clamp(area.y, area.bottom() - 1) - area.y;
+        let mut at = (self.folds.visible_line(self.start_line.min(self.rope.len_lines() - 1)), self.start_row);
+        for _ in 0..y {
+            match self.next_row(at) {
+                Some(next) => at = next,
+                None => return self.char_to_position(self.rope.len_chars()),
+            }
+        }
+        let (line, row) = at;
+        let line_len = self.line_len(line);
+        let col = match self.soft_wrap {
+            Some(wrap) => {
+                let (start, end) = self.layouts.row_range(&self.rope, line, row);
+                let indicator = if row == 0 { 0 } else { wrap.indicator_width() };
+                let col = start + x.saturating_sub(indicator);
+                // The end of a row that wraps is the start of the next one, so stop short of it.
+                if end < line_len { col.min(end.saturating_sub(1).max(start)) } else { col.min(end) }
+            }
+            None => (self.start_col + x).min(line_len),
+        };
+        TextPosition::new(line, col)
+    }
+    
+    /// Clicking places the cursor, or extends the selection with Shift held, and dragging
+    /// selects. The wheel scrolls without moving the cursor: sideways with Shift held, and
+    /// faster with Alt. Scrollbars can be dragged, or clicked to jump there, and so can the
+    /// minimap. Returns `false` for events that weren't used, such as clicks outside the editor.
+    pub fn handle_mouse(&mut self, event: MouseEvent) -> bool {
+        let position = Position::new(event.column, event.row);
+        let shift = event.modifiers.contains(KeyModifiers::SHIFT);
+        let wheel = if event.modifiers.contains(KeyModifiers::ALT) { FAST_WHEEL_SCROLL } else { WHEEL_SCROLL };
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => return self.mouse_down(position, shift),
+            MouseEventKind::Drag(MouseButton::Left) => return self.mouse_drag(position),
+            MouseEventKind::Up(MouseButton::Left) => return self.mouse_drag.take().is_some(),
+            MouseEventKind::ScrollDown if shift => self.scroll_cols_by(wheel),
+            MouseEventKind::ScrollUp if shift => self.scroll_cols_by(-wheel),
+            MouseEventKind::ScrollDown => self.scroll_by(wheel),
+            MouseEventKind::ScrollUp => self.scroll_by(-wheel),
+            MouseEventKind::ScrollRight => self.scroll_cols_by(wheel),
+            MouseEventKind::ScrollLeft => self.scroll_cols_by(-wheel),
+            _ => return false,
+        }
+        true
+    }
+    
+    fn mouse_down(&mut self, position: Position, extend: bool) -> bool {
+        if !self.area.contains(position) {
+            return false;
+        }
+        if self.minimap.is_some_and(|minimap| minimap.area.contains(position)) {
+            self.mouse_drag = Some(MouseDrag::Minimap);
+            return self.mouse_drag(position);
+        }
+        if let Some(bar) = self.vertical_scrollbar.filter(|bar| bar.contains(position)) {
+            let grab = grab_offset(self.vertical_scroll_metrics(), bar.height, position.y - bar.y);
+            self.mouse_drag = Some(MouseDrag::VerticalThumb { grab });
+            return self.mouse_drag(position);
+        }
+        if let Some(bar) = self.horizontal_scrollbar.filter(|bar| bar.contains(position)) {
+            let grab = grab_offset(self.horizontal_scroll_metrics(), bar.width, position.x - bar.x);
+            self.mouse_drag = Some(MouseDrag::HorizontalThumb { grab });
+            return self.mouse_drag(position);
+        }
+        let target = self.screen_to_position(position);
+        self.clear_extra_cursors();
+        let anchor = match self.selection {
+            Some(selection) if extend => selection.anchor,
+            _ if extend => self.cursor,
+            _ => target,
+        };
+        self.selection = None;
+        self.mouse_drag = Some(MouseDrag::Text(anchor));
+        self.select_to(anchor, target);
+        true
+    }
+    
+    fn mouse_drag(&mut self, position: Position) -> bool {
+        match self.mouse_drag {
+            Some(MouseDrag::Text(anchor)) => {
+                // Dragging past the top or bottom keeps scrolling that way.
+                if position.y < self.text_area.y {
+                    self.scroll_by(-1);
+                } else if position.y >= self.text_area.bottom() {
+                    self.scroll_by(1);
+                }
+                let target = self.screen_to_position(position);
+                self.select_to(anchor, target);
+            }
+            Some(MouseDrag::Minimap) => {
+                let Some(minimap) = self.minimap else {
+                    return false;
+                };
+                // Centers the view on the line under the mouse.
+                let line = minimap.line_at(position.y).min(self.rope.len_lines() - 1);
+                self.start_line = self.folds.visible_line(line.saturating_sub(self.text_area.height as usize / 2));
+                self.start_row = 0;
+            }
+            Some(MouseDrag::VerticalThumb { grab }) => {
+                let Some(bar) = self.vertical_scrollbar else {
+                    return false;
+                };
+                let thumb_start = position.y.saturating_sub(bar.y).saturating_sub(grab);
+                let line = self.vertical_scroll_metrics().position_at(bar.height, thumb_start);
+                self.start_line = self.folds.visible_line(line);
+                self.start_row = 0;
+            }
+            Some(MouseDrag::HorizontalThumb { grab }) => {
+                let Some(bar) = self.horizontal_scrollbar else {
+                    return false;
+                };
+                let thumb_start = position.x.saturating_sub(bar.x).saturating_sub(grab);
+                self.start_col = self.horizontal_scroll_metrics().position_at(bar.width, thumb_start);
+            }
+            None => return false,
+        }
+        true
+    }
+    
+    /// Moves the cursor to `target`, selecting from `anchor` unless they are the same.
+    fn select_to(&mut self, anchor: TextPosition, target: TextPosition) {
+        self.selection = (anchor != target).then_some(Selection {
+            anchor,
+            kind: SelectionKind::Exclusive,
+        });
+        self.set_cursor(target);
+    }
+}
+
+/// Where a click lands on the thumb. Clicks on the track grab the middle of the thumb, so
+/// that it jumps to be centered there.
+fn grab_offset(metrics: ScrollMetrics, track: u16, offset: u16) -> u16 {
+    let (start, len) = metrics.thumb(track);
+    if (start..start + len).contains(&offset) {
+        offset - start
+    } else {
+        len / 2
+    }
+}
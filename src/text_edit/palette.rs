@@ -0,0 +1,342 @@
+use std::marker::PhantomData;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{prelude::*, widgets::{Block, Borders, Clear}};
+
+use crate::theme::Theme;
+
+/// A command that can be run from the palette.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteItem<C> {
+    pub title: String,
+    /// Shown next to the title, such as `Ctrl+Z`.
+    pub binding: Option<String>,
+    pub command: C,
+}
+
+impl<C> PaletteItem<C> {
+    pub fn new<S: Into<String>>(title: S, command: C) -> Self {
+        Self {
+            title: title.into(),
+            binding: None,
+            command,
+        }
+    }
+    
+    pub fn binding(mut self, binding: Option<KeyEvent>) -> Self {
+        self.binding = binding.map(key_name);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteEvent<C> {
+    Handled,
+    Ignored,
+    Close,
+    /// The palette should close and run this.
+    Run(C),
+}
+
+/// A searchable list of commands, filtered by fuzzy matching against their titles.
+pub struct CommandPaletteState<C> {
+    items: Vec<PaletteItem<C>>,
+    query: String,
+    /// Indices into `items` that match the query, best first, with the matched char positions.
+    matches: Vec<(usize, Vec<usize>)>,
+    selected: usize,
+    /// The first match shown, so that the selection stays in view.
+    scroll: usize,
+    pub cursor_screen_position: Option<Position>,
+}
+
+impl<C: Clone> CommandPaletteState<C> {
+    pub fn new(items: Vec<PaletteItem<C>>) -> Self {
+        let mut palette = Self {
+            items,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            cursor_screen_position: None,
+        };
+        palette.update();
+        palette
+    }
+    
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+    
+    pub fn selected(&self) -> Option<&PaletteItem<C>> {
+        self.matches.get(self.selected).map(|&(index, _)| &self.items[index])
+    }
+    
+    fn update(&mut self) {
+        let mut scored: Vec<(i64, usize, Vec<usize>)> = self.items.iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                fuzzy_match(&self.query, &item.title).map(|(score, positions)| (score, index, positions))
+            })
+            .collect();
+        // Stable, so equally good matches keep their registration order.
+        scored.sort_by_key(|&(score, _, _)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(_, index, positions)| (index, positions)).collect();
+        self.selected = 0;
+        self.scroll = 0;
+    }
+    
+    fn move_selection(&mut self, delta: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        self.selected = self.selected.saturating_add_signed(delta).min(self.matches.len() - 1);
+    }
+    
+    pub fn handle_key(&mut self, key: KeyEvent) -> PaletteEvent<C> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Esc => return PaletteEvent::Close,
+            KeyCode::Enter => return match self.selected() {
+                Some(item) => PaletteEvent::Run(item.command.clone()),
+                None => PaletteEvent::Close,
+            },
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('p') if ctrl => self.move_selection(-1),
+            KeyCode::Char('n') if ctrl => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-(Self::PAGE as isize)),
+            KeyCode::PageDown => self.move_selection(Self::PAGE as isize),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.update();
+            }
+            KeyCode::Char(chr) if !ctrl && !alt => {
+                self.query.push(chr);
+                self.update();
+            }
+            _ => return PaletteEvent::Ignored,
+        }
+        PaletteEvent::Handled
+    }
+    
+    pub fn paste(&mut self, text: &str) {
+        self.query.push_str(text.lines().next().unwrap_or_default());
+        self.update();
+    }
+}
+
+impl<C> CommandPaletteState<C> {
+    /// Matches listed at once, and how far PageUp and PageDown move.
+    const PAGE: usize = 12;
+}
+
+/// Scores `text` against `query` if every char of the query appears in it in order, ignoring
+/// case. Matches at word starts and runs of consecutive chars score higher. Returns the score
+/// and the char positions in `text` that matched.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let text: Vec<char> = text.chars().collect();
+    let mut positions = Vec::new();
+    let mut score = 0i64;
+    let mut next = 0;
+    for query_chr in query.chars().filter(|chr| !chr.is_whitespace()) {
+        let found = (next..text.len()).find(|&col| chars_match(query_chr, text[col]))?;
+        let word_start = found == 0 || !text[found - 1].is_alphanumeric()
+            || (text[found - 1].is_lowercase() && text[found].is_uppercase());
+        score += if word_start { 10 } else { 1 };
+        if found != 0 && positions.last() == Some(&(found - 1)) {
+            score += 5;
+        }
+        // Gaps count against the match, but less than a good match counts for it.
+        score -= (found - next).min(5) as i64;
+        positions.push(found);
+        next = found + 1;
+    }
+    Some((score, positions))
+}
+
+fn chars_match(query: char, text: char) -> bool {
+    query == text || query.to_lowercase().eq(text.to_lowercase())
+}
+
+/// A readable name for a key, such as `Ctrl+Shift+P`.
+pub fn key_name(key: KeyEvent) -> String {
+    let mut name = String::new();
+    for (modifier, label) in [
+        (KeyModifiers::CONTROL, "Ctrl+"),
+        (KeyModifiers::ALT, "Alt+"),
+        (KeyModifiers::SHIFT, "Shift+"),
+    ] {
+        if key.modifiers.contains(modifier) {
+            name.push_str(label);
+        }
+    }
+    match key.code {
+        KeyCode::Char(' ') => name.push_str("Space"),
+        KeyCode::Char(chr) => name.extend(chr.to_uppercase()),
+        KeyCode::F(number) => name.push_str(&format!("F{number}")),
+        KeyCode::PageUp => name.push_str("PgUp"),
+        KeyCode::PageDown => name.push_str("PgDn"),
+        KeyCode::BackTab => name.push_str("Shift+Tab"),
+        code => name.push_str(&format!("{code:?}")),
+    }
+    name
+}
+
+/// The simplest key that `binding` maps to `command`, for showing bindings that are
+/// defined as functions from keys to commands.
+pub fn find_binding<C: PartialEq>(command: &C, binding: impl Fn(KeyEvent) -> Option<C>) -> Option<KeyEvent> {
+    const MODIFIERS: [KeyModifiers; 7] = [
+        KeyModifiers::NONE,
+        KeyModifiers::CONTROL,
+        KeyModifiers::ALT,
+        KeyModifiers::SHIFT,
+        KeyModifiers::CONTROL.union(KeyModifiers::SHIFT),
+        KeyModifiers::CONTROL.union(KeyModifiers::ALT),
+        KeyModifiers::ALT.union(KeyModifiers::SHIFT),
+    ];
+    let named = [
+        KeyCode::Up,
+        KeyCode::Down,
+        KeyCode::Left,
+        KeyCode::Right,
+        KeyCode::Home,
+        KeyCode::End,
+        KeyCode::PageUp,
+        KeyCode::PageDown,
+        KeyCode::Insert,
+        KeyCode::Delete,
+        KeyCode::Backspace,
+        KeyCode::Tab,
+        KeyCode::BackTab,
+        KeyCode::Enter,
+        KeyCode::Esc,
+    ];
+    let codes = named.into_iter()
+        .chain((1..=12).map(KeyCode::F))
+        .chain(('a'..='z').chain('0'..='9').chain("`-=[]\\;',./ ".chars()).map(KeyCode::Char));
+    let codes: Vec<KeyCode> = codes.collect();
+    MODIFIERS.into_iter()
+        .flat_map(|modifiers| codes.iter().map(move |&code| KeyEvent::new(code, modifiers)))
+        .find(|&key| binding(key).as_ref() == Some(command))
+}
+
+/// Draws the palette as a box over the top of `area`.
+pub struct CommandPalette<C> {
+    style: Style,
+    selected_style: Style,
+    match_style: Style,
+    binding_style: Style,
+    /// Ties the widget to the state's command type.
+    command: PhantomData<fn() -> C>,
+}
+
+impl<C> CommandPalette<C> {
+    pub fn new() -> Self {
+        Self {
+            style: Style::new().bg(Color::Rgb(37, 41, 50)),
+            selected_style: Style::new().bg(Color::Rgb(62, 68, 81)),
+            match_style: Style::new().fg(Color::Rgb(97, 175, 239)).add_modifier(Modifier::BOLD),
+            binding_style: Style::new().fg(Color::DarkGray),
+            command: PhantomData,
+        }
+    }
+    
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.style(theme.style("palette"))
+            .selected_style(theme.style("palette.selected"))
+            .match_style(theme.style("palette.match"))
+            .binding_style(theme.style("palette.binding"))
+    }
+    
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn selected_style(mut self, style: Style) -> Self {
+        self.selected_style = style;
+        self
+    }
+    
+    pub fn match_style(mut self, style: Style) -> Self {
+        self.match_style = style;
+        self
+    }
+    
+    pub fn binding_style(mut self, style: Style) -> Self {
+        self.binding_style = style;
+        self
+    }
+}
+
+impl<C> Default for CommandPalette<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> StatefulWidget for CommandPalette<C> {
+    type State = CommandPaletteState<C>;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        state.cursor_screen_position = None;
+        let width = area.width.min(64);
+        let list_height = state.matches.len().clamp(1, CommandPaletteState::<C>::PAGE) as u16;
+        // Borders, the query and the list.
+        let height = (list_height + 3).min(area.height);
+        let popup = Rect::new(area.x + (area.width - width) / 2, area.y + area.height.min(1), width, height)
+            .intersection(area);
+        Clear.render(popup, buf);
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(self.binding_style)
+            .style(self.style);
+        let inner = block.inner(popup);
+        block.render(popup, buf);
+        if inner.is_empty() {
+            return;
+        }
+        let prompt = format!("> {}", state.query);
+        buf.set_stringn(inner.x, inner.y, &prompt, inner.width as usize, self.style);
+        let cursor_x = inner.x as usize + prompt.chars().count();
+        if cursor_x < inner.right() as usize {
+            state.cursor_screen_position = Some(Position::new(cursor_x as u16, inner.y));
+        }
+        let list = Rect {
+            y: inner.y + 1,
+            height: inner.height - 1,
+            ..inner
+        };
+        if state.matches.is_empty() {
+            buf.set_stringn(list.x + 1, list.y, "No matching commands", list.width.saturating_sub(1) as usize, self.binding_style);
+            return;
+        }
+        let rows = list.height as usize;
+        if state.selected < state.scroll {
+            state.scroll = state.selected;
+        } else if rows != 0 && state.selected >= state.scroll + rows {
+            state.scroll = state.selected + 1 - rows;
+        }
+        for (row, (match_index, (item_index, positions))) in state.matches.iter().enumerate().skip(state.scroll).take(rows).enumerate() {
+            let y = list.y + row as u16;
+            let item = &state.items[*item_index];
+            let row_style = if match_index == state.selected { self.selected_style } else { self.style };
+            buf.set_style(Rect::new(list.x, y, list.width, 1), row_style);
+            let binding_width = item.binding.as_ref().map_or(0, |binding| binding.chars().count() as u16 + 1);
+            let title_width = list.width.saturating_sub(binding_width + 1) as usize;
+            let mut matched = positions.iter().peekable();
+            for (col, (chr, x)) in item.title.chars().zip(list.x + 1..).take(title_width).enumerate() {
+                let cell = &mut buf[(x, y)];
+                cell.set_char(chr);
+                if matched.next_if_eq(&&col).is_some() {
+                    cell.set_style(self.match_style);
+                }
+            }
+            if let Some(binding) = &item.binding && binding_width < list.width {
+                let x = list.right() - binding_width;
+                buf.set_stringn(x, y, binding, binding_width as usize, self.binding_style);
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use ratatui::prelude::*;
+
+use crate::theme::Theme;
+
+use super::TextEditor;
+
+/// Scrollbars along the right and bottom edges of [`TextEdit`](super::TextEdit). They can be
+/// dragged, or clicked to jump there. See [`TextEditor::handle_mouse`](super::TextEditor::handle_mouse).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Scrollbars {
+    pub vertical: bool,
+    /// Only drawn while the lines in view are wider than the text area, and never with soft wrap on.
+    pub horizontal: bool,
+    pub track_style: Style,
+    pub thumb_style: Style,
+}
+
+impl Default for Scrollbars {
+    fn default() -> Self {
+        Self {
+            vertical: true,
+            horizontal: true,
+            track_style: Style::new().bg(Color::Rgb(33, 37, 43)),
+            thumb_style: Style::new().bg(Color::Rgb(78, 86, 102)),
+        }
+    }
+}
+
+impl Scrollbars {
+    pub fn theme(mut self, theme: &Theme) -> Self {
+        self.track_style = theme.style("scrollbar.track");
+        self.thumb_style = theme.style("scrollbar.thumb");
+        self
+    }
+    
+    pub(crate) fn render(&self, area: Rect, buf: &mut Buffer, metrics: ScrollMetrics) {
+        buf.set_style(area, self.track_style);
+        let vertical = area.width == 1;
+        let track = if vertical { area.height } else { area.width };
+        let (start, len) = metrics.thumb(track);
+        let thumb = if vertical {
+            Rect::new(area.x, area.y + start, 1, len)
+        } else {
+            Rect::new(area.x + start, area.y, len, 1)
+        };
+        buf.set_style(thumb, self.thumb_style);
+    }
+}
+
+/// How far a view has scrolled, in lines or columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ScrollMetrics {
+    pub position: usize,
+    pub max_position: usize,
+    /// How much fits in the view at once.
+    pub view: usize,
+}
+
+impl ScrollMetrics {
+    /// The start and length of the thumb on a track `track` cells long.
+    pub fn thumb(&self, track: u16) -> (u16, u16) {
+        let content = self.max_position + self.view.max(1);
+        let len = ((track as usize * self.view) / content).clamp(1, track.max(1) as usize) as u16;
+        let free = track.saturating_sub(len) as usize;
+        let start = match self.max_position {
+            0 => 0,
+            max => (free * self.position.min(max) + max / 2) / max,
+        };
+        (start as u16, len)
+    }
+    
+    /// The position that puts the thumb at `thumb_start`.
+    pub fn position_at(&self, track: u16, thumb_start: u16) -> usize {
+        let (_, len) = self.thumb(track);
+        let free = track.saturating_sub(len) as usize;
+        if free == 0 {
+            return 0;
+        }
+        ((thumb_start as usize * self.max_position + free / 2) / free).min(self.max_position)
+    }
+}
+
+impl TextEditor {
+    /// In lines. Wrapped rows and folds aren't counted, which is close enough for a scrollbar.
+    pub(crate) fn vertical_scroll_metrics(&mut self) -> ScrollMetrics {
+        ScrollMetrics {
+            position: self.start_line,
+            max_position: self.max_scroll().0,
+            view: self.text_area.height as usize,
+        }
+    }
+    
+    pub(crate) fn horizontal_scroll_metrics(&self) -> ScrollMetrics {
+        ScrollMetrics {
+            position: self.start_col,
+            max_position: self.max_start_col(),
+            view: self.text_area.width as usize,
+        }
+    }
+}
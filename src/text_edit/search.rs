@@ -0,0 +1,337 @@
+use std::{borrow::Cow, ops::Range};
+
+use regex::{Regex, RegexBuilder};
+use ropey::Rope;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SearchError {
+    #[error("Invalid regex: {0}")]
+    Regex(#[from] regex::Error),
+}
+
+enum Matcher {
+    Empty,
+    /// Case folded (if insensitive) pattern with its KMP failure table.
+    Literal {
+        needle: Vec<char>,
+        failure: Vec<usize>,
+    },
+    /// Regexes run one line at a time so that only lines that span rope chunks are copied.
+    Regex(Regex),
+}
+
+/// A compiled search over a rope. Matches are char ranges.
+pub struct Search {
+    pattern: String,
+    options: SearchOptions,
+    matcher: Matcher,
+}
+
+fn fold(chr: char, case_sensitive: bool) -> char {
+    if case_sensitive {
+        chr
+    } else {
+        chr.to_lowercase().next().unwrap_or(chr)
+    }
+}
+
+fn is_word_char(chr: char) -> bool {
+    chr == '_' || chr.is_alphanumeric()
+}
+
+impl Search {
+    pub fn new(pattern: &str, options: SearchOptions) -> Result<Self, SearchError> {
+        let matcher = if pattern.is_empty() {
+            Matcher::Empty
+        } else if options.regex {
+            let pattern = if options.whole_word {
+                format!(r"\b(?:{pattern})\b")
+            } else {
+                pattern.to_owned()
+            };
+            Matcher::Regex(
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(!options.case_sensitive)
+                    .multi_line(true)
+                    .build()?
+            )
+        } else {
+            let needle: Vec<char> = pattern.chars().map(|c| fold(c, options.case_sensitive)).collect();
+            let mut failure = vec![0; needle.len()];
+            let mut k = 0;
+            for i in 1..needle.len() {
+                while k > 0 && needle[i] != needle[k] {
+                    k = failure[k - 1];
+                }
+                if needle[i] == needle[k] {
+                    k += 1;
+                }
+                failure[i] = k;
+            }
+            Matcher::Literal {
+                needle,
+                failure,
+            }
+        };
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            options,
+            matcher,
+        })
+    }
+    
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+    
+    pub fn options(&self) -> SearchOptions {
+        self.options
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        matches!(self.matcher, Matcher::Empty)
+    }
+    
+    fn is_whole_word(&self, rope: &Rope, range: &Range<usize>) -> bool {
+        let before = range.start.checked_sub(1).map(|i| rope.char(i));
+        let after = (range.end < rope.len_chars()).then(|| rope.char(range.end));
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    }
+    
+    /// Calls `f` with each non-empty match that lies entirely within `range`, in order.
+    /// Stops early if `f` returns `false`.
+    pub fn for_each_match<F: FnMut(Range<usize>) -> bool>(&self, rope: &Rope, range: Range<usize>, mut f: F) {
+        let range = range.start.min(rope.len_chars())..range.end.min(rope.len_chars());
+        match &self.matcher {
+            Matcher::Empty => (),
+            Matcher::Literal { needle, failure } => {
+                // Streams over the rope's chunks without building a string.
+                let mut k = 0;
+                for (offset, chr) in rope.slice(range.clone()).chars().enumerate() {
+                    let chr = fold(chr, self.options.case_sensitive);
+                    while k > 0 && chr != needle[k] {
+                        k = failure[k - 1];
+                    }
+                    if chr == needle[k] {
+                        k += 1;
+                    }
+                    if k == needle.len() {
+                        let end = range.start + offset + 1;
+                        let found = end - needle.len()..end;
+                        if !self.options.whole_word || self.is_whole_word(rope, &found) {
+                            if !f(found) {
+                                return;
+                            }
+                            k = 0;
+                        } else {
+                            k = failure[k - 1];
+                        }
+                    }
+                }
+            }
+            Matcher::Regex(regex) => {
+                if range.is_empty() {
+                    return;
+                }
+                let first_line = rope.char_to_line(range.start);
+                let last_line = rope.char_to_line(range.end - 1);
+                for line_index in first_line..=last_line {
+                    let line = rope.line(line_index);
+                    let line_start = rope.line_to_char(line_index);
+                    let text: Cow<str> = line.into();
+                    for found in regex.find_iter(&text) {
+                        if found.is_empty() {
+                            continue;
+                        }
+                        let start = line_start + line.byte_to_char(found.start());
+                        let end = line_start + line.byte_to_char(found.end());
+                        if start < range.start {
+                            continue;
+                        }
+                        if end > range.end {
+                            break;
+                        }
+                        if !f(start..end) {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    pub fn find_in_range(&self, rope: &Rope, range: Range<usize>) -> Vec<Range<usize>> {
+        let mut found = Vec::new();
+        self.for_each_match(rope, range, |range| {
+            found.push(range);
+            true
+        });
+        found
+    }
+    
+    pub fn find_all(&self, rope: &Rope) -> Vec<Range<usize>> {
+        self.find_in_range(rope, 0..rope.len_chars())
+    }
+    
+    /// The first match starting at or after `from`, wrapping around to the start if `wrap` is set.
+    pub fn find_next(&self, rope: &Rope, from: usize, wrap: bool) -> Option<Range<usize>> {
+        let mut result = None;
+        self.for_each_match(rope, from..rope.len_chars(), |range| {
+            result = Some(range);
+            false
+        });
+        if result.is_none() && wrap && from != 0 {
+            self.for_each_match(rope, 0..rope.len_chars(), |range| {
+                result = Some(range);
+                false
+            });
+        }
+        result
+    }
+    
+    /// The last match ending at or before `before`, wrapping around to the end if `wrap` is set.
+    pub fn find_prev(&self, rope: &Rope, before: usize, wrap: bool) -> Option<Range<usize>> {
+        let last_in = |range: Range<usize>| {
+            // Search windows of doubling size backwards from the end of the range,
+            // so that finding a nearby match doesn't scan the whole buffer.
+            let mut window = 4096;
+            loop {
+                let start = range.end.saturating_sub(window).max(range.start);
+                let mut last = None;
+                self.for_each_match(rope, start..range.end, |found| {
+                    last = Some(found);
+                    true
+                });
+                if last.is_some() || start == range.start {
+                    return last;
+                }
+                window *= 2;
+            }
+        };
+        let before = before.min(rope.len_chars());
+        last_in(0..before).or_else(|| {
+            (wrap && before < rope.len_chars())
+                .then(|| last_in(before..rope.len_chars()))
+                .flatten()
+        })
+    }
+    
+    /// Whether `range` is still exactly a match, e.g. after the buffer was edited.
+    pub fn is_match(&self, rope: &Rope, range: &Range<usize>) -> bool {
+        if range.end > rope.len_chars() || range.is_empty() {
+            return false;
+        }
+        let line = rope.char_to_line(range.start);
+        let search_range = rope.line_to_char(line).min(range.start)..range.end;
+        let mut matched = false;
+        self.for_each_match(rope, search_range, |found| {
+            matched = found == *range;
+            found.start < range.start
+        });
+        matched
+    }
+    
+    /// The text that replaces the match at `range`. For regex searches, `$1` and `${name}`
+    /// in `replacement` expand to capture groups.
+    pub fn expand_replacement(&self, rope: &Rope, range: &Range<usize>, replacement: &str) -> String {
+        let Matcher::Regex(regex) = &self.matcher else {
+            return replacement.to_owned();
+        };
+        let line_index = rope.char_to_line(range.start);
+        let line = rope.line(line_index);
+        let text: Cow<str> = line.into();
+        let start_byte = line.char_to_byte(range.start - rope.line_to_char(line_index));
+        let mut expanded = String::new();
+        match regex.captures_at(&text, start_byte) {
+            Some(captures) => captures.expand(replacement, &mut expanded),
+            None => expanded.push_str(replacement),
+        }
+        expanded
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init, reason = "Lists of matches that happen to have one match.")]
+mod tests {
+    use super::*;
+    
+    fn search(pattern: &str, options: SearchOptions) -> Search {
+        Search::new(pattern, options).unwrap()
+    }
+    
+    const LITERAL: SearchOptions = SearchOptions {
+        case_sensitive: true,
+        whole_word: false,
+        regex: false,
+    };
+    
+    const REGEX: SearchOptions = SearchOptions {
+        regex: true,
+        ..LITERAL
+    };
+    
+    #[test]
+    fn literal_matches_do_not_overlap() {
+        let rope = Rope::from_str("aaaaa abaabab");
+        assert_eq!(search("aa", LITERAL).find_all(&rope), [0..2, 2..4, 8..10]);
+        // The failure table has to fall back to a partial match rather than start over.
+        assert_eq!(search("abab", LITERAL).find_all(&rope), [9..13]);
+        assert!(search("", LITERAL).find_all(&rope).is_empty());
+    }
+    
+    #[test]
+    fn literal_case_and_whole_word() {
+        let rope = Rope::from_str("Grüße grüßen\nGRÜßE");
+        let insensitive = SearchOptions { case_sensitive: false, ..LITERAL };
+        assert_eq!(search("grüße", LITERAL).find_all(&rope), [6..11]);
+        assert_eq!(search("grüße", insensitive).find_all(&rope), [0..5, 6..11, 13..18]);
+        let whole = SearchOptions { whole_word: true, ..insensitive };
+        assert_eq!(search("grüße", whole).find_all(&rope), [0..5, 13..18]);
+        // A match that isn't a whole word can still overlap one that is.
+        assert_eq!(search("ab", SearchOptions { whole_word: true, ..LITERAL }).find_all(&Rope::from_str("abab ab")), [5..7]);
+    }
+    
+    #[test]
+    fn regex_matches_are_char_ranges() {
+        let rope = Rope::from_str("naïve 12\n🦀 345 x\n");
+        assert_eq!(search(r"\d+", REGEX).find_all(&rope), [6..8, 11..14]);
+        assert_eq!(search(r"^\S+", REGEX).find_all(&rope), [0..5, 9..10]);
+        assert_eq!(search(r"\d+", REGEX).find_in_range(&rope, 7..14), [11..14]);
+        let whole = SearchOptions { whole_word: true, case_sensitive: false, ..REGEX };
+        assert_eq!(search("X|NA", whole).find_all(&rope), [15..16]);
+        assert!(Search::new("(", REGEX).is_err());
+    }
+    
+    #[test]
+    fn next_and_prev_wrap_around() {
+        let rope = Rope::from_str("one two one two one");
+        let search = search("one", LITERAL);
+        assert_eq!(search.find_next(&rope, 1, false), Some(8..11));
+        assert_eq!(search.find_next(&rope, 17, false), None);
+        assert_eq!(search.find_next(&rope, 17, true), Some(0..3));
+        assert_eq!(search.find_prev(&rope, 16, false), Some(8..11));
+        assert_eq!(search.find_prev(&rope, 2, false), None);
+        assert_eq!(search.find_prev(&rope, 2, true), Some(16..19));
+        assert!(search.is_match(&rope, &(8..11)));
+        assert!(!search.is_match(&rope, &(9..12)));
+    }
+    
+    #[test]
+    fn replacements_expand_captures() {
+        let rope = Rope::from_str("first\nkey = ünï; other = two\n");
+        let search = search(r"(?<name>\w+) = (\w+)", REGEX);
+        let found = search.find_all(&rope);
+        assert_eq!(found, [6..15, 17..28]);
+        assert_eq!(search.expand_replacement(&rope, &found[0], "$2 => ${name}"), "ünï => key");
+        assert_eq!(search.expand_replacement(&rope, &found[1], "$2 => ${name}"), "two => other");
+        let literal = Search::new("key", LITERAL).unwrap();
+        assert_eq!(literal.expand_replacement(&rope, &(6..9), "$1"), "$1");
+    }
+}
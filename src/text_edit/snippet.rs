@@ -0,0 +1,707 @@
+//! Snippets: text with tabstops that Tab moves between once it's inserted.
+//!
+//! Bodies use the syntax of TextMate and the language server protocol:
+//!
+//! - `$1` or `${1}` is a tabstop, visited in order of its number.
+//! - `${1:name}` is a tabstop with a placeholder, which is selected when it's reached and
+//!   replaced by typing.
+//! - `${1|one,two,three|}` is a tabstop with choices. The first is inserted.
+//! - `$0` is where the cursor ends up. It's the end of the snippet if there isn't one.
+//! - A number used more than once is a mirror: editing one copy changes the others.
+//! - `\$`, `\}` and `\\` are literal, as are `\,` and `\|` in choices.
+//!
+//! Placeholders can't hold other tabstops, and variables such as `$TM_FILENAME` aren't
+//! supported, so a `$` that isn't followed by a number or `{` is literal. Tabs in the body
+//! become spaces, and every line after the first is indented like the one the snippet is
+//! inserted on.
+//!
+//! Snippet files hold any number of snippets, each between a `snippet <prefix> [description]`
+//! line and an `endsnippet` line. Lines outside of them that start with `#` are comments:
+//!
+//! ```text
+//! # Named `rust.snippets`, so that these are only used in Rust files.
+//! snippet fn A function
+//! fn ${1:name}($2) {
+//!     $0
+//! }
+//! endsnippet
+//! ```
+
+use std::{collections::BTreeMap, ops::Range, path::Path, rc::Rc};
+
+use super::{highlight::Language, Selection, SelectionKind, TextEditor};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnippetError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
+/// Every buffer shares the snippets of the [`BufferManager`](super::workspace::BufferManager).
+pub type SharedSnippets = Rc<Snippets>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Tabstop {
+        number: usize,
+        placeholder: String,
+        choices: Vec<String>,
+    },
+}
+
+/// Text that's inserted by typing its prefix and pressing Tab.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// The word that expands to the snippet.
+    pub prefix: String,
+    pub description: Option<String>,
+    /// Only expands in files of this language, if set.
+    pub language: Option<Language>,
+    parts: Vec<Part>,
+}
+
+impl Snippet {
+    /// Parses the body of a snippet. Errors give the line of the body they're on, counting from 1.
+    pub fn new<S: Into<String>>(prefix: S, body: &str) -> Result<Self, SnippetError> {
+        Ok(Self {
+            prefix: prefix.into(),
+            description: None,
+            language: None,
+            parts: parse_body(body)?,
+        })
+    }
+    
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+    
+    pub fn language(mut self, language: Option<Language>) -> Self {
+        self.language = language;
+        self
+    }
+    
+    /// The text to insert, with `indent` after every line break, and the tabstops in the order
+    /// that Tab visits them, the final one last. Ranges are char offsets into the text.
+    fn expand(&self, indent: &str) -> (String, Vec<Tabstop>) {
+        // The first copy of a tabstop with a placeholder or choices gives the text of every copy.
+        let mut texts: BTreeMap<usize, (&str, &[String])> = BTreeMap::new();
+        for part in &self.parts {
+            if let Part::Tabstop { number, placeholder, choices } = part {
+                let text = choices.first().map_or(placeholder.as_str(), String::as_str);
+                let entry = texts.entry(*number).or_insert((text, choices));
+                if entry.0.is_empty() && entry.1.is_empty() {
+                    *entry = (text, choices);
+                }
+            }
+        }
+        let mut text = String::new();
+        let mut len = 0;
+        let mut stops: BTreeMap<usize, Tabstop> = BTreeMap::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(part) => push_indented(&mut text, &mut len, part, indent),
+                Part::Tabstop { number, .. } => {
+                    let (placeholder, choices) = texts[number];
+                    let start = len;
+                    push_indented(&mut text, &mut len, placeholder, indent);
+                    let stop = stops.entry(*number).or_insert_with(|| Tabstop {
+                        ranges: Vec::new(),
+                        choices: choices.to_vec(),
+                    });
+                    stop.ranges.push(start..len);
+                }
+            }
+        }
+        let last = stops.remove(&0).unwrap_or_else(|| Tabstop {
+            ranges: std::iter::once(len..len).collect(),
+            choices: Vec::new(),
+        });
+        let mut stops: Vec<Tabstop> = stops.into_values().collect();
+        stops.push(last);
+        (text, stops)
+    }
+}
+
+/// Appends `part`, indenting each new line by `indent` and turning tabs into spaces.
+fn push_indented(text: &mut String, len: &mut usize, part: &str, indent: &str) {
+    for chr in part.chars() {
+        match chr {
+            '\n' => {
+                text.push('\n');
+                text.push_str(indent);
+                *len += 1 + indent.chars().count();
+            }
+            '\t' => {
+                text.extend(std::iter::repeat_n(' ', TextEditor::TAB_WIDTH));
+                *len += TextEditor::TAB_WIDTH;
+            }
+            chr => {
+                text.push(chr);
+                *len += 1;
+            }
+        }
+    }
+}
+
+fn parse_body(body: &str) -> Result<Vec<Part>, SnippetError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = body.chars().peekable();
+    let mut line = 1;
+    let error = |line: usize, message: &str| SnippetError::Parse { line, message: message.to_owned() };
+    let number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        let mut digits = String::new();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            digits.push(digit);
+        }
+        digits.parse::<usize>().ok()
+    };
+    while let Some(chr) = chars.next() {
+        match chr {
+            '\\' if let Some(escaped) = chars.next_if(|&chr| matches!(chr, '$' | '}' | '\\')) => text.push(escaped),
+            '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+                let number = number(&mut chars).ok_or_else(|| error(line, "Tabstop number is too large"))?;
+                parts.push(Part::Tabstop {
+                    number,
+                    placeholder: String::new(),
+                    choices: Vec::new(),
+                });
+            }
+            '$' if chars.next_if_eq(&'{').is_some() => {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+                let number = number(&mut chars).ok_or_else(|| error(line, "Expected a tabstop number after `${`"))?;
+                let mut placeholder = String::new();
+                let mut choices = Vec::new();
+                match chars.next() {
+                    Some('}') => (),
+                    Some(':') => loop {
+                        match chars.next() {
+                            Some('\\') if let Some(escaped) = chars.next_if(|&chr| matches!(chr, '$' | '}' | '\\')) => placeholder.push(escaped),
+                            Some('}') => break,
+                            Some(chr) => {
+                                line += usize::from(chr == '\n');
+                                placeholder.push(chr);
+                            }
+                            None => return Err(error(line, "Expected `}` after the placeholder")),
+                        }
+                    },
+                    Some('|') => {
+                        let mut choice = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('\\') if let Some(escaped) = chars.next_if(|&chr| matches!(chr, ',' | '|' | '\\')) => choice.push(escaped),
+                                Some(',') => choices.push(std::mem::take(&mut choice)),
+                                Some('|') if chars.next_if_eq(&'}').is_some() => break,
+                                Some(chr) => {
+                                    line += usize::from(chr == '\n');
+                                    choice.push(chr);
+                                }
+                                None => return Err(error(line, "Expected `|}` after the choices")),
+                            }
+                        }
+                        choices.push(choice);
+                    }
+                    _ => return Err(error(line, "Expected `}`, `:` or `|` after the tabstop number")),
+                }
+                parts.push(Part::Tabstop { number, placeholder, choices });
+            }
+            chr => {
+                line += usize::from(chr == '\n');
+                text.push(chr);
+            }
+        }
+    }
+    parts.push(Part::Text(text));
+    parts.retain(|part| *part != Part::Text(String::new()));
+    Ok(parts)
+}
+
+/// A set of snippets, looked up by prefix.
+#[derive(Debug, Clone, Default)]
+pub struct Snippets {
+    snippets: Vec<Snippet>,
+}
+
+impl Snippets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Loads a snippet file. If its name (without the extension) is the name of a language,
+    /// such as `rust.snippets`, its snippets are only used in files of that language.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SnippetError> {
+        let path = path.as_ref();
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+        let language = Language::ALL.into_iter().find(|language| language.name().eq_ignore_ascii_case(&stem));
+        Self::parse(&std::fs::read_to_string(path)?, language)
+    }
+    
+    /// Parses a snippet file, whose snippets are for `language`, or every file if `None`.
+    pub fn parse(text: &str, language: Option<Language>) -> Result<Self, SnippetError> {
+        let mut snippets = Self::new();
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line));
+        while let Some((line, header)) = lines.next() {
+            let header = header.trim();
+            if header.is_empty() || header.starts_with('#') {
+                continue;
+            }
+            let Some(header) = header.strip_prefix("snippet ") else {
+                return Err(SnippetError::Parse { line, message: String::from("Expected `snippet <prefix>`") });
+            };
+            let (prefix, description) = header.trim().split_once(char::is_whitespace).unwrap_or((header.trim(), ""));
+            let mut body = Vec::new();
+            loop {
+                match lines.next() {
+                    Some((_, "endsnippet")) => break,
+                    Some((_, text)) => body.push(text),
+                    None => return Err(SnippetError::Parse { line, message: format!("Snippet `{prefix}` has no `endsnippet`") }),
+                }
+            }
+            let snippet = Snippet::new(prefix, &body.join("\n")).map_err(|error| match error {
+                SnippetError::Parse { line: body_line, message } => SnippetError::Parse { line: line + body_line, message },
+                error => error,
+            })?;
+            let snippet = snippet.language(language);
+            let description = description.trim();
+            snippets.add(if description.is_empty() { snippet } else { snippet.description(description) });
+        }
+        Ok(snippets)
+    }
+    
+    /// Adds a snippet, replacing one with the same prefix and language.
+    pub fn add(&mut self, snippet: Snippet) {
+        self.snippets.retain(|other| other.prefix != snippet.prefix || other.language != snippet.language);
+        self.snippets.push(snippet);
+    }
+    
+    /// Adds every snippet of `other`.
+    pub fn extend(&mut self, other: Snippets) {
+        for snippet in other.snippets {
+            self.add(snippet);
+        }
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = &Snippet> {
+        self.snippets.iter()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.snippets.is_empty()
+    }
+    
+    /// The snippet for `prefix` in a file of `language`, preferring one made for the language.
+    pub fn find(&self, prefix: &str, language: Option<Language>) -> Option<&Snippet> {
+        let matching = |snippet: &&Snippet| snippet.prefix == prefix;
+        self.snippets.iter().filter(matching).find(|snippet| snippet.language.is_some() && snippet.language == language)
+            .or_else(|| self.snippets.iter().filter(matching).find(|snippet| snippet.language.is_none()))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Tabstop {
+    /// The first copy, then its mirrors.
+    ranges: Vec<Range<usize>>,
+    choices: Vec<String>,
+}
+
+/// A snippet that's being filled in. Its ranges are kept in sync with edits.
+#[derive(Debug, Clone)]
+pub(crate) struct SnippetSession {
+    stops: Vec<Tabstop>,
+    active: usize,
+    /// The whole snippet.
+    range: Range<usize>,
+    /// Set when the cursor reaches a tabstop with choices, until they're taken.
+    offer_choices: bool,
+    /// Set while mirrors are being updated, so that their edits aren't mirrored in turn.
+    mirroring: bool,
+}
+
+impl SnippetSession {
+    pub(crate) fn is_mirroring(&self) -> bool {
+        self.mirroring
+    }
+    
+    /// Moves the ranges after `len` chars were inserted at `at`. The ranges of the active
+    /// tabstop grow when text is inserted at either of their ends, so that typing fills empty
+    /// ones. Returns which of them was typed in.
+    pub(crate) fn inserted(&mut self, at: usize, len: usize) -> Option<usize> {
+        let mut edited = None;
+        for (index, stop) in self.stops.iter_mut().enumerate() {
+            let active = index == self.active;
+            for (range_index, range) in stop.ranges.iter_mut().enumerate() {
+                if at < range.start || (at == range.start && !active) {
+                    range.start += len;
+                    range.end += len;
+                } else if at < range.end || (at == range.end && active) {
+                    range.end += len;
+                    if active {
+                        edited.get_or_insert(range_index);
+                    }
+                }
+            }
+        }
+        if at < self.range.start {
+            self.range.start += len;
+            self.range.end += len;
+        } else if at <= self.range.end {
+            self.range.end += len;
+        }
+        edited
+    }
+    
+    /// Moves the ranges after `removed` was removed. Returns which range of the active
+    /// tabstop it was removed from.
+    pub(crate) fn removed(&mut self, removed: Range<usize>) -> Option<usize> {
+        let shift = |index: usize| if index <= removed.start {
+            index
+        } else {
+            index - (index.min(removed.end) - removed.start)
+        };
+        let mut edited = None;
+        for (index, stop) in self.stops.iter_mut().enumerate() {
+            for (range_index, range) in stop.ranges.iter_mut().enumerate() {
+                if index == self.active && range.start <= removed.start && removed.end <= range.end {
+                    edited.get_or_insert(range_index);
+                }
+                *range = shift(range.start)..shift(range.end);
+            }
+        }
+        self.range = shift(self.range.start)..shift(self.range.end);
+        edited
+    }
+}
+
+impl TextEditor {
+    /// Expands the snippet whose prefix is the word before the cursor. Returns `false` if there
+    /// isn't one, or text is selected.
+    pub fn expand_snippet(&mut self) -> bool {
+        if !self.is_editable() || self.selection.is_some() || !self.extra_cursors.is_empty() {
+            return false;
+        }
+        let line = self.rope.line(self.cursor.line);
+        let end = self.cursor.col.min(self.line_len(self.cursor.line));
+        let mut start = end;
+        while start > 0 && (line.char(start - 1).is_alphanumeric() || line.char(start - 1) == '_') {
+            start -= 1;
+        }
+        if start == end {
+            return false;
+        }
+        let prefix = line.slice(start..end).to_string();
+        let language = self.path.as_deref().and_then(Language::from_path);
+        let snippets = Rc::clone(&self.snippets);
+        let Some(snippet) = snippets.find(&prefix, language) else {
+            return false;
+        };
+        let line_start = self.rope.line_to_char(self.cursor.line);
+        self.transaction(|editor| {
+            if editor.try_remove(line_start + start..line_start + end).is_ok() {
+                editor.set_cursor(editor.char_to_position(line_start + start));
+                editor.insert_snippet(snippet);
+            }
+        });
+        true
+    }
+    
+    /// Inserts a snippet at the cursor and moves to its first tabstop. Any snippet that was
+    /// being filled in is left as it is.
+    pub fn insert_snippet(&mut self, snippet: &Snippet) {
+        if !self.is_editable() {
+            return;
+        }
+        self.snippet = None;
+        self.selection = None;
+        let line = self.rope.line(self.cursor.line);
+        let indent: String = line.chars()
+            .take(self.cursor.col)
+            .take_while(|chr| *chr == ' ' || *chr == '\t')
+            .collect();
+        let (text, mut stops) = snippet.expand(&indent);
+        let at = self.cursor_char_index();
+        if self.try_insert(at, &text).is_err() {
+            return;
+        }
+        for range in stops.iter_mut().flat_map(|stop| &mut stop.ranges) {
+            *range = at + range.start..at + range.end;
+        }
+        self.snippet = Some(SnippetSession {
+            stops,
+            active: 0,
+            range: at..at + text.chars().count(),
+            offer_choices: false,
+            mirroring: false,
+        });
+        self.select_tabstop(0);
+    }
+    
+    /// Whether a snippet is being filled in. It's done once the cursor leaves it, or reaches
+    /// its final tabstop.
+    pub fn in_snippet(&self) -> bool {
+        let cursor = self.cursor_char_index();
+        // Undoing the snippet leaves it empty.
+        self.snippet.as_ref().is_some_and(|session| !session.range.is_empty() && (session.range.start..=session.range.end).contains(&cursor))
+    }
+    
+    /// Stops filling in the snippet, leaving its text as it is.
+    pub fn exit_snippet(&mut self) -> bool {
+        self.snippet.take().is_some()
+    }
+    
+    /// Moves to the next tabstop of the snippet being filled in, if there is one.
+    pub fn next_tabstop(&mut self) -> bool {
+        if !self.in_snippet() {
+            self.snippet = None;
+            return false;
+        }
+        let next = self.snippet.as_ref().map_or(0, |session| session.active + 1);
+        self.select_tabstop(next);
+        true
+    }
+    
+    /// Moves back to the previous tabstop of the snippet being filled in, if there is one.
+    pub fn prev_tabstop(&mut self) -> bool {
+        if !self.in_snippet() {
+            self.snippet = None;
+            return false;
+        }
+        let prev = self.snippet.as_ref().map_or(0, |session| session.active.saturating_sub(1));
+        self.select_tabstop(prev);
+        true
+    }
+    
+    /// The choices of the tabstop the cursor just moved to, once. Show them in a list, such as
+    /// [`CompletionState::choices`](super::completion::CompletionState::choices).
+    pub fn take_snippet_choices(&mut self) -> Option<Vec<String>> {
+        let session = self.snippet.as_mut()?;
+        if !std::mem::take(&mut session.offer_choices) {
+            return None;
+        }
+        Some(session.stops[session.active].choices.clone())
+    }
+    
+    /// Puts the cursor at the end of a tabstop, selecting its placeholder. Reaching the final
+    /// tabstop ends the snippet.
+    fn select_tabstop(&mut self, index: usize) {
+        let Some(session) = &mut self.snippet else {
+            return;
+        };
+        session.active = index.min(session.stops.len() - 1);
+        let stop = &session.stops[session.active];
+        let range = stop.ranges[0].clone();
+        session.offer_choices = !stop.choices.is_empty();
+        if session.active + 1 == session.stops.len() {
+            self.snippet = None;
+        }
+        self.set_cursor(self.char_to_position(range.end));
+        self.selection = (!range.is_empty()).then(|| Selection {
+            anchor: self.char_to_position(range.start),
+            kind: SelectionKind::Exclusive,
+        });
+    }
+    
+    /// Removes the placeholder of the tabstop if it's still selected, so that typing replaces it.
+    pub(crate) fn take_placeholder(&mut self) -> bool {
+        let Some(session) = &self.snippet else {
+            return false;
+        };
+        let range = session.stops[session.active].ranges[0].clone();
+        !range.is_empty() && self.selection_range() == Some(range) && self.delete_selection()
+    }
+    
+    /// Copies the text of the active tabstop's range `source` to its other ranges, as part of
+    /// the same undo step as the edit that changed it.
+    pub(crate) fn sync_mirrors(&mut self, source: usize) {
+        let Some(session) = &mut self.snippet else {
+            return;
+        };
+        if self.replaying || session.mirroring {
+            return;
+        }
+        session.mirroring = true;
+        let text = self.rope.slice(session.stops[session.active].ranges[source].clone()).to_string();
+        let count = session.stops[session.active].ranges.len();
+        for index in (0..count).filter(|&index| index != source) {
+            let Some(session) = &self.snippet else {
+                break;
+            };
+            let range = session.stops[session.active].ranges[index].clone();
+            if self.rope.slice(range.clone()) == text.as_str() {
+                continue;
+            }
+            if self.try_remove(range.clone()).is_err() || self.try_insert(range.start, &text).is_err() {
+                break;
+            }
+        }
+        if let Some(session) = &mut self.snippet {
+            session.mirroring = false;
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init, reason = "Tabstops that happen to have one range.")]
+mod tests {
+    use super::*;
+    use crate::text_edit::{command::EditorCommand, TextPosition};
+    
+    fn text(text: &str) -> Part {
+        Part::Text(text.to_owned())
+    }
+    
+    fn tabstop(number: usize, placeholder: &str, choices: &[&str]) -> Part {
+        Part::Tabstop {
+            number,
+            placeholder: placeholder.to_owned(),
+            choices: choices.iter().map(|choice| choice.to_string()).collect(),
+        }
+    }
+    
+    fn parse_error(result: Result<impl std::fmt::Debug, SnippetError>) -> (usize, String) {
+        match result {
+            Err(SnippetError::Parse { line, message }) => (line, message),
+            result => panic!("Expected a parse error, got {result:?}"),
+        }
+    }
+    
+    #[test]
+    fn parses_tabstops() {
+        assert_eq!(parse_body("fn ${1:name}($2) {\n\t$0\n}").unwrap(), [
+            text("fn "),
+            tabstop(1, "name", &[]),
+            text("("),
+            tabstop(2, "", &[]),
+            text(") {\n\t"),
+            tabstop(0, "", &[]),
+            text("\n}"),
+        ]);
+        assert_eq!(parse_body("${12}${3|one,t\\,wo,th\\|ree|}").unwrap(), [
+            tabstop(12, "", &[]),
+            tabstop(3, "", &["one", "t,wo", "th|ree"]),
+        ]);
+        assert_eq!(parse_body("\\$1 $HOME ${1:a\\}b} \\\\").unwrap(), [
+            text("$1 $HOME "),
+            tabstop(1, "a}b", &[]),
+            text(" \\"),
+        ]);
+    }
+    
+    #[test]
+    fn reports_the_line_of_errors() {
+        assert_eq!(parse_error(parse_body("a\nb ${x}")).0, 2);
+        assert_eq!(parse_error(parse_body("${1:a\nb")).0, 2);
+        assert_eq!(parse_error(parse_body("${1|a,b}")).0, 1);
+        assert_eq!(parse_error(parse_body("${1!}")).0, 1);
+        assert_eq!(parse_error(parse_body("$99999999999999999999999")).0, 1);
+        // Lines of the file, counting the header.
+        let file = "# Comment\nsnippet ok\n$1\nendsnippet\n\nsnippet bad\nfirst\n${\nendsnippet\n";
+        assert_eq!(parse_error(Snippets::parse(file, None)).0, 8);
+        assert_eq!(parse_error(Snippets::parse("snippet open\ntext\n", None)), (1, String::from("Snippet `open` has no `endsnippet`")));
+        assert_eq!(parse_error(Snippets::parse("stray\n", None)).0, 1);
+    }
+    
+    #[test]
+    fn parses_files() {
+        let file = "# Comment\nsnippet fn A function\nfn $1() {}\nendsnippet\n\nsnippet if\nif $1 {\n}\nendsnippet\n";
+        let snippets = Snippets::parse(file, Some(Language::Rust)).unwrap();
+        let snippets: Vec<_> = snippets.iter().collect();
+        assert_eq!(snippets.len(), 2);
+        assert_eq!((snippets[0].prefix.as_str(), snippets[0].description.as_deref()), ("fn", Some("A function")));
+        assert_eq!((snippets[1].prefix.as_str(), snippets[1].description.as_deref()), ("if", None));
+        assert_eq!(snippets[1].parts, [text("if "), tabstop(1, "", &[]), text(" {\n}")]);
+        assert!(snippets.iter().all(|snippet| snippet.language == Some(Language::Rust)));
+    }
+    
+    #[test]
+    fn prefers_snippets_for_the_language() {
+        let mut snippets = Snippets::new();
+        snippets.add(Snippet::new("log", "any").unwrap());
+        snippets.add(Snippet::new("log", "rust").unwrap().language(Some(Language::Rust)));
+        let body = |snippets: &Snippets, language| snippets.find("log", language).map(|snippet| snippet.parts.clone());
+        assert_eq!(body(&snippets, Some(Language::Rust)), Some(vec![text("rust")]));
+        assert_eq!(body(&snippets, None), Some(vec![text("any")]));
+        assert_eq!(body(&snippets, Some(Language::Toml)), Some(vec![text("any")]));
+        assert!(snippets.find("lo", None).is_none());
+        
+        snippets.add(Snippet::new("log", "replaced").unwrap());
+        assert_eq!(snippets.iter().count(), 2);
+        assert_eq!(body(&snippets, None), Some(vec![text("replaced")]));
+    }
+    
+    #[test]
+    fn expands_with_indent_and_mirrors() {
+        let snippet = Snippet::new("s", "a ${1:x} $2\n\t$1$0").unwrap();
+        let (text, stops) = snippet.expand("  ");
+        assert_eq!(text, "a x \n      x");
+        let ranges: Vec<_> = stops.iter().map(|stop| stop.ranges.clone()).collect();
+        assert_eq!(ranges, [vec![2..3, 11..12], vec![4..4], vec![12..12]]);
+        
+        // The end of the snippet is the final tabstop when there isn't a `$0`.
+        let (text, stops) = Snippet::new("s", "${1|yes,no|}!").unwrap().expand("");
+        assert_eq!(text, "yes!");
+        assert_eq!(stops.len(), 2);
+        assert_eq!(stops[0].choices, ["yes", "no"]);
+        assert_eq!(stops[1].ranges, [4..4]);
+    }
+    
+    fn editor(text: &str, snippets: &str) -> TextEditor {
+        let mut editor = TextEditor::new();
+        editor.snippets = Rc::new(Snippets::parse(snippets, None).unwrap());
+        editor.execute(&EditorCommand::InsertText(text.to_owned()));
+        editor
+    }
+    
+    #[test]
+    fn fills_in_tabstops() {
+        let mut editor = editor("    fn", "snippet fn\nfn ${1:name}() -> $2 {\n\t$1\n}\nendsnippet\n");
+        editor.execute(&EditorCommand::InsertTab);
+        assert_eq!(editor.rope, "    fn name() ->  {\n        name\n    }");
+        assert!(editor.in_snippet());
+        assert_eq!(editor.selection_range(), Some(7..11));
+        
+        // Typing replaces the placeholder, and the mirror follows.
+        editor.execute(&EditorCommand::InsertText(String::from("run")));
+        assert_eq!(editor.rope, "    fn run() ->  {\n        run\n    }");
+        editor.execute(&EditorCommand::Backspace);
+        assert_eq!(editor.rope, "    fn ru() ->  {\n        ru\n    }");
+        
+        editor.execute(&EditorCommand::InsertTab);
+        editor.execute(&EditorCommand::InsertText(String::from("u8")));
+        assert_eq!(editor.rope, "    fn ru() -> u8 {\n        ru\n    }");
+        editor.execute(&EditorCommand::PrevTabstop);
+        assert_eq!(editor.selection_range(), Some(7..9));
+        editor.execute(&EditorCommand::InsertTab);
+        editor.execute(&EditorCommand::InsertTab);
+        assert!(!editor.in_snippet());
+        assert_eq!(editor.cursor, TextPosition::new(2, 5));
+        
+        // Once it's done, Tab indents to the next tab stop again.
+        editor.execute(&EditorCommand::InsertTab);
+        assert_eq!(editor.rope, "    fn ru() -> u8 {\n        ru\n    }   ");
+    }
+    
+    #[test]
+    fn offers_choices_once() {
+        let mut editor = editor("b", "snippet b\n${1|true,false|};\nendsnippet\n");
+        editor.execute(&EditorCommand::InsertTab);
+        assert_eq!(editor.rope, "true;");
+        assert_eq!(editor.take_snippet_choices(), Some(vec![String::from("true"), String::from("false")]));
+        assert_eq!(editor.take_snippet_choices(), None);
+    }
+    
+    #[test]
+    fn leaving_the_snippet_ends_it() {
+        let mut editor = editor("x\nfn", "snippet fn\nfn $1() {}\nendsnippet\n");
+        editor.execute(&EditorCommand::InsertTab);
+        assert!(editor.in_snippet());
+        editor.execute(&EditorCommand::MoveTo(TextPosition::new(0, 0)));
+        assert!(!editor.in_snippet());
+        editor.execute(&EditorCommand::InsertTab);
+        assert_eq!(editor.rope, "    x\nfn () {}");
+    }
+}
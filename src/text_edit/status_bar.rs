@@ -0,0 +1,156 @@
+use ratatui::prelude::*;
+use ropey::Rope;
+
+use crate::theme::Theme;
+
+use super::{diagnostics::Severity, TextEditor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// Guesses from the end of the first line, so the cost doesn't depend on the buffer size.
+    /// Buffers without line breaks count as `Lf`.
+    pub fn detect(rope: &Rope) -> Self {
+        let line = rope.line(0);
+        let len = line.len_chars();
+        if len >= 2 && line.char(len - 2) == '\r' && line.char(len - 1) == '\n' {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+    
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        }
+    }
+}
+
+/// One line about the buffer: the mode, file name and state on the left, and the cursor
+/// position, encoding and line endings on the right.
+pub struct StatusBar<'a> {
+    editor: &'a TextEditor,
+    mode: Option<&'a str>,
+    message: Option<&'a str>,
+    style: Style,
+    mode_style: Style,
+    modified_style: Style,
+}
+
+impl<'a> StatusBar<'a> {
+    pub fn new(editor: &'a TextEditor) -> Self {
+        Self {
+            editor,
+            mode: None,
+            message: None,
+            style: Style::new().fg(Color::Gray).bg(Color::Rgb(37, 41, 50)),
+            mode_style: Style::new().fg(Color::Black).bg(Color::Rgb(97, 175, 239)).add_modifier(Modifier::BOLD),
+            modified_style: Style::new().fg(Color::Rgb(229, 192, 123)),
+        }
+    }
+    
+    /// Shown first, such as the Vim mode or the name of the keymap.
+    pub fn mode(mut self, mode: &'a str) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+    
+    /// Shown after the state of the buffer, such as a message from a language server.
+    pub fn message(mut self, message: &'a str) -> Self {
+        self.message = Some(message);
+        self
+    }
+    
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.style(theme.style("status_bar"))
+            .mode_style(theme.style("status_bar.mode"))
+            .modified_style(theme.style("status_bar.modified"))
+    }
+    
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn mode_style(mut self, style: Style) -> Self {
+        self.mode_style = style;
+        self
+    }
+    
+    pub fn modified_style(mut self, style: Style) -> Self {
+        self.modified_style = style;
+        self
+    }
+}
+
+impl Widget for StatusBar<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let editor = self.editor;
+        buf.set_style(area, self.style);
+        let name = editor.path.as_deref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy())
+            .unwrap_or("[No Name]".into());
+        let mut left = Vec::new();
+        if let Some(mode) = self.mode.filter(|mode| !mode.is_empty()) {
+            left.push(Span::styled(format!(" {mode} "), self.mode_style));
+        }
+        left.push(Span::raw(format!(" {name}")));
+        if editor.is_modified() {
+            left.push(Span::styled(" ●", self.modified_style));
+        }
+        if editor.is_loading() {
+            left.push(Span::raw(" [Loading]"));
+        } else if editor.read_only {
+            left.push(Span::raw(" [Read-only]"));
+        }
+        if editor.is_following() {
+            left.push(Span::raw(" [Following]"));
+        }
+        if let Some(message) = self.message.and_then(|message| message.lines().next()) {
+            left.push(Span::raw(format!("  {message}")));
+        }
+        let mut right = Vec::new();
+        let count = |severity| editor.diagnostics().iter().filter(|diagnostic| diagnostic.severity == severity).count();
+        let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+        if errors + warnings != 0 {
+            right.push(format!("{errors} errors, {warnings} warnings"));
+        }
+        if let Some(selection) = editor.selection_range() {
+            right.push(format!("({} selected)", selection.len()));
+        }
+        if !editor.extra_cursors.is_empty() {
+            right.push(format!("{} cursors", editor.extra_cursors.len() + 1));
+        }
+        right.push(format!("Ln {}, Col {}", editor.cursor.line + 1, editor.cursor.col + 1));
+        right.push(String::from("UTF-8"));
+        right.push(String::from(LineEnding::detect(&editor.rope).name()));
+        let right = format!("{} ", right.join("  "));
+        let right_width = (right.chars().count() as u16).min(area.width);
+        let right_area = Rect {
+            x: area.right() - right_width,
+            width: right_width,
+            ..area
+        };
+        // The left side gives way to the cursor position when space runs out.
+        let left_area = Rect {
+            width: area.width - right_width,
+            ..area
+        };
+        Line::from(left).render(left_area, buf);
+        Line::raw(right).render(right_area, buf);
+    }
+}
@@ -0,0 +1,517 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
+};
+
+use indicatif::HumanDuration;
+use ratatui::prelude::*;
+use ropey::Rope;
+use twox_hash::XxHash64;
+
+use crate::theme::Theme;
+
+use super::{TextEditor, TextPosition};
+
+/// The first line of every swap file.
+const MAGIC: &str = "hacker swap file";
+
+/// Numbers the swap files of buffers without a path, which are told apart by process as well.
+static UNNAMED_SWAP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// How long autosave waits before trying again to save a buffer that it couldn't save,
+/// unless the buffer is edited in the meantime.
+const SAVE_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When buffers with unsaved changes are written to swap files, and saved on their own.
+/// See [`TextEditor::poll_autosave`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Autosave {
+    /// Where swap files go. They're turned off when this is `None`.
+    pub swap_dir: Option<PathBuf>,
+    /// How often changes are written to the swap file while editing goes on.
+    pub swap_interval: Duration,
+    /// Saves buffers that have a path once they've gone this long without an edit.
+    pub save_when_idle: Option<Duration>,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self {
+            swap_dir: None,
+            swap_interval: Duration::from_secs(4),
+            save_when_idle: None,
+        }
+    }
+}
+
+impl Autosave {
+    /// `$XDG_STATE_HOME/hacker/swap`, or the same under `~/.local/state`. Windows uses
+    /// `%LOCALAPPDATA%`, and anything else the temporary directory.
+    pub fn default_swap_dir() -> PathBuf {
+        let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+        var("XDG_STATE_HOME")
+            .or_else(|| var("HOME").map(|home| home.join(".local").join("state")))
+            .or_else(|| var("LOCALAPPDATA"))
+            .unwrap_or_else(std::env::temp_dir)
+            .join("hacker")
+            .join("swap")
+    }
+}
+
+/// The swap file of a buffer. It's locked for as long as it's open, which is how the swap
+/// files left behind by a crash are told apart from those of editors that are still running.
+pub(crate) struct SwapFile {
+    path: PathBuf,
+    file: File,
+    /// The version of the buffer last written, and when the write started.
+    version: u64,
+    written_at: Option<Instant>,
+    /// The write in progress. Writes happen on another thread, from a snapshot of the rope,
+    /// so that large buffers don't hold up the editor.
+    writer: Option<JoinHandle<std::io::Result<()>>>,
+}
+
+impl SwapFile {
+    /// Files are named after the absolute path of the buffer, so that a crashed session's swap
+    /// file is found again when the file is.
+    fn open(dir: &Path, buffer_path: Option<&Path>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let name = match buffer_path {
+            Some(buffer_path) => {
+                let absolute = std::path::absolute(buffer_path)?;
+                let hash = XxHash64::oneshot(0, absolute.as_os_str().as_encoded_bytes());
+                let file_name = absolute.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                format!("{file_name}-{hash:016x}.swp")
+            }
+            None => format!(
+                "unnamed-{}-{}.swp",
+                std::process::id(),
+                UNNAMED_SWAP_FILES.fetch_add(1, Ordering::Relaxed),
+            ),
+        };
+        let path = dir.join(name);
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        Self::lock(path, file)?.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::ResourceBusy, "The swap file is in use by another editor")
+        })
+    }
+    
+    /// Returns `None` if another editor has the file locked.
+    fn lock(path: PathBuf, file: File) -> std::io::Result<Option<Self>> {
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self {
+                path,
+                file,
+                version: 0,
+                written_at: None,
+                writer: None,
+            })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(error)) => Err(error),
+        }
+    }
+    
+    /// Starts writing `rope` in the background. See [`SwapFile::poll_write`].
+    fn write(&mut self, rope: &Rope, buffer_path: Option<&Path>, version: u64) -> std::io::Result<()> {
+        // The clone shares the lock, which belongs to the open file rather than the handle.
+        let mut file = self.file.try_clone()?;
+        let rope = rope.clone();
+        let buffer_path = buffer_path.map(std::path::absolute).transpose()?;
+        let writer = std::thread::Builder::new()
+            .name(String::from("swap file writer"))
+            .spawn(move || {
+                file.set_len(0)?;
+                file.seek(SeekFrom::Start(0))?;
+                let mut writer = BufWriter::new(&file);
+                writeln!(writer, "{MAGIC}")?;
+                writeln!(writer, "pid: {}", std::process::id())?;
+                if let Some(buffer_path) = buffer_path {
+                    writeln!(writer, "path: {}", buffer_path.display())?;
+                }
+                writeln!(writer)?;
+                rope.write_to(&mut writer)?;
+                writer.flush()
+            })?;
+        self.writer = Some(writer);
+        self.version = version;
+        self.written_at = Some(Instant::now());
+        Ok(())
+    }
+    
+    /// Returns `false` while a write is still in progress, and the error if the last one failed.
+    fn poll_write(&mut self) -> std::io::Result<bool> {
+        if self.writer.as_ref().is_some_and(|writer| !writer.is_finished()) {
+            return Ok(false);
+        }
+        self.finish_write()?;
+        Ok(true)
+    }
+    
+    /// Waits for the write in progress, if there is one.
+    fn finish_write(&mut self) -> std::io::Result<()> {
+        let Some(writer) = self.writer.take() else {
+            return Ok(());
+        };
+        let result = writer.join().unwrap_or_else(|_| Err(std::io::Error::other("Swap file writer panicked.")));
+        if result.is_err() {
+            // Written again on the next poll.
+            self.version = 0;
+            self.written_at = None;
+        }
+        result
+    }
+    
+    fn remove(mut self) -> std::io::Result<()> {
+        // A write still going would create the file again.
+        _ = self.finish_write();
+        // Windows won't remove files that are open.
+        drop(self.file);
+        std::fs::remove_file(self.path)
+    }
+}
+
+/// A swap file left behind by an editor that didn't exit cleanly.
+pub struct Recovery {
+    /// The file that the buffer belonged to, or `None` if it didn't have a path.
+    pub path: Option<PathBuf>,
+    pub text: Rope,
+    /// When the swap file was last written.
+    pub modified: Option<SystemTime>,
+    swap: SwapFile,
+}
+
+impl Recovery {
+    /// Every swap file in `dir` that no running editor is using. Each is kept locked until
+    /// it's recovered, discarded or dropped, so that opening the same file doesn't start
+    /// writing over it.
+    pub fn find<P: AsRef<Path>>(dir: P) -> std::io::Result<Vec<Recovery>> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut recoveries = Vec::new();
+        // One swap file that can't be read, such as another user's, doesn't hide the rest.
+        for entry in entries {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "swp") {
+                continue;
+            }
+            let Ok(file) = OpenOptions::new().read(true).write(true).open(&path) else {
+                continue;
+            };
+            let Ok(Some(mut swap)) = SwapFile::lock(path, file) else {
+                continue;
+            };
+            let mut contents = String::new();
+            // Files that aren't swap files, or were cut short, are left alone.
+            if swap.file.read_to_string(&mut contents).is_err() {
+                continue;
+            }
+            let Some((path, text)) = parse(&contents) else {
+                continue;
+            };
+            recoveries.push(Recovery {
+                path,
+                text: Rope::from_str(text),
+                modified: swap.file.metadata().and_then(|metadata| metadata.modified()).ok(),
+                swap,
+            });
+        }
+        recoveries.sort_by_key(|recovery| std::cmp::Reverse(recovery.modified));
+        Ok(recoveries)
+    }
+    
+    /// The file name, or `[No Name]`.
+    pub fn name(&self) -> String {
+        self.path.as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("[No Name]"))
+    }
+    
+    /// Deletes the swap file.
+    pub fn discard(self) -> std::io::Result<()> {
+        self.swap.remove()
+    }
+}
+
+/// Splits a swap file into the path of its buffer and its text.
+fn parse(contents: &str) -> Option<(Option<PathBuf>, &str)> {
+    let (header, text) = contents.split_once("\n\n")?;
+    let mut lines = header.lines();
+    if lines.next()? != MAGIC {
+        return None;
+    }
+    let path = lines.find_map(|line| line.strip_prefix("path: ")).map(PathBuf::from);
+    Some((path, text))
+}
+
+impl TextEditor {
+    /// Writes unsaved changes to the swap file every [`Autosave::swap_interval`], and saves
+    /// the buffer once it's been idle for [`Autosave::save_when_idle`]. The swap file is
+    /// removed once there's nothing unsaved. Call it regularly; returns `true` if the buffer
+    /// was saved.
+    ///
+    /// A save that fails is returned as the error, and isn't tried again until the buffer has
+    /// been edited and left idle again, or [`SAVE_RETRY_INTERVAL`] has passed. The swap file
+    /// is still written in the meantime.
+    pub fn poll_autosave(&mut self) -> std::io::Result<bool> {
+        let now = Instant::now();
+        if self.last_edit.0 != self.version {
+            self.last_edit = (self.version, now);
+        }
+        if !self.is_modified() || !self.is_editable() {
+            self.remove_swap_file()?;
+            return Ok(false);
+        }
+        let mut save_error = None;
+        if let Some(idle) = self.autosave.save_when_idle
+            && self.path.is_some()
+            && now.duration_since(self.last_edit.1) >= idle
+            && self.failed_save.is_none_or(|(version, at)| version != self.version || now.duration_since(at) >= SAVE_RETRY_INTERVAL) {
+            match self.save() {
+                Ok(()) => {
+                    self.failed_save = None;
+                    self.remove_swap_file()?;
+                    return Ok(true);
+                }
+                Err(error) => {
+                    self.failed_save = Some((self.version, now));
+                    save_error = Some(error);
+                }
+            }
+        }
+        let swapped = self.poll_swap(now);
+        match save_error {
+            Some(error) => Err(error),
+            None => swapped.map(|()| false),
+        }
+    }
+    
+    /// Starts writing the swap file if it's out of date and hasn't been written for a while.
+    fn poll_swap(&mut self, now: Instant) -> std::io::Result<()> {
+        let Some(dir) = &self.autosave.swap_dir else {
+            return Ok(());
+        };
+        if let Some(swap) = &mut self.swap
+            && (!swap.poll_write()?
+                || swap.version == self.version
+                || swap.written_at.is_some_and(|at| now.duration_since(at) < self.autosave.swap_interval)) {
+            return Ok(());
+        }
+        let swap = match self.swap.take() {
+            Some(swap) => swap,
+            None => SwapFile::open(dir, self.path.as_deref())?,
+        };
+        self.swap.insert(swap).write(&self.rope, self.path.as_deref(), self.version)
+    }
+    
+    /// Deletes the swap file, as when exiting cleanly.
+    pub fn remove_swap_file(&mut self) -> std::io::Result<()> {
+        match self.swap.take() {
+            Some(swap) => swap.remove(),
+            None => Ok(()),
+        }
+    }
+    
+    /// Replaces the text with that of a swap file, as an edit that can be undone, and takes
+    /// the swap file over. A file that's still loading stops, so that it can't replace the
+    /// recovered text when it's done. Read-only buffers can't be recovered into, and leave
+    /// the swap file where it is.
+    pub fn recover(&mut self, recovery: Recovery) -> std::io::Result<()> {
+        if self.read_only {
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "The buffer is read-only"));
+        }
+        if self.loader.take().is_some() {
+            self.apply_large_file_thresholds();
+        }
+        let text = recovery.text.to_string();
+        let mut result = Ok(());
+        self.transaction(|editor| {
+            result = editor.try_remove(..).and_then(|()| editor.try_insert(0, &text));
+        });
+        result.map_err(std::io::Error::other)?;
+        self.set_cursor(TextPosition::new(0, 0));
+        _ = self.remove_swap_file();
+        self.swap = Some(recovery.swap);
+        Ok(())
+    }
+}
+
+/// Asks whether to recover a swap file, in a single row.
+pub struct RecoveryBar<'a> {
+    recovery: &'a Recovery,
+    /// How many more are waiting after this one.
+    remaining: usize,
+    style: Style,
+    hint_style: Style,
+}
+
+impl<'a> RecoveryBar<'a> {
+    pub fn new(recovery: &'a Recovery, remaining: usize) -> Self {
+        Self {
+            recovery,
+            remaining,
+            style: Style::new().bg(Color::Rgb(97, 76, 41)).fg(Color::Rgb(229, 192, 123)),
+            hint_style: Style::new().fg(Color::Rgb(171, 178, 191)),
+        }
+    }
+    
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.style(theme.style("recovery_bar"))
+            .hint_style(theme.style("recovery_bar.hint"))
+    }
+    
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn hint_style(mut self, style: Style) -> Self {
+        self.hint_style = style;
+        self
+    }
+}
+
+impl Widget for RecoveryBar<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        buf.set_style(area, self.style);
+        if area.is_empty() {
+            return;
+        }
+        let age = self.recovery.modified
+            .and_then(|modified| modified.elapsed().ok())
+            .map(|age| format!(" from {} ago", HumanDuration(age)))
+            .unwrap_or_default();
+        let prompt = format!(" Found unsaved changes to {}{age}.", self.recovery.name());
+        let (x, _) = buf.set_stringn(area.x, area.y, &prompt, area.width as usize, self.style);
+        let mut hint = String::from("  [r]ecover  [d]iscard  [s]kip");
+        if self.remaining != 0 {
+            hint.push_str(&format!("  ({} more)", self.remaining));
+        }
+        buf.set_stringn(x, area.y, &hint, area.right().saturating_sub(x) as usize, self.hint_style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_edit::command::EditorCommand;
+    
+    /// A directory of its own for each test, removed when it's done.
+    struct TempDir(PathBuf);
+    
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("hacker-swap-{name}-{}", std::process::id()));
+            _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+    
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+    
+    fn editor(text: &str, autosave: Autosave) -> TextEditor {
+        let mut editor = TextEditor::new();
+        editor.autosave = autosave;
+        editor.execute(&EditorCommand::InsertText(text.to_owned()));
+        editor
+    }
+    
+    /// Writes the swap file and waits for the write to finish.
+    fn write_swap_file(editor: &mut TextEditor) {
+        assert!(!editor.poll_autosave().unwrap());
+        editor.swap.as_mut().unwrap().finish_write().unwrap();
+    }
+    
+    #[test]
+    fn parses_header_and_text() {
+        let contents = format!("{MAGIC}\npid: 1\npath: /tmp/a.rs\n\nfn main() {{}}\n\nmore\n");
+        assert_eq!(parse(&contents), Some((Some(PathBuf::from("/tmp/a.rs")), "fn main() {}\n\nmore\n")));
+        assert_eq!(parse(&format!("{MAGIC}\npid: 1\n\ntext")), Some((None, "text")));
+        assert_eq!(parse("not a swap file\n\ntext"), None);
+        assert_eq!(parse(MAGIC), None);
+    }
+    
+    #[test]
+    fn recovers_what_a_crashed_editor_left() {
+        let dir = TempDir::new("recover");
+        let autosave = Autosave {
+            swap_dir: Some(dir.0.clone()),
+            ..Autosave::default()
+        };
+        let mut crashed = editor("unsaved\ntext", autosave.clone());
+        write_swap_file(&mut crashed);
+        // It's locked while the editor that wrote it is running.
+        assert!(Recovery::find(&dir.0).unwrap().is_empty());
+        drop(crashed);
+        
+        let recovery = Recovery::find(&dir.0).unwrap().pop().unwrap();
+        assert_eq!(recovery.path, None);
+        assert_eq!(recovery.name(), "[No Name]");
+        assert_eq!(recovery.text, "unsaved\ntext");
+        let mut read_only = TextEditor::new();
+        read_only.read_only = true;
+        let error = read_only.recover(recovery).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+        assert_eq!(read_only.rope, "");
+        
+        // The swap file is left for the next try.
+        let recovery = Recovery::find(&dir.0).unwrap().pop().unwrap();
+        let mut editor = editor("other", autosave);
+        editor.recover(recovery).unwrap();
+        assert_eq!(editor.rope, "unsaved\ntext");
+        assert!(Recovery::find(&dir.0).unwrap().is_empty());
+        editor.remove_swap_file().unwrap();
+        assert_eq!(std::fs::read_dir(&dir.0).unwrap().count(), 0);
+    }
+    
+    #[test]
+    fn discarding_removes_the_swap_file() {
+        let dir = TempDir::new("discard");
+        let mut crashed = editor("text", Autosave {
+            swap_dir: Some(dir.0.clone()),
+            ..Autosave::default()
+        });
+        write_swap_file(&mut crashed);
+        drop(crashed);
+        let recoveries = Recovery::find(&dir.0).unwrap();
+        assert_eq!(recoveries.len(), 1);
+        for recovery in recoveries {
+            recovery.discard().unwrap();
+        }
+        assert_eq!(std::fs::read_dir(&dir.0).unwrap().count(), 0);
+    }
+    
+    #[test]
+    fn failed_saves_wait_for_an_edit() {
+        let dir = TempDir::new("failed-save");
+        let missing = dir.0.join("missing");
+        let mut editor = editor("text", Autosave {
+            save_when_idle: Some(Duration::ZERO),
+            ..Autosave::default()
+        });
+        editor.path = Some(missing.join("file.txt"));
+        assert!(editor.poll_autosave().is_err());
+        std::fs::create_dir(&missing).unwrap();
+        assert!(!editor.poll_autosave().unwrap());
+        
+        editor.execute(&EditorCommand::InsertText(String::from("!")));
+        assert!(editor.poll_autosave().unwrap());
+        assert!(!editor.is_modified());
+        assert_eq!(std::fs::read_to_string(missing.join("file.txt")).unwrap(), "text!");
+    }
+}
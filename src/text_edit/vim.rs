@@ -0,0 +1,1805 @@
+use std::{collections::HashMap, ops::Range};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::Direction;
+
+use super::{
+    command::{default_binding, EditorCommand},
+    macros::Macros,
+    workspace::{workspace_binding, FocusDirection, WorkspaceCommand},
+    wrap::SoftWrap,
+    Selection,
+    SelectionKind,
+    TextEditor,
+    TextPosition,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VimMode {
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+}
+
+impl VimMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            VimMode::Normal => "NORMAL",
+            VimMode::Insert => "INSERT",
+            VimMode::Visual => "VISUAL",
+            VimMode::VisualLine => "VISUAL LINE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VimEvent {
+    Handled,
+    /// `:q` and friends, which close the window (and Vim with the last one).
+    /// The buffer has already been saved if that was asked for.
+    Quit,
+    /// `:qa`, which the owner of the other buffers should refuse unless `force` is set
+    /// (`:qa!`) or none of them have unsaved changes.
+    QuitAll {
+        force: bool,
+    },
+    /// `CTRL-W` window commands and the tab and buffer ex commands, for whoever owns the panes.
+    Workspace(WorkspaceCommand),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Register {
+    pub text: String,
+    /// Linewise registers are put on their own lines.
+    pub linewise: bool,
+}
+
+/// Vim's registers: `"` (unnamed), `0` (last yank), `1`-`9` (deletes, newest first),
+/// `-` (small deletes), `a`-`z` (appended to by `A`-`Z`) and `_` (discarded).
+#[derive(Debug, Clone, Default)]
+pub struct Registers {
+    registers: HashMap<char, Register>,
+}
+
+impl Registers {
+    pub fn is_valid_name(name: char) -> bool {
+        matches!(name, '"' | '-' | '_') || name.is_ascii_alphanumeric()
+    }
+    
+    pub fn get(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name.to_ascii_lowercase())
+    }
+    
+    /// Sets register `name`, or appends to its lowercase register if it's `A`-`Z`.
+    pub fn set(&mut self, name: char, register: Register) {
+        if !name.is_ascii_uppercase() {
+            self.registers.insert(name, register);
+            return;
+        }
+        let entry = self.registers.entry(name.to_ascii_lowercase()).or_default();
+        if register.linewise && !entry.text.is_empty() && !entry.text.ends_with('\n') {
+            entry.text.push('\n');
+        }
+        entry.text.push_str(&register.text);
+        entry.linewise |= register.linewise;
+    }
+    
+    fn store(&mut self, name: Option<char>, register: Register, is_yank: bool) {
+        match name {
+            Some('_') => return,
+            Some(upper @ 'A'..='Z') => {
+                self.set(upper, register);
+                let appended = self.registers[&upper.to_ascii_lowercase()].clone();
+                self.registers.insert('"', appended);
+                return;
+            }
+            Some(name) if name != '"' => {
+                self.registers.insert(name, register.clone());
+            }
+            _ if is_yank => {
+                self.registers.insert('0', register.clone());
+            }
+            _ if register.linewise || register.text.contains('\n') => {
+                for digit in (1..9).rev() {
+                    let from = char::from_digit(digit, 10).unwrap();
+                    if let Some(shifted) = self.registers.remove(&from) {
+                        self.registers.insert(char::from_digit(digit + 1, 10).unwrap(), shifted);
+                    }
+                }
+                self.registers.insert('1', register.clone());
+            }
+            _ => {
+                self.registers.insert('-', register.clone());
+            }
+        }
+        self.registers.insert('"', register);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+}
+
+impl Operator {
+    fn from_char(chr: char) -> Option<Self> {
+        Some(match chr {
+            'd' => Operator::Delete,
+            'c' => Operator::Change,
+            'y' => Operator::Yank,
+            '>' => Operator::Indent,
+            '<' => Operator::Outdent,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// `w`/`W`. The flag is set for WORDs, which are only separated by whitespace.
+    WordStart(bool),
+    WordEnd(bool),
+    WordBack(bool),
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    /// `f`, `t`, `F` and `T`.
+    Find(Find),
+    /// `;`, or `,` when reversed.
+    RepeatFind {
+        reverse: bool,
+    },
+    /// `gg` defaults to the first line, `G` to the last.
+    GotoLine {
+        last: bool,
+    },
+    MatchBracket,
+    ParagraphForward,
+    ParagraphBack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Find {
+    chr: char,
+    forward: bool,
+    /// Stop just before the char.
+    till: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TextObject {
+    Word(bool),
+    Quote(char),
+    Bracket(char, char),
+    Paragraph,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Target {
+    Motion(Motion),
+    TextObject {
+        object: TextObject,
+        around: bool,
+    },
+    /// `dd`, `cc`, `yy` and so on.
+    Lines,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum InsertAt {
+    Cursor,
+    After,
+    LineStart,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Action {
+    Motion(Motion),
+    Operator(Operator, Target),
+    Insert(InsertAt),
+    Put {
+        before: bool,
+    },
+    Replace(char),
+    Join,
+    ToggleCase,
+    Undo,
+    Redo,
+    Repeat,
+    Visual(VimMode),
+    /// `o` in visual mode.
+    SwapAnchor,
+    CommandLine,
+    SearchNext {
+        reverse: bool,
+    },
+    HalfPage {
+        down: bool,
+    },
+    /// `q{register}`. A bare `q` stops recording, and is handled before parsing.
+    RecordMacro(char),
+    /// `@{register}`, or `@@` for the last macro played.
+    PlayMacro(Option<char>),
+}
+
+impl Action {
+    /// Whether `.` should repeat this.
+    fn is_change(self) -> bool {
+        matches!(
+            self,
+            Action::Operator(Operator::Delete | Operator::Change | Operator::Indent | Operator::Outdent, _)
+                | Action::Insert(_)
+                | Action::Put { .. }
+                | Action::Replace(_)
+                | Action::Join
+                | Action::ToggleCase
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Command {
+    register: Option<char>,
+    count: Option<usize>,
+    action: Action,
+}
+
+enum Parse {
+    Incomplete,
+    Invalid,
+    Complete(Command),
+}
+
+/// Maps keys to the chars that commands are parsed from. Arrow keys and the like map to their
+/// Vim equivalents. Backspace and Enter stay distinct so that they can be the argument of `r`,
+/// `f` and `t`, and [`parse_motion`] treats them as `h` and `j`.
+fn key_char(key: KeyEvent) -> Option<char> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    Some(match key.code {
+        KeyCode::Char('r') if ctrl => '\u{12}',
+        KeyCode::Char('d') if ctrl => '\u{4}',
+        KeyCode::Char('u') if ctrl => '\u{15}',
+        KeyCode::Char(_) if ctrl => return None,
+        KeyCode::Char(chr) => chr,
+        KeyCode::Left => 'h',
+        KeyCode::Right => 'l',
+        KeyCode::Up => 'k',
+        KeyCode::Down => 'j',
+        KeyCode::Backspace => '\u{8}',
+        KeyCode::Enter => '\n',
+        KeyCode::Home => '0',
+        KeyCode::End => '$',
+        KeyCode::Delete => 'x',
+        _ => return None,
+    })
+}
+
+fn parse_count<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> Option<usize> {
+    let mut count: Option<usize> = None;
+    // A leading zero is the `0` motion, not a count.
+    while let Some(digit) = chars.next_if(|&chr| chr.is_ascii_digit() && (count.is_some() || chr != '0')) {
+        let digit = digit.to_digit(10).unwrap() as usize;
+        count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+    }
+    count
+}
+
+/// `Err(())` means more keys are needed.
+fn parse_motion<I: Iterator<Item = char>>(chr: char, chars: &mut I) -> Result<Option<Motion>, ()> {
+    let motion = match chr {
+        'h' | '\u{8}' => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'j' | '\n' => Motion::Down,
+        'k' => Motion::Up,
+        'w' | 'W' => Motion::WordStart(chr == 'W'),
+        'e' | 'E' => Motion::WordEnd(chr == 'E'),
+        'b' | 'B' => Motion::WordBack(chr == 'B'),
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        'f' | 't' | 'F' | 'T' => {
+            let target = chars.next().ok_or(())?;
+            Motion::Find(Find {
+                chr: target,
+                forward: chr.is_ascii_lowercase(),
+                till: chr.eq_ignore_ascii_case(&'t'),
+            })
+        }
+        ';' | ',' => Motion::RepeatFind {
+            reverse: chr == ',',
+        },
+        'G' => Motion::GotoLine {
+            last: true,
+        },
+        'g' => match chars.next().ok_or(())? {
+            'g' => Motion::GotoLine {
+                last: false,
+            },
+            _ => return Ok(None),
+        },
+        '%' => Motion::MatchBracket,
+        '}' => Motion::ParagraphForward,
+        '{' => Motion::ParagraphBack,
+        _ => return Ok(None),
+    };
+    Ok(Some(motion))
+}
+
+fn parse_text_object(chr: char) -> Option<TextObject> {
+    Some(match chr {
+        'w' => TextObject::Word(false),
+        'W' => TextObject::Word(true),
+        '"' | '\'' | '`' => TextObject::Quote(chr),
+        '(' | ')' | 'b' => TextObject::Bracket('(', ')'),
+        '{' | '}' | 'B' => TextObject::Bracket('{', '}'),
+        '[' | ']' => TextObject::Bracket('[', ']'),
+        '<' | '>' => TextObject::Bracket('<', '>'),
+        'p' => TextObject::Paragraph,
+        _ => return None,
+    })
+}
+
+fn parse_command(keys: &[char], mode: VimMode) -> Parse {
+    let visual = mode != VimMode::Normal;
+    let mut chars = keys.iter().copied().peekable();
+    let mut register = None;
+    if chars.next_if_eq(&'"').is_some() {
+        match chars.next() {
+            None => return Parse::Incomplete,
+            Some(name) if Registers::is_valid_name(name) => register = Some(name),
+            Some(_) => return Parse::Invalid,
+        }
+    }
+    let count = parse_count(&mut chars);
+    let Some(chr) = chars.next() else {
+        return Parse::Incomplete;
+    };
+    let line_end = Target::Motion(Motion::LineEnd);
+    let action = match chr {
+        'd' | 'c' | 'y' | '>' | '<' if visual => Action::Operator(Operator::from_char(chr).unwrap(), Target::Lines),
+        'd' | 'c' | 'y' | '>' | '<' => {
+            let operator = Operator::from_char(chr).unwrap();
+            let motion_count = parse_count(&mut chars);
+            let Some(next) = chars.next() else {
+                return Parse::Incomplete;
+            };
+            let target = if next == chr {
+                Target::Lines
+            } else if next == 'i' || next == 'a' {
+                let Some(object) = chars.next() else {
+                    return Parse::Incomplete;
+                };
+                match parse_text_object(object) {
+                    Some(object) => Target::TextObject {
+                        object,
+                        around: next == 'a',
+                    },
+                    None => return Parse::Invalid,
+                }
+            } else {
+                match parse_motion(next, &mut chars) {
+                    Ok(Some(motion)) => Target::Motion(motion),
+                    Ok(None) => return Parse::Invalid,
+                    Err(()) => return Parse::Incomplete,
+                }
+            };
+            let count = match (count, motion_count) {
+                (None, None) => None,
+                (count, motion_count) => Some(count.unwrap_or(1) * motion_count.unwrap_or(1)),
+            };
+            return Parse::Complete(Command {
+                register,
+                count,
+                action: Action::Operator(operator, target),
+            });
+        }
+        'x' if visual => Action::Operator(Operator::Delete, Target::Lines),
+        's' if visual => Action::Operator(Operator::Change, Target::Lines),
+        'x' => Action::Operator(Operator::Delete, Target::Motion(Motion::Right)),
+        'X' => Action::Operator(Operator::Delete, Target::Motion(Motion::Left)),
+        'D' => Action::Operator(Operator::Delete, line_end),
+        'C' => Action::Operator(Operator::Change, line_end),
+        's' => Action::Operator(Operator::Change, Target::Motion(Motion::Right)),
+        'S' => Action::Operator(Operator::Change, Target::Lines),
+        'Y' => Action::Operator(Operator::Yank, Target::Lines),
+        'o' | 'O' if visual => Action::SwapAnchor,
+        'i' if !visual => Action::Insert(InsertAt::Cursor),
+        'a' if !visual => Action::Insert(InsertAt::After),
+        'I' if !visual => Action::Insert(InsertAt::LineStart),
+        'A' if !visual => Action::Insert(InsertAt::LineEnd),
+        'o' => Action::Insert(InsertAt::LineBelow),
+        'O' => Action::Insert(InsertAt::LineAbove),
+        'p' | 'P' => Action::Put {
+            before: chr == 'P',
+        },
+        'r' => match chars.next() {
+            Some(replacement) => Action::Replace(replacement),
+            None => return Parse::Incomplete,
+        },
+        'J' => Action::Join,
+        '~' => Action::ToggleCase,
+        'u' => Action::Undo,
+        '\u{12}' => Action::Redo,
+        '.' => Action::Repeat,
+        'v' => Action::Visual(VimMode::Visual),
+        'V' => Action::Visual(VimMode::VisualLine),
+        ':' => Action::CommandLine,
+        'n' | 'N' => Action::SearchNext {
+            reverse: chr == 'N',
+        },
+        '\u{4}' | '\u{15}' => Action::HalfPage {
+            down: chr == '\u{4}',
+        },
+        'q' if !visual => match chars.next() {
+            Some(register) if Macros::is_valid_register(register) => Action::RecordMacro(register),
+            Some(_) => return Parse::Invalid,
+            None => return Parse::Incomplete,
+        },
+        '@' if !visual => match chars.next() {
+            Some('@') => Action::PlayMacro(None),
+            Some(register) if Macros::is_valid_register(register) => Action::PlayMacro(Some(register)),
+            Some(_) => return Parse::Invalid,
+            None => return Parse::Incomplete,
+        },
+        _ => match parse_motion(chr, &mut chars) {
+            Ok(Some(motion)) => Action::Motion(motion),
+            Ok(None) => return Parse::Invalid,
+            Err(()) => return Parse::Incomplete,
+        },
+    };
+    Parse::Complete(Command {
+        register,
+        count,
+        action,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+fn char_class(chr: char, big: bool) -> CharClass {
+    if chr.is_whitespace() {
+        CharClass::Blank
+    } else if big || chr == '_' || chr.is_alphanumeric() {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+fn is_line_break(chr: char) -> bool {
+    matches!(chr, '\n' | '\r')
+}
+
+struct Motions<'a> {
+    editor: &'a TextEditor,
+}
+
+impl Motions<'_> {
+    fn len(&self) -> usize {
+        self.editor.rope.len_chars()
+    }
+    
+    fn char(&self, char_idx: usize) -> char {
+        self.editor.rope.char(char_idx)
+    }
+    
+    fn class(&self, char_idx: usize, big: bool) -> CharClass {
+        char_class(self.char(char_idx), big)
+    }
+    
+    fn line_end(&self, line: usize) -> usize {
+        self.editor.rope.line_to_char(line) + self.editor.line_len(line)
+    }
+    
+    fn first_non_blank(&self, line: usize) -> usize {
+        let line_start = self.editor.rope.line_to_char(line);
+        let line_end = self.line_end(line);
+        (line_start..line_end).find(|&i| !self.char(i).is_whitespace()).unwrap_or(line_end)
+    }
+    
+    fn word_start(&self, from: usize, big: bool) -> usize {
+        let len = self.len();
+        let mut i = from;
+        if i >= len {
+            return len;
+        }
+        let class = self.class(i, big);
+        if class != CharClass::Blank {
+            while i < len && self.class(i, big) == class {
+                i += 1;
+            }
+        }
+        while i < len && self.char(i).is_whitespace() {
+            // An empty line counts as a word.
+            if self.char(i) == '\n' && i + 1 < len && is_line_break(self.char(i + 1)) {
+                return i + 1;
+            }
+            i += 1;
+        }
+        i
+    }
+    
+    fn word_end(&self, from: usize, big: bool) -> usize {
+        let len = self.len();
+        let mut i = from + 1;
+        while i < len && self.char(i).is_whitespace() {
+            i += 1;
+        }
+        if i >= len {
+            return len.saturating_sub(1);
+        }
+        let class = self.class(i, big);
+        while i + 1 < len && self.class(i + 1, big) == class {
+            i += 1;
+        }
+        i
+    }
+    
+    fn word_back(&self, from: usize, big: bool) -> usize {
+        if from == 0 {
+            return 0;
+        }
+        let mut i = from - 1;
+        while i > 0 && self.char(i).is_whitespace() {
+            if self.char(i) == '\n' && self.char(i - 1) == '\n' {
+                return i;
+            }
+            i -= 1;
+        }
+        let class = self.class(i, big);
+        while i > 0 && self.class(i - 1, big) == class {
+            i -= 1;
+        }
+        i
+    }
+    
+    /// `repeat` is set for `;` and `,`, which look past a match right next to the cursor.
+    fn find(&self, from: usize, find: Find, count: usize, repeat: bool) -> Option<usize> {
+        let position = self.editor.char_to_position(from);
+        let line_start = from - position.col;
+        let line_end = self.line_end(position.line);
+        let mut i = from;
+        for step in 0..count {
+            // Repeating `t` from just before a match would find the same match again.
+            let skip = usize::from(find.till && repeat && step == 0);
+            i = if find.forward {
+                (i + 1 + skip..line_end).find(|&i| self.char(i) == find.chr)?
+            } else {
+                (line_start..i.saturating_sub(skip)).rev().find(|&i| self.char(i) == find.chr)?
+            };
+        }
+        Some(match (find.till, find.forward) {
+            (true, true) => i - 1,
+            (true, false) => i + 1,
+            _ => i,
+        })
+    }
+    
+    fn match_bracket(&self, from: usize) -> Option<usize> {
+        const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+        let line_end = self.line_end(self.editor.char_to_position(from).line);
+        let start = (from..line_end).find(|&i| PAIRS.iter().any(|&(open, close)| self.char(i) == open || self.char(i) == close))?;
+        let chr = self.char(start);
+        let (open, close, forward) = PAIRS.iter()
+            .find_map(|&(open, close)| {
+                (chr == open).then_some((open, close, true))
+                    .or((chr == close).then_some((open, close, false)))
+            })?;
+        let mut depth = 0usize;
+        let mut step = |i: usize, opener: char, closer: char| {
+            let chr = self.char(i);
+            if chr == opener {
+                depth += 1;
+            } else if chr == closer {
+                depth -= 1;
+            }
+            depth == 0
+        };
+        if forward {
+            (start..self.len()).find(|&i| step(i, open, close))
+        } else {
+            // Walking backwards, closers open and openers close.
+            (0..=start).rev().find(|&i| step(i, close, open))
+        }
+    }
+    
+    fn is_blank_line(&self, line: usize) -> bool {
+        self.editor.rope.line(line).chars().all(char::is_whitespace)
+    }
+    
+    fn paragraph(&self, line: usize, forward: bool, count: usize) -> usize {
+        let last = self.editor.rope.len_lines() - 1;
+        let mut line = line;
+        for _ in 0..count {
+            if forward {
+                while line < last && self.is_blank_line(line) {
+                    line += 1;
+                }
+                while line < last && !self.is_blank_line(line) {
+                    line += 1;
+                }
+            } else {
+                while line > 0 && self.is_blank_line(line) {
+                    line -= 1;
+                }
+                while line > 0 && !self.is_blank_line(line) {
+                    line -= 1;
+                }
+            }
+        }
+        if forward && line == last && !self.is_blank_line(line) {
+            return self.len();
+        }
+        self.editor.rope.line_to_char(line)
+    }
+    
+    fn quote_object(&self, quote: char, around: bool) -> Option<Range<usize>> {
+        let cursor = self.editor.cursor;
+        let line_start = self.editor.rope.line_to_char(cursor.line);
+        let line = self.editor.rope.line(cursor.line);
+        let mut quotes = Vec::new();
+        let mut escaped = false;
+        for (col, chr) in line.chars().enumerate() {
+            if chr == quote && !escaped {
+                quotes.push(col);
+            }
+            escaped = chr == '\\' && !escaped;
+        }
+        // The first pair that ends at or after the cursor.
+        let pair = quotes.chunks_exact(2).find(|pair| pair[1] >= cursor.col)?;
+        if !around {
+            return Some(line_start + pair[0] + 1..line_start + pair[1]);
+        }
+        // Like `aw`, trailing whitespace is included, or leading whitespace if there is none.
+        let line_len = self.editor.line_len(cursor.line);
+        let trailing = (pair[1] + 1..line_len).take_while(|&col| line.char(col).is_whitespace()).count();
+        let leading = if trailing == 0 {
+            (0..pair[0]).rev().take_while(|&col| line.char(col).is_whitespace()).count()
+        } else {
+            0
+        };
+        Some(line_start + pair[0] - leading..line_start + pair[1] + 1 + trailing)
+    }
+    
+    fn bracket_object(&self, open: char, close: char, around: bool, count: usize) -> Option<Range<usize>> {
+        let cursor = self.editor.cursor_char_index();
+        let start;
+        let mut depth = 0;
+        // Find the `count`th enclosing opener, counting one under the cursor.
+        let mut found = 0;
+        let mut i = (cursor + 1).min(self.len());
+        loop {
+            if i == 0 {
+                return None;
+            }
+            i -= 1;
+            let chr = self.char(i);
+            if chr == close && i != cursor {
+                depth += 1;
+            } else if chr == open {
+                if depth == 0 {
+                    found += 1;
+                    if found == count {
+                        start = i;
+                        break;
+                    }
+                } else {
+                    depth -= 1;
+                }
+            }
+        }
+        let mut depth = 0;
+        let end = (start + 1..self.len()).find(|&i| {
+            let chr = self.char(i);
+            if chr == open {
+                depth += 1;
+            } else if chr == close {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            false
+        })?;
+        if around {
+            return Some(start..end + 1);
+        }
+        let mut inner = start + 1..end;
+        // Multi-line blocks keep their braces on their own lines.
+        if self.char(inner.start) == '\n' {
+            inner.start += 1;
+            let close_line = self.editor.rope.char_to_line(end);
+            let close_line_start = self.editor.rope.line_to_char(close_line);
+            if (close_line_start..end).all(|i| self.char(i).is_whitespace()) {
+                inner.end = close_line_start.max(inner.start);
+            }
+        }
+        Some(inner)
+    }
+    
+    fn word_object(&self, big: bool, around: bool, count: usize) -> Option<Range<usize>> {
+        let position = self.editor.cursor;
+        let line_start = self.editor.rope.line_to_char(position.line);
+        let line_end = self.line_end(position.line);
+        if line_start == line_end {
+            return None;
+        }
+        let run = |from: usize| {
+            let class = self.class(from, big);
+            let mut end = from;
+            while end < line_end && self.class(end, big) == class {
+                end += 1;
+            }
+            end
+        };
+        let cursor = self.editor.cursor_char_index().min(line_end - 1);
+        let class = self.class(cursor, big);
+        let mut start = cursor;
+        while start > line_start && self.class(start - 1, big) == class {
+            start -= 1;
+        }
+        let mut end = start;
+        for _ in 0..count {
+            if end >= line_end {
+                break;
+            }
+            let run_class = self.class(end, big);
+            end = run(end);
+            // `aw` takes the whitespace after a word, or the word after whitespace.
+            if around && end < line_end && (run_class == CharClass::Blank) != (self.class(end, big) == CharClass::Blank) {
+                end = run(end);
+            }
+        }
+        if around && class != CharClass::Blank && self.class(end - 1, big) != CharClass::Blank {
+            // No trailing whitespace was taken, so take the leading whitespace instead.
+            while start > line_start && self.class(start - 1, big) == CharClass::Blank {
+                start -= 1;
+            }
+        }
+        Some(start..end)
+    }
+    
+    fn paragraph_object(&self, around: bool) -> (usize, usize) {
+        let last = self.editor.rope.len_lines() - 1;
+        let line = self.editor.cursor.line;
+        let blank = self.is_blank_line(line);
+        let mut first = line;
+        while first > 0 && self.is_blank_line(first - 1) == blank {
+            first -= 1;
+        }
+        let mut end = line;
+        while end < last && self.is_blank_line(end + 1) == blank {
+            end += 1;
+        }
+        if around {
+            while end < last && self.is_blank_line(end + 1) != blank {
+                end += 1;
+            }
+        }
+        (first, end)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Region {
+    Chars(usize, usize),
+    Lines(usize, usize),
+}
+
+/// A modal input layer that drives a [`TextEditor`] through [`EditorCommand`]s.
+pub struct Vim {
+    mode: VimMode,
+    pub registers: Registers,
+    /// Keys of the normal/visual mode command being typed.
+    pending: Vec<char>,
+    pending_keys: Vec<KeyEvent>,
+    last_find: Option<Find>,
+    /// Keys of the change being recorded for `.`, which continues through insert mode.
+    recording: Option<Vec<KeyEvent>>,
+    last_change: Vec<KeyEvent>,
+    replaying: bool,
+    /// Set while an insert session holds an undo group open.
+    insert_group: bool,
+    /// Set after `CTRL-W` while waiting for the window command.
+    window_prefix: bool,
+    command_line: Option<String>,
+    message: Option<String>,
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Self {
+            mode: VimMode::Normal,
+            registers: Registers::default(),
+            pending: Vec::new(),
+            pending_keys: Vec::new(),
+            last_find: None,
+            recording: None,
+            last_change: Vec::new(),
+            replaying: false,
+            insert_group: false,
+            window_prefix: false,
+            command_line: None,
+            message: None,
+        }
+    }
+    
+    pub fn mode(&self) -> VimMode {
+        self.mode
+    }
+    
+    /// The `:` command being typed, if any.
+    pub fn command_line(&self) -> Option<&str> {
+        self.command_line.as_deref()
+    }
+    
+    /// The result of the last ex command, or an error.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+    
+    /// Shows `message` until the next key, as for errors from commands that Vim hands on.
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+    
+    /// The partially typed normal mode command, as shown by Vim's `showcmd`.
+    pub fn pending(&self) -> String {
+        if self.window_prefix {
+            return String::from("^W");
+        }
+        self.pending.iter().collect()
+    }
+    
+    /// Drops any half-typed command and returns to normal mode, as when the editor loses focus.
+    pub fn reset(&mut self, editor: &mut TextEditor) {
+        self.pending.clear();
+        self.pending_keys.clear();
+        self.window_prefix = false;
+        self.command_line = None;
+        match self.mode {
+            VimMode::Insert => self.leave_insert(editor),
+            VimMode::Visual | VimMode::VisualLine => self.set_mode(VimMode::Normal, editor),
+            VimMode::Normal => (),
+        }
+    }
+    
+    pub fn handle_key(&mut self, key: KeyEvent, editor: &mut TextEditor) -> VimEvent {
+        if self.command_line.is_some() {
+            return self.handle_command_line_key(key, editor);
+        }
+        match self.mode {
+            VimMode::Insert => {
+                // Control keys are left for insert mode's own uses, like Vim's.
+                let is_char = matches!(key.code, KeyCode::Char(_));
+                if !is_char && default_binding(key).is_none() && let Some(command) = workspace_binding(key) {
+                    return VimEvent::Workspace(command);
+                }
+                self.handle_insert_key(key, editor);
+                VimEvent::Handled
+            }
+            VimMode::Normal | VimMode::Visual | VimMode::VisualLine => {
+                if let Some(event) = self.handle_window_key(key) {
+                    return event;
+                }
+                self.handle_normal_key(key, editor);
+                VimEvent::Handled
+            }
+        }
+    }
+    
+    /// `CTRL-W` commands, and workspace keys that Vim doesn't use.
+    fn handle_window_key(&mut self, key: KeyEvent) -> Option<VimEvent> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if !std::mem::take(&mut self.window_prefix) {
+            if key.code == KeyCode::Char('w') && ctrl && self.pending.is_empty() {
+                self.window_prefix = true;
+                return Some(VimEvent::Handled);
+            }
+            if key_char(key).is_none() && let Some(command) = workspace_binding(key) {
+                return Some(VimEvent::Workspace(command));
+            }
+            return None;
+        }
+        let command = match key.code {
+            KeyCode::Char('s' | 'S') => WorkspaceCommand::Split(Direction::Vertical),
+            KeyCode::Char('v') => WorkspaceCommand::Split(Direction::Horizontal),
+            KeyCode::Char('w') => WorkspaceCommand::FocusNext,
+            KeyCode::Char('W') => WorkspaceCommand::FocusPrev,
+            KeyCode::Char('c' | 'q') => WorkspaceCommand::ClosePane,
+            KeyCode::Char('o') => WorkspaceCommand::CloseOtherPanes,
+            KeyCode::Char('h') | KeyCode::Left => WorkspaceCommand::Focus(FocusDirection::Left),
+            KeyCode::Char('l') | KeyCode::Right => WorkspaceCommand::Focus(FocusDirection::Right),
+            KeyCode::Char('k') | KeyCode::Up => WorkspaceCommand::Focus(FocusDirection::Up),
+            KeyCode::Char('j') | KeyCode::Down => WorkspaceCommand::Focus(FocusDirection::Down),
+            _ => return Some(VimEvent::Handled),
+        };
+        Some(VimEvent::Workspace(command))
+    }
+    
+    fn handle_insert_key(&mut self, key: KeyEvent, editor: &mut TextEditor) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(key);
+        }
+        if key.code == KeyCode::Esc {
+            self.leave_insert(editor);
+            return;
+        }
+        if let Some(command) = default_binding(key) {
+            editor.execute(&command);
+        }
+    }
+    
+    fn leave_insert(&mut self, editor: &mut TextEditor) {
+        if std::mem::take(&mut self.insert_group) {
+            editor.execute(&EditorCommand::EndUndoGroup);
+        }
+        if let Some(recording) = self.recording.take() {
+            self.last_change = recording;
+        }
+        self.mode = VimMode::Normal;
+        if editor.cursor.col != 0 {
+            editor.execute(&EditorCommand::MoveLeft);
+        }
+    }
+    
+    fn enter_insert(&mut self, editor: &mut TextEditor) {
+        if !self.insert_group {
+            editor.execute(&EditorCommand::BeginUndoGroup);
+            self.insert_group = true;
+        }
+        self.mode = VimMode::Insert;
+    }
+    
+    fn handle_normal_key(&mut self, key: KeyEvent, editor: &mut TextEditor) {
+        self.message = None;
+        if key.code == KeyCode::Esc {
+            self.pending.clear();
+            self.pending_keys.clear();
+            if self.mode != VimMode::Normal {
+                self.set_mode(VimMode::Normal, editor);
+            }
+            return;
+        }
+        let Some(chr) = key_char(key) else {
+            return;
+        };
+        if chr == 'q' && self.pending.is_empty() && let Some(register) = editor.stop_recording() {
+            self.message = Some(format!("Recorded @{register}"));
+            return;
+        }
+        self.pending.push(chr);
+        self.pending_keys.push(key);
+        let command = match parse_command(&self.pending, self.mode) {
+            Parse::Incomplete => return,
+            Parse::Invalid => {
+                self.pending.clear();
+                self.pending_keys.clear();
+                return;
+            }
+            Parse::Complete(command) => command,
+        };
+        self.pending.clear();
+        let keys = std::mem::take(&mut self.pending_keys);
+        let was_normal = self.mode == VimMode::Normal;
+        let is_change = command.action.is_change();
+        let changes = is_change && !matches!(command.action, Action::Insert(_));
+        if changes {
+            editor.execute(&EditorCommand::BeginUndoGroup);
+        }
+        self.run(command, editor);
+        if changes {
+            editor.execute(&EditorCommand::EndUndoGroup);
+        }
+        if is_change && was_normal && !self.replaying {
+            if self.mode == VimMode::Insert {
+                self.recording = Some(keys);
+            } else {
+                self.last_change = keys;
+            }
+        }
+        if self.mode == VimMode::Normal {
+            self.clamp_cursor(editor);
+        }
+    }
+    
+    fn set_mode(&mut self, mode: VimMode, editor: &mut TextEditor) {
+        let kind = match mode {
+            VimMode::Visual => Some(SelectionKind::Inclusive),
+            VimMode::VisualLine => Some(SelectionKind::Line),
+            VimMode::Normal | VimMode::Insert => None,
+        };
+        match (kind, editor.selection) {
+            // Switching between visual modes keeps the anchor.
+            (Some(kind), Some(selection)) => editor.execute(&EditorCommand::SetSelection(Selection {
+                anchor: selection.anchor,
+                kind,
+            })),
+            (Some(kind), None) => editor.execute(&EditorCommand::StartSelection(kind)),
+            (None, _) => editor.execute(&EditorCommand::ClearSelection),
+        }
+        self.mode = mode;
+        if mode == VimMode::Normal {
+            self.clamp_cursor(editor);
+        }
+    }
+    
+    /// Normal mode keeps the cursor on a char rather than after the end of the line.
+    fn clamp_cursor(&self, editor: &mut TextEditor) {
+        let line_len = editor.line_len(editor.cursor.line);
+        if line_len != 0 && editor.cursor.col >= line_len {
+            editor.execute(&EditorCommand::MoveTo(TextPosition::new(editor.cursor.line, line_len - 1)));
+        }
+    }
+    
+    fn run(&mut self, command: Command, editor: &mut TextEditor) {
+        let count = command.count.unwrap_or(1);
+        match command.action {
+            Action::Motion(motion) => self.move_cursor(motion, command.count, editor),
+            Action::Operator(operator, target) => {
+                let region = match self.mode {
+                    VimMode::Visual | VimMode::VisualLine => self.visual_region(editor),
+                    _ => self.target_region(operator, target, command.count, editor),
+                };
+                if let Some(region) = region {
+                    self.apply_operator(operator, region, command.register, editor);
+                }
+            }
+            Action::Insert(at) => self.insert(at, editor),
+            Action::Put { before } => self.put(before, count, command.register, editor),
+            Action::Replace(chr) => self.replace(chr, count, editor),
+            Action::Join => self.join(count, editor),
+            Action::ToggleCase => self.toggle_case(count, editor),
+            Action::Undo => {
+                for _ in 0..count {
+                    editor.execute(&EditorCommand::Undo);
+                }
+            }
+            Action::Redo => {
+                for _ in 0..count {
+                    editor.execute(&EditorCommand::Redo);
+                }
+            }
+            Action::Repeat => self.repeat(command.count, editor),
+            Action::Visual(mode) if mode == self.mode => self.set_mode(VimMode::Normal, editor),
+            Action::Visual(mode) => self.set_mode(mode, editor),
+            Action::SwapAnchor => {
+                if let Some(selection) = editor.selection {
+                    editor.execute(&EditorCommand::SetSelection(Selection {
+                        anchor: editor.cursor,
+                        kind: selection.kind,
+                    }));
+                    editor.execute(&EditorCommand::MoveTo(selection.anchor));
+                }
+            }
+            Action::CommandLine => self.command_line = Some(String::new()),
+            Action::SearchNext { reverse } => {
+                let Some(search) = &editor.search else {
+                    self.message = Some(String::from("E35: No previous regular expression"));
+                    return;
+                };
+                let from = editor.cursor_char_index();
+                let mut found = None;
+                let mut at = from;
+                for _ in 0..count {
+                    found = if reverse {
+                        search.find_prev(&editor.rope, at, true)
+                    } else {
+                        search.find_next(&editor.rope, at + 1, true)
+                    };
+                    let Some(range) = &found else {
+                        break;
+                    };
+                    at = range.start;
+                }
+                match found {
+                    Some(range) => {
+                        editor.current_match = Some(range.clone());
+                        editor.execute(&EditorCommand::MoveTo(editor.char_to_position(range.start)));
+                    }
+                    None => self.message = Some(format!("E486: Pattern not found: {}", search.pattern())),
+                }
+            }
+            Action::HalfPage { down } => {
+                let lines = (editor.text_area.height as usize / 2).max(1) * count;
+                let command = if down { EditorCommand::MoveDown } else { EditorCommand::MoveUp };
+                for _ in 0..lines {
+                    editor.execute(&command);
+                }
+            }
+            Action::RecordMacro(register) => {
+                editor.start_recording(register);
+                self.message = Some(format!("recording @{register}"));
+            }
+            Action::PlayMacro(register) => {
+                let played = match register {
+                    Some(register) => editor.play_macro(register, count),
+                    None => editor.play_last_macro(count),
+                };
+                if !played {
+                    self.message = Some(match register {
+                        Some(register) => format!("E748: Register {register} has no macro"),
+                        None => String::from("E748: No previously used register"),
+                    });
+                }
+            }
+        }
+    }
+    
+    /// The char index a motion lands on, and how an operator treats it.
+    fn motion_target(&mut self, motion: Motion, count: Option<usize>, editor: &TextEditor) -> Option<(usize, MotionKind)> {
+        let motions = Motions {
+            editor,
+        };
+        let n = count.unwrap_or(1);
+        let cursor = editor.cursor;
+        let from = editor.cursor_char_index();
+        let line_start = from - cursor.col;
+        let last_line = editor.rope.len_lines() - 1;
+        let target = match motion {
+            Motion::Left => (line_start + cursor.col.saturating_sub(n), MotionKind::Exclusive),
+            Motion::Right => ((from + n).min(motions.line_end(cursor.line)), MotionKind::Exclusive),
+            Motion::Up => {
+                let line = cursor.line.checked_sub(n)?;
+                (editor.rope.line_to_char(line), MotionKind::Linewise)
+            }
+            Motion::Down => {
+                let line = cursor.line + n;
+                if line > last_line {
+                    return None;
+                }
+                (editor.rope.line_to_char(line), MotionKind::Linewise)
+            }
+            Motion::WordStart(big) => ((0..n).fold(from, |at, _| motions.word_start(at, big)), MotionKind::Exclusive),
+            Motion::WordEnd(big) => ((0..n).fold(from, |at, _| motions.word_end(at, big)), MotionKind::Inclusive),
+            Motion::WordBack(big) => ((0..n).fold(from, |at, _| motions.word_back(at, big)), MotionKind::Exclusive),
+            Motion::LineStart => (line_start, MotionKind::Exclusive),
+            Motion::FirstNonBlank => (motions.first_non_blank(cursor.line), MotionKind::Exclusive),
+            Motion::LineEnd => {
+                let line = (cursor.line + n - 1).min(last_line);
+                let line_end = motions.line_end(line);
+                let last_char = line_end.saturating_sub(1).max(editor.rope.line_to_char(line));
+                (last_char, MotionKind::Inclusive)
+            }
+            Motion::Find(find) => {
+                self.last_find = Some(find);
+                let kind = if find.forward { MotionKind::Inclusive } else { MotionKind::Exclusive };
+                (motions.find(from, find, n, false)?, kind)
+            }
+            Motion::RepeatFind { reverse } => {
+                let mut find = self.last_find?;
+                find.forward ^= reverse;
+                let kind = if find.forward { MotionKind::Inclusive } else { MotionKind::Exclusive };
+                (motions.find(from, find, n, true)?, kind)
+            }
+            Motion::GotoLine { last } => {
+                let line = match count {
+                    Some(line) => line.saturating_sub(1).min(last_line),
+                    None if last => last_line,
+                    None => 0,
+                };
+                (motions.first_non_blank(line), MotionKind::Linewise)
+            }
+            Motion::MatchBracket => (motions.match_bracket(from)?, MotionKind::Inclusive),
+            Motion::ParagraphForward => (motions.paragraph(cursor.line, true, n), MotionKind::Exclusive),
+            Motion::ParagraphBack => (motions.paragraph(cursor.line, false, n), MotionKind::Exclusive),
+        };
+        Some(target)
+    }
+    
+    fn move_cursor(&mut self, motion: Motion, count: Option<usize>, editor: &mut TextEditor) {
+        // Vertical motions go through the editor so that they respect folds and keep the preferred column.
+        let vertical = match motion {
+            Motion::Up => Some(EditorCommand::MoveUp),
+            Motion::Down => Some(EditorCommand::MoveDown),
+            _ => None,
+        };
+        if let Some(command) = vertical {
+            for _ in 0..count.unwrap_or(1) {
+                editor.execute(&command);
+            }
+            return;
+        }
+        if let Some((target, _)) = self.motion_target(motion, count, editor) {
+            let mut position = editor.char_to_position(target);
+            if motion == Motion::LineEnd && self.mode == VimMode::Normal {
+                position.col = editor.line_len(position.line).saturating_sub(1);
+            }
+            editor.execute(&EditorCommand::MoveTo(position));
+        }
+    }
+    
+    fn target_region(&mut self, operator: Operator, target: Target, count: Option<usize>, editor: &TextEditor) -> Option<Region> {
+        let n = count.unwrap_or(1);
+        let from = editor.cursor_char_index();
+        let cursor = editor.cursor;
+        let motions = Motions {
+            editor,
+        };
+        let last_line = editor.rope.len_lines() - 1;
+        match target {
+            Target::Lines => Some(Region::Lines(cursor.line, (cursor.line + n - 1).min(last_line))),
+            Target::TextObject { object, around } => match object {
+                TextObject::Word(big) => motions.word_object(big, around, n).map(|range| Region::Chars(range.start, range.end)),
+                TextObject::Quote(quote) => motions.quote_object(quote, around).map(|range| Region::Chars(range.start, range.end)),
+                TextObject::Bracket(open, close) => motions.bracket_object(open, close, around, n).map(|range| Region::Chars(range.start, range.end)),
+                TextObject::Paragraph => {
+                    let (first, last) = motions.paragraph_object(around);
+                    Some(Region::Lines(first, last))
+                }
+            },
+            Target::Motion(motion) => {
+                let on_word = from < motions.len() && !motions.char(from).is_whitespace();
+                match motion {
+                    // `cw` on a word changes to the end of the word, like `ce` except that it
+                    // stays on the current word when the cursor is on its last char.
+                    Motion::WordStart(big) if operator == Operator::Change && on_word => {
+                        let at_word_end = from + 1 >= motions.len() || motions.class(from + 1, big) != motions.class(from, big);
+                        let first_end = if at_word_end { from } else { motions.word_end(from, big) };
+                        let end = (1..n).fold(first_end, |at, _| motions.word_end(at, big));
+                        return Some(Region::Chars(from, end + 1));
+                    }
+                    Motion::WordStart(big) => {
+                        // The last word never extends past the end of its line.
+                        let before_last = (1..n).fold(from, |at, _| motions.word_start(at, big));
+                        let mut end = motions.word_start(before_last, big);
+                        let line = editor.rope.char_to_line(before_last);
+                        let line_end = motions.line_end(line);
+                        if editor.rope.char_to_line(end) > line && line_end > before_last {
+                            end = line_end;
+                        }
+                        return Some(Region::Chars(from, end));
+                    }
+                    _ => (),
+                }
+                let (target, kind) = self.motion_target(motion, count, editor)?;
+                match kind {
+                    MotionKind::Linewise => {
+                        let line = editor.rope.char_to_line(target);
+                        Some(Region::Lines(cursor.line.min(line), cursor.line.max(line)))
+                    }
+                    MotionKind::Exclusive => Some(Region::Chars(from.min(target), from.max(target))),
+                    MotionKind::Inclusive => {
+                        let (start, end) = (from.min(target), from.max(target));
+                        let end = if end < motions.len() && !is_line_break(motions.char(end)) { end + 1 } else { end };
+                        Some(Region::Chars(start, end))
+                    }
+                }
+            }
+        }
+    }
+    
+    fn visual_region(&self, editor: &TextEditor) -> Option<Region> {
+        let selection = editor.selection?;
+        if self.mode == VimMode::VisualLine {
+            let first = selection.anchor.line.min(editor.cursor.line);
+            let last = selection.anchor.line.max(editor.cursor.line);
+            return Some(Region::Lines(first, last));
+        }
+        let range = editor.selection_range()?;
+        Some(Region::Chars(range.start, range.end))
+    }
+    
+    /// The chars covered by whole lines. If the last of them has no line break, the one before
+    /// the first is taken instead, so that deleting them doesn't leave an empty line behind.
+    fn line_range(editor: &TextEditor, first: usize, last: usize) -> Range<usize> {
+        let start = editor.rope.line_to_char(first);
+        let end = editor.rope.line_to_char(last + 1);
+        if last + 1 >= editor.rope.len_lines() && first != 0 && end == editor.rope.len_chars() {
+            // No line break after the last line, so remove the one before the first.
+            let break_len = if start >= 2 && editor.rope.char(start - 2) == '\r' { 2 } else { 1 };
+            return start - break_len..end;
+        }
+        start..end
+    }
+    
+    fn line_text(editor: &TextEditor, first: usize, last: usize) -> String {
+        let start = editor.rope.line_to_char(first);
+        let end = editor.rope.line_to_char(last + 1);
+        let mut text = editor.rope.slice(start..end).to_string();
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+    
+    fn apply_operator(&mut self, operator: Operator, region: Region, register: Option<char>, editor: &mut TextEditor) {
+        let was_visual = self.mode != VimMode::Normal;
+        if was_visual {
+            self.set_mode(VimMode::Normal, editor);
+        }
+        let (text, linewise) = match region {
+            Region::Chars(start, end) => (editor.rope.slice(start..end).to_string(), false),
+            Region::Lines(first, last) => (Self::line_text(editor, first, last), true),
+        };
+        match operator {
+            Operator::Yank => {
+                self.registers.store(register, Register { text, linewise }, true);
+                let start = match region {
+                    Region::Chars(start, _) => editor.char_to_position(start),
+                    Region::Lines(first, _) if was_visual => TextPosition::new(first, 0),
+                    Region::Lines(..) => editor.cursor,
+                };
+                editor.execute(&EditorCommand::MoveTo(start));
+            }
+            Operator::Delete => {
+                self.registers.store(register, Register { text, linewise }, false);
+                match region {
+                    Region::Chars(start, end) => {
+                        editor.execute(&EditorCommand::DeleteRange(start..end));
+                        editor.execute(&EditorCommand::MoveTo(editor.char_to_position(start)));
+                    }
+                    Region::Lines(first, last) => {
+                        editor.execute(&EditorCommand::DeleteRange(Self::line_range(editor, first, last)));
+                        let line = first.min(editor.rope.len_lines() - 1);
+                        let col = Motions { editor }.first_non_blank(line) - editor.rope.line_to_char(line);
+                        editor.execute(&EditorCommand::MoveTo(TextPosition::new(line, col)));
+                    }
+                }
+            }
+            Operator::Change => {
+                self.registers.store(register, Register { text, linewise }, false);
+                self.enter_insert(editor);
+                let (start, end) = match region {
+                    Region::Chars(start, end) => (start, end),
+                    Region::Lines(first, last) => {
+                        // Keep the first line's indent and one line to type on.
+                        let motions = Motions { editor };
+                        (motions.first_non_blank(first), motions.line_end(last))
+                    }
+                };
+                editor.execute(&EditorCommand::DeleteRange(start..end));
+                editor.execute(&EditorCommand::MoveTo(editor.char_to_position(start)));
+            }
+            Operator::Indent | Operator::Outdent => {
+                let (first, last) = match region {
+                    Region::Chars(start, end) => (editor.rope.char_to_line(start), editor.rope.char_to_line(end.max(start + 1) - 1)),
+                    Region::Lines(first, last) => (first, last),
+                };
+                for line in first..=last {
+                    let line_start = editor.rope.line_to_char(line);
+                    if operator == Operator::Indent {
+                        if editor.line_len(line) != 0 {
+                            editor.execute(&EditorCommand::InsertAt {
+                                char_idx: line_start,
+                                text: " ".repeat(TextEditor::TAB_WIDTH),
+                            });
+                        }
+                    } else {
+                        let spaces = editor.rope.line(line).chars().take(TextEditor::TAB_WIDTH).take_while(|&c| c == ' ').count();
+                        if spaces != 0 {
+                            editor.execute(&EditorCommand::DeleteRange(line_start..line_start + spaces));
+                        }
+                    }
+                }
+                let col = Motions { editor }.first_non_blank(first) - editor.rope.line_to_char(first);
+                editor.execute(&EditorCommand::MoveTo(TextPosition::new(first, col)));
+            }
+        }
+    }
+    
+    fn insert(&mut self, at: InsertAt, editor: &mut TextEditor) {
+        self.enter_insert(editor);
+        let line = editor.cursor.line;
+        let indent: String = editor.rope.line(line).chars().take_while(|&c| c == ' ' || c == '\t').collect();
+        match at {
+            InsertAt::Cursor => (),
+            InsertAt::After => {
+                if editor.line_len(line) != 0 {
+                    editor.execute(&EditorCommand::MoveTo(TextPosition::new(line, editor.cursor.col + 1)));
+                }
+            }
+            InsertAt::LineStart => {
+                editor.execute(&EditorCommand::MoveTo(TextPosition::new(line, indent.chars().count())));
+            }
+            InsertAt::LineEnd => editor.execute(&EditorCommand::MoveLineEnd),
+            InsertAt::LineBelow => {
+                editor.execute(&EditorCommand::MoveLineEnd);
+                editor.execute(&EditorCommand::InsertText(format!("\n{indent}")));
+            }
+            InsertAt::LineAbove => {
+                editor.execute(&EditorCommand::MoveTo(TextPosition::new(line, 0)));
+                editor.execute(&EditorCommand::InsertText(format!("{indent}\n")));
+                editor.execute(&EditorCommand::MoveTo(TextPosition::new(line, indent.chars().count())));
+            }
+        }
+    }
+    
+    fn put(&mut self, before: bool, count: usize, register: Option<char>, editor: &mut TextEditor) {
+        let Some(put) = self.registers.get(register.unwrap_or('"')).cloned() else {
+            self.message = Some(String::from("E353: Nothing in register"));
+            return;
+        };
+        if self.mode != VimMode::Normal {
+            // Putting over a selection replaces it, and the replaced text goes to the unnamed register.
+            let Some(region) = self.visual_region(editor) else {
+                return;
+            };
+            self.apply_operator(Operator::Delete, region, None, editor);
+            let before = match region {
+                Region::Chars(..) => !put.linewise,
+                // Deleting the last lines leaves the cursor above where they were.
+                Region::Lines(first, _) => first < editor.rope.len_lines(),
+            };
+            self.put_register(&put, before, count, editor);
+            return;
+        }
+        self.put_register(&put, before, count, editor);
+    }
+    
+    fn put_register(&mut self, put: &Register, before: bool, count: usize, editor: &mut TextEditor) {
+        let text = put.text.repeat(count);
+        if put.linewise {
+            let line = if before { editor.cursor.line } else { editor.cursor.line + 1 };
+            let (char_idx, text) = if line >= editor.rope.len_lines() {
+                (editor.rope.len_chars(), format!("\n{}", text.strip_suffix('\n').unwrap_or(&text)))
+            } else {
+                (editor.rope.line_to_char(line), text)
+            };
+            editor.execute(&EditorCommand::InsertAt { char_idx, text });
+            let col = Motions { editor }.first_non_blank(line) - editor.rope.line_to_char(line);
+            editor.execute(&EditorCommand::MoveTo(TextPosition::new(line, col)));
+        } else {
+            let offset = usize::from(!before && editor.line_len(editor.cursor.line) != 0);
+            let char_idx = editor.cursor_char_index() + offset;
+            let len = text.chars().count();
+            editor.execute(&EditorCommand::InsertAt { char_idx, text });
+            editor.execute(&EditorCommand::MoveTo(editor.char_to_position(char_idx + len.saturating_sub(1))));
+        }
+    }
+    
+    fn replace(&mut self, chr: char, count: usize, editor: &mut TextEditor) {
+        let from = editor.cursor_char_index();
+        if editor.cursor.col + count > editor.line_len(editor.cursor.line) {
+            return;
+        }
+        let cursor = editor.cursor;
+        editor.execute(&EditorCommand::DeleteRange(from..from + count));
+        if chr == '\r' || chr == '\n' {
+            editor.execute(&EditorCommand::InsertAt { char_idx: from, text: String::from("\n") });
+            editor.execute(&EditorCommand::MoveTo(TextPosition::new(cursor.line + 1, 0)));
+            return;
+        }
+        editor.execute(&EditorCommand::InsertAt { char_idx: from, text: std::iter::repeat_n(chr, count).collect() });
+        editor.execute(&EditorCommand::MoveTo(TextPosition::new(cursor.line, cursor.col + count - 1)));
+    }
+    
+    fn join(&mut self, count: usize, editor: &mut TextEditor) {
+        let (first, joins) = match self.visual_region(editor) {
+            Some(Region::Lines(first, last)) if self.mode != VimMode::Normal => (first, (last - first).max(1)),
+            Some(Region::Chars(start, end)) if self.mode != VimMode::Normal => {
+                let first = editor.rope.char_to_line(start);
+                (first, (editor.rope.char_to_line(end.max(start + 1) - 1) - first).max(1))
+            }
+            _ => (editor.cursor.line, count.saturating_sub(1).max(1)),
+        };
+        if self.mode != VimMode::Normal {
+            self.set_mode(VimMode::Normal, editor);
+        }
+        let mut join_col = None;
+        for _ in 0..joins {
+            if first + 1 >= editor.rope.len_lines() {
+                break;
+            }
+            let motions = Motions { editor };
+            let line_end = motions.line_end(first);
+            let next_content = motions.first_non_blank(first + 1);
+            let next_is_empty = next_content == motions.line_end(first + 1);
+            let ends_with_space = line_end != editor.rope.line_to_char(first) && motions.char(line_end - 1) == ' ';
+            let separator = if next_is_empty || ends_with_space || motions.char(next_content) == ')' { "" } else { " " };
+            editor.execute(&EditorCommand::DeleteRange(line_end..next_content));
+            if !separator.is_empty() {
+                editor.execute(&EditorCommand::InsertAt { char_idx: line_end, text: separator.to_owned() });
+            }
+            join_col = Some(line_end - editor.rope.line_to_char(first));
+        }
+        if let Some(col) = join_col {
+            editor.execute(&EditorCommand::MoveTo(TextPosition::new(first, col)));
+        }
+    }
+    
+    fn toggle_case(&mut self, count: usize, editor: &mut TextEditor) {
+        let (start, end) = match self.visual_region(editor) {
+            Some(Region::Chars(start, end)) if self.mode != VimMode::Normal => (start, end),
+            Some(Region::Lines(first, last)) if self.mode != VimMode::Normal => {
+                (editor.rope.line_to_char(first), editor.rope.line_to_char(last + 1))
+            }
+            _ => {
+                let from = editor.cursor_char_index();
+                let line_end = Motions { editor }.line_end(editor.cursor.line);
+                (from, (from + count).min(line_end))
+            }
+        };
+        let was_visual = self.mode != VimMode::Normal;
+        if was_visual {
+            self.set_mode(VimMode::Normal, editor);
+        }
+        if start >= end {
+            return;
+        }
+        let mut toggled = String::new();
+        for chr in editor.rope.slice(start..end).chars() {
+            if chr.is_lowercase() {
+                toggled.extend(chr.to_uppercase());
+            } else {
+                toggled.extend(chr.to_lowercase());
+            }
+        }
+        editor.execute(&EditorCommand::DeleteRange(start..end));
+        editor.execute(&EditorCommand::InsertAt { char_idx: start, text: toggled });
+        let target = if was_visual { start } else { end };
+        editor.execute(&EditorCommand::MoveTo(editor.char_to_position(target)));
+    }
+    
+    fn repeat(&mut self, count: Option<usize>, editor: &mut TextEditor) {
+        if self.replaying || self.last_change.is_empty() {
+            return;
+        }
+        let mut keys = self.last_change.clone();
+        if let Some(count) = count {
+            // A new count replaces the recorded one, which follows any register name.
+            let skip = if keys.first().is_some_and(|key| key.code == KeyCode::Char('"')) {
+                keys.len().min(2)
+            } else {
+                0
+            };
+            let recorded_digits = keys[skip..].iter()
+                .take_while(|key| matches!(key.code, KeyCode::Char(chr) if chr.is_ascii_digit()))
+                .count();
+            let digits: Vec<KeyEvent> = count.to_string()
+                .chars()
+                .map(|chr| KeyEvent::new(KeyCode::Char(chr), KeyModifiers::NONE))
+                .collect();
+            keys.splice(skip..skip + recorded_digits, digits);
+        }
+        self.replaying = true;
+        for key in keys {
+            self.handle_key(key, editor);
+        }
+        // A change that ended in insert mode is finished by its recorded `Esc`.
+        self.replaying = false;
+    }
+    
+    fn handle_command_line_key(&mut self, key: KeyEvent, editor: &mut TextEditor) -> VimEvent {
+        let Some(command_line) = &mut self.command_line else {
+            return VimEvent::Handled;
+        };
+        match key.code {
+            KeyCode::Esc => self.command_line = None,
+            KeyCode::Backspace if command_line.is_empty() => self.command_line = None,
+            KeyCode::Backspace => _ = command_line.pop(),
+            KeyCode::Enter => {
+                let command = self.command_line.take().unwrap_or_default();
+                return self.execute_ex(command.trim(), editor);
+            }
+            KeyCode::Char(chr) => command_line.push(chr),
+            _ => (),
+        }
+        VimEvent::Handled
+    }
+    
+    fn write(&mut self, path: &str, editor: &mut TextEditor) -> bool {
+        let result = if path.is_empty() {
+            editor.save()
+        } else {
+            editor.save_as(path)
+        };
+        match result {
+            Ok(()) => {
+                let name = editor.path.as_ref().map(|path| path.display().to_string()).unwrap_or_default();
+                self.message = Some(format!("\"{name}\" {}L written", editor.rope.len_lines()));
+                true
+            }
+            Err(err) => {
+                self.message = Some(format!("E212: Can't open file for writing: {err}"));
+                false
+            }
+        }
+    }
+    
+    fn execute_ex(&mut self, command: &str, editor: &mut TextEditor) -> VimEvent {
+        let (name, argument) = command.split_once(' ').unwrap_or((command, ""));
+        let argument = argument.trim();
+        match name {
+            "" => (),
+            "w" | "write" => _ = self.write(argument, editor),
+            "q" | "quit" if editor.is_modified() => {
+                self.message = Some(String::from("E37: No write since last change (add ! to override)"));
+            }
+            "q" | "quit" | "q!" | "quit!" | "clo" | "close" => return VimEvent::Quit,
+            "qa" | "qall" if editor.is_modified() => {
+                self.message = Some(String::from("E37: No write since last change (add ! to override)"));
+            }
+            "qa" | "qall" => return VimEvent::QuitAll { force: false },
+            "qa!" | "qall!" => return VimEvent::QuitAll { force: true },
+            "sp" | "split" => return VimEvent::Workspace(WorkspaceCommand::Split(Direction::Vertical)),
+            "vs" | "vsplit" => return VimEvent::Workspace(WorkspaceCommand::Split(Direction::Horizontal)),
+            "on" | "only" => return VimEvent::Workspace(WorkspaceCommand::CloseOtherPanes),
+            "tabnew" | "tabe" | "tabedit" => return VimEvent::Workspace(WorkspaceCommand::NewTab),
+            "tabn" | "tabnext" => return VimEvent::Workspace(WorkspaceCommand::NextTab),
+            "tabp" | "tabprevious" => return VimEvent::Workspace(WorkspaceCommand::PrevTab),
+            "tabc" | "tabclose" => return VimEvent::Workspace(WorkspaceCommand::CloseTab),
+            "bn" | "bnext" => return VimEvent::Workspace(WorkspaceCommand::NextBuffer),
+            "bp" | "bprevious" => return VimEvent::Workspace(WorkspaceCommand::PrevBuffer),
+            "wq" | "x" | "exit" => {
+                if self.write(argument, editor) {
+                    return VimEvent::Quit;
+                }
+            }
+            "set" => match argument {
+                "wrap" => editor.set_soft_wrap(Some(SoftWrap::default())),
+                "nowrap" => editor.set_soft_wrap(None),
+                _ => self.message = Some(format!("E518: Unknown option: {argument}")),
+            },
+            line if line.chars().all(|chr| chr.is_ascii_digit()) => {
+                let line = line.parse::<usize>().unwrap_or(usize::MAX).saturating_sub(1);
+                let line = line.min(editor.rope.len_lines() - 1);
+                let col = Motions { editor }.first_non_blank(line) - editor.rope.line_to_char(line);
+                editor.execute(&EditorCommand::MoveTo(TextPosition::new(line, col)));
+            }
+            _ => self.message = Some(format!("E492: Not an editor command: {command}")),
+        }
+        VimEvent::Handled
+    }
+}
+
+impl Default for Vim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn editor(text: &str) -> TextEditor {
+        let mut editor = TextEditor::new();
+        editor.execute(&EditorCommand::InsertText(text.to_owned()));
+        editor.execute(&EditorCommand::MoveTo(TextPosition::new(0, 0)));
+        editor
+    }
+    
+    /// Types `keys`, with `\n` as Enter, `\u{8}` as Backspace and `\u{1b}` as Esc.
+    fn type_keys(vim: &mut Vim, editor: &mut TextEditor, keys: &str) -> VimEvent {
+        let mut event = VimEvent::Handled;
+        for chr in keys.chars() {
+            let code = match chr {
+                '\n' => KeyCode::Enter,
+                '\u{8}' => KeyCode::Backspace,
+                '\u{1b}' => KeyCode::Esc,
+                chr => KeyCode::Char(chr),
+            };
+            event = vim.handle_key(KeyEvent::new(code, KeyModifiers::NONE), editor);
+        }
+        event
+    }
+    
+    fn parse(keys: &str) -> Option<Command> {
+        match parse_command(&keys.chars().collect::<Vec<_>>(), VimMode::Normal) {
+            Parse::Complete(command) => Some(command),
+            Parse::Incomplete | Parse::Invalid => None,
+        }
+    }
+    
+    #[test]
+    fn parses_counts_registers_and_targets() {
+        let command = |register, count, action| Some(Command { register, count, action });
+        let word = Target::Motion(Motion::WordStart(false));
+        assert_eq!(parse("dw"), command(None, None, Action::Operator(Operator::Delete, word)));
+        assert_eq!(parse("2d3w"), command(None, Some(6), Action::Operator(Operator::Delete, word)));
+        assert_eq!(parse("\"a3yy"), command(Some('a'), Some(3), Action::Operator(Operator::Yank, Target::Lines)));
+        assert_eq!(parse("ci("), command(None, None, Action::Operator(Operator::Change, Target::TextObject {
+            object: TextObject::Bracket('(', ')'),
+            around: false,
+        })));
+        assert_eq!(parse("0"), command(None, None, Action::Motion(Motion::LineStart)));
+        assert_eq!(parse("10l"), command(None, Some(10), Action::Motion(Motion::Right)));
+        assert_eq!(parse("T\n"), command(None, None, Action::Motion(Motion::Find(Find { chr: '\n', forward: false, till: true }))));
+        assert_eq!(parse("d\n"), command(None, None, Action::Operator(Operator::Delete, Target::Motion(Motion::Down))));
+        assert_eq!(parse("r\n"), command(None, None, Action::Replace('\n')));
+        assert_eq!(parse("@@"), command(None, None, Action::PlayMacro(None)));
+        
+        let parse_in = |keys: &str, mode| parse_command(&keys.chars().collect::<Vec<_>>(), mode);
+        assert!(matches!(parse_in("d", VimMode::Normal), Parse::Incomplete));
+        assert!(matches!(parse_in("\"", VimMode::Normal), Parse::Incomplete));
+        assert!(matches!(parse_in("dz", VimMode::Normal), Parse::Invalid));
+        assert!(matches!(parse_in("\"!", VimMode::Normal), Parse::Invalid));
+        assert!(matches!(
+            parse_in("d", VimMode::Visual),
+            Parse::Complete(Command { action: Action::Operator(Operator::Delete, Target::Lines), .. }),
+        ));
+    }
+    
+    #[test]
+    fn registers_append_and_rotate() {
+        let mut registers = Registers::default();
+        let text = |text: &str, linewise| Register { text: text.to_owned(), linewise };
+        registers.store(Some('a'), text("one", false), true);
+        registers.store(Some('A'), text("two", false), true);
+        assert_eq!(registers.get('a'), Some(&text("onetwo", false)));
+        assert_eq!(registers.get('"'), Some(&text("onetwo", false)));
+        registers.set('A', text("three\n", true));
+        assert_eq!(registers.get('A'), Some(&text("onetwo\nthree\n", true)));
+        
+        registers.store(None, text("yanked", false), true);
+        registers.store(None, text("first\n", true), false);
+        registers.store(None, text("second\n", true), false);
+        registers.store(None, text("word", false), false);
+        registers.store(Some('_'), text("gone", false), false);
+        assert_eq!(registers.get('0'), Some(&text("yanked", false)));
+        assert_eq!(registers.get('1'), Some(&text("second\n", true)));
+        assert_eq!(registers.get('2'), Some(&text("first\n", true)));
+        assert_eq!(registers.get('-'), Some(&text("word", false)));
+        assert_eq!(registers.get('"'), Some(&text("word", false)));
+    }
+    
+    #[test]
+    fn operators_edit_the_buffer() {
+        let mut vim = Vim::new();
+        let mut editor = editor("one two three\nfour\nfive");
+        type_keys(&mut vim, &mut editor, "dw");
+        assert_eq!(editor.rope.to_string(), "two three\nfour\nfive");
+        type_keys(&mut vim, &mut editor, "jdd");
+        assert_eq!(editor.rope.to_string(), "two three\nfive");
+        type_keys(&mut vim, &mut editor, "u");
+        assert_eq!(editor.rope.to_string(), "two three\nfour\nfive");
+        // The last line has no line break, so the one before it goes with it.
+        type_keys(&mut vim, &mut editor, "Gdd");
+        assert_eq!(editor.rope.to_string(), "two three\nfour");
+        type_keys(&mut vim, &mut editor, "ggcwto\u{1b}");
+        assert_eq!(editor.rope.to_string(), "to three\nfour");
+        type_keys(&mut vim, &mut editor, "w.");
+        assert_eq!(editor.rope.to_string(), "to to\nfour");
+    }
+    
+    #[test]
+    fn enter_and_backspace_are_only_motions_on_their_own() {
+        let mut vim = Vim::new();
+        let mut editor = editor("abc def\nghi");
+        type_keys(&mut vim, &mut editor, "f\n");
+        assert_eq!(editor.cursor, TextPosition::new(0, 0), "There is no line break to find.");
+        type_keys(&mut vim, &mut editor, "\n");
+        assert_eq!(editor.cursor, TextPosition::new(1, 0));
+        type_keys(&mut vim, &mut editor, "k$\u{8}");
+        assert_eq!(editor.cursor, TextPosition::new(0, 5));
+        type_keys(&mut vim, &mut editor, "hhr\n");
+        assert_eq!(editor.rope.to_string(), "abc\ndef\nghi");
+        assert_eq!(editor.cursor, TextPosition::new(1, 0));
+    }
+    
+    #[test]
+    fn quit_all_leaves_unsaved_changes_to_the_owner() {
+        let mut vim = Vim::new();
+        let mut editor = TextEditor::new();
+        assert_eq!(type_keys(&mut vim, &mut editor, ":qa\n"), VimEvent::QuitAll { force: false });
+        assert_eq!(type_keys(&mut vim, &mut editor, ":qa!\n"), VimEvent::QuitAll { force: true });
+        type_keys(&mut vim, &mut editor, "ix\u{1b}");
+        assert_eq!(type_keys(&mut vim, &mut editor, ":qa\n"), VimEvent::Handled);
+        assert!(vim.message().is_some_and(|message| message.starts_with("E37")));
+    }
+}
@@ -0,0 +1,163 @@
+use ratatui::prelude::*;
+use ropey::RopeSlice;
+
+use crate::theme::Theme;
+
+/// The indent guide backgrounds that [`IndentGuides::default`] cycles through.
+pub const RAINBOW_INDENT_COLORS: [Color; 6] = [
+    Color::Rgb(68, 17, 10),
+    Color::Rgb(70, 34, 6),
+    Color::Rgb(69, 58, 2),
+    Color::Rgb(7, 40, 24),
+    Color::Rgb(16, 30, 51),
+    Color::Rgb(26, 14, 45)
+];
+
+/// Which whitespace is drawn with visible glyphs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ShowWhitespace {
+    None,
+    /// Indentation only.
+    Leading,
+    /// Whitespace at the end of lines only, line endings included.
+    Trailing,
+    #[default]
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IndentGuides {
+    Off,
+    /// A guide glyph at every indent level in a single color, for terminals with few colors.
+    Plain(Color),
+    /// Guide glyphs over backgrounds that cycle through the palette, one color per indent level.
+    Rainbow(Vec<Color>),
+}
+
+impl Default for IndentGuides {
+    fn default() -> Self {
+        IndentGuides::Rainbow(RAINBOW_INDENT_COLORS.to_vec())
+    }
+}
+
+/// How [`TextEdit`](super::TextEdit) draws whitespace and indentation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Whitespace {
+    pub show: ShowWhitespace,
+    /// Draws whitespace at the end of lines with `trailing_style`, whatever `show` is.
+    /// Whitespace the cursor is in or just after is left alone while typing.
+    pub highlight_trailing: bool,
+    pub indent_guides: IndentGuides,
+    /// Columns per indent level.
+    pub indent_width: usize,
+    pub space: char,
+    pub tab: char,
+    pub nbsp: char,
+    /// Drawn after the end of lines that end in a carriage return, such as CRLF lines.
+    pub carriage_return: char,
+    pub guide: char,
+    /// Style of the glyphs, and of the guides when they have no color of their own.
+    pub style: Style,
+    pub trailing_style: Style,
+}
+
+impl Default for Whitespace {
+    fn default() -> Self {
+        Self {
+            show: ShowWhitespace::All,
+            highlight_trailing: false,
+            indent_guides: IndentGuides::default(),
+            indent_width: 4,
+            space: '·',
+            tab: '→',
+            nbsp: '⍽',
+            carriage_return: '␍',
+            guide: '┆',
+            style: Style::new().fg(Color::DarkGray),
+            trailing_style: Style::new().bg(Color::Rgb(110, 30, 36)),
+        }
+    }
+}
+
+impl Whitespace {
+    /// Takes the glyph styles and the rainbow guide colors from the theme. Plain guides keep their color.
+    pub fn theme(mut self, theme: &Theme) -> Self {
+        self.style = theme.style("editor.whitespace");
+        self.trailing_style = theme.style("editor.trailing_whitespace");
+        if let IndentGuides::Rainbow(colors) = &mut self.indent_guides {
+            colors.clone_from(&theme.indent_colors);
+        }
+        self
+    }
+    
+    /// Whether a whitespace char at `col` gets a glyph.
+    fn shows(&self, line: &LineWhitespace, col: usize) -> bool {
+        match self.show {
+            ShowWhitespace::None => false,
+            ShowWhitespace::Leading => col < line.indent_len,
+            ShowWhitespace::Trailing => col >= line.trailing_start,
+            ShowWhitespace::All => true,
+        }
+    }
+    
+    /// The glyph and style for a whitespace char, or `None` for chars that aren't whitespace.
+    pub(crate) fn cell(&self, line: &LineWhitespace, col: usize, chr: char) -> Option<(char, Style)> {
+        let glyph = match chr {
+            ' ' => self.space,
+            '\t' => self.tab,
+            '\u{a0}' => self.nbsp,
+            _ => return None,
+        };
+        let (mut chr, mut style) = match self.shows(line, col) {
+            true => (glyph, self.style),
+            false => (' ', Style::new()),
+        };
+        if col < line.indent_len && col.is_multiple_of(self.indent_width.max(1)) {
+            match &self.indent_guides {
+                IndentGuides::Off => (),
+                &IndentGuides::Plain(color) => (chr, style) = (self.guide, Style::new().fg(color)),
+                IndentGuides::Rainbow(_) => (chr, style) = (self.guide, self.style),
+            }
+        }
+        if let IndentGuides::Rainbow(palette) = &self.indent_guides
+            && !palette.is_empty()
+            && col < line.indent_len {
+            let level = col / self.indent_width.max(1);
+            style = style.bg(palette[level % palette.len()]);
+        }
+        if line.flag_trailing && col >= line.trailing_start {
+            style = style.patch(self.trailing_style);
+        }
+        Some((chr, style))
+    }
+}
+
+/// Where the whitespace at either end of a line is, in char columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct LineWhitespace {
+    pub indent_len: usize,
+    /// The column after the last char that isn't whitespace. Blank lines are all trailing whitespace.
+    pub trailing_start: usize,
+    /// Whether the trailing whitespace gets the trailing style.
+    pub flag_trailing: bool,
+    /// Whether the line ends in a carriage return.
+    pub carriage_return: bool,
+}
+
+impl LineWhitespace {
+    pub fn new(line: RopeSlice, line_len: usize) -> Self {
+        let is_blank = |chr: char| matches!(chr, ' ' | '\t' | '\u{a0}');
+        let indent_len = line.chars().take(line_len).take_while(|&chr| is_blank(chr)).count();
+        let trailing_len = if indent_len == line_len {
+            line_len
+        } else {
+            line.chars_at(line_len).reversed().take_while(|&chr| is_blank(chr)).count()
+        };
+        Self {
+            indent_len,
+            trailing_start: line_len - trailing_len,
+            flag_trailing: false,
+            carriage_return: line.get_char(line_len) == Some('\r'),
+        }
+    }
+}
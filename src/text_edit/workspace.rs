@@ -0,0 +1,934 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::prelude::*;
+
+use crate::theme::Theme;
+
+use super::{
+    clipboard::{MemoryClipboard, SharedClipboard},
+    highlight::Language,
+    macros::{Macros, SharedMacros},
+    minimap::MinimapLayout,
+    snippet::{SharedSnippets, Snippets},
+    swap::{Autosave, Recovery},
+    Selection,
+    TextEdit,
+    TextEditor,
+    TextPosition,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BufferId(usize);
+
+/// Owns every open buffer. Buffers are shared by all of the panes that show them.
+pub struct BufferManager {
+    buffers: BTreeMap<BufferId, TextEditor>,
+    next_id: usize,
+    clipboard: SharedClipboard,
+    autosave: Autosave,
+    snippets: SharedSnippets,
+    macros: SharedMacros,
+}
+
+impl BufferManager {
+    pub fn new() -> Self {
+        Self {
+            buffers: BTreeMap::new(),
+            next_id: 0,
+            clipboard: MemoryClipboard::shared(),
+            autosave: Autosave::default(),
+            snippets: SharedSnippets::default(),
+            macros: Macros::shared(),
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+    
+    pub fn ids(&self) -> impl Iterator<Item = BufferId> + '_ {
+        self.buffers.keys().copied()
+    }
+    
+    /// The buffers with unsaved changes.
+    pub fn modified(&self) -> impl Iterator<Item = BufferId> + '_ {
+        self.buffers.iter()
+            .filter(|(_, editor)| editor.is_modified())
+            .map(|(&id, _)| id)
+    }
+    
+    pub fn get(&self, id: BufferId) -> Option<&TextEditor> {
+        self.buffers.get(&id)
+    }
+    
+    pub fn get_mut(&mut self, id: BufferId) -> Option<&mut TextEditor> {
+        self.buffers.get_mut(&id)
+    }
+    
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (BufferId, &mut TextEditor)> {
+        self.buffers.iter_mut().map(|(&id, editor)| (id, editor))
+    }
+    
+    /// Every buffer copies to and pastes from this clipboard.
+    pub fn set_clipboard(&mut self, clipboard: SharedClipboard) {
+        for editor in self.buffers.values_mut() {
+            editor.clipboard = clipboard.clone();
+        }
+        self.clipboard = clipboard;
+    }
+    
+    /// Every buffer, including those added later, autosaves like this.
+    pub fn set_autosave(&mut self, autosave: Autosave) {
+        for editor in self.buffers.values_mut() {
+            editor.autosave = autosave.clone();
+        }
+        self.autosave = autosave;
+    }
+    
+    /// Every buffer, including those added later, expands these snippets.
+    pub fn set_snippets(&mut self, snippets: Snippets) {
+        let snippets = SharedSnippets::new(snippets);
+        for editor in self.buffers.values_mut() {
+            editor.snippets = snippets.clone();
+        }
+        self.snippets = snippets;
+    }
+    
+    /// Every buffer records into and plays from these macros, so a macro recorded in one
+    /// buffer can be played in another.
+    pub fn set_macros(&mut self, macros: SharedMacros) {
+        for editor in self.buffers.values_mut() {
+            editor.macros = macros.clone();
+        }
+        self.macros = macros;
+    }
+    
+    pub fn macros(&self) -> &SharedMacros {
+        &self.macros
+    }
+    
+    pub fn add(&mut self, mut editor: TextEditor) -> BufferId {
+        let id = BufferId(self.next_id);
+        self.next_id += 1;
+        editor.clipboard = self.clipboard.clone();
+        editor.autosave = self.autosave.clone();
+        editor.snippets = self.snippets.clone();
+        editor.macros = self.macros.clone();
+        self.buffers.insert(id, editor);
+        id
+    }
+    
+    /// Opens `path`, or finds the buffer that already has it open.
+    /// A file that doesn't exist yet gives an empty buffer that saves to `path`.
+    pub fn open<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<BufferId> {
+        let path = path.as_ref();
+        let existing = self.buffers.iter()
+            .find(|(_, editor)| editor.path.as_deref() == Some(path))
+            .map(|(&id, _)| id);
+        if let Some(id) = existing {
+            return Ok(id);
+        }
+        let editor = if path.exists() {
+            TextEditor::open(path)?
+        } else {
+            let mut editor = TextEditor::new();
+            editor.path = Some(path.into());
+            if let Some(language) = Language::from_path(path) {
+                editor.set_language(language);
+            }
+            editor
+        };
+        Ok(self.add(editor))
+    }
+    
+    /// Also deletes the buffer's swap file.
+    pub fn remove(&mut self, id: BufferId) -> Option<TextEditor> {
+        let mut editor = self.buffers.remove(&id)?;
+        _ = editor.remove_swap_file();
+        Some(editor)
+    }
+    
+    /// Opens the file that a swap file belongs to, or an empty buffer if it didn't have
+    /// one, and recovers the swap file into it. See [`TextEditor::recover`].
+    pub fn recover(&mut self, recovery: Recovery) -> std::io::Result<BufferId> {
+        let id = match &recovery.path {
+            Some(path) => self.open(path)?,
+            None => self.add(TextEditor::new()),
+        };
+        self.buffers.get_mut(&id).expect("The buffer was just opened.").recover(recovery)?;
+        Ok(id)
+    }
+    
+    /// Deletes the swap files of buffers without unsaved changes, for exiting. Buffers with
+    /// unsaved changes keep theirs, so that the changes can still be recovered.
+    pub fn remove_swap_files(&mut self) -> std::io::Result<()> {
+        self.buffers.values_mut()
+            .filter(|editor| !editor.is_modified())
+            .try_for_each(TextEditor::remove_swap_file)
+    }
+    
+    /// The file name, or `[No Name]` for buffers without a path.
+    pub fn name(&self, id: BufferId) -> String {
+        self.get(id)
+            .and_then(|editor| editor.path.as_ref())
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("[No Name]"))
+    }
+    
+    /// The buffer after (or before) `id`, wrapping around.
+    fn cycle(&self, id: BufferId, forward: bool) -> BufferId {
+        let next = if forward {
+            self.buffers.range(BufferId(id.0 + 1)..).next().or_else(|| self.buffers.iter().next())
+        } else {
+            self.buffers.range(..id).next_back().or_else(|| self.buffers.iter().next_back())
+        };
+        next.map_or(id, |(&id, _)| id)
+    }
+}
+
+impl Default for BufferManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PaneId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FocusDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WorkspaceCommand {
+    /// Opens an empty buffer in a new tab.
+    NewTab,
+    CloseTab,
+    NextTab,
+    PrevTab,
+    SelectTab(usize),
+    /// Splits the focused pane in two, laid out in `Direction`. Both halves show the same buffer.
+    Split(Direction),
+    /// Closes the focused pane, and its tab if it was the last pane there.
+    ClosePane,
+    CloseOtherPanes,
+    FocusNext,
+    FocusPrev,
+    Focus(FocusDirection),
+    /// Shows the next buffer in the focused pane.
+    NextBuffer,
+    PrevBuffer,
+}
+
+/// Workspace commands with titles for menus and the command palette.
+pub const NAMED_WORKSPACE_COMMANDS: &[(&str, WorkspaceCommand)] = &[
+    ("New Tab", WorkspaceCommand::NewTab),
+    ("Close Tab", WorkspaceCommand::CloseTab),
+    ("Next Tab", WorkspaceCommand::NextTab),
+    ("Previous Tab", WorkspaceCommand::PrevTab),
+    ("Split Pane Right", WorkspaceCommand::Split(Direction::Horizontal)),
+    ("Split Pane Down", WorkspaceCommand::Split(Direction::Vertical)),
+    ("Close Pane", WorkspaceCommand::ClosePane),
+    ("Close Other Panes", WorkspaceCommand::CloseOtherPanes),
+    ("Focus Next Pane", WorkspaceCommand::FocusNext),
+    ("Focus Previous Pane", WorkspaceCommand::FocusPrev),
+    ("Focus Left Pane", WorkspaceCommand::Focus(FocusDirection::Left)),
+    ("Focus Right Pane", WorkspaceCommand::Focus(FocusDirection::Right)),
+    ("Focus Pane Above", WorkspaceCommand::Focus(FocusDirection::Up)),
+    ("Focus Pane Below", WorkspaceCommand::Focus(FocusDirection::Down)),
+    ("Next Buffer", WorkspaceCommand::NextBuffer),
+    ("Previous Buffer", WorkspaceCommand::PrevBuffer),
+];
+
+/// The default bindings for tabs and panes.
+pub fn workspace_binding(key: KeyEvent) -> Option<WorkspaceCommand> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    let command = match key.code {
+        KeyCode::Char('t') if ctrl => WorkspaceCommand::NewTab,
+        KeyCode::Char('w') if ctrl => WorkspaceCommand::ClosePane,
+        KeyCode::PageDown if ctrl => WorkspaceCommand::NextTab,
+        KeyCode::PageUp if ctrl => WorkspaceCommand::PrevTab,
+        KeyCode::PageDown if alt => WorkspaceCommand::NextBuffer,
+        KeyCode::PageUp if alt => WorkspaceCommand::PrevBuffer,
+        KeyCode::Char(digit @ '1'..='9') if alt => WorkspaceCommand::SelectTab(digit as usize - '1' as usize),
+        KeyCode::Char('\\') if alt => WorkspaceCommand::Split(Direction::Horizontal),
+        KeyCode::Char('-') if alt => WorkspaceCommand::Split(Direction::Vertical),
+        // Alt+Up and Alt+Down move lines, so focus takes Shift as well.
+        KeyCode::Left if alt && shift && !ctrl => WorkspaceCommand::Focus(FocusDirection::Left),
+        KeyCode::Right if alt && shift && !ctrl => WorkspaceCommand::Focus(FocusDirection::Right),
+        KeyCode::Up if alt && shift && !ctrl => WorkspaceCommand::Focus(FocusDirection::Up),
+        KeyCode::Down if alt && shift && !ctrl => WorkspaceCommand::Focus(FocusDirection::Down),
+        KeyCode::F(6) if shift => WorkspaceCommand::FocusPrev,
+        KeyCode::F(6) => WorkspaceCommand::FocusNext,
+        _ => return None,
+    };
+    Some(command)
+}
+
+/// How a tab's panes are arranged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PaneLayout {
+    Pane(PaneId),
+    /// Children share the space equally, one cell apart, laid out in `direction`.
+    Split {
+        direction: Direction,
+        children: Vec<PaneLayout>,
+    },
+}
+
+impl PaneLayout {
+    fn panes(&self, panes: &mut Vec<PaneId>) {
+        match self {
+            &PaneLayout::Pane(id) => panes.push(id),
+            PaneLayout::Split { children, .. } => {
+                for child in children {
+                    child.panes(panes);
+                }
+            }
+        }
+    }
+    
+    /// Puts `new` after `target`, joining `target`'s split if it goes the same way.
+    fn split(&mut self, target: PaneId, new: PaneId, direction: Direction) -> bool {
+        match self {
+            &mut PaneLayout::Pane(id) if id == target => {
+                *self = PaneLayout::Split {
+                    direction,
+                    children: vec![PaneLayout::Pane(target), PaneLayout::Pane(new)],
+                };
+                true
+            }
+            PaneLayout::Pane(_) => false,
+            PaneLayout::Split { direction: split_direction, children } => {
+                let index = children.iter().position(|child| *child == PaneLayout::Pane(target));
+                if let Some(index) = index && *split_direction == direction {
+                    children.insert(index + 1, PaneLayout::Pane(new));
+                    return true;
+                }
+                children.iter_mut().any(|child| child.split(target, new, direction))
+            }
+        }
+    }
+    
+    /// Removes `target`, collapsing splits that are left with one child. A lone pane can't be removed.
+    fn remove(&mut self, target: PaneId) -> bool {
+        let PaneLayout::Split { children, .. } = self else {
+            return false;
+        };
+        match children.iter().position(|child| *child == PaneLayout::Pane(target)) {
+            Some(index) => _ = children.remove(index),
+            None if children.iter_mut().any(|child| child.remove(target)) => (),
+            None => return false,
+        }
+        if children.len() == 1 {
+            *self = children.pop().expect("Split has one child.");
+        }
+        true
+    }
+    
+    fn areas(&self, area: Rect, panes: &mut Vec<(PaneId, Rect)>, separators: &mut Vec<(Rect, Direction)>) {
+        let (direction, children) = match self {
+            &PaneLayout::Pane(id) => {
+                panes.push((id, area));
+                return;
+            }
+            PaneLayout::Split { direction, children } => (*direction, children),
+        };
+        let count = children.len() as u16;
+        let total = match direction {
+            Direction::Horizontal => area.width,
+            Direction::Vertical => area.height,
+        };
+        let available = total.saturating_sub(count - 1);
+        let mut offset = 0;
+        for (index, child) in children.iter().enumerate() {
+            let size = available / count + u16::from((index as u16) < available % count);
+            let (child_area, separator) = match direction {
+                Direction::Horizontal => (
+                    Rect::new(area.x + offset, area.y, size, area.height),
+                    Rect::new(area.x + offset + size, area.y, 1, area.height),
+                ),
+                Direction::Vertical => (
+                    Rect::new(area.x, area.y + offset, area.width, size),
+                    Rect::new(area.x, area.y + offset + size, area.width, 1),
+                ),
+            };
+            child.areas(child_area, panes, separators);
+            offset += size;
+            if index + 1 < children.len() {
+                separators.push((separator.intersection(area), direction));
+                offset += 1;
+            }
+        }
+    }
+}
+
+/// A pane's own place in its buffer. The focused pane's viewport lives in the editor itself,
+/// and the others are swapped in while they are drawn.
+#[derive(Debug, Clone, Default)]
+struct Viewport {
+    start_line: usize,
+    start_col: usize,
+    start_row: usize,
+    cursor: TextPosition,
+    extra_cursors: Vec<TextPosition>,
+    selection: Option<Selection>,
+    preferred_col: usize,
+    scroll_to_cursor: bool,
+    /// Where the pane was drawn, for mapping mouse positions into the buffer.
+    area: Rect,
+    text_area: Rect,
+    vertical_scrollbar: Option<Rect>,
+    horizontal_scrollbar: Option<Rect>,
+    minimap: Option<MinimapLayout>,
+}
+
+impl Viewport {
+    fn save(editor: &TextEditor) -> Self {
+        Self {
+            start_line: editor.start_line,
+            start_col: editor.start_col,
+            start_row: editor.start_row,
+            cursor: editor.cursor,
+            extra_cursors: editor.extra_cursors.clone(),
+            selection: editor.selection,
+            preferred_col: editor.preferred_col,
+            scroll_to_cursor: editor.scroll_to_cursor,
+            area: editor.area,
+            text_area: editor.text_area,
+            vertical_scrollbar: editor.vertical_scrollbar,
+            horizontal_scrollbar: editor.horizontal_scrollbar,
+            minimap: editor.minimap,
+        }
+    }
+    
+    /// Leaves the viewport's positions with `editor` while `pane` isn't focused, so that edits
+    /// made through other panes move them along. [`Viewport::unpark`] takes them back.
+    fn park(&self, pane: PaneId, editor: &mut TextEditor) {
+        let line = self.start_line.min(editor.rope.len_lines() - 1);
+        let mut positions = vec![editor.rope.line_to_char(line), editor.position_to_char(self.cursor)];
+        positions.extend(self.selection.map(|selection| editor.position_to_char(selection.anchor)));
+        positions.extend(self.extra_cursors.iter().map(|&cursor| editor.position_to_char(cursor)));
+        editor.parked_views.insert(pane.0, positions);
+    }
+    
+    /// Restores a viewport that [`Viewport::park`] left with `editor`, moved by any edits since.
+    /// Viewports that weren't parked are restored as they are.
+    fn unpark(&mut self, pane: PaneId, editor: &mut TextEditor) {
+        if let Some(positions) = editor.parked_views.remove(&pane.0) {
+            let mut positions = positions.into_iter().map(|char_idx| editor.char_to_position(char_idx));
+            let mut next = || positions.next().expect("Parked viewports keep every position.");
+            self.start_line = next().line;
+            self.cursor = next();
+            if let Some(selection) = &mut self.selection {
+                selection.anchor = next();
+            }
+            for cursor in &mut self.extra_cursors {
+                *cursor = next();
+            }
+        }
+        self.restore(editor);
+    }
+    
+    /// Positions are clamped, since the buffer may have changed in ways that parking doesn't follow.
+    fn restore(&self, editor: &mut TextEditor) {
+        let clamp = |editor: &TextEditor, position: TextPosition| {
+            let line = position.line.min(editor.rope.len_lines() - 1);
+            TextPosition::new(line, position.col.min(editor.line_len(line)))
+        };
+        editor.start_line = self.start_line;
+        editor.start_col = self.start_col;
+        editor.start_row = self.start_row;
+        editor.cursor = clamp(editor, self.cursor);
+        editor.extra_cursors = self.extra_cursors.iter().map(|&cursor| clamp(editor, cursor)).collect();
+        editor.selection = self.selection.map(|selection| Selection {
+            anchor: clamp(editor, selection.anchor),
+            ..selection
+        });
+        editor.preferred_col = self.preferred_col;
+        editor.scroll_to_cursor = self.scroll_to_cursor;
+        editor.area = self.area;
+        editor.text_area = self.text_area;
+        editor.vertical_scrollbar = self.vertical_scrollbar;
+        editor.horizontal_scrollbar = self.horizontal_scrollbar;
+        editor.minimap = self.minimap;
+    }
+}
+
+struct Pane {
+    buffer: BufferId,
+    viewport: Viewport,
+}
+
+struct Tab {
+    layout: PaneLayout,
+    focus: PaneId,
+    /// Where each pane was drawn during the last render.
+    pane_areas: Vec<(PaneId, Rect)>,
+}
+
+/// Tabs of split panes onto the buffers in a [`BufferManager`]. There is always at least one pane.
+pub struct Workspace {
+    pub buffers: BufferManager,
+    panes: BTreeMap<PaneId, Pane>,
+    tabs: Vec<Tab>,
+    active_tab: usize,
+    next_pane_id: usize,
+    /// Where each tab's title was drawn by the last [`TabBar`] render.
+    tab_areas: Vec<Rect>,
+}
+
+impl Workspace {
+    /// Starts with a single tab showing `buffer`.
+    pub fn new(buffers: BufferManager, buffer: BufferId) -> Self {
+        let mut workspace = Self {
+            buffers,
+            panes: BTreeMap::new(),
+            tabs: Vec::new(),
+            active_tab: 0,
+            next_pane_id: 0,
+            tab_areas: Vec::new(),
+        };
+        let pane = workspace.add_pane(buffer);
+        workspace.tabs.push(Tab {
+            layout: PaneLayout::Pane(pane),
+            focus: pane,
+            pane_areas: Vec::new(),
+        });
+        workspace
+    }
+    
+    fn add_pane(&mut self, buffer: BufferId) -> PaneId {
+        let id = PaneId(self.next_pane_id);
+        self.next_pane_id += 1;
+        let viewport = self.buffers.get(buffer).map(Viewport::save).unwrap_or_default();
+        self.panes.insert(id, Pane {
+            buffer,
+            viewport,
+        });
+        id
+    }
+    
+    pub fn tab_count(&self) -> usize {
+        self.tabs.len()
+    }
+    
+    pub fn active_tab(&self) -> usize {
+        self.active_tab
+    }
+    
+    pub fn pane_count(&self) -> usize {
+        self.panes.len()
+    }
+    
+    pub fn active_pane(&self) -> PaneId {
+        self.tabs[self.active_tab].focus
+    }
+    
+    pub fn active_buffer(&self) -> BufferId {
+        self.panes[&self.active_pane()].buffer
+    }
+    
+    /// The focused pane's buffer.
+    pub fn editor(&self) -> &TextEditor {
+        self.buffers.get(self.active_buffer()).expect("Panes always show an open buffer.")
+    }
+    
+    pub fn editor_mut(&mut self) -> &mut TextEditor {
+        let buffer = self.active_buffer();
+        self.buffers.get_mut(buffer).expect("Panes always show an open buffer.")
+    }
+    
+    /// The title of a tab: its focused pane's buffer name, marked with `*` if modified.
+    pub fn tab_title(&self, index: usize) -> String {
+        let buffer = self.panes[&self.tabs[index].focus].buffer;
+        let modified = self.buffers.get(buffer).is_some_and(TextEditor::is_modified);
+        let name = self.buffers.name(buffer);
+        if modified { format!("{name}*") } else { name }
+    }
+    
+    /// Saves the focused pane's viewport and parks it, before the focus moves.
+    fn store_focus(&mut self) {
+        let id = self.active_pane();
+        let buffer = self.active_buffer();
+        let (Some(pane), Some(editor)) = (self.panes.get_mut(&id), self.buffers.get_mut(buffer)) else {
+            return;
+        };
+        pane.viewport = Viewport::save(editor);
+        pane.viewport.park(id, editor);
+    }
+    
+    fn load_focus(&mut self) {
+        let id = self.active_pane();
+        let pane = self.panes.get_mut(&id).expect("The focused pane exists.");
+        if let Some(editor) = self.buffers.get_mut(pane.buffer) {
+            pane.viewport.unpark(id, editor);
+        }
+    }
+    
+    fn set_focus(&mut self, tab: usize, pane: PaneId) {
+        self.store_focus();
+        self.active_tab = tab;
+        self.tabs[tab].focus = pane;
+        self.load_focus();
+    }
+    
+    /// Removes a pane, and its buffer too if nothing else shows it and it has no unsaved changes.
+    fn release(&mut self, pane: PaneId) {
+        let Some(Pane { buffer, .. }) = self.panes.remove(&pane) else {
+            return;
+        };
+        if let Some(editor) = self.buffers.get_mut(buffer) {
+            editor.parked_views.remove(&pane.0);
+        }
+        let shown = self.panes.values().any(|pane| pane.buffer == buffer);
+        let modified = self.buffers.get(buffer).is_some_and(TextEditor::is_modified);
+        if !shown && !modified {
+            self.buffers.remove(buffer);
+        }
+    }
+    
+    pub fn new_tab(&mut self, buffer: BufferId) {
+        self.store_focus();
+        let pane = self.add_pane(buffer);
+        self.tabs.push(Tab {
+            layout: PaneLayout::Pane(pane),
+            focus: pane,
+            pane_areas: Vec::new(),
+        });
+        self.active_tab = self.tabs.len() - 1;
+        self.load_focus();
+    }
+    
+    /// Opens `path` in a new tab. See [`BufferManager::open`].
+    pub fn open_tab<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        let buffer = self.buffers.open(path)?;
+        self.new_tab(buffer);
+        Ok(())
+    }
+    
+    /// Returns `false` for the last tab, which stays open.
+    pub fn close_tab(&mut self) -> bool {
+        if self.tabs.len() == 1 {
+            return false;
+        }
+        let tab = self.tabs.remove(self.active_tab);
+        let mut panes = Vec::new();
+        tab.layout.panes(&mut panes);
+        for pane in panes {
+            self.release(pane);
+        }
+        self.active_tab = self.active_tab.min(self.tabs.len() - 1);
+        self.load_focus();
+        true
+    }
+    
+    pub fn select_tab(&mut self, index: usize) -> bool {
+        if index >= self.tabs.len() {
+            return false;
+        }
+        let focus = self.tabs[index].focus;
+        self.set_focus(index, focus);
+        true
+    }
+    
+    pub fn cycle_tab(&mut self, forward: bool) {
+        let count = self.tabs.len();
+        let index = if forward { self.active_tab + 1 } else { self.active_tab + count - 1 };
+        self.select_tab(index % count);
+    }
+    
+    /// Splits the focused pane. The new pane gets the focus and starts at the same place.
+    pub fn split(&mut self, direction: Direction) {
+        self.store_focus();
+        let focus = self.active_pane();
+        let new = self.add_pane(self.active_buffer());
+        self.panes.get_mut(&new).expect("Pane was just added.").viewport = self.panes[&focus].viewport.clone();
+        self.tabs[self.active_tab].layout.split(focus, new, direction);
+        self.tabs[self.active_tab].focus = new;
+        self.load_focus();
+    }
+    
+    /// Closes the focused pane, or its tab if it is the only pane there.
+    /// Returns `false` for the last pane of the last tab, which stays open.
+    pub fn close_pane(&mut self) -> bool {
+        let tab = &mut self.tabs[self.active_tab];
+        let closing = tab.focus;
+        if !tab.layout.remove(closing) {
+            return self.close_tab();
+        }
+        let mut panes = Vec::new();
+        tab.layout.panes(&mut panes);
+        tab.focus = panes[0];
+        self.release(closing);
+        self.load_focus();
+        true
+    }
+    
+    pub fn close_other_panes(&mut self) {
+        let tab = &mut self.tabs[self.active_tab];
+        let mut panes = Vec::new();
+        tab.layout.panes(&mut panes);
+        tab.layout = PaneLayout::Pane(tab.focus);
+        let focus = tab.focus;
+        for pane in panes.into_iter().filter(|&pane| pane != focus) {
+            self.release(pane);
+        }
+    }
+    
+    pub fn cycle_focus(&mut self, forward: bool) {
+        let mut panes = Vec::new();
+        self.tabs[self.active_tab].layout.panes(&mut panes);
+        let index = panes.iter().position(|&pane| pane == self.active_pane()).unwrap_or(0);
+        let count = panes.len();
+        let next = if forward { index + 1 } else { index + count - 1 };
+        self.set_focus(self.active_tab, panes[next % count]);
+    }
+    
+    /// Focuses the nearest pane in `direction`, using where the panes were last drawn.
+    pub fn focus_direction(&mut self, direction: FocusDirection) -> bool {
+        let tab = &self.tabs[self.active_tab];
+        let Some(&(_, from)) = tab.pane_areas.iter().find(|&&(pane, _)| pane == tab.focus) else {
+            return false;
+        };
+        // Distance along the direction, then how far apart the panes are across it.
+        let score = |area: Rect| -> Option<(u16, u16)> {
+            let overlaps_rows = area.y < from.bottom() && from.y < area.bottom();
+            let overlaps_cols = area.x < from.right() && from.x < area.right();
+            let center = |start: u16, len: u16| start + len / 2;
+            match direction {
+                FocusDirection::Left if overlaps_rows && area.right() <= from.x => Some((from.x - area.right(), center(area.y, area.height).abs_diff(center(from.y, from.height)))),
+                FocusDirection::Right if overlaps_rows && area.x >= from.right() => Some((area.x - from.right(), center(area.y, area.height).abs_diff(center(from.y, from.height)))),
+                FocusDirection::Up if overlaps_cols && area.bottom() <= from.y => Some((from.y - area.bottom(), center(area.x, area.width).abs_diff(center(from.x, from.width)))),
+                FocusDirection::Down if overlaps_cols && area.y >= from.bottom() => Some((area.y - from.bottom(), center(area.x, area.width).abs_diff(center(from.x, from.width)))),
+                _ => None,
+            }
+        };
+        let target = tab.pane_areas.iter()
+            .filter_map(|&(pane, area)| score(area).map(|score| (score, pane)))
+            .min()
+            .map(|(_, pane)| pane);
+        match target {
+            Some(pane) => {
+                self.set_focus(self.active_tab, pane);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Focuses the tab or pane drawn at `position`. Returns `false` if there was none.
+    pub fn click(&mut self, position: Position) -> bool {
+        if let Some(index) = self.tab_areas.iter().position(|area| area.contains(position)) {
+            return self.select_tab(index);
+        }
+        let tab = &self.tabs[self.active_tab];
+        match tab.pane_areas.iter().find(|(_, area)| area.contains(position)) {
+            Some(&(pane, _)) => {
+                self.set_focus(self.active_tab, pane);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    pub fn cycle_buffer(&mut self, forward: bool) {
+        let buffer = self.buffers.cycle(self.active_buffer(), forward);
+        if buffer == self.active_buffer() {
+            return;
+        }
+        // The pane's place in the buffer it's leaving isn't kept.
+        let viewport = self.buffers.get(buffer).map(Viewport::save).unwrap_or_default();
+        let pane = self.panes.get_mut(&self.tabs[self.active_tab].focus).expect("The focused pane exists.");
+        pane.buffer = buffer;
+        pane.viewport = viewport;
+        self.load_focus();
+    }
+    
+    /// Returns `false` if the command did nothing, such as closing the last pane.
+    pub fn execute(&mut self, command: WorkspaceCommand) -> bool {
+        match command {
+            WorkspaceCommand::NewTab => {
+                let buffer = self.buffers.add(TextEditor::new());
+                self.new_tab(buffer);
+            }
+            WorkspaceCommand::CloseTab => return self.close_tab(),
+            WorkspaceCommand::NextTab => self.cycle_tab(true),
+            WorkspaceCommand::PrevTab => self.cycle_tab(false),
+            WorkspaceCommand::SelectTab(index) => return self.select_tab(index),
+            WorkspaceCommand::Split(direction) => self.split(direction),
+            WorkspaceCommand::ClosePane => return self.close_pane(),
+            WorkspaceCommand::CloseOtherPanes => self.close_other_panes(),
+            WorkspaceCommand::FocusNext => self.cycle_focus(true),
+            WorkspaceCommand::FocusPrev => self.cycle_focus(false),
+            WorkspaceCommand::Focus(direction) => return self.focus_direction(direction),
+            WorkspaceCommand::NextBuffer => self.cycle_buffer(true),
+            WorkspaceCommand::PrevBuffer => self.cycle_buffer(false),
+        }
+        true
+    }
+}
+
+/// Draws the active tab's panes, each through its own copy of `text_edit`.
+pub struct WorkspaceView<'a> {
+    text_edit: TextEdit<'a>,
+    separator_style: Style,
+}
+
+impl<'a> WorkspaceView<'a> {
+    pub fn new(text_edit: TextEdit<'a>) -> Self {
+        Self {
+            text_edit,
+            separator_style: Style::new().fg(Color::DarkGray),
+        }
+    }
+    
+    /// Themes the separators between panes. The editor is themed on its own, before it's passed in.
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.separator_style(theme.style("workspace.separator"))
+    }
+    
+    pub fn separator_style(mut self, style: Style) -> Self {
+        self.separator_style = style;
+        self
+    }
+}
+
+impl StatefulWidget for WorkspaceView<'_> {
+    type State = Workspace;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let tab = &mut state.tabs[state.active_tab];
+        let mut pane_areas = Vec::new();
+        let mut separators = Vec::new();
+        tab.layout.areas(area, &mut pane_areas, &mut separators);
+        for (separator, direction) in separators {
+            let symbol = match direction {
+                Direction::Horizontal => symbols::line::VERTICAL,
+                Direction::Vertical => symbols::line::HORIZONTAL,
+            };
+            for position in separator.positions() {
+                buf[position].set_symbol(symbol).set_style(self.separator_style);
+            }
+        }
+        let focus = tab.focus;
+        // The focused pane goes last so that the editor keeps its text area and cursor position.
+        pane_areas.sort_by_key(|&(pane, _)| pane == focus);
+        for &(pane_id, pane_area) in &pane_areas {
+            let pane = state.panes.get_mut(&pane_id).expect("Layouts only hold open panes.");
+            let Some(editor) = state.buffers.get_mut(pane.buffer) else {
+                continue;
+            };
+            if pane_id == focus {
+                self.text_edit.clone().render(pane_area, buf, editor);
+                continue;
+            }
+            let focused = Viewport::save(editor);
+            pane.viewport.unpark(pane_id, editor);
+            self.text_edit.clone().render(pane_area, buf, editor);
+            pane.viewport = Viewport::save(editor);
+            pane.viewport.park(pane_id, editor);
+            focused.restore(editor);
+        }
+        state.tabs[state.active_tab].pane_areas = pane_areas;
+    }
+}
+
+/// One title per tab, with the active one highlighted.
+pub struct TabBar {
+    style: Style,
+    active_style: Style,
+}
+
+impl TabBar {
+    pub fn new() -> Self {
+        Self {
+            style: Style::new().fg(Color::Gray).bg(Color::Rgb(33, 37, 43)),
+            active_style: Style::new().fg(Color::White).bg(Color::Rgb(40, 44, 52)).add_modifier(Modifier::BOLD),
+        }
+    }
+    
+    pub fn theme(self, theme: &Theme) -> Self {
+        self.style(theme.style("tab_bar"))
+            .active_style(theme.style("tab_bar.active"))
+    }
+    
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    
+    pub fn active_style(mut self, style: Style) -> Self {
+        self.active_style = style;
+        self
+    }
+}
+
+impl Default for TabBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatefulWidget for TabBar {
+    type State = Workspace;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        buf.set_style(area, self.style);
+        state.tab_areas.clear();
+        let mut x = area.x;
+        for index in 0..state.tabs.len() {
+            let title = format!(" {} ", state.tab_title(index));
+            let style = if index == state.active_tab { self.active_style } else { self.style };
+            let width = (title.chars().count() as u16).min(area.right().saturating_sub(x));
+            buf.set_stringn(x, area.y, &title, width as usize, style);
+            state.tab_areas.push(Rect::new(x, area.y, width, area.height.min(1)));
+            x += width;
+            if x >= area.right() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_edit::command::EditorCommand;
+    
+    #[test]
+    fn unfocused_panes_follow_edits() {
+        let mut editor = TextEditor::new();
+        editor.execute(&EditorCommand::InsertText(String::from("one\ntwo\nthree\n")));
+        let mut buffers = BufferManager::new();
+        let buffer = buffers.add(editor);
+        let mut workspace = Workspace::new(buffers, buffer);
+        workspace.editor_mut().set_cursor(TextPosition::new(2, 3));
+        workspace.editor_mut().start_line = 1;
+        workspace.split(Direction::Vertical);
+        
+        let editor = workspace.editor_mut();
+        editor.set_cursor(TextPosition::new(0, 0));
+        editor.execute(&EditorCommand::InsertText(String::from("new\nlines\n")));
+        editor.set_cursor(TextPosition::new(4, 1));
+        editor.execute(&EditorCommand::DeleteRange(editor.cursor_char_index()..editor.cursor_char_index() + 2));
+        
+        workspace.cycle_focus(true);
+        assert_eq!(workspace.editor().cursor, TextPosition::new(4, 1));
+        assert_eq!(workspace.editor().start_line, 3);
+        workspace.cycle_focus(true);
+        assert_eq!(workspace.editor().cursor, TextPosition::new(4, 1));
+        workspace.close_other_panes();
+        assert!(workspace.editor().parked_views.is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+
+use ropey::{Rope, RopeSlice};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WrapMode {
+    /// Break wherever the viewport ends.
+    Char,
+    /// Break after whitespace when possible, falling back to `Char` for long words.
+    Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoftWrap {
+    pub mode: WrapMode,
+    /// Drawn at the start of every continuation row.
+    pub indicator: Option<char>,
+}
+
+impl Default for SoftWrap {
+    fn default() -> Self {
+        Self {
+            mode: WrapMode::Word,
+            indicator: Some('↪'),
+        }
+    }
+}
+
+impl SoftWrap {
+    pub fn indicator_width(&self) -> usize {
+        usize::from(self.indicator.is_some())
+    }
+    
+    /// Width available to text on the given row of a line.
+    pub fn row_width(&self, row: usize, width: usize) -> usize {
+        if row == 0 {
+            width
+        } else {
+            width.saturating_sub(self.indicator_width()).max(1)
+        }
+    }
+    
+    /// Returns the start column of every row after the first.
+    pub fn wrap_line(&self, line: RopeSlice, line_len: usize, width: usize) -> Vec<usize> {
+        let mut starts = Vec::new();
+        if width == 0 {
+            return starts;
+        }
+        let mut row_start = 0;
+        while line_len - row_start > self.row_width(starts.len(), width) {
+            let row_end = row_start + self.row_width(starts.len(), width);
+            let break_at = match self.mode {
+                WrapMode::Char => row_end,
+                WrapMode::Word => {
+                    // Break after the last whitespace in the row, keeping at least one char.
+                    (row_start + 1..=row_end)
+                        .rev()
+                        .find(|&col| line.char(col - 1).is_whitespace() && !line.char(col).is_whitespace())
+                        .unwrap_or(row_end)
+                }
+            };
+            starts.push(break_at);
+            row_start = break_at;
+        }
+        starts
+    }
+}
+
+/// Lazily computed row breaks for the lines that have been drawn, invalidated by edits
+/// and by changes to the wrap width or mode. Only recently used lines are kept, so the
+/// cache stays small in huge buffers.
+#[derive(Debug, Default)]
+pub(crate) struct LineLayouts {
+    key: Option<(SoftWrap, usize)>,
+    lines: BTreeMap<usize, Box<[usize]>>,
+}
+
+impl LineLayouts {
+    /// Starting over is cheaper than tracking which lines were used least recently.
+    const MAX_LINES: usize = 4096;
+    
+    pub fn reset(&mut self, wrap: SoftWrap, width: usize) {
+        if self.key != Some((wrap, width)) {
+            self.key = Some((wrap, width));
+            self.lines.clear();
+        }
+    }
+    
+    pub fn lines_changed(&mut self, start_line: usize, old_end_line: usize, new_end_line: usize) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let moved = self.lines.split_off(&(old_end_line + 1));
+        self.lines.retain(|&line, _| line < start_line);
+        self.lines.extend(moved.into_iter().map(|(line, starts)| (line + new_end_line - old_end_line, starts)));
+    }
+    
+    /// Start columns of the continuation rows of `line`. Empty if the line fits on one row.
+    pub fn row_starts(&mut self, rope: &Rope, line: usize) -> &[usize] {
+        let Some((wrap, width)) = self.key else {
+            return &[];
+        };
+        if line >= rope.len_lines() {
+            return &[];
+        }
+        if self.lines.len() >= Self::MAX_LINES && !self.lines.contains_key(&line) {
+            self.lines.clear();
+        }
+        self.lines.entry(line).or_insert_with(|| {
+            wrap.wrap_line(rope.line(line), super::rope_line_len(rope, line), width).into_boxed_slice()
+        })
+    }
+    
+    pub fn row_count(&mut self, rope: &Rope, line: usize) -> usize {
+        self.row_starts(rope, line).len() + 1
+    }
+    
+    /// The row that `col` is drawn on, and the column that row starts at.
+    pub fn row_of(&mut self, rope: &Rope, line: usize, col: usize) -> (usize, usize) {
+        let starts = self.row_starts(rope, line);
+        let row = starts.partition_point(|&start| start <= col);
+        (row, if row == 0 { 0 } else { starts[row - 1] })
+    }
+    
+    /// The range of columns on a row. The last row extends to the end of the line.
+    pub fn row_range(&mut self, rope: &Rope, line: usize, row: usize) -> (usize, usize) {
+        let line_len = super::rope_line_len(rope, line);
+        let starts = self.row_starts(rope, line);
+        let start = if row == 0 { 0 } else { starts[row - 1] };
+        let end = starts.get(row).copied().unwrap_or(line_len);
+        (start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn wrap(mode: WrapMode, indicator: Option<char>) -> SoftWrap {
+        SoftWrap {
+            mode,
+            indicator,
+        }
+    }
+    
+    fn starts(wrap: SoftWrap, text: &str, width: usize) -> Vec<usize> {
+        let rope = Rope::from_str(text);
+        wrap.wrap_line(rope.line(0), super::super::rope_line_len(&rope, 0), width)
+    }
+    
+    #[test]
+    fn char_wrap_leaves_room_for_the_indicator() {
+        assert_eq!(starts(wrap(WrapMode::Char, None), "abcdefghij", 4), [4, 8]);
+        // Continuation rows lose a column to the indicator.
+        assert_eq!(starts(wrap(WrapMode::Char, Some('>')), "abcdefghij", 4), [4, 7]);
+        assert_eq!(starts(wrap(WrapMode::Char, None), "abcd\n", 4), Vec::<usize>::new());
+        assert_eq!(starts(wrap(WrapMode::Char, None), "abcd", 0), Vec::<usize>::new());
+    }
+    
+    #[test]
+    fn word_wrap_breaks_after_whitespace() {
+        assert_eq!(starts(wrap(WrapMode::Word, None), "one two three", 8), [8]);
+        assert_eq!(starts(wrap(WrapMode::Word, None), "one two three", 6), [4, 8]);
+        // A word longer than the row is broken where the row ends.
+        assert_eq!(starts(wrap(WrapMode::Word, None), "abcdefgh ij", 4), [4, 8]);
+    }
+    
+    #[test]
+    fn layouts_follow_edits_and_settings() {
+        let mut rope = Rope::from_str("short\nabcdefghij\nxy\n");
+        let mut layouts = LineLayouts::default();
+        assert_eq!(layouts.row_count(&rope, 1), 1, "Nothing wraps before the width is known.");
+        layouts.reset(wrap(WrapMode::Char, None), 4);
+        assert_eq!(layouts.row_count(&rope, 0), 2);
+        assert_eq!(layouts.row_count(&rope, 1), 3);
+        assert_eq!(layouts.row_of(&rope, 1, 9), (2, 8));
+        assert_eq!(layouts.row_range(&rope, 1, 1), (4, 8));
+        assert_eq!(layouts.row_range(&rope, 1, 2), (8, 10));
+        
+        // A line inserted above moves the cached layouts down with their lines.
+        rope.insert(0, "new\n");
+        layouts.lines_changed(0, 0, 1);
+        assert_eq!(layouts.row_count(&rope, 0), 1);
+        assert_eq!(layouts.row_count(&rope, 2), 3);
+        assert_eq!(layouts.row_count(&rope, 3), 1);
+        
+        // A new width lays everything out again.
+        layouts.reset(wrap(WrapMode::Char, None), 20);
+        assert_eq!(layouts.row_count(&rope, 2), 1);
+        assert_eq!(layouts.row_count(&rope, 99), 1);
+    }
+}
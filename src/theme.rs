@@ -0,0 +1,363 @@
+//! Colors for every widget, by name, so that they can be swapped out together.
+//!
+//! Themes are plain text, one setting per line. Lines starting with `#` are comments:
+//!
+//! ```text
+//! name = Solarized
+//! # Settings that aren't given are taken from here. Either `dark` (the default) or `light`.
+//! extends = dark
+//! editor.selection = bg:#073642
+//! syntax.comment = fg:#586e75 italic
+//! status_bar.mode = fg:black bg:blue bold
+//! # The indent guide backgrounds, one per indent level.
+//! indent_guides = #1c2a30 #20303a
+//! ```
+//!
+//! Styles are made of `fg:<color>`, `bg:<color>` and modifiers (`bold`, `dim`, `italic`,
+//! `underlined`, `reversed`, `crossed_out` and so on), or `none`. Colors are `#rrggbb`,
+//! palette indices or ANSI color names such as `lightblue`. Themes are written in RGB and
+//! [`ColorDepth`] brings them down to what the terminal can show.
+
+mod color_depth;
+
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use ratatui::prelude::*;
+
+use crate::text_edit::highlight::{SyntaxTheme, TokenKind, DEFAULT_SYNTAX_THEME};
+
+pub use color_depth::ColorDepth;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ThemeError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
+const fn fg(r: u8, g: u8, b: u8) -> Style {
+    Style::new().fg(Color::Rgb(r, g, b))
+}
+
+const fn bg(r: u8, g: u8, b: u8) -> Style {
+    Style::new().bg(Color::Rgb(r, g, b))
+}
+
+/// Styles of the dark theme, which are also the defaults of each widget.
+const DARK_STYLES: &[(&str, Style)] = &[
+    ("editor", Style::new()),
+    ("editor.current_line", bg(30, 33, 40)),
+    ("editor.selection", bg(62, 68, 81)),
+    ("editor.match", bg(81, 71, 34)),
+    ("editor.current_match", bg(229, 192, 123).fg(Color::Black)),
+    ("editor.extra_cursor", Style::new().add_modifier(Modifier::REVERSED)),
+    ("editor.matching_bracket", bg(62, 68, 81).add_modifier(Modifier::BOLD)),
+    ("editor.fold_placeholder", bg(44, 49, 58).fg(Color::DarkGray)),
+    ("editor.wrap_indicator", Style::new().fg(Color::DarkGray)),
+    ("editor.whitespace", Style::new().fg(Color::DarkGray)),
+    ("editor.trailing_whitespace", bg(110, 30, 36)),
+    ("minimap", fg(92, 99, 112)),
+    ("minimap.viewport", bg(44, 49, 58)),
+    ("minimap.match", bg(166, 137, 76)),
+    ("scrollbar.track", bg(33, 37, 43)),
+    ("scrollbar.thumb", bg(78, 86, 102)),
+    ("gutter", Style::new()),
+    ("gutter.line_number", Style::new().fg(Color::DarkGray)),
+    ("gutter.current_line_number", Style::new().fg(Color::Yellow)),
+    ("gutter.fold_marker", Style::new().fg(Color::Gray)),
+    ("loading", Style::new().fg(Color::Gray)),
+    ("loading.gauge", fg(97, 175, 239)),
+    ("loading.gauge_unfilled", Style::new().fg(Color::DarkGray)),
+    ("status_bar", bg(37, 41, 50).fg(Color::Gray)),
+    ("status_bar.mode", bg(97, 175, 239).fg(Color::Black).add_modifier(Modifier::BOLD)),
+    ("status_bar.modified", fg(229, 192, 123)),
+    ("mode_line", Style::new()),
+    ("tab_bar", bg(33, 37, 43).fg(Color::Gray)),
+    ("tab_bar.active", bg(40, 44, 52).fg(Color::White).add_modifier(Modifier::BOLD)),
+    ("workspace.separator", Style::new().fg(Color::DarkGray)),
+    ("find_bar", bg(37, 41, 50)),
+    ("find_bar.toggle_on", bg(97, 175, 239).fg(Color::Black)),
+    ("find_bar.toggle_off", Style::new().fg(Color::DarkGray)),
+    ("find_bar.error", fg(224, 108, 117)),
+    ("diff", Style::new()),
+    ("diff.header", bg(37, 41, 50).add_modifier(Modifier::BOLD)),
+    ("diff.gutter", Style::new().fg(Color::DarkGray)),
+    ("diff.current_hunk", fg(229, 192, 123)),
+    ("diff.added", bg(40, 58, 42)),
+    ("diff.removed", bg(66, 40, 44)),
+    ("diff.added_text", bg(58, 94, 60)),
+    ("diff.removed_text", bg(110, 52, 58)),
+    ("diff.filler", fg(62, 68, 81)),
+    ("go_to_line", bg(37, 41, 50)),
+    ("go_to_line.hint", Style::new().fg(Color::DarkGray)),
+    ("go_to_line.error", fg(224, 108, 117)),
+    ("recovery_bar", bg(97, 76, 41).fg(Color::Rgb(229, 192, 123))),
+    ("recovery_bar.hint", fg(171, 178, 191)),
+    ("palette", bg(37, 41, 50)),
+    ("palette.selected", bg(62, 68, 81)),
+    ("palette.match", fg(97, 175, 239).add_modifier(Modifier::BOLD)),
+    ("palette.binding", Style::new().fg(Color::DarkGray)),
+    ("completion", bg(37, 41, 50)),
+    ("completion.selected", bg(62, 68, 81)),
+    ("completion.match", fg(97, 175, 239).add_modifier(Modifier::BOLD)),
+    ("completion.detail", Style::new().fg(Color::DarkGray)),
+    ("hover", bg(37, 41, 50)),
+    ("hover.border", Style::new().fg(Color::DarkGray)),
+    ("diagnostic.error", fg(224, 108, 117)),
+    ("diagnostic.warning", fg(229, 192, 123)),
+    ("diagnostic.information", fg(97, 175, 239)),
+    ("diagnostic.hint", fg(92, 99, 112)),
+    ("hacker_text", fg(152, 195, 121).bg(Color::Black)),
+];
+
+const DARK_INDENT_COLORS: [Color; 6] = crate::text_edit::whitespace::RAINBOW_INDENT_COLORS;
+
+/// One Light, to go with the One Dark colors of the dark theme.
+const LIGHT_STYLES: &[(&str, Style)] = &[
+    ("editor", bg(250, 250, 250).fg(Color::Rgb(56, 58, 66))),
+    ("editor.current_line", bg(240, 240, 241)),
+    ("editor.selection", bg(210, 220, 240)),
+    ("editor.match", bg(250, 230, 170)),
+    ("editor.current_match", bg(193, 132, 1).fg(Color::White)),
+    ("editor.extra_cursor", Style::new().add_modifier(Modifier::REVERSED)),
+    ("editor.matching_bracket", bg(210, 220, 240).add_modifier(Modifier::BOLD)),
+    ("editor.fold_placeholder", bg(229, 229, 230).fg(Color::Rgb(160, 161, 167))),
+    ("editor.wrap_indicator", fg(160, 161, 167)),
+    ("editor.whitespace", fg(200, 200, 205)),
+    ("editor.trailing_whitespace", bg(250, 200, 200)),
+    ("minimap", fg(160, 161, 167).bg(Color::Rgb(250, 250, 250))),
+    ("minimap.viewport", bg(229, 229, 230)),
+    ("minimap.match", bg(250, 210, 120)),
+    ("scrollbar.track", bg(240, 240, 241)),
+    ("scrollbar.thumb", bg(200, 200, 205)),
+    ("gutter", bg(250, 250, 250)),
+    ("gutter.line_number", fg(160, 161, 167)),
+    ("gutter.current_line_number", fg(56, 58, 66)),
+    ("gutter.fold_marker", fg(105, 108, 119)),
+    ("loading", fg(105, 108, 119)),
+    ("loading.gauge", fg(64, 120, 242)),
+    ("loading.gauge_unfilled", fg(200, 200, 205)),
+    ("status_bar", bg(234, 234, 235).fg(Color::Rgb(105, 108, 119))),
+    ("status_bar.mode", bg(64, 120, 242).fg(Color::White).add_modifier(Modifier::BOLD)),
+    ("status_bar.modified", fg(193, 132, 1)),
+    ("mode_line", bg(250, 250, 250).fg(Color::Rgb(56, 58, 66))),
+    ("tab_bar", bg(234, 234, 235).fg(Color::Rgb(105, 108, 119))),
+    ("tab_bar.active", bg(250, 250, 250).fg(Color::Rgb(56, 58, 66)).add_modifier(Modifier::BOLD)),
+    ("workspace.separator", bg(250, 250, 250).fg(Color::Rgb(200, 200, 205))),
+    ("find_bar", bg(234, 234, 235).fg(Color::Rgb(56, 58, 66))),
+    ("find_bar.toggle_on", bg(64, 120, 242).fg(Color::White)),
+    ("find_bar.toggle_off", fg(160, 161, 167)),
+    ("find_bar.error", fg(228, 86, 73)),
+    ("diff", Style::new()),
+    ("diff.header", bg(234, 234, 235).fg(Color::Rgb(56, 58, 66)).add_modifier(Modifier::BOLD)),
+    ("diff.gutter", fg(160, 161, 167)),
+    ("diff.current_hunk", fg(193, 132, 1)),
+    ("diff.added", bg(226, 244, 226)),
+    ("diff.removed", bg(251, 226, 226)),
+    ("diff.added_text", bg(180, 228, 180)),
+    ("diff.removed_text", bg(244, 184, 184)),
+    ("diff.filler", fg(210, 210, 214)),
+    ("go_to_line", bg(234, 234, 235).fg(Color::Rgb(56, 58, 66))),
+    ("go_to_line.hint", fg(160, 161, 167)),
+    ("go_to_line.error", fg(228, 86, 73)),
+    ("recovery_bar", bg(250, 236, 204).fg(Color::Rgb(152, 104, 1))),
+    ("recovery_bar.hint", fg(105, 108, 119)),
+    ("palette", bg(234, 234, 235).fg(Color::Rgb(56, 58, 66))),
+    ("palette.selected", bg(210, 220, 240)),
+    ("palette.match", fg(64, 120, 242).add_modifier(Modifier::BOLD)),
+    ("palette.binding", fg(160, 161, 167)),
+    ("completion", bg(234, 234, 235).fg(Color::Rgb(56, 58, 66))),
+    ("completion.selected", bg(210, 220, 240)),
+    ("completion.match", fg(64, 120, 242).add_modifier(Modifier::BOLD)),
+    ("completion.detail", fg(160, 161, 167)),
+    ("hover", bg(234, 234, 235).fg(Color::Rgb(56, 58, 66))),
+    ("hover.border", fg(160, 161, 167)),
+    ("diagnostic.error", fg(228, 86, 73)),
+    ("diagnostic.warning", fg(193, 132, 1)),
+    ("diagnostic.information", fg(64, 120, 242)),
+    ("diagnostic.hint", fg(160, 161, 167)),
+    ("hacker_text", fg(80, 161, 79).bg(Color::White)),
+];
+
+const LIGHT_INDENT_COLORS: [Color; 6] = [
+    Color::Rgb(250, 228, 228),
+    Color::Rgb(250, 236, 218),
+    Color::Rgb(246, 242, 208),
+    Color::Rgb(222, 242, 228),
+    Color::Rgb(222, 232, 248),
+    Color::Rgb(236, 226, 246),
+];
+
+const LIGHT_SYNTAX_THEME: SyntaxTheme = SyntaxTheme::new(Style::new())
+    .with(TokenKind::Keyword, fg(166, 38, 164))
+    .with(TokenKind::Type, fg(193, 132, 1))
+    .with(TokenKind::Function, fg(64, 120, 242))
+    .with(TokenKind::Macro, fg(1, 132, 188))
+    .with(TokenKind::Variable, fg(228, 86, 73))
+    .with(TokenKind::Constant, fg(152, 104, 1))
+    .with(TokenKind::Number, fg(152, 104, 1))
+    .with(TokenKind::String, fg(80, 161, 79))
+    .with(TokenKind::Escape, fg(1, 132, 188))
+    .with(TokenKind::Comment, fg(160, 161, 167).add_modifier(Modifier::ITALIC))
+    .with(TokenKind::Operator, fg(56, 58, 66))
+    .with(TokenKind::Punctuation, fg(105, 108, 119))
+    .with(TokenKind::Attribute, fg(152, 104, 1))
+    .with(TokenKind::Lifetime, fg(166, 38, 164).add_modifier(Modifier::ITALIC))
+    .with(TokenKind::Key, fg(228, 86, 73))
+    .with(TokenKind::Heading, fg(228, 86, 73).add_modifier(Modifier::BOLD))
+    .with(TokenKind::Emphasis, Style::new().add_modifier(Modifier::ITALIC))
+    .with(TokenKind::Strong, Style::new().add_modifier(Modifier::BOLD))
+    .with(TokenKind::Code, fg(80, 161, 79))
+    .with(TokenKind::Link, fg(64, 120, 242).add_modifier(Modifier::UNDERLINED))
+    .with(TokenKind::Quote, fg(160, 161, 167))
+    .with(TokenKind::ListMarker, fg(152, 104, 1));
+
+/// Named styles for every widget, along with the syntax highlighting colors.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    styles: HashMap<String, Style>,
+    syntax: SyntaxTheme,
+    /// Backgrounds for the rainbow indent guides.
+    pub indent_colors: Vec<Color>,
+}
+
+impl Theme {
+    fn built_in(name: &str, styles: &[(&str, Style)], syntax: SyntaxTheme, indent_colors: &[Color]) -> Self {
+        let mut theme = Self {
+            name: name.to_owned(),
+            styles: styles.iter().map(|&(key, style)| (key.to_owned(), style)).collect(),
+            syntax: SyntaxTheme::new(Style::new()),
+            indent_colors: indent_colors.to_vec(),
+        };
+        for kind in TokenKind::ALL {
+            theme.set_style(&format!("syntax.{}", kind.name()), syntax.style(kind));
+        }
+        theme
+    }
+    
+    pub fn dark() -> Self {
+        Self::built_in("dark", DARK_STYLES, DEFAULT_SYNTAX_THEME, &DARK_INDENT_COLORS)
+    }
+    
+    pub fn light() -> Self {
+        Self::built_in("light", LIGHT_STYLES, LIGHT_SYNTAX_THEME, &LIGHT_INDENT_COLORS)
+    }
+    
+    /// `dark` or `light`.
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+    
+    /// The style of a part of a widget, such as `status_bar.mode`, or no style if the theme
+    /// doesn't have it.
+    pub fn style(&self, key: &str) -> Style {
+        self.styles.get(key).copied().unwrap_or_default()
+    }
+    
+    /// Sets a style. Keys starting with `syntax.` and a [`TokenKind::name`] set the style of that token kind.
+    pub fn set_style(&mut self, key: &str, style: Style) {
+        if let Some(name) = key.strip_prefix("syntax.")
+            && let Some(kind) = TokenKind::ALL.into_iter().find(|kind| kind.name() == name) {
+            self.syntax.set_style(kind, style);
+        }
+        self.styles.insert(key.to_owned(), style);
+    }
+    
+    pub fn syntax(&self) -> &SyntaxTheme {
+        &self.syntax
+    }
+    
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ThemeError> {
+        let path = path.as_ref();
+        let mut theme = Self::parse(&std::fs::read_to_string(path)?)?;
+        if theme.name.is_empty() {
+            theme.name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        }
+        Ok(theme)
+    }
+    
+    /// Parses a theme file. Settings that it doesn't give come from the theme that it extends.
+    pub fn parse(text: &str) -> Result<Self, ThemeError> {
+        let settings = text.lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(line, text)| match text.split_once('=') {
+                Some((key, value)) => Ok((line, key.trim(), value.trim())),
+                None => Err(parse_error(line, "Expected `key = value`")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut theme = match settings.iter().find(|&&(_, key, _)| key == "extends") {
+            Some(&(line, _, name)) => Self::by_name(name).ok_or_else(|| parse_error(line, format!("No built-in theme named `{name}`")))?,
+            None => Self::dark(),
+        };
+        theme.name.clear();
+        for (line, key, value) in settings {
+            match key {
+                "name" => theme.name = value.to_owned(),
+                "extends" => (),
+                "indent_guides" => {
+                    theme.indent_colors = value.split_whitespace()
+                        .map(parse_color)
+                        .collect::<Result<_, _>>()
+                        .map_err(|message| parse_error(line, message))?;
+                }
+                key if theme.styles.contains_key(key) => {
+                    let style = parse_style(value).map_err(|message| parse_error(line, message))?;
+                    theme.set_style(key, style);
+                }
+                key => return Err(parse_error(line, format!("Unknown setting `{key}`"))),
+            }
+        }
+        Ok(theme)
+    }
+}
+
+fn parse_error<S: Into<String>>(line: usize, message: S) -> ThemeError {
+    ThemeError::Parse { line, message: message.into() }
+}
+
+fn parse_color(text: &str) -> Result<Color, String> {
+    Color::from_str(text).map_err(|_| format!("Invalid color `{text}`"))
+}
+
+fn parse_style(text: &str) -> Result<Style, String> {
+    let mut style = Style::new();
+    for word in text.split_whitespace() {
+        if let Some(color) = word.strip_prefix("fg:") {
+            style = style.fg(parse_color(color)?);
+            continue;
+        }
+        if let Some(color) = word.strip_prefix("bg:") {
+            style = style.bg(parse_color(color)?);
+            continue;
+        }
+        let modifier = match word.to_ascii_lowercase().as_str() {
+            "none" => Modifier::empty(),
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" => Modifier::UNDERLINED,
+            "slow_blink" => Modifier::SLOW_BLINK,
+            "rapid_blink" => Modifier::RAPID_BLINK,
+            "reversed" => Modifier::REVERSED,
+            "hidden" => Modifier::HIDDEN,
+            "crossed_out" => Modifier::CROSSED_OUT,
+            _ => return Err(format!("Unknown style `{word}`")),
+        };
+        style = style.add_modifier(modifier);
+    }
+    Ok(style)
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
@@ -0,0 +1,150 @@
+use ratatui::prelude::*;
+
+/// How many colors the terminal can show. Colors that it can't are swapped for the
+/// nearest ones that it can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum ColorDepth {
+    /// No colors at all, as asked for with `NO_COLOR` or by dumb terminals.
+    Monochrome,
+    /// The 16 ANSI colors, whatever the terminal's palette makes of them.
+    Ansi16,
+    /// The xterm 256 color palette.
+    Ansi256,
+    #[default]
+    TrueColor,
+}
+
+/// What the xterm palette draws the 16 ANSI colors as, for finding the nearest one.
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The levels of each channel in the 6x6x6 color cube of the 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// How different two colors look. Red and blue count for more or less depending on how
+/// red the colors are, which is closer to what eyes see than plain RGB distance.
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let mean_red = (r1 as i32 + r2 as i32) / 2;
+    let (dr, dg, db) = (r1 as i32 - r2 as i32, g1 as i32 - g2 as i32, b1 as i32 - b2 as i32);
+    ((((512 + mean_red) * dr * dr) >> 8) + 4 * dg * dg + (((767 - mean_red) * db * db) >> 8)) as u32
+}
+
+/// The RGB value of a color in the 256 color palette.
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..16 => ANSI_COLORS[index as usize].1,
+        16..232 => {
+            let index = index - 16;
+            (CUBE_LEVELS[index as usize / 36], CUBE_LEVELS[index as usize / 6 % 6], CUBE_LEVELS[index as usize % 6])
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+/// The nearest color in the cube or the gray ramp of the 256 color palette.
+fn nearest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    let nearest_level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(value))
+            .expect("There are cube levels.")
+    };
+    let (r, g, b) = (nearest_level(rgb.0), nearest_level(rgb.1), nearest_level(rgb.2));
+    let cube = 16 + 36 * r as u8 + 6 * g as u8 + b as u8;
+    let average = (rgb.0 as u16 + rgb.1 as u16 + rgb.2 as u16) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+    if distance(indexed_rgb(gray), rgb) < distance(indexed_rgb(cube), rgb) { gray } else { cube }
+}
+
+fn nearest_ansi(rgb: (u8, u8, u8)) -> Color {
+    ANSI_COLORS.iter()
+        .min_by_key(|&&(_, ansi)| distance(ansi, rgb))
+        .map(|&(color, _)| color)
+        .expect("There are ANSI colors.")
+}
+
+impl ColorDepth {
+    /// Works out the color depth from the environment: `NO_COLOR`, `COLORTERM` and `TERM`.
+    /// Terminals that don't say otherwise are assumed to have 16 colors.
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).unwrap_or_default().to_ascii_lowercase();
+        if !var("NO_COLOR").is_empty() {
+            return ColorDepth::Monochrome;
+        }
+        let colorterm = var("COLORTERM");
+        let term = var("TERM");
+        if matches!(colorterm.as_str(), "truecolor" | "24bit")
+            || ["truecolor", "24bit", "direct"].iter().any(|suffix| term.ends_with(suffix))
+            // Windows Terminal doesn't set `COLORTERM`.
+            || !var("WT_SESSION").is_empty() {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else if term == "dumb" {
+            ColorDepth::Monochrome
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+    
+    /// Parses `truecolor`, `256`, `16` or `none`, as given on the command line.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "truecolor" | "24bit" => ColorDepth::TrueColor,
+            "256" => ColorDepth::Ansi256,
+            "16" => ColorDepth::Ansi16,
+            "none" | "0" => ColorDepth::Monochrome,
+            _ => return None,
+        })
+    }
+    
+    /// The nearest color that can be shown at this depth.
+    pub fn adapt(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorDepth::TrueColor, color) | (_, color @ Color::Reset) => color,
+            (ColorDepth::Monochrome, _) => Color::Reset,
+            (ColorDepth::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(nearest_indexed((r, g, b))),
+            (ColorDepth::Ansi16, Color::Rgb(r, g, b)) => nearest_ansi((r, g, b)),
+            (ColorDepth::Ansi16, Color::Indexed(index)) => nearest_ansi(indexed_rgb(index)),
+            (_, color) => color,
+        }
+    }
+    
+    pub fn adapt_style(self, style: Style) -> Style {
+        Style {
+            fg: style.fg.map(|color| self.adapt(color)),
+            bg: style.bg.map(|color| self.adapt(color)),
+            ..style
+        }
+    }
+    
+    /// Adapts every cell of a buffer. Called once everything has been drawn, this covers
+    /// every widget, as well as colors that come from the text, such as ANSI escapes.
+    pub fn adapt_buffer(self, buf: &mut Buffer) {
+        if self == ColorDepth::TrueColor {
+            return;
+        }
+        for cell in &mut buf.content {
+            cell.fg = self.adapt(cell.fg);
+            cell.bg = self.adapt(cell.bg);
+        }
+    }
+}
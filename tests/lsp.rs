@@ -0,0 +1,141 @@
+//! Runs the language server client against `src/bin/mock_lsp.rs` in each position encoding,
+//! with text whose chars take more than one byte and more than one UTF-16 unit.
+
+use std::{
+    path::PathBuf,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use hacker::text_edit::{
+    command::EditorCommand,
+    diagnostics::{Diagnostic, Severity},
+    lsp::{LspClient, LspEvent},
+    workspace::{BufferId, BufferManager},
+    TextPosition,
+};
+
+const TEXT: &str = "fn grüße() {}\n// naïve 🦀 code FIXME\nfn main() { grüße(); }\n";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A directory of its own for each test, removed when it's done.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("hacker-lsp-{name}-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Polls until `found` returns something, keeping every event seen.
+fn wait_for<T>(client: &mut LspClient, buffers: &mut BufferManager, mut found: impl FnMut(&LspClient, &BufferManager, &[LspEvent]) -> Option<T>) -> T {
+    let deadline = Instant::now() + TIMEOUT;
+    let mut events = Vec::new();
+    loop {
+        events.extend(client.poll(buffers));
+        assert!(!client.has_exited(), "The server exited. Events: {events:?}");
+        if let Some(result) = found(client, buffers, &events) {
+            return result;
+        }
+        assert!(Instant::now() < deadline, "Timed out. Events: {events:?}");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn diagnostics(buffers: &BufferManager, buffer: BufferId) -> Vec<Diagnostic> {
+    buffers.get(buffer).unwrap().diagnostics().iter().cloned().collect()
+}
+
+/// The diagnostics that the server reports for a `TODO` and a `FIXME` on line 1.
+fn expected(todo: Option<usize>, fixme: usize) -> Vec<Diagnostic> {
+    let diagnostic = |col: usize, len: usize, severity, message: &str| Diagnostic {
+        start: TextPosition::new(1, col),
+        end: TextPosition::new(1, col + len),
+        severity,
+        message: message.to_owned(),
+        source: Some(String::from("mock_lsp")),
+    };
+    todo.map(|col| diagnostic(col, 4, Severity::Warning, "Unfinished work")).into_iter()
+        .chain([diagnostic(fixme, 5, Severity::Error, "Something here needs fixing")])
+        .collect()
+}
+
+fn round_trips(encoding: &str) {
+    let dir = TempDir::new(encoding);
+    let path = dir.0.join("sample.rs");
+    std::fs::write(&path, TEXT).unwrap();
+    let mut buffers = BufferManager::new();
+    let buffer = buffers.open(&path).unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mock_lsp"));
+    command.arg(format!("--encoding={encoding}"));
+    let mut client = LspClient::spawn(command, &dir.0, None).unwrap();
+
+    // The buffer is opened once the server has answered the initialize request, and the
+    // server answers that with diagnostics in the encoding they agreed on.
+    wait_for(&mut client, &mut buffers, |client, _, _| client.is_ready().then_some(()));
+    wait_for(&mut client, &mut buffers, |_, buffers, _| (diagnostics(buffers, buffer) == expected(None, 16)).then_some(()));
+
+    // Each edit is sent as a range, which the server has to find in text it only has a copy of.
+    let editor = buffers.get_mut(buffer).unwrap();
+    let code = editor.position_to_char(TextPosition::new(1, "// naïve 🦀 ".chars().count()));
+    editor.execute(&EditorCommand::InsertAt {
+        char_idx: code,
+        text: String::from("TODO "),
+    });
+    wait_for(&mut client, &mut buffers, |_, buffers, _| (diagnostics(buffers, buffer) == expected(Some(11), 21)).then_some(()));
+    let editor = buffers.get_mut(buffer).unwrap();
+    let naive = editor.position_to_char(TextPosition::new(1, 3));
+    editor.execute(&EditorCommand::DeleteRange(naive..naive + "naïve ".chars().count()));
+    wait_for(&mut client, &mut buffers, |_, buffers, _| (diagnostics(buffers, buffer) == expected(Some(5), 15)).then_some(()));
+
+    // Completions are the server's words, so they show that its copy matches the buffer.
+    buffers.get_mut(buffer).unwrap().set_cursor(TextPosition::new(2, 14));
+    client.completion(&mut buffers, buffer).unwrap();
+    let mut labels = wait_for(&mut client, &mut buffers, |_, _, events| events.iter().find_map(|event| match event {
+        LspEvent::Completion { items, .. } => Some(items.iter().map(|item| item.label.clone()).collect::<Vec<_>>()),
+        _ => None,
+    }));
+    labels.sort();
+    assert_eq!(labels, ["FIXME", "TODO", "code", "grüße", "main"]);
+
+    client.hover(&mut buffers, buffer).unwrap();
+    let (position, text) = wait_for(&mut client, &mut buffers, |_, _, events| events.iter().find_map(|event| match event {
+        LspEvent::Hover { buffer: hovered, position, text } if *hovered == buffer => Some((*position, text.clone())),
+        _ => None,
+    }));
+    assert_eq!(position, TextPosition::new(2, 14));
+    assert_eq!(text, "`grüße`\n\nAppears 2 times in this document.");
+
+    client.definition(&mut buffers, buffer).unwrap();
+    let definition = wait_for(&mut client, &mut buffers, |_, _, events| events.iter().find_map(|event| match event {
+        LspEvent::Definition { buffer, position } => Some((*buffer, *position)),
+        _ => None,
+    }));
+    assert_eq!(definition, (buffer, TextPosition::new(0, 3)));
+
+    client.shutdown(Duration::from_secs(1));
+}
+
+#[test]
+fn utf8() {
+    round_trips("utf-8");
+}
+
+#[test]
+fn utf16() {
+    round_trips("utf-16");
+}
+
+#[test]
+fn utf32() {
+    round_trips("utf-32");
+}